@@ -0,0 +1,37 @@
+//! Captures build info for `graphctl version --verbose`: the git commit
+//! being built and the version of libsql it's built against.
+use std::process::Command;
+
+fn main() {
+    // Get the git commit (if building from a checkout)...
+    let sha = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|out| out.status.success())
+        .and_then(|out| String::from_utf8(out.stdout).ok())
+        .map(|sha| sha.trim().to_string())
+        .filter(|sha| !sha.is_empty())
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=GRAPHCTL_GIT_SHA={}", sha);
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs/heads");
+
+    // Get the resolved libsql version from the lock file...
+    let lock = std::fs::read_to_string("Cargo.lock").unwrap_or_default();
+    let libsql_version = lock
+        .split("[[package]]")
+        .find_map(|pkg| {
+            let mut lines = pkg.lines().map(str::trim);
+            if !lines.clone().any(|line| line == "name = \"libsql\"") {
+                return None;
+            }
+            lines.find_map(|line| {
+                line.strip_prefix("version = \"")
+                    .map(|v| v.trim_end_matches('"').to_string())
+            })
+        })
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=GRAPHCTL_LIBSQL_VERSION={}", libsql_version);
+    println!("cargo:rerun-if-changed=Cargo.lock");
+}
@@ -0,0 +1,157 @@
+//! Handles graph-level analysis (e.g. finding connected components).
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Graphs with more nodes than this are slow to analyze, since the
+/// whole graph has to be loaded into memory.
+pub const LARGE_GRAPH_NODES: usize = 1_000_000;
+
+/// A union-find (disjoint set) over node indices.
+struct UnionFind {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(n: usize) -> Self {
+        UnionFind {
+            parent: (0..n).collect(),
+            size: vec![1; n],
+        }
+    }
+
+    /// Find the root of an item's set (compressing the path to it).
+    fn find(&mut self, mut i: usize) -> usize {
+        while self.parent[i] != i {
+            self.parent[i] = self.parent[self.parent[i]];
+            i = self.parent[i];
+        }
+        i
+    }
+
+    /// Merge the sets containing two items (the smaller into the larger).
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        let (big, small) = match self.size[a] >= self.size[b] {
+            true => (a, b),
+            false => (b, a),
+        };
+        self.parent[small] = big;
+        self.size[big] += self.size[small];
+    }
+}
+
+/// The connected components of a graph.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct Components {
+    /// The number of components.
+    pub count: usize,
+
+    /// The size of the largest component.
+    pub largest: usize,
+
+    /// The size of each component, largest first. A component's ID
+    /// is its index in this list.
+    pub sizes: Vec<usize>,
+
+    /// Maps each node's ID to its component's ID.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node_components: Option<BTreeMap<String, usize>>,
+}
+
+/// Find the connected components of a graph, given its node IDs and
+/// its edges' `(from, to)` node IDs.
+///
+/// Edges are treated as undirected, and edges with an endpoint that
+/// isn't in `node_ids` are ignored. Component IDs are assigned largest
+/// first (with ties broken by the smallest node ID in each), so they're
+/// stable for a given graph.
+pub fn connected_components(
+    node_ids: &[String],
+    edges: &[(String, String)],
+    with_node_components: bool,
+) -> Components {
+    // Index the nodes...
+    let mut ids: Vec<&String> = node_ids.iter().collect();
+    ids.sort();
+    ids.dedup();
+    let index: HashMap<&String, usize> = ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+
+    // Merge the nodes joined by each edge...
+    let mut uf = UnionFind::new(ids.len());
+    for (from, to) in edges.iter() {
+        if let (Some(&a), Some(&b)) = (index.get(from), index.get(to)) {
+            uf.union(a, b);
+        }
+    }
+
+    // Group the nodes by their root...
+    // Note - The nodes are sorted, so each group's first node is its smallest...
+    let mut groups: Vec<(usize, usize)> = Vec::new(); // (root, first node index)
+    let mut seen = HashSet::new();
+    for i in 0..ids.len() {
+        let root = uf.find(i);
+        if seen.insert(root) {
+            groups.push((root, i));
+        }
+    }
+    groups.sort_by_key(|&(root, first)| (std::cmp::Reverse(uf.size[root]), first));
+
+    // Number the components...
+    let component_ids: HashMap<usize, usize> = groups
+        .iter()
+        .enumerate()
+        .map(|(cid, &(root, _))| (root, cid))
+        .collect();
+    let sizes: Vec<usize> = groups.iter().map(|&(root, _)| uf.size[root]).collect();
+    let node_components = with_node_components.then(|| {
+        (0..ids.len())
+            .map(|i| (ids[i].clone(), component_ids[&uf.find(i)]))
+            .collect()
+    });
+
+    Components {
+        count: sizes.len(),
+        largest: sizes.first().copied().unwrap_or_default(),
+        sizes,
+        node_components,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connected_components() {
+        let ids = |ids: &[&str]| ids.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let edge = |a: &str, b: &str| (a.to_string(), b.to_string());
+
+        // Two triangles-ish, a pair, and an isolated node...
+        let nodes = ids(&["a", "b", "c", "d", "e", "f", "g"]);
+        let edges = vec![
+            edge("a", "b"),
+            edge("c", "b"),
+            edge("d", "e"),
+            edge("f", "f"),
+            edge("a", "missing"),
+        ];
+        let res = connected_components(&nodes, &edges, true);
+        assert_eq!(res.count, 4);
+        assert_eq!(res.largest, 3);
+        assert_eq!(res.sizes, vec![3, 2, 1, 1]);
+        let comps = res.node_components.unwrap();
+        assert_eq!(comps["a"], 0);
+        assert_eq!(comps["c"], 0);
+        assert_eq!(comps["e"], 1);
+        assert_eq!(comps["f"], 2);
+        assert_eq!(comps["g"], 3);
+
+        // An empty graph...
+        let res = connected_components(&[], &[], false);
+        assert_eq!(res, Components::default());
+    }
+}
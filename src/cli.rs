@@ -1,5 +1,13 @@
-///! Handles the CLI definition and parsing.
+//! Handles the CLI definition and parsing.
+use anyhow::Result;
+use chrono::{DateTime, Utc};
 use clap::{Args, Parser, Subcommand, ValueEnum};
+use graphctl::conf::{DBType, DisplayTimezone, JournalMode, SynchronousMode};
+use graphctl::db::{EdgeDirection, EdgeGroupBy, DEFAULT_BUSY_TIMEOUT_MS, DEFAULT_MAX_RESULTS};
+use graphctl::export::ExportEdges;
+use graphctl::util::{self, PropType};
+use regex::Regex;
+use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
 #[clap(
@@ -20,6 +28,112 @@ pub struct Cli {
         help = "Path to the config directory. Defaults to $HOME/.graphctl"
     )]
     pub config_dir: Option<String>,
+
+    #[clap(
+        long,
+        global = true,
+        env = "GRAPHCTL_BUSY_TIMEOUT",
+        default_value_t = DEFAULT_BUSY_TIMEOUT_MS,
+        help = "How long (in milliseconds) to wait for a locked database before failing"
+    )]
+    pub busy_timeout: u64,
+
+    #[clap(
+        long,
+        global = true,
+        help = "Use a temporary in-memory database. Nothing is saved once the command exits"
+    )]
+    pub memory: bool,
+
+    #[clap(
+        long,
+        global = true,
+        help = "The maximum size (in bytes) of a prop value. Overrides `max_prop_bytes` in the config [default: 1048576]"
+    )]
+    pub max_prop_bytes: Option<usize>,
+
+    #[clap(
+        long,
+        global = true,
+        help = "Allow the same prop key to be given more than once (the last value wins)"
+    )]
+    pub allow_duplicate_props: bool,
+
+    #[clap(
+        long,
+        global = true,
+        help = "Trim surrounding whitespace from plain (non-JSON) string prop values, e.g. `name= Alice `. JSON values are kept as-is"
+    )]
+    pub trim_values: bool,
+
+    #[clap(
+        long,
+        global = true,
+        value_enum,
+        env = "GRAPHCTL_TIMEZONE",
+        help = "The timezone to display timestamps in. Overrides `timezone` in the config [default: utc]"
+    )]
+    pub timezone: Option<DisplayTimezone>,
+
+    #[clap(
+        long,
+        global = true,
+        help = "In table output, show each prop on its own line, with nested values pretty-printed"
+    )]
+    pub expand_props: bool,
+
+    #[clap(
+        long,
+        global = true,
+        env = "GRAPHCTL_SEED",
+        help = "Generate deterministic IDs from this seed (e.g. for reproducible tests). Note - Reusing a seed against the same database will generate IDs that already exist"
+    )]
+    pub seed: Option<u64>,
+
+    #[clap(
+        long,
+        global = true,
+        help = "Remove a stale lock file (left by a graphctl that was killed) before running"
+    )]
+    pub force_unlock: bool,
+
+    #[clap(
+        long,
+        global = true,
+        help = "Don't run pending schema migrations on startup -- use the database's schema as-is (e.g. to inspect a database managed by another graphctl). Run them with `meta migrate`"
+    )]
+    pub no_migrate: bool,
+
+    #[clap(
+        long,
+        global = true,
+        default_value_t = DEFAULT_MAX_RESULTS,
+        help = "The most nodes a traversal (e.g. `--ancestors`, `--descendants`) will collect before failing (0 for no limit)"
+    )]
+    pub max_results: usize,
+
+    #[clap(
+        long,
+        global = true,
+        help = "Read the local database encryption key from this file, rather than the `GRAPHCTL_ENCRYPTION_KEY` env var or the keyring"
+    )]
+    pub encryption_key_file: Option<PathBuf>,
+
+    #[clap(
+        long,
+        global = true,
+        value_enum,
+        help = "The SQLite journal mode for local databases. Overrides `journal_mode` in the config [default: delete]"
+    )]
+    pub journal_mode: Option<JournalMode>,
+
+    #[clap(
+        long,
+        global = true,
+        value_enum,
+        help = "The SQLite sync level for local databases. Overrides `synchronous` in the config [default: full]. Note - `off` risks corrupting the database on a crash"
+    )]
+    pub synchronous: Option<SynchronousMode>,
 }
 
 #[derive(Subcommand, Debug)]
@@ -40,6 +154,19 @@ pub enum Commands {
     Get {
         #[command(subcommand)]
         cmd: GetCmd,
+
+        #[clap(
+            long,
+            global = true,
+            help = "Print how long each database query took to stderr (e.g. to see where a slow `get` spends its time)"
+        )]
+        trace: bool,
+    },
+
+    #[clap(about = "Check whether a node or edge exists")]
+    Exists {
+        #[command(subcommand)]
+        cmd: ExistsCmd,
     },
 
     #[clap(about = "Update a node or edge in the graph")]
@@ -54,9 +181,46 @@ pub enum Commands {
         cmd: DeleteCmd,
     },
 
+    #[clap(about = "Restore an archived (soft-deleted) node or edge")]
+    Unarchive {
+        #[command(subcommand)]
+        cmd: UnarchiveCmd,
+    },
+
+    #[clap(about = "Replace a node with a new one, moving its edges over")]
+    Replace {
+        #[command(subcommand)]
+        cmd: ReplaceCmd,
+    },
+
+    #[clap(
+        about = "Export the graph (or a filtered slice of it, or just recent changes) as JSON, NDJSON, or GraphML"
+    )]
+    Export(ExportArgs),
+
+    #[clap(about = "Import an NDJSON export (from `export --format ndjson`)")]
+    Import(ImportArgs),
+
+    #[clap(about = "Compare the graph against another database or an export")]
+    Diff(DiffArgs),
+
+    #[clap(about = "Sync the local replica with the remote database")]
+    Sync(SyncArgs),
+
     /// This may be able to do stuff like create-/view-schemas, etc.
     #[clap(about = "Meta graph commands")]
-    Meta,
+    Meta {
+        #[clap(subcommand)]
+        cmd: MetaCmd,
+    },
+
+    #[clap(about = "Check that graphctl is set up correctly and can connect to the database")]
+    Doctor,
+
+    #[clap(
+        about = "Show the graphctl version (with --verbose, build and environment info for bug reports)"
+    )]
+    Version(VersionArgs),
 
     #[clap(about = "Configure the graphctl CLI")]
     Cfg {
@@ -70,218 +234,1134 @@ pub enum CreateCmd {
     #[clap(about = "Create a node in the graph")]
     Node(CreateNodeArgs),
 
-    #[clap(about = "Create an edge in the graph")]
-    Edge(CreateEdgeArgs),
+    #[clap(about = "Create an edge in the graph")]
+    Edge(CreateEdgeArgs),
+
+    #[clap(about = "Create a set of nodes and the edges between them, all at once")]
+    Subgraph(CreateSubgraphArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct CreateSubgraphArgs {
+    #[clap(
+        short,
+        long,
+        help = "Path to a JSON spec of the nodes and edges to create (or `-` for stdin). Edges can refer to the spec's nodes as `$<alias>`"
+    )]
+    pub file: PathBuf,
+
+    #[clap(
+        long,
+        help = "Allow edges whose source and target are the same node. Can also be set with `allow_self_loops` in the config"
+    )]
+    pub allow_self_loop: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct CreateNodeArgs {
+    #[clap(help = "A label for the node (shorthand for `-l <LABEL>`)")]
+    pub first_label: Option<String>,
+
+    #[clap(short, long, num_args=0.., help = "The node's label")]
+    pub label: Vec<String>,
+
+    #[clap(short, long, num_args=0.., help="A property attached to the node")]
+    pub prop: Vec<String>,
+
+    #[clap(
+        long,
+        help = "Fail if the node has no labels. Can also be set with `require_node_label` in the config"
+    )]
+    pub require_label: bool,
+
+    #[clap(long, help = "The node's ID. Generated if not set")]
+    pub id: Option<String>,
+
+    #[clap(
+        long,
+        requires = "id",
+        help = "If a node with the ID already exists, replace its labels and merge in its props"
+    )]
+    pub upsert: bool,
+
+    #[clap(
+        long,
+        help = "Start from a node template's labels and props (set with `meta set-template`). \
+            Labels are added to the template's and props override the template's"
+    )]
+    pub from_template: Option<String>,
+
+    #[clap(
+        long,
+        conflicts_with_all = ["from_template", "upsert"],
+        help = "Copy an existing node's labels and props into a new node (with a new ID). \
+            Labels are added to the source's and props override the source's"
+    )]
+    pub clone_from: Option<String>,
+
+    #[clap(
+        long,
+        help = "Print just the new node's ID, with no JSON (e.g. to pass to another command)"
+    )]
+    pub ids_only: bool,
+}
+
+impl CreateNodeArgs {
+    /// Returns all of the node's labels -- the positional
+    /// label (if any) followed by the `-l` labels.
+    pub fn labels(&self) -> Vec<String> {
+        self.first_label
+            .iter()
+            .chain(self.label.iter())
+            .cloned()
+            .collect()
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct CreateEdgeArgs {
+    #[clap(short, long, help = "The edge's type")]
+    pub edge_type: String,
+
+    #[clap(
+        short,
+        long,
+        required_unless_present = "from_selector",
+        conflicts_with = "from_selector",
+        help = "The edge's source node"
+    )]
+    pub from_node: Option<String>,
+
+    #[clap(
+        long,
+        help = "Select the source node by its props and label (e.g. `label:Person,name:Alice`)"
+    )]
+    pub from_selector: Option<String>,
+
+    #[clap(
+        short,
+        long,
+        required_unless_present = "to_selector",
+        conflicts_with = "to_selector",
+        help = "The edge's target node"
+    )]
+    pub to_node: Option<String>,
+
+    #[clap(
+        long,
+        help = "Select the target node by its props and label (e.g. `label:Person,name:Bob`)"
+    )]
+    pub to_selector: Option<String>,
+
+    #[clap(
+        short,
+        long,
+        conflicts_with = "undirected",
+        help = "Make the edge directed. Defaults to `default_edge_directed` in the config, or undirected"
+    )]
+    pub directed: bool,
+
+    #[clap(long, help = "Make the edge undirected")]
+    pub undirected: bool,
+
+    #[clap(
+        long,
+        conflicts_with = "undirected",
+        help = "Also create a directed edge going back the other way"
+    )]
+    pub both_directions: bool,
+
+    #[clap(short, long, num_args=0.., help="A property on the edge")]
+    pub prop: Vec<String>,
+
+    #[clap(
+        long,
+        help = "Allow the edge's source and target to be the same node. Can also be set with `allow_self_loops` in the config"
+    )]
+    pub allow_self_loop: bool,
+
+    #[clap(
+        long,
+        help = "When the relationship became active (an RFC 3339 timestamp)"
+    )]
+    pub valid_from: Option<DateTime<Utc>>,
+
+    #[clap(
+        long,
+        help = "When the relationship stopped being active (an RFC 3339 timestamp)"
+    )]
+    pub valid_to: Option<DateTime<Utc>>,
+
+    #[clap(
+        long,
+        help = "Print just the new edge's ID (or IDs, with --both-directions), with no JSON"
+    )]
+    pub ids_only: bool,
+}
+
+impl CreateEdgeArgs {
+    /// The edge's direction, if it was given explicitly.
+    pub fn direction(&self) -> Option<bool> {
+        if self.directed {
+            Some(true)
+        } else if self.undirected {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ListCmd {
+    #[clap(about = "List nodes in the graph")]
+    Nodes(ListNodesArgs),
+
+    #[clap(about = "List edges in the graph")]
+    Edges(ListEdgesArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct NodeFilterArgs {
+    #[clap(long, help = "The node's label")]
+    pub has_label: Option<String>,
+
+    #[clap(long, help = "Exclude nodes with this label (can be repeated)")]
+    pub exclude_label: Vec<String>,
+
+    #[clap(long, num_args=0.., help = "Filter to nodes with a certain property")]
+    pub has_prop: Vec<String>,
+
+    #[clap(short, long, num_args=0.., help = "Filter to nodes with a key-value pair. Use `key=?value` to match JSON array props containing the value")]
+    pub prop: Vec<String>,
+
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = PropType::Auto,
+        help = "How `--prop` values are typed before matching (`auto` parses JSON, falling back to a string)"
+    )]
+    pub prop_type: PropType,
+
+    #[clap(
+        long,
+        help = "Match string `--prop` values case-insensitively (ASCII letters only; other values still match exactly)"
+    )]
+    pub ignore_case: bool,
+
+    #[clap(
+        long,
+        conflicts_with = "connected",
+        help = "Only show nodes with no edges"
+    )]
+    pub orphans: bool,
+
+    #[clap(long, help = "Only show nodes with at least one edge")]
+    pub connected: bool,
+
+    #[clap(long, help = "Only show nodes with no incoming directed edges")]
+    pub roots: bool,
+
+    #[clap(long, help = "Only show nodes with no outgoing directed edges")]
+    pub leaves: bool,
+
+    #[clap(
+        long,
+        help = "Filter with a boolean expression, AND-ed with the other filters (e.g. `label:Person AND (prop:age>30 OR prop:vip=true)`)"
+    )]
+    pub filter: Option<String>,
+
+    #[clap(long, help = "Include archived (soft-deleted) nodes")]
+    pub include_archived: bool,
+}
+
+impl NodeFilterArgs {
+    /// Returns true if any of the filters are set.
+    pub fn is_set(&self) -> bool {
+        self.has_label.is_some()
+            || !self.exclude_label.is_empty()
+            || !self.has_prop.is_empty()
+            || !self.prop.is_empty()
+            || self.orphans
+            || self.connected
+            || self.roots
+            || self.leaves
+            || self.filter.is_some()
+            || self.include_archived
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ListNodesArgs {
+    #[command(flatten)]
+    pub filter: NodeFilterArgs,
+
+    #[clap(short='o', long, num_args=0.., help = "IDs of edges going out")]
+    pub edge_out: Vec<String>,
+
+    #[clap(short='i', long, num_args=0.., help = "IDs of edges coming in")]
+    pub edge_in: Vec<String>,
+
+    #[clap(long, num_args=0.., help = "Key-value pairs of edges out. Either `EDGE_TYPE=:NodeLabel` or `EDGE_TYPE=node-id`")]
+    pub edge_out_to: Vec<String>,
+
+    #[clap(long, num_args=0.., help = "Key-value pairs of edges in. Either `EDGE_TYPE=:NodeLabel` or `EDGE_TYPE=node-id`")]
+    pub edge_in_from: Vec<String>,
+
+    #[clap(
+        long,
+        help = "Only show nodes with at least one edge of this type (can be repeated)"
+    )]
+    pub has_edge_type: Vec<String>,
+
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = EdgeDirection::Both,
+        help = "Which of a node's edges `--has-edge-type` looks at"
+    )]
+    pub has_edge_direction: EdgeDirection,
+
+    #[clap(
+        long,
+        help = "Only show nodes with at least this many edges (in or out, directed or not)"
+    )]
+    pub min_degree: Option<usize>,
+
+    #[clap(
+        long,
+        help = "Only show nodes with at most this many edges (in or out, directed or not)"
+    )]
+    pub max_degree: Option<usize>,
+
+    #[clap(
+        long,
+        help = "Only show nodes created or updated since the last `sync` (based on `updated_at`)"
+    )]
+    pub modified_since_sync: bool,
+
+    #[clap(short, long, help = "Count the number of nodes returned")]
+    pub count: bool,
+
+    #[clap(
+        long,
+        conflicts_with_all = ["count", "stats", "format"],
+        help = "Print just the matching nodes' IDs, one per line (e.g. to pipe into `xargs`)"
+    )]
+    pub ids_only: bool,
+
+    #[clap(
+        long,
+        help = "Include stats (e.g. the number of nodes returned). JSON output becomes `{\"items\": [...], \"stats\": {...}}`, and NDJSON gets a trailing `_stats` line"
+    )]
+    pub stats: bool,
+
+    #[clap(
+        long,
+        help = "Leave out `created_at`/`updated_at` (e.g. to compare content across graphs)"
+    )]
+    pub omit_timestamps: bool,
+
+    #[clap(
+        short,
+        long,
+        help = "Limit the number of nodes returned (0 for no limit). Defaults to `list_limit` in the config, or 100"
+    )]
+    pub limit: Option<usize>,
+
+    #[clap(
+        long,
+        conflicts_with = "limit",
+        help = "Return all of the nodes (no limit)"
+    )]
+    pub all: bool,
+
+    #[clap(
+        long,
+        conflicts_with_all = ["limit", "all"],
+        help = "Return a random sample of this many matching nodes. Note - This is slow on very large graphs (narrow it down with other filters) and isn't affected by --seed"
+    )]
+    pub sample: Option<usize>,
+
+    #[clap(short, long, help = "Output format", value_enum, default_value_t=OutputFormat::Json)]
+    pub format: OutputFormat,
+
+    #[command(flatten)]
+    pub prop_keys: PropKeyFilterArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct PropKeyFilterArgs {
+    #[clap(long, help = "Only show props whose keys match this regex")]
+    pub include_props: Option<String>,
+
+    #[clap(long, help = "Don't show props whose keys match this regex")]
+    pub exclude_props: Option<String>,
+}
+
+impl PropKeyFilterArgs {
+    /// Compile the include and exclude patterns.
+    pub fn compile(&self) -> Result<(Option<Regex>, Option<Regex>)> {
+        let include = self
+            .include_props
+            .as_deref()
+            .map(util::compile_prop_pattern);
+        let exclude = self
+            .exclude_props
+            .as_deref()
+            .map(util::compile_prop_pattern);
+        Ok((include.transpose()?, exclude.transpose()?))
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct EdgeFilterArgs {
+    #[clap(long, help = "The edge's type")]
+    pub has_label: Option<String>,
+
+    #[clap(long, num_args=0.., help = "Filter to edges with a certain property")]
+    pub has_prop: Vec<String>,
+
+    #[clap(short, long, num_args=0.., help = "Filter to edges with property key-value match. Use `key=?value` to match JSON array props containing the value")]
+    pub prop: Vec<String>,
+
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = PropType::Auto,
+        help = "How `--prop` values are typed before matching (`auto` parses JSON, falling back to a string)"
+    )]
+    pub prop_type: PropType,
+
+    #[clap(
+        long,
+        help = "Match string `--prop` values case-insensitively (ASCII letters only; other values still match exactly)"
+    )]
+    pub ignore_case: bool,
+
+    #[clap(
+        long,
+        help = "Filter to edges whose property compares with a value (e.g. `weight>5`; operators are = != < <= > >=)"
+    )]
+    pub edge_prop: Vec<String>,
+
+    #[clap(short, long, help = "ID of the source node")]
+    pub source_node: Option<String>,
+
+    #[clap(short, long, help = "ID of the target node")]
+    pub target_node: Option<String>,
+
+    #[clap(
+        long,
+        help = "Only include edges active at this RFC 3339 timestamp (per `valid_from`/`valid_to`)"
+    )]
+    pub valid_at: Option<DateTime<Utc>>,
+
+    #[clap(long, help = "Include archived (soft-deleted) edges")]
+    pub include_archived: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct ListEdgesArgs {
+    #[command(flatten)]
+    pub filter: EdgeFilterArgs,
+
+    #[clap(
+        long,
+        help = "Only show edges created or updated since the last `sync` (based on `updated_at`)"
+    )]
+    pub modified_since_sync: bool,
+
+    #[clap(short, long, help = "Count the number of edges returned")]
+    pub count: bool,
+
+    #[clap(
+        long,
+        conflicts_with_all = ["count", "stats", "format", "group_by"],
+        help = "Print just the matching edges' IDs, one per line (e.g. to pipe into `xargs`)"
+    )]
+    pub ids_only: bool,
+
+    #[clap(
+        long,
+        help = "Include stats (e.g. the number of edges returned). JSON output becomes `{\"items\": [...], \"stats\": {...}}`, and NDJSON gets a trailing `_stats` line"
+    )]
+    pub stats: bool,
+
+    #[clap(
+        long,
+        help = "Leave out `created_at`/`updated_at` (e.g. to compare content across graphs)"
+    )]
+    pub omit_timestamps: bool,
+
+    #[clap(
+        long,
+        value_enum,
+        help = "Count the edges per type, source node, or target node rather than listing them"
+    )]
+    pub group_by: Option<EdgeGroupBy>,
+
+    #[clap(
+        short,
+        long,
+        help = "Limit the number of edges returned (0 for no limit). Defaults to `list_limit` in the config, or 100"
+    )]
+    pub limit: Option<usize>,
+
+    #[clap(
+        long,
+        conflicts_with = "limit",
+        help = "Return all of the edges (no limit)"
+    )]
+    pub all: bool,
+
+    #[clap(
+        long,
+        conflicts_with_all = ["limit", "all"],
+        help = "Return a random sample of this many matching edges. Note - This is slow on very large graphs (narrow it down with other filters) and isn't affected by --seed"
+    )]
+    pub sample: Option<usize>,
+
+    #[clap(short, long, help = "Output format", value_enum, default_value_t=OutputFormat::Json)]
+    pub format: OutputFormat,
+
+    #[command(flatten)]
+    pub prop_keys: PropKeyFilterArgs,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum GetCmd {
+    #[clap(about = "Get a node from the graph")]
+    Node(GetNodeArgs),
+
+    #[clap(about = "Get a edge from the graph")]
+    Edge(GetEdgeArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct GetNodeArgs {
+    #[clap(
+        short,
+        long,
+        required_unless_present = "ids_file",
+        help = "The node's ID (or a unique prefix of it)"
+    )]
+    pub id: Option<String>,
+
+    #[clap(
+        long,
+        conflicts_with_all = [
+            "id", "edges_in", "edges_out", "expand_edges", "ancestors", "descendants", "json_path",
+        ],
+        help = "Get every node whose full ID is listed in this file, one per line (or `-` for stdin)"
+    )]
+    pub ids_file: Option<PathBuf>,
+
+    #[clap(
+        long,
+        requires = "ids_file",
+        help = "With --ids-file, fail if any of the nodes don't exist (rather than warning and skipping them)"
+    )]
+    pub strict: bool,
+
+    #[clap(short, long, help = "Show the node's properties")]
+    pub props: bool,
+
+    #[clap(long, help = "Show the node even if it's archived")]
+    pub include_archived: bool,
+
+    #[clap(
+        long,
+        help = "Leave out `created_at`/`updated_at` (e.g. to compare content across graphs)"
+    )]
+    pub omit_timestamps: bool,
+
+    #[clap(long, help = "Show the node's incoming edges")]
+    pub edges_in: bool,
+
+    #[clap(long, help = "Show the node's outgoing edges")]
+    pub edges_out: bool,
+
+    #[clap(
+        long,
+        help = "Show full edge objects (rather than IDs) for --edges-in/--edges-out. Includes edge props with --props"
+    )]
+    pub expand_edges: bool,
+
+    #[clap(
+        long,
+        requires = "expand_edges",
+        help = "With --expand-edges, always include the edges' props and the node at each edge's other end"
+    )]
+    pub include_edges_props: bool,
+
+    #[clap(long, help = "Show all nodes that reach this node via directed edges")]
+    pub ancestors: bool,
+
+    #[clap(
+        long,
+        help = "Show all nodes reachable from this node via directed edges"
+    )]
+    pub descendants: bool,
+
+    #[clap(
+        long,
+        help = "Only follow edges of this type for --ancestors/--descendants"
+    )]
+    pub edge_type: Option<String>,
+
+    #[clap(
+        long,
+        help = "Only follow edges whose property compares with a value for --ancestors/--descendants \
+            (e.g. `weight>5`, `status=active`; operators are = != < <= > >=)"
+    )]
+    pub edge_prop: Vec<String>,
+
+    #[clap(
+        long,
+        help = "Only print the value(s) matching this JSONPath expression (e.g. `$.props.address.city`)"
+    )]
+    pub json_path: Option<String>,
+
+    #[clap(
+        short,
+        long,
+        value_enum,
+        default_value_t = GetNodeFormat::Json,
+        help = "The output format. `mermaid` renders the node and its neighbors as a Mermaid graph"
+    )]
+    pub format: GetNodeFormat,
+
+    #[command(flatten)]
+    pub prop_keys: PropKeyFilterArgs,
+}
+
+#[derive(Debug, Default, Clone, ValueEnum)]
+pub enum GetNodeFormat {
+    /// The node as a JSON object.
+    #[default]
+    Json,
+
+    /// The node and its neighborhood as Mermaid `graph` syntax.
+    Mermaid,
+
+    /// One compact JSON object per line (e.g. for `--ids-file`).
+    Ndjson,
+}
+
+#[derive(Args, Debug)]
+pub struct GetEdgeArgs {
+    #[clap(short, long, help = "The edge's ID (or a unique prefix of it)")]
+    pub id: String,
+
+    #[clap(short, long, help = "Show the edge's properties")]
+    pub props: bool,
+
+    #[clap(long, help = "Show the edge even if it's archived")]
+    pub include_archived: bool,
+
+    #[clap(
+        long,
+        help = "Leave out `created_at`/`updated_at` (e.g. to compare content across graphs)"
+    )]
+    pub omit_timestamps: bool,
+
+    #[clap(
+        long,
+        help = "Only print the value(s) matching this JSONPath expression (e.g. `$.props.weight`)"
+    )]
+    pub json_path: Option<String>,
+
+    #[command(flatten)]
+    pub prop_keys: PropKeyFilterArgs,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ExistsCmd {
+    #[clap(about = "Check whether a node exists")]
+    Node(ExistsArgs),
+
+    #[clap(about = "Check whether an edge exists")]
+    Edge(ExistsArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct ExistsArgs {
+    #[clap(short, long, help = "The ID to check for")]
+    pub id: String,
+
+    #[clap(
+        short,
+        long,
+        help = "Don't print anything. Only set the exit code (0 if it exists, 3 if not)"
+    )]
+    pub quiet: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum UpdateCmd {
+    #[clap(about = "Update nodes in the graph")]
+    Node(UpdateNodeArgs),
+
+    #[clap(about = "Update all nodes matching a filter")]
+    Nodes(UpdateNodesArgs),
+
+    #[clap(about = "Update edges in the graph")]
+    Edge(UpdateEdgeArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct UpdateNodeArgs {
+    #[clap(short, long, help = "The node's ID (or a unique prefix of it)")]
+    pub id: String,
+
+    #[clap(short, long, help = "Labels to add to the node")]
+    pub add_label: Vec<String>,
+
+    #[clap(long, help = "Labels to remove from the node")]
+    pub remove_label: Vec<String>,
+
+    #[clap(short, long, help = "Props to set on the node")]
+    pub set_prop: Vec<String>,
+
+    #[clap(short, long, help = "Props to remove from the node")]
+    pub remove_prop: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct UpdateNodesArgs {
+    #[command(flatten)]
+    pub filter: NodeFilterArgs,
+
+    #[clap(long, num_args=0.., help = "Props to set on the nodes (as `key=value`)")]
+    pub set_prop: Vec<String>,
+
+    #[clap(long, num_args=0.., help = "Keys of props to remove from the nodes")]
+    pub remove_prop: Vec<String>,
+
+    #[clap(
+        long,
+        help = "Show how many nodes would be updated without changing them"
+    )]
+    pub dry_run: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct UpdateEdgeArgs {
+    #[clap(short, long, help = "The edge's ID (or a unique prefix of it)")]
+    pub id: String,
+
+    #[clap(short, long, help = "Set the edge's type")]
+    pub edge_type: Option<String>,
+
+    #[clap(short, long, help = "Set the edge's source node")]
+    pub from_node: Option<String>,
+
+    #[clap(short, long, help = "Set the edge's target node")]
+    pub to_node: Option<String>,
+
+    #[clap(
+        long,
+        conflicts_with_all = ["from_node", "to_node"],
+        help = "Swap the edge's source and target nodes (e.g. if it was created backwards)"
+    )]
+    pub swap: bool,
+
+    #[clap(
+        long,
+        conflicts_with = "set_undirected",
+        help = "Set the edge as directed"
+    )]
+    pub set_directed: bool,
+
+    #[clap(long, help = "Set the edge as undirected")]
+    pub set_undirected: bool,
+
+    #[clap(short, long, help = "Props to set on the edge")]
+    pub set_prop: Vec<String>,
+
+    #[clap(short, long, help = "Props to remove from the edge")]
+    pub remove_prop: Vec<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DeleteCmd {
+    #[clap(about = "Delete nodes from the graph")]
+    Node(DeleteNodeArgs),
+
+    #[clap(about = "Delete edges from the graph")]
+    Edge(DeleteEdgeArgs),
+
+    #[clap(about = "Delete all nodes matching a filter")]
+    Nodes(DeleteNodesArgs),
+
+    #[clap(about = "Delete all edges matching a filter")]
+    Edges(DeleteEdgesArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct DeleteNodeArgs {
+    #[clap(short, long, help = "The node's ID (or a unique prefix of it)")]
+    pub id: String,
+
+    #[clap(
+        long,
+        help = "Archive the node (and its edges) instead of deleting it. Restore it with `unarchive node`"
+    )]
+    pub soft: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct DeleteEdgeArgs {
+    #[clap(short, long, help = "The edge's ID (or a unique prefix of it)")]
+    pub id: String,
+
+    #[clap(
+        long,
+        help = "Archive the edge instead of deleting it. Restore it with `unarchive edge`"
+    )]
+    pub soft: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum UnarchiveCmd {
+    #[clap(about = "Restore an archived node (and the edges archived with it)")]
+    Node(UnarchiveArgs),
+
+    #[clap(about = "Restore an archived edge")]
+    Edge(UnarchiveArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct UnarchiveArgs {
+    #[clap(short, long, help = "The ID (or a unique prefix of it)")]
+    pub id: String,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ReplaceCmd {
+    #[clap(
+        about = "Replace a node with a new one, repointing its edges and deleting the old node"
+    )]
+    Node(ReplaceNodeArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct ReplaceNodeArgs {
+    #[clap(
+        short,
+        long,
+        help = "The ID of the node to replace (or a unique prefix of it)"
+    )]
+    pub id: String,
+
+    #[clap(short, long, num_args=0.., help = "The new node's label")]
+    pub label: Vec<String>,
+
+    #[clap(short, long, num_args=0.., help = "A property attached to the new node")]
+    pub prop: Vec<String>,
+
+    #[clap(long, help = "The new node's ID. Generated if not set")]
+    pub new_id: Option<String>,
+
+    #[clap(
+        long,
+        help = "Start from the old node's labels and props. Labels are added to the old node's \
+            and props override the old node's"
+    )]
+    pub keep: bool,
+
+    #[clap(
+        long,
+        help = "Fail if the new node has no labels. Can also be set with `require_node_label` in the config"
+    )]
+    pub require_label: bool,
 }
 
 #[derive(Args, Debug)]
-pub struct CreateNodeArgs {
-    #[clap(short, long, num_args=0.., help = "The node's label")]
-    pub label: Vec<String>,
+pub struct DeleteNodesArgs {
+    #[command(flatten)]
+    pub filter: NodeFilterArgs,
 
-    #[clap(short, long, num_args=0.., help="A property attached to the node")]
-    pub prop: Vec<String>,
+    #[clap(short, long, help = "Delete without asking for confirmation")]
+    pub yes: bool,
+
+    #[clap(long, help = "Show what would be deleted without deleting anything")]
+    pub dry_run: bool,
 }
 
 #[derive(Args, Debug)]
-pub struct CreateEdgeArgs {
-    #[clap(short, long, help = "The edge's type")]
-    pub edge_type: String,
+pub struct DeleteEdgesArgs {
+    #[command(flatten)]
+    pub filter: EdgeFilterArgs,
 
-    #[clap(short, long, help = "The edge's source node")]
-    pub from_node: String,
-
-    #[clap(short, long, help = "The edge's target node")]
-    pub to_node: String,
+    #[clap(
+        long,
+        help = "Only delete edges whose source or target node doesn't exist"
+    )]
+    pub dangling: bool,
 
-    #[clap(short, long, help = "Whether the edge is directed.")]
-    pub directed: bool,
+    #[clap(short, long, help = "Delete without asking for confirmation")]
+    pub yes: bool,
 
-    #[clap(short, long, num_args=0.., help="A property on the edge")]
-    pub prop: Vec<String>,
+    #[clap(long, help = "Show what would be deleted without deleting anything")]
+    pub dry_run: bool,
 }
 
 #[derive(Subcommand, Debug)]
-pub enum ListCmd {
-    #[clap(about = "List nodes in the graph")]
-    Nodes(ListNodesArgs),
+pub enum MetaCmd {
+    #[clap(about = "Set a piece of graph-level metadata (e.g. a description)")]
+    Set(MetaSetArgs),
 
-    #[clap(about = "List edges in the graph")]
-    Edges(ListEdgesArgs),
-}
+    #[clap(about = "Get a piece of graph-level metadata")]
+    Get(MetaGetArgs),
 
-#[derive(Args, Debug)]
-pub struct ListNodesArgs {
-    #[clap(long, help = "The node's label")]
-    pub has_label: Option<String>,
+    #[clap(about = "List all graph-level metadata")]
+    List,
 
-    #[clap(long, num_args=0.., help = "Filter to nodes with a certain property")]
-    pub has_prop: Vec<String>,
+    #[clap(about = "Refresh the query planner's statistics (e.g. after a bulk import)")]
+    Reindex(MetaReindexArgs),
 
-    #[clap(short, long, num_args=0.., help = "Filter to nodes with a key-value pair")]
-    pub prop: Vec<String>,
+    #[clap(about = "Create (or replace) a node template, for `create node --from-template`")]
+    SetTemplate(MetaSetTemplateArgs),
 
-    #[clap(short='o', long, num_args=0.., help = "IDs of edges going out")]
-    pub edge_out: Vec<String>,
+    #[clap(
+        about = "Set the default props for new nodes with a label (or remove them, with no props)"
+    )]
+    SetLabelDefaults(MetaSetLabelDefaultsArgs),
 
-    #[clap(short='i', long, num_args=0.., help = "IDs of edges coming in")]
-    pub edge_in: Vec<String>,
+    #[clap(about = "List the default props for each label")]
+    ListLabelDefaults,
 
-    #[clap(long, num_args=0.., help = "Key-value pairs of edges out. Either `EDGE_TYPE=:NodeLabel` or `EDGE_TYPE=node-id`")]
-    pub edge_out_to: Vec<String>,
+    #[clap(about = "Find the graph's connected components (treating edges as undirected)")]
+    Components(MetaComponentsArgs),
 
-    #[clap(long, num_args=0.., help = "Key-value pairs of edges in. Either `EDGE_TYPE=:NodeLabel` or `EDGE_TYPE=node-id`")]
-    pub edge_in_from: Vec<String>,
+    #[clap(about = "Show the graph's density, average degree, and degree distribution")]
+    Density(MetaDensityArgs),
 
-    #[clap(short, long, help = "Count the number of nodes returned")]
-    pub count: bool,
+    #[clap(about = "Count the nodes reachable from a node (following directed edges)")]
+    ReachableFrom(MetaReachableFromArgs),
 
-    #[clap(short, long, help = "Limit the number of nodes returned")]
-    pub limit: Option<usize>,
+    #[clap(about = "Run any pending schema migrations, reporting what was applied")]
+    Migrate(MetaMigrateArgs),
+
+    #[clap(
+        about = "Show the recorded changes to a node or edge (with `audit` enabled in the config)"
+    )]
+    Audit(MetaAuditArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct MetaAuditArgs {
+    #[clap(
+        long,
+        help = "The node's or edge's ID (the full ID, since it may have been deleted)"
+    )]
+    pub id: String,
+}
 
+#[derive(Args, Debug)]
+pub struct MetaDensityArgs {
     #[clap(short, long, help = "Output format", value_enum, default_value_t=OutputFormat::Json)]
     pub format: OutputFormat,
 }
 
 #[derive(Args, Debug)]
-pub struct ListEdgesArgs {
-    #[clap(long, help = "The edge's type")]
-    pub has_label: Option<String>,
-
-    #[clap(long, num_args=0.., help = "Filter to edges with a certain property")]
-    pub has_prop: Vec<String>,
+pub struct MetaReachableFromArgs {
+    #[clap(help = "The starting node's ID (or a unique prefix of it)")]
+    pub id: String,
 
-    #[clap(short, long, num_args=0.., help = "Filter to edges with property key-value match")]
-    pub prop: Vec<String>,
+    #[clap(
+        long,
+        help = "Follow every edge both ways (giving the rest of the node's connected component)"
+    )]
+    pub undirected: bool,
 
-    #[clap(short, long, help = "ID of the source node")]
-    pub source_node: Option<String>,
+    #[clap(long, help = "Only follow edges of this type")]
+    pub edge_type: Option<String>,
 
-    #[clap(short, long, help = "ID of the target node")]
-    pub target_node: Option<String>,
+    #[clap(
+        long,
+        help = "Only follow edges whose property compares with a value (e.g. `weight>5`; operators are = != < <= > >=)"
+    )]
+    pub edge_prop: Vec<String>,
 
-    #[clap(short, long, help = "Count the number of edges returned")]
-    pub count: bool,
+    #[clap(long, help = "Also list the reachable nodes' IDs")]
+    pub ids: bool,
+}
 
-    #[clap(short, long, help = "Limit the number of nodes returned")]
-    pub limit: Option<usize>,
+#[derive(Args, Debug)]
+pub struct MetaMigrateArgs {
+    #[clap(long, help = "List the pending migrations without applying them")]
+    pub dry_run: bool,
+}
 
-    #[clap(short, long, help = "Output format", value_enum, default_value_t=OutputFormat::Json)]
-    pub format: OutputFormat,
+#[derive(Args, Debug)]
+pub struct MetaComponentsArgs {
+    #[clap(long, help = "Also show which component each node is in")]
+    pub per_node: bool,
 }
 
-#[derive(Subcommand, Debug)]
-pub enum GetCmd {
-    #[clap(about = "Get a node from the graph")]
-    Node(GetNodeArgs),
+#[derive(Args, Debug)]
+pub struct MetaSetLabelDefaultsArgs {
+    #[clap(short, long, help = "The label")]
+    pub label: String,
 
-    #[clap(about = "Get a edge from the graph")]
-    Edge(GetEdgeArgs),
+    #[clap(short, long, num_args=0.., help = "A default property for nodes with the label")]
+    pub prop: Vec<String>,
 }
 
 #[derive(Args, Debug)]
-pub struct GetNodeArgs {
-    #[clap(short, long, help = "The node's ID")]
-    pub id: String,
+pub struct MetaSetTemplateArgs {
+    #[clap(short, long, help = "The template's name")]
+    pub name: String,
 
-    #[clap(short, long, help = "Show the node's properties")]
-    pub props: bool,
+    #[clap(short, long, num_args=0.., help = "A default label for nodes")]
+    pub label: Vec<String>,
 
-    #[clap(short, long, help = "Show the node's incoming edges")]
-    pub edges_in: bool,
+    #[clap(short, long, num_args=0.., help = "A default property for nodes")]
+    pub prop: Vec<String>,
+}
 
-    #[clap(short, long, help = "Show the node's outgoing edges")]
-    pub edges_out: bool,
+#[derive(Args, Debug)]
+pub struct MetaReindexArgs {
+    #[clap(long, help = "Also rebuild the indexes (with `REINDEX`)")]
+    pub reindex: bool,
 }
 
 #[derive(Args, Debug)]
-pub struct GetEdgeArgs {
-    #[clap(short, long, help = "The edge's ID")]
-    pub id: String,
+pub struct MetaSetArgs {
+    #[clap(short, long, help = "The metadata key (e.g. `description`)")]
+    pub key: String,
 
-    #[clap(short, long, help = "Show the edge's properties")]
-    pub props: bool,
+    #[clap(short, long, help = "The value to set")]
+    pub value: String,
 }
 
-#[derive(Subcommand, Debug)]
-pub enum UpdateCmd {
-    #[clap(about = "Update nodes in the graph")]
-    Node(UpdateNodeArgs),
-
-    #[clap(about = "Update edges in the graph")]
-    Edge(UpdateEdgeArgs),
+#[derive(Args, Debug)]
+pub struct MetaGetArgs {
+    #[clap(short, long, help = "The metadata key (e.g. `description`)")]
+    pub key: String,
 }
 
 #[derive(Args, Debug)]
-pub struct UpdateNodeArgs {
-    #[clap(short, long, help = "The node's ID")]
-    pub id: String,
+pub struct ExportArgs {
+    #[clap(short, long, help = "Write the export to this file rather than stdout")]
+    pub output: Option<PathBuf>,
 
-    #[clap(short, long, help = "Labels to add to the node")]
-    pub add_label: Vec<String>,
+    #[clap(short, long, help = "Export format", value_enum, default_value_t=ExportFormat::Json)]
+    pub format: ExportFormat,
 
-    #[clap(short, long, help = "Labels to remove from the node")]
-    pub remove_label: Vec<String>,
+    #[clap(
+        long,
+        help = "Only export nodes and edges created or updated after this RFC 3339 timestamp. \
+            Deletes aren't captured"
+    )]
+    pub since: Option<DateTime<Utc>>,
 
-    #[clap(short, long, help = "Props to set on the node")]
-    pub set_prop: Vec<String>,
+    #[clap(
+        long,
+        requires = "output",
+        help = "Append to the output file rather than overwriting it (`ndjson` format only)"
+    )]
+    pub append: bool,
 
-    #[clap(short, long, help = "Props to remove from the node")]
-    pub remove_prop: Vec<String>,
-}
+    #[clap(
+        long,
+        help = "Leave out `created_at`/`updated_at` (e.g. to compare content across graphs). The export can't be imported"
+    )]
+    pub omit_timestamps: bool,
 
-#[derive(Args, Debug)]
-pub struct UpdateEdgeArgs {
-    #[clap(short, long, help = "The edge's ID")]
-    pub id: String,
+    #[command(flatten)]
+    pub filter: NodeFilterArgs,
 
-    #[clap(short, long, help = "Set the edge's type")]
-    pub edge_type: Option<String>,
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = ExportEdges::Within,
+        help = "When filtering nodes, which edges to export: those between exported nodes (`within`), \
+            those with at least one end in them (`touching`), or `none`"
+    )]
+    pub edges: ExportEdges,
+}
 
-    #[clap(short, long, help = "Set the edge's source node")]
-    pub from_node: Option<String>,
+#[derive(Debug, Default, Clone, ValueEnum)]
+pub enum ExportFormat {
+    /// A JSON dump, which can be read back by `diff`.
+    #[default]
+    Json,
 
-    #[clap(short, long, help = "Set the edge's target node")]
-    pub to_node: Option<String>,
+    /// GraphML XML (e.g. for Gephi).
+    Graphml,
 
-    #[clap(short, long, help = "Set the edge as directed")]
-    pub set_directed: bool,
+    /// One JSON object per line, each a node or edge tagged with its `kind`.
+    Ndjson,
+}
 
-    #[clap(short, long, help = "Set the edge as undirected")]
-    pub set_undirected: bool,
+#[derive(Args, Debug)]
+pub struct ImportArgs {
+    #[clap(short, long, help = "The NDJSON file to import (or `-` for stdin)")]
+    pub from_file: PathBuf,
 
-    #[clap(short, long, help = "Props to set on the edge")]
-    pub set_prop: Vec<String>,
+    #[clap(
+        long,
+        help = "Commit in batches, recording progress in this file so a failed import can be resumed by re-running it. \
+            Note - A failed import keeps the batches that were already committed"
+    )]
+    pub checkpoint: Option<PathBuf>,
 
-    #[clap(short, long, help = "Props to remove from the edge")]
-    pub remove_prop: Vec<String>,
-}
+    #[clap(
+        long,
+        help = "With --checkpoint or --workers, the number of lines committed at a time [default: 1000]"
+    )]
+    pub batch_size: Option<usize>,
 
-#[derive(Subcommand, Debug)]
-pub enum DeleteCmd {
-    #[clap(about = "Delete nodes from the graph")]
-    Node(DeleteNodeArgs),
+    #[clap(
+        long,
+        conflicts_with = "checkpoint",
+        help = "Insert batches in parallel over this many connections (for remote databases). \
+            Note - Each batch is committed separately, so a failed import keeps the batches that were already committed"
+    )]
+    pub workers: Option<usize>,
 
-    #[clap(about = "Delete edges from the graph")]
-    Edge(DeleteEdgeArgs),
+    #[clap(
+        long,
+        conflicts_with_all = ["checkpoint", "workers"],
+        help = "Check the whole file and report every problem (by line and field) without importing anything"
+    )]
+    pub validate_only: bool,
 }
 
 #[derive(Args, Debug)]
-pub struct DeleteNodeArgs {
-    #[clap(short, long, help = "The node's ID")]
-    pub id: String,
+pub struct DiffArgs {
+    #[clap(
+        short,
+        long,
+        help = "The other graph -- either a config directory or a file written by `graphctl export`"
+    )]
+    pub against: PathBuf,
+
+    #[clap(short, long, help = "Print a human-readable summary rather than JSON")]
+    pub summary: bool,
 }
 
 #[derive(Args, Debug)]
-pub struct DeleteEdgeArgs {
-    #[clap(short, long, help = "The edge's ID")]
-    pub id: String,
+pub struct SyncArgs {
+    #[clap(
+        long,
+        help = "List the nodes and edges updated since the previous sync. \
+            Based on timestamps, so clock skew between writers can cause misses"
+    )]
+    pub report: bool,
 }
 
 #[derive(Subcommand, Debug)]
 pub enum CfgCmd {
     #[clap(about = "Initialize the graphctl CLI")]
-    Init,
+    Init(InitArgs),
+
+    #[clap(about = "Show the current configuration")]
+    Show(ShowArgs),
 
     #[clap(about = "Get the database type")]
     GetDbType(GetDbTypeArgs),
@@ -306,6 +1386,116 @@ pub enum CfgCmd {
 
     #[clap(about = "Set the local database encryption key")]
     SetEncryptionKey(SetEncryptionKeyArgs),
+
+    #[clap(
+        about = "Switch the database type, copying the graph to the new database (local <-> remote-only)"
+    )]
+    MigrateDbType(MigrateDbTypeArgs),
+
+    #[clap(about = "Move the local database files to another directory, updating the config")]
+    SetDataDir(SetDataDirArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct MigrateDbTypeArgs {
+    #[clap(long, value_enum, help = "The database type to switch to")]
+    pub to: DBType,
+
+    #[clap(
+        long,
+        help = "The remote database URL to copy the graph to (if not already set in the config)"
+    )]
+    pub remote_db_url: Option<String>,
+
+    #[clap(long, help = "Show what would be copied without copying it")]
+    pub dry_run: bool,
+
+    #[clap(short, long, help = "Don't ask for confirmation")]
+    pub yes: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct VersionArgs {
+    #[clap(
+        short,
+        long,
+        help = "Also show the git commit, libsql version, OS/arch, and keyring backend"
+    )]
+    pub verbose: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct SetDataDirArgs {
+    #[clap(long, help = "The directory to move the database files to")]
+    pub path: PathBuf,
+
+    #[clap(long, help = "Show what would be moved without moving it")]
+    pub dry_run: bool,
+
+    #[clap(short, long, help = "Don't ask for confirmation")]
+    pub yes: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct InitArgs {
+    #[clap(
+        long,
+        help = "Set up from a template config file (e.g. one checked into version control). \
+            The other flags override its settings, and only missing secrets are prompted for"
+    )]
+    pub from_config: Option<PathBuf>,
+
+    #[clap(long, value_enum, help = "The database type. Prompted for if not set")]
+    pub db_type: Option<DBType>,
+
+    #[clap(
+        long,
+        help = "The URL of the remote database. Prompted for if needed and not set"
+    )]
+    pub remote_db_url: Option<String>,
+
+    #[clap(
+        long,
+        env = "GRAPHCTL_REMOTE_DB_TOKEN",
+        hide_env_values = true,
+        help = "The auth token for the remote database. Prompted for if needed and not set"
+    )]
+    pub remote_db_token: Option<String>,
+
+    #[clap(
+        long,
+        conflicts_with = "no_encrypt",
+        help = "Encrypt the local database / replica"
+    )]
+    pub encrypt: bool,
+
+    #[clap(long, help = "Don't encrypt the local database / replica")]
+    pub no_encrypt: bool,
+
+    #[clap(
+        long,
+        value_enum,
+        help = "Print a summary of the created setup in this format"
+    )]
+    pub output: Option<InitOutput>,
+}
+
+impl InitArgs {
+    /// Returns whether encryption was explicitly enabled or disabled,
+    /// or `None` if the user should be prompted.
+    pub fn encrypt(&self) -> Option<bool> {
+        match (self.encrypt, self.no_encrypt) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ShowArgs {
+    #[clap(short, long, help = "Output format", value_enum, default_value_t=OutputFormat::Json)]
+    pub format: OutputFormat,
 }
 
 #[derive(Args, Debug)]
@@ -341,10 +1531,109 @@ pub struct SetEncryptionKeyArgs {
     pub key: String,
 }
 
+/// The output formats for the `init` command's summary.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum InitOutput {
+    Json,
+}
+
 #[derive(Debug, Default, Clone, ValueEnum)]
 pub enum OutputFormat {
+    /// Pretty-printed JSON (the default).
     #[default]
+    #[value(alias = "json-pretty")]
     Json,
+    /// JSON on a single line.
+    JsonCompact,
+    #[value(alias = "jsonl")]
     Ndjson,
     Table,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_node_positional_label() {
+        let app = Cli::try_parse_from([
+            "graphctl",
+            "create",
+            "node",
+            "Person",
+            "-l",
+            "Admin",
+            "-p",
+            "name=Alice",
+        ])
+        .unwrap();
+        let args = match app.cmd {
+            Commands::Create {
+                cmd: CreateCmd::Node(args),
+            } => args,
+            _ => panic!("Expected a create node command"),
+        };
+        assert_eq!(args.labels(), vec!["Person", "Admin"]);
+        assert_eq!(args.prop, vec!["name=Alice"]);
+        assert!(!args.require_label);
+
+        let app = Cli::try_parse_from(["graphctl", "create", "node", "--require-label"]).unwrap();
+        let args = match app.cmd {
+            Commands::Create {
+                cmd: CreateCmd::Node(args),
+            } => args,
+            _ => panic!("Expected a create node command"),
+        };
+        assert!(args.labels().is_empty());
+        assert!(args.require_label);
+    }
+
+    #[test]
+    fn test_create_edge_direction() {
+        let direction = |flags: &[&str]| {
+            let mut argv = vec![
+                "graphctl", "create", "edge", "-e", "knows", "-f", "a", "-t", "b",
+            ];
+            argv.extend_from_slice(flags);
+            match Cli::try_parse_from(argv).unwrap().cmd {
+                Commands::Create {
+                    cmd: CreateCmd::Edge(args),
+                } => args.direction(),
+                _ => panic!("Expected a create edge command"),
+            }
+        };
+        assert_eq!(direction(&["--directed"]), Some(true));
+        assert_eq!(direction(&["--undirected"]), Some(false));
+        assert_eq!(direction(&[]), None);
+
+        // The flags can't be combined...
+        let argv = [
+            "graphctl", "create", "edge", "-e", "knows", "-f", "a", "-t", "b",
+        ];
+        let both = [&argv[..], &["--directed", "--undirected"]].concat();
+        assert!(Cli::try_parse_from(both).is_err());
+    }
+
+    #[test]
+    fn test_output_format_values() {
+        let format = |flags: &[&str]| {
+            let argv = [&["graphctl", "list", "nodes"][..], flags].concat();
+            match Cli::try_parse_from(argv).unwrap().cmd {
+                Commands::List {
+                    cmd: ListCmd::Nodes(args),
+                } => args.format,
+                _ => panic!("Expected a list nodes command"),
+            }
+        };
+
+        // Pretty JSON is still the default, and can be asked for by name...
+        assert!(matches!(format(&[]), OutputFormat::Json));
+        assert!(matches!(format(&["-f", "json"]), OutputFormat::Json));
+        assert!(matches!(format(&["-f", "json-pretty"]), OutputFormat::Json));
+        assert!(matches!(
+            format(&["-f", "json-compact"]),
+            OutputFormat::JsonCompact
+        ));
+        assert!(matches!(format(&["-f", "jsonl"]), OutputFormat::Ndjson));
+    }
+}
@@ -1,12 +1,13 @@
+//! Handles application configuration.
 #![allow(dead_code, unused_variables)]
 
+use crate::error;
 use anyhow::{anyhow, Result};
+use clap::ValueEnum;
 use home::home_dir;
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 
-///! Handles application configuration.
-
 /// The name of the default config directory expected
 /// to be in the user's $HOME directory.
 pub const CONFIG_DIR_NAME: &str = ".graphctl";
@@ -21,6 +22,13 @@ pub const DB_DIR_NAME: &str = "data";
 /// The name of the main database file.
 pub const DB_FILE_NAME: &str = "graph.db";
 
+/// The name of the lock file (within the config directory) held
+/// while an init or destructive command is running.
+pub const LOCK_FILE_NAME: &str = ".lock";
+
+/// The default number of results list commands return.
+pub const DEFAULT_LIST_LIMIT: usize = 100;
+
 /// Get the path to the app config directory.
 pub fn get_config_dir(config_dir: Option<String>) -> Option<PathBuf> {
     // Was a config dir passed in?
@@ -34,27 +42,142 @@ pub fn get_config_dir(config_dir: Option<String>) -> Option<PathBuf> {
     Some(config_dir)
 }
 
+/// Where the config directory's path came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigDirSource {
+    /// The `--config-dir` flag.
+    Flag,
+
+    /// The `GRAPHCTL_CONFIG_DIR` environment variable.
+    Env,
+
+    /// The default (`$HOME/.graphctl`).
+    Default,
+}
+
+impl std::fmt::Display for ConfigDirSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigDirSource::Flag => write!(f, "the `--config-dir` flag"),
+            ConfigDirSource::Env => write!(f, "the `GRAPHCTL_CONFIG_DIR` environment variable"),
+            ConfigDirSource::Default => write!(f, "the default (`$HOME/{}`)", CONFIG_DIR_NAME),
+        }
+    }
+}
+
+/// Describe where a config directory came from and how to set it up,
+/// for when it's missing or invalid.
+pub fn config_dir_hint(config_dir: &Path, source: ConfigDirSource) -> String {
+    format!(
+        "The config directory \"{}\" was set by {}. If that's the right path, run `graphctl cfg init --config-dir {}` to set it up. Otherwise, pass the path you meant with `--config-dir`.",
+        config_dir.display(),
+        source,
+        config_dir.display(),
+    )
+}
+
 /// Given a config directory, get the path to the config file.
-pub fn get_config_file(config_dir: &PathBuf) -> PathBuf {
+pub fn get_config_file(config_dir: &Path) -> PathBuf {
     config_dir.join(CONFIG_FILE_NAME)
 }
 
-/// Given a config directory, get the path to the database directory.
-pub fn get_db_dir(config_dir: &PathBuf) -> PathBuf {
+/// Given a config directory, get the path to the default database directory.
+pub fn get_db_dir(config_dir: &Path) -> PathBuf {
     config_dir.join(DB_DIR_NAME)
 }
 
+/// Given a config directory and the configured `data_dir` (if any), get
+/// the path to the database directory. A relative `data_dir` is relative
+/// to the config directory.
+pub fn resolve_db_dir(config_dir: &Path, data_dir: Option<&Path>) -> PathBuf {
+    match data_dir {
+        Some(dir) => config_dir.join(dir),
+        None => get_db_dir(config_dir),
+    }
+}
+
+/// List the database files (the main file plus its WAL, shared-memory,
+/// and replica metadata files) in a database directory.
+pub fn list_db_files(db_dir: &Path) -> Result<Vec<PathBuf>> {
+    if !db_dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(db_dir)? {
+        let entry = entry?;
+        let is_db_file = entry
+            .file_name()
+            .to_str()
+            .is_some_and(|name| name.starts_with(DB_FILE_NAME));
+        if is_db_file && entry.file_type()?.is_file() {
+            files.push(entry.path());
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Given a config directory, get the path to the lock file.
+pub fn get_lock_file(config_dir: &Path) -> PathBuf {
+    config_dir.join(LOCK_FILE_NAME)
+}
+
 /// Given a config directory, get the path to the database file.
-pub fn get_db_file(config_dir: &PathBuf) -> PathBuf {
+pub fn get_db_file(config_dir: &Path) -> PathBuf {
     config_dir.join(DB_DIR_NAME).join(DB_FILE_NAME)
 }
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Config {
     #[serde(skip)]
     pub conf_dir: PathBuf,
 
     pub db: DbConfig,
+
+    /// The timezone timestamps are displayed in.
+    /// Note - Timestamps are always stored in UTC.
+    #[serde(default)]
+    pub timezone: DisplayTimezone,
+
+    /// The number of results list commands return when `--limit`
+    /// isn't passed (with `0` meaning no limit).
+    /// Defaults to `DEFAULT_LIST_LIMIT` if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub list_limit: Option<usize>,
+
+    /// Whether new edges are directed when neither `--directed`
+    /// nor `--undirected` is passed.
+    #[serde(default)]
+    pub default_edge_directed: bool,
+}
+
+impl Config {
+    /// Get the limit for a list command, given its `--limit` and `--all` args.
+    ///
+    /// Returns `None` for no limit.
+    pub fn list_limit(&self, limit: Option<usize>, all: bool) -> Option<usize> {
+        if all {
+            return None;
+        }
+        let limit = limit.or(self.list_limit).unwrap_or(DEFAULT_LIST_LIMIT);
+        (limit > 0).then_some(limit)
+    }
+
+    /// Get whether a new edge is directed, given its explicit
+    /// direction (if any).
+    pub fn edge_directed(&self, directed: Option<bool>) -> bool {
+        directed.unwrap_or(self.default_edge_directed)
+    }
+
+    /// Get the path to the database directory (see `DbConfig::data_dir`).
+    pub fn db_dir(&self) -> PathBuf {
+        resolve_db_dir(&self.conf_dir, self.db.data_dir.as_deref())
+    }
+
+    /// Get the path to the database file.
+    pub fn db_file(&self) -> PathBuf {
+        self.db_dir().join(DB_FILE_NAME)
+    }
 }
 
 impl Config {
@@ -69,15 +192,17 @@ impl Config {
                 db_type: DBType::Local,
                 remote_db_path: None,
                 encrypt_replica: false,
+                ..Default::default()
             },
+            ..Default::default()
         })
     }
 
-    pub fn read_from_file(config_dir: &PathBuf) -> Result<Self> {
+    pub fn read_from_file(config_dir: &Path) -> Result<Self> {
         let conf_file = get_config_file(config_dir);
         let conf_str = std::fs::read_to_string(conf_file)?;
         let mut conf: Config = toml::from_str(&conf_str)?;
-        conf.conf_dir = config_dir.clone();
+        conf.conf_dir = config_dir.to_path_buf();
         Ok(conf)
     }
 
@@ -87,10 +212,95 @@ impl Config {
         std::fs::write(conf_file, conf_str)?;
         Ok(())
     }
+
+    /// Read a template config file (e.g. one checked into version
+    /// control) to set up the config dir `config_dir` from.
+    ///
+    /// The template is validated, and can't contain secrets.
+    pub fn read_template(path: &Path, config_dir: &Path) -> Result<Self> {
+        let conf_str = std::fs::read_to_string(path).map_err(|err| {
+            error::config(format!(
+                "Could not read config file \"{}\": {}",
+                path.display(),
+                err
+            ))
+        })?;
+        let mut conf: Config = toml::from_str(&conf_str).map_err(|err| {
+            error::config(format!(
+                "Invalid config file \"{}\": {}",
+                path.display(),
+                err
+            ))
+        })?;
+        conf.conf_dir = config_dir.to_path_buf();
+        conf.validate()?;
+
+        // Secrets don't belong in a (shared) template...
+        if conf
+            .db
+            .remote_url()
+            .is_some_and(|url| url.auth_token.is_some())
+        {
+            return Err(error::config(format!(
+                "The remote URL in \"{}\" has an auth token embedded in it. Remove it from \
+                the file and pass it with `--remote-db-token` (or GRAPHCTL_REMOTE_DB_TOKEN).",
+                path.display()
+            )));
+        }
+        Ok(conf)
+    }
+
+    /// Check that the config's settings make sense together.
+    pub fn validate(&self) -> Result<()> {
+        let is_remote = matches!(
+            self.db.db_type,
+            DBType::RemoteOnly | DBType::RemoteWithReplica
+        );
+        let has_local_file = matches!(self.db.db_type, DBType::Local | DBType::RemoteWithReplica);
+
+        // Remote databases need a URL...
+        match self.db.remote_url() {
+            None if is_remote => {
+                return Err(error::config(format!(
+                    "`db.remote_db_path` is required for the `{}` database type.",
+                    self.db.db_type.name()
+                )));
+            }
+            Some(url) if is_remote && !url.url.contains("://") => {
+                return Err(error::config(format!(
+                    "Invalid `db.remote_db_path` '{}'. Expected a URL like \
+                    `libsql://<db>.turso.io`.",
+                    url.url
+                )));
+            }
+            Some(_) if !is_remote => {
+                return Err(error::config(format!(
+                    "`db.remote_db_path` is set, but the `{}` database type doesn't use it.",
+                    self.db.db_type.name()
+                )));
+            }
+            _ => (),
+        }
+
+        // Only local files can be encrypted or moved...
+        if self.db.encrypt_replica && !has_local_file {
+            return Err(error::config(format!(
+                "`db.encrypt_replica` is set, but the `{}` database type has no local file.",
+                self.db.db_type.name()
+            )));
+        }
+        if self.db.data_dir.is_some() && !has_local_file {
+            return Err(error::config(format!(
+                "`db.data_dir` is set, but the `{}` database type has no local file.",
+                self.db.db_type.name()
+            )));
+        }
+        Ok(())
+    }
 }
 
 /// Configuration for the underlying database.
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct DbConfig {
     /// The type/location of database to use.
     #[serde(rename = "type")]
@@ -100,20 +310,443 @@ pub struct DbConfig {
     /// the path to the remote database.
     pub remote_db_path: Option<String>,
 
+    /// If `db_type` is `local` or `remote-with-replica`, the directory
+    /// the database files are stored in. Defaults to `data` in the
+    /// config directory. Change it with `cfg set-data-dir`, which moves
+    /// the files too.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_dir: Option<PathBuf>,
+
     /// If `db_type` is `local` or `remote-with-replica`,
     /// should the replica be encrypted?
+    #[serde(default)]
     pub encrypt_replica: bool,
+
+    /// The maximum size (in bytes) of a single prop value.
+    /// Defaults to 1 MiB if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_prop_bytes: Option<usize>,
+
+    /// Should nodes be required to have at least one label?
+    #[serde(default)]
+    pub require_node_label: bool,
+
+    /// Should edges from a node to itself be allowed without
+    /// passing `--allow-self-loop`?
+    #[serde(default)]
+    pub allow_self_loops: bool,
+
+    /// How node labels are cased when stored and filtered on.
+    #[serde(default)]
+    pub label_case: CasePolicy,
+
+    /// How edge types are cased when stored and filtered on.
+    #[serde(default)]
+    pub edge_type_case: CasePolicy,
+
+    /// Should large prop values be gzip-compressed when stored?
+    ///
+    /// Note - Compressed values can't be matched by prop filters.
+    #[serde(default)]
+    pub compress_large_props: bool,
+
+    /// With `compress_large_props`, the size (in bytes) above which
+    /// prop values are compressed. Defaults to 4 KiB if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compress_props_over_bytes: Option<usize>,
+
+    /// Should changes (node/edge creates and deletes, and prop sets
+    /// and deletes) be recorded in the `_audit` table?
+    ///
+    /// Note - This adds a write for every change, so it's off by default.
+    #[serde(default)]
+    pub audit: bool,
+
+    /// The SQLite journal mode for local databases (and replicas).
+    /// If not set, SQLite's default (`delete`) is used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub journal_mode: Option<JournalMode>,
+
+    /// How hard SQLite works to make sure writes reach the disk, for
+    /// local databases (and replicas). If not set, SQLite's default
+    /// (`full`) is used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub synchronous: Option<SynchronousMode>,
+
+    /// A file to read the encryption key from, rather than the keyring.
+    /// Set by `--encryption-key-file` (never stored in the config file).
+    #[serde(skip)]
+    pub encryption_key_file: Option<PathBuf>,
+}
+
+impl DbConfig {
+    /// The remote database URL (if set), with any embedded
+    /// auth token split out.
+    pub fn remote_url(&self) -> Option<RemoteUrl> {
+        self.remote_db_path.as_deref().map(RemoteUrl::parse)
+    }
+
+    /// Which secrets (from the keyring) are needed to connect
+    /// to this database.
+    ///
+    /// The remote auth token isn't needed if the remote URL has
+    /// one embedded in it.
+    pub fn required_secrets(&self) -> RequiredSecrets {
+        let remote_db_token = self.remote_url().is_none_or(|url| url.auth_token.is_none());
+        match self.db_type {
+            DBType::Local => RequiredSecrets {
+                remote_db_token: false,
+                encryption_key: self.encrypt_replica,
+            },
+            DBType::RemoteOnly => RequiredSecrets {
+                remote_db_token,
+                encryption_key: false,
+            },
+            DBType::RemoteWithReplica => RequiredSecrets {
+                remote_db_token,
+                encryption_key: self.encrypt_replica,
+            },
+            DBType::Memory => RequiredSecrets::default(),
+        }
+    }
+}
+
+/// The secrets needed to connect to a database.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RequiredSecrets {
+    /// The remote database's auth token.
+    pub remote_db_token: bool,
+
+    /// The local database (or replica) encryption key.
+    pub encryption_key: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+/// The query param Turso connection strings use to embed
+/// an auth token in the URL.
+const AUTH_TOKEN_PARAM: &str = "authToken";
+
+/// A remote database URL, with any auth token embedded in it
+/// (as an `authToken` query param) split out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteUrl {
+    /// The URL to connect to, without the auth token.
+    pub url: String,
+
+    /// The auth token embedded in the URL, if any.
+    pub auth_token: Option<String>,
+}
+
+impl RemoteUrl {
+    /// Parse a remote database URL (e.g. a connection string copied
+    /// from the Turso dashboard), removing the `authToken` query param.
+    ///
+    /// Any other query params are kept as-is.
+    pub fn parse(raw: &str) -> RemoteUrl {
+        let raw = raw.trim();
+        let (base, query) = match raw.split_once('?') {
+            Some((base, query)) => (base, query),
+            None => {
+                return RemoteUrl {
+                    url: raw.to_string(),
+                    auth_token: None,
+                }
+            }
+        };
+
+        // Split the auth token out of the query params...
+        let mut auth_token = None;
+        let mut params = Vec::new();
+        for param in query.split('&').filter(|p| !p.is_empty()) {
+            match param.split_once('=') {
+                Some((AUTH_TOKEN_PARAM, token)) if !token.is_empty() => {
+                    auth_token = Some(token.to_string());
+                }
+                Some((AUTH_TOKEN_PARAM, _)) => {}
+                _ => params.push(param),
+            }
+        }
+
+        // Put the URL back together...
+        let url = match params.is_empty() {
+            true => base.to_string(),
+            false => format!("{}?{}", base, params.join("&")),
+        };
+        RemoteUrl { url, auth_token }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default, ValueEnum)]
 pub enum DBType {
     #[default]
     #[serde(rename = "local")]
+    #[value(name = "local")]
     Local,
 
     #[serde(rename = "remote-only")]
+    #[value(name = "remote-only")]
     RemoteOnly,
 
     #[serde(rename = "remote-with-replica")]
+    #[value(name = "remote-with-replica")]
     RemoteWithReplica,
+
+    /// An in-memory database. Nothing is persisted between
+    /// invocations, so this is mainly useful for testing.
+    #[serde(rename = "memory")]
+    #[value(name = "memory")]
+    Memory,
+}
+
+impl DBType {
+    /// The type's name, as it's written in the config.
+    pub fn name(self) -> &'static str {
+        match self {
+            DBType::Local => "local",
+            DBType::RemoteOnly => "remote-only",
+            DBType::RemoteWithReplica => "remote-with-replica",
+            DBType::Memory => "memory",
+        }
+    }
+}
+
+/// The timezone used when displaying timestamps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default, ValueEnum)]
+pub enum DisplayTimezone {
+    /// Show timestamps in UTC.
+    #[default]
+    #[serde(rename = "utc")]
+    #[value(name = "utc")]
+    Utc,
+
+    /// Show timestamps in the machine's local timezone.
+    #[serde(rename = "local")]
+    #[value(name = "local")]
+    Local,
+}
+
+/// The SQLite journal mode (`PRAGMA journal_mode`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+pub enum JournalMode {
+    /// Write-ahead logging. Usually faster for writes, and readers
+    /// don't block writers.
+    #[serde(rename = "wal")]
+    #[value(name = "wal")]
+    Wal,
+
+    /// A rollback journal, deleted after each transaction (SQLite's default).
+    #[serde(rename = "delete")]
+    #[value(name = "delete")]
+    Delete,
+}
+
+impl JournalMode {
+    /// The mode's name, as SQLite knows it.
+    pub fn as_sql(self) -> &'static str {
+        match self {
+            JournalMode::Wal => "WAL",
+            JournalMode::Delete => "DELETE",
+        }
+    }
+}
+
+/// How hard SQLite works to make sure writes reach the disk
+/// (`PRAGMA synchronous`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+pub enum SynchronousMode {
+    /// Sync less often. Safe from corruption in WAL mode, but the
+    /// last transactions can be lost on a power failure.
+    #[serde(rename = "normal")]
+    #[value(name = "normal")]
+    Normal,
+
+    /// Sync after every transaction (SQLite's default).
+    #[serde(rename = "full")]
+    #[value(name = "full")]
+    Full,
+
+    /// Never sync. The fastest, but a crash or power failure can
+    /// corrupt the database.
+    #[serde(rename = "off")]
+    #[value(name = "off")]
+    Off,
+}
+
+impl SynchronousMode {
+    /// The mode's name, as SQLite knows it.
+    pub fn as_sql(self) -> &'static str {
+        match self {
+            SynchronousMode::Normal => "NORMAL",
+            SynchronousMode::Full => "FULL",
+            SynchronousMode::Off => "OFF",
+        }
+    }
+}
+
+/// How a name (e.g. a label or edge type) should be cased.
+///
+/// The policy is applied both when storing a name and when filtering
+/// on it, so `lower` lets `--has-label person` match a node created
+/// with the label `Person`. Names stored before a policy was set
+/// aren't changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default, ValueEnum)]
+pub enum CasePolicy {
+    /// Keep names as they were given.
+    #[default]
+    #[serde(rename = "preserve")]
+    #[value(name = "preserve")]
+    Preserve,
+
+    /// Convert names to lowercase.
+    #[serde(rename = "lower")]
+    #[value(name = "lower")]
+    Lower,
+
+    /// Convert names to uppercase.
+    #[serde(rename = "upper")]
+    #[value(name = "upper")]
+    Upper,
+}
+
+impl CasePolicy {
+    /// Apply the policy to a name.
+    pub fn apply(&self, name: &str) -> String {
+        match self {
+            CasePolicy::Preserve => name.to_string(),
+            CasePolicy::Lower => name.to_lowercase(),
+            CasePolicy::Upper => name.to_uppercase(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_limit() {
+        let mut cfg = Config::default();
+        assert_eq!(cfg.list_limit(None, false), Some(DEFAULT_LIST_LIMIT));
+        assert_eq!(cfg.list_limit(Some(5), false), Some(5));
+        assert_eq!(cfg.list_limit(Some(0), false), None);
+        assert_eq!(cfg.list_limit(None, true), None);
+
+        // The config can change the default...
+        cfg.list_limit = Some(0);
+        assert_eq!(cfg.list_limit(None, false), None);
+        assert_eq!(cfg.list_limit(Some(5), false), Some(5));
+    }
+
+    #[test]
+    fn test_edge_directed() {
+        let mut cfg = Config::default();
+        assert!(cfg.edge_directed(Some(true)));
+        assert!(!cfg.edge_directed(Some(false)));
+        assert!(!cfg.edge_directed(None));
+
+        // The config can change the default...
+        cfg.default_edge_directed = true;
+        assert!(cfg.edge_directed(None));
+        assert!(cfg.edge_directed(Some(true)));
+        assert!(!cfg.edge_directed(Some(false)));
+    }
+
+    #[test]
+    fn test_parse_remote_url() {
+        // Without a token...
+        let url = RemoteUrl::parse("libsql://db-org.turso.io");
+        assert_eq!(url.url, "libsql://db-org.turso.io");
+        assert_eq!(url.auth_token, None);
+
+        // With a token...
+        let url = RemoteUrl::parse("libsql://db-org.turso.io?authToken=abc.def");
+        assert_eq!(url.url, "libsql://db-org.turso.io");
+        assert_eq!(url.auth_token.as_deref(), Some("abc.def"));
+
+        // With a token and other params...
+        let url = RemoteUrl::parse("libsql://db-org.turso.io?tls=1&authToken=abc&x=y");
+        assert_eq!(url.url, "libsql://db-org.turso.io?tls=1&x=y");
+        assert_eq!(url.auth_token.as_deref(), Some("abc"));
+
+        // An embedded token means the keyring token isn't needed...
+        let mut cfg = DbConfig {
+            db_type: DBType::RemoteOnly,
+            remote_db_path: Some("libsql://db-org.turso.io?authToken=abc".to_string()),
+            ..Default::default()
+        };
+        assert!(!cfg.required_secrets().remote_db_token);
+        cfg.remote_db_path = Some("libsql://db-org.turso.io".to_string());
+        assert!(cfg.required_secrets().remote_db_token);
+    }
+
+    #[test]
+    fn test_read_template() {
+        let dir = tempfile::tempdir().unwrap();
+        let conf_dir = dir.path().join("conf");
+        let write = |name: &str, contents: &str| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, contents).unwrap();
+            path
+        };
+
+        // Provision a config dir from a fixture...
+        let path = write(
+            "team.toml",
+            r#"
+            list_limit = 50
+
+            [db]
+            type = "remote-with-replica"
+            remote_db_path = "libsql://team-graph.turso.io"
+            data_dir = "/var/lib/graphctl"
+            encrypt_replica = true
+            label_case = "upper"
+            "#,
+        );
+        let cfg = Config::read_template(&path, &conf_dir).unwrap();
+        assert_eq!(cfg.conf_dir, conf_dir);
+        assert_eq!(cfg.db.db_type, DBType::RemoteWithReplica);
+        assert_eq!(cfg.db.data_dir, Some(PathBuf::from("/var/lib/graphctl")));
+        assert_eq!(cfg.db_dir(), PathBuf::from("/var/lib/graphctl"));
+        assert!(cfg.db.encrypt_replica);
+
+        // ...and it round-trips through the written config file...
+        std::fs::create_dir_all(&conf_dir).unwrap();
+        cfg.write_to_file().unwrap();
+        let read = Config::read_from_file(&conf_dir).unwrap();
+        assert_eq!(read.db.remote_db_path, cfg.db.remote_db_path);
+        assert_eq!(read.db.label_case, CasePolicy::Upper);
+        assert_eq!(read.list_limit, Some(50));
+
+        // Invalid templates are rejected...
+        for (name, contents) in [
+            ("missing.toml", "[db]\ntype = \"remote-only\"\n"),
+            (
+                "bad-url.toml",
+                "[db]\ntype = \"remote-only\"\nremote_db_path = \"turso\"\n",
+            ),
+            (
+                "unused-url.toml",
+                "[db]\ntype = \"local\"\nremote_db_path = \"libsql://x\"\n",
+            ),
+            (
+                "no-file.toml",
+                "[db]\ntype = \"memory\"\nencrypt_replica = true\n",
+            ),
+            (
+                "token.toml",
+                "[db]\ntype = \"remote-only\"\nremote_db_path = \"libsql://x?authToken=abc\"\n",
+            ),
+            ("bad-type.toml", "[db]\ntype = \"cloud\"\n"),
+        ] {
+            let path = write(name, contents);
+            let err = Config::read_template(&path, &conf_dir).unwrap_err();
+            assert_eq!(
+                error::kind(&err),
+                Some(error::ErrorKind::Config),
+                "{}",
+                name
+            );
+        }
+        assert!(Config::read_template(&dir.path().join("nope.toml"), &conf_dir).is_err());
+    }
 }
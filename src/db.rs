@@ -1,44 +1,109 @@
 #![allow(dead_code, unused_variables)]
-///! Handles the connection to the database.
-use super::conf::{Config, DBType, DB_DIR_NAME, DB_FILE_NAME};
-use super::secrets::{get_local_db_encryption_key, get_remote_db_auth_token};
+//! Handles the connection to the database.
+use crate::conf::{
+    resolve_db_dir, CasePolicy, Config, DBType, JournalMode, RemoteUrl, RequiredSecrets,
+    SynchronousMode, DB_FILE_NAME,
+};
+use crate::error::{self, ErrorKind};
+use crate::filter::FilterExpr;
+use crate::secrets::{
+    get_remote_db_auth_token, has_local_db_encryption_key_source, has_remote_db_auth_token,
+    resolve_local_db_encryption_key, ENCRYPTION_KEY_ENV_VAR,
+};
 use crate::util;
 use anyhow::{anyhow, Context, Result};
 use bytes::Bytes;
-use chrono::{DateTime, Local};
-use libsql::{de, Builder, Cipher, Connection, Database, EncryptionConfig};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use libsql::{
+    de, Builder, Cipher, Connection, Database, EncryptionConfig, Transaction, TransactionBehavior,
+};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// The default amount of time (in milliseconds) a connection will wait
+/// for a lock held by another writer before failing with "database is busy".
+pub const DEFAULT_BUSY_TIMEOUT_MS: u64 = 5000;
+
+/// The number of times a write transaction will be retried if the database
+/// is still busy after the busy timeout has elapsed.
+const BUSY_RETRY_ATTEMPTS: u32 = 3;
+
+/// The delay between busy retries (multiplied by the attempt number).
+const BUSY_RETRY_DELAY: Duration = Duration::from_millis(100);
 
 /// Using the given configuration, connect to the database.
-pub async fn connect_to_db(conf_path: &PathBuf, config: &Config) -> Result<Database> {
+pub async fn connect_to_db(conf_path: &Path, config: &Config) -> Result<Database> {
+    // Make sure the needed secrets are there before trying to connect...
+    check_required_secrets(
+        config.db.required_secrets(),
+        has_remote_db_auth_token(),
+        has_local_db_encryption_key_source(config.db.encryption_key_file.as_deref()),
+    )?;
+
+    let key_file = config.db.encryption_key_file.as_deref();
+    let db_dir = resolve_db_dir(conf_path, config.db.data_dir.as_deref());
     let db = match config.db.db_type {
-        DBType::Local => connect_to_local_db(conf_path, config.db.encrypt_replica).await?,
+        DBType::Local => connect_to_local_db(&db_dir, config.db.encrypt_replica, key_file).await?,
         DBType::RemoteOnly => {
             let url = config
                 .db
-                .remote_db_path
-                .as_ref()
-                .ok_or_else(|| anyhow!("No remote database path set."))?;
-            connect_to_remote_db(url).await?
+                .remote_url()
+                .ok_or_else(|| error::config("No remote database path set."))?;
+            connect_to_remote_db(&url).await?
         }
         DBType::RemoteWithReplica => {
             let url = config
                 .db
-                .remote_db_path
-                .as_ref()
-                .ok_or_else(|| anyhow!("No remote database path set."))?;
-            connect_to_remote_with_replica_db(conf_path, url, config.db.encrypt_replica).await?
+                .remote_url()
+                .ok_or_else(|| error::config("No remote database path set."))?;
+            connect_to_remote_with_replica_db(&db_dir, &url, config.db.encrypt_replica, key_file)
+                .await?
         }
+        DBType::Memory => connect_to_memory_db().await?,
     };
     Ok(db)
 }
 
-async fn connect_to_local_db(conf_path: &PathBuf, encrypt: bool) -> Result<Database> {
+/// Check that the secrets needed to connect are in the keyring,
+/// returning an actionable config error if not.
+fn check_required_secrets(
+    required: RequiredSecrets,
+    has_remote_db_token: bool,
+    has_encryption_key: bool,
+) -> Result<()> {
+    if required.remote_db_token && !has_remote_db_token {
+        return Err(error::config(
+            "No remote database auth token configured. Add it to the config's \
+            `remote_db_path` (as an `authToken` query param), or set the config up \
+            again with `graphctl cfg init --remote-db-token <token>`.",
+        ));
+    }
+    if required.encryption_key && !has_encryption_key {
+        return Err(error::config(format!(
+            "The database is encrypted, but no encryption key is configured. \
+            Pass it with `--encryption-key-file` or the `{}` env var.",
+            ENCRYPTION_KEY_ENV_VAR
+        )));
+    }
+    Ok(())
+}
+
+async fn connect_to_local_db(
+    db_dir: &Path,
+    encrypt: bool,
+    key_file: Option<&Path>,
+) -> Result<Database> {
     // Get the local path...
-    let local_path = conf_path.join(DB_DIR_NAME).join(DB_FILE_NAME);
+    let local_path = db_dir.join(DB_FILE_NAME);
 
     // Create the builder...
     let mut builder = Builder::new_local(local_path);
@@ -46,7 +111,7 @@ async fn connect_to_local_db(conf_path: &PathBuf, encrypt: bool) -> Result<Datab
     // Should it be encrypted?
     if encrypt {
         // Get the encryption key (as bytes)...
-        let keys = get_local_db_encryption_key()?;
+        let keys = resolve_local_db_encryption_key(key_file)?;
         let keyb = Bytes::from(keys);
 
         // Add it to the builder...
@@ -60,35 +125,74 @@ async fn connect_to_local_db(conf_path: &PathBuf, encrypt: bool) -> Result<Datab
     Ok(builder.build().await?)
 }
 
-async fn connect_to_remote_db(remote_path: &str) -> Result<Database> {
+/// Create an in-memory database.
+///
+/// Nothing is persisted -- the data is gone as soon as the connection is
+/// closed -- and each connection to the returned database gets its own,
+/// separate, empty database.
+async fn connect_to_memory_db() -> Result<Database> {
+    Ok(Builder::new_local(":memory:").build().await?)
+}
+
+/// Get the auth token for a remote database -- preferring one embedded
+/// in the URL over the one in the keyring.
+fn remote_auth_token(remote_url: &RemoteUrl) -> Result<String> {
+    match &remote_url.auth_token {
+        Some(token) => Ok(token.clone()),
+        None => get_remote_db_auth_token(),
+    }
+}
+
+/// Get a warning to show before connecting, if the remote URL has an
+/// `authToken` that differs from the token in the keyring (since the
+/// one from the URL is used).
+pub fn remote_auth_token_warning(config: &Config) -> Option<String> {
+    if !matches!(
+        config.db.db_type,
+        DBType::RemoteOnly | DBType::RemoteWithReplica
+    ) {
+        return None;
+    }
+    let token = config.db.remote_url()?.auth_token?;
+    get_remote_db_auth_token()
+        .is_ok_and(|t| t != token)
+        .then(|| {
+            "The remote database URL has an `authToken` that differs from the token \
+            in the keyring. Using the token from the URL."
+                .to_string()
+        })
+}
+
+async fn connect_to_remote_db(remote_url: &RemoteUrl) -> Result<Database> {
     // Get the remote auth token...
-    let auth_token = get_remote_db_auth_token()?;
+    let auth_token = remote_auth_token(remote_url)?;
 
     // Create the builder...
-    let builder = Builder::new_remote(remote_path.to_string(), auth_token);
+    let builder = Builder::new_remote(remote_url.url.clone(), auth_token);
 
     // Build and return...
     Ok(builder.build().await?)
 }
 
 async fn connect_to_remote_with_replica_db(
-    conf_path: &PathBuf,
-    remote_path: &str,
+    db_dir: &Path,
+    remote_url: &RemoteUrl,
     encrypt: bool,
+    key_file: Option<&Path>,
 ) -> Result<Database> {
     // Get the local path...
-    let local_path = conf_path.join(DB_DIR_NAME).join(DB_FILE_NAME);
+    let local_path = db_dir.join(DB_FILE_NAME);
 
     // Get the auth token...
-    let auth_token = get_remote_db_auth_token()?;
+    let auth_token = remote_auth_token(remote_url)?;
 
     // Create the builder...
-    let mut builder = Builder::new_remote_replica(local_path, remote_path.to_string(), auth_token);
+    let mut builder = Builder::new_remote_replica(local_path, remote_url.url.clone(), auth_token);
 
     // Should it be encrypted?
     if encrypt {
         // Get the encryption key (as bytes)...
-        let keys = get_local_db_encryption_key()?;
+        let keys = resolve_local_db_encryption_key(key_file)?;
         let keyb = Bytes::from(keys);
 
         // Add it to the builder...
@@ -102,26 +206,334 @@ async fn connect_to_remote_with_replica_db(
     Ok(builder.build().await?)
 }
 
-/// Initialize the database.
-pub async fn init_db(conn: &Connection) -> Result<()> {
-    // Get the migration count...
-    let count = get_migration_count(conn).await?;
+/// Apply the per-connection settings.
+///
+/// Foreign keys are enforced, so edges and props can't point at nodes
+/// that don't exist. The busy timeout makes SQLite wait (up to
+/// `busy_timeout_ms` milliseconds) for another process's write lock to
+/// be released, rather than failing immediately with "database is locked".
+pub async fn configure_connection(conn: &Connection, busy_timeout_ms: u64) -> Result<()> {
+    conn.execute("PRAGMA foreign_keys = ON;", ())
+        .await
+        .context("Failed to enable foreign keys")?;
+
+    // Note - PRAGMA returns a row, so this needs to be a query...
+    conn.query(&format!("PRAGMA busy_timeout = {};", busy_timeout_ms), ())
+        .await
+        .context("Failed to set the busy timeout")?;
+    Ok(())
+}
+
+/// Options for `open_and_migrate`.
+#[derive(Debug, Clone, Copy)]
+pub struct OpenOptions {
+    /// How long to wait for another process's write lock, in milliseconds.
+    pub busy_timeout_ms: u64,
+
+    /// Overrides the config's journal mode.
+    pub journal_mode: Option<JournalMode>,
+
+    /// Overrides the config's sync level.
+    pub synchronous: Option<SynchronousMode>,
+
+    /// Run any pending migrations.
+    pub migrate: bool,
+
+    /// Record changes in the audit log (if the config turns it on).
+    pub audit: bool,
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        OpenOptions {
+            busy_timeout_ms: DEFAULT_BUSY_TIMEOUT_MS,
+            journal_mode: None,
+            synchronous: None,
+            migrate: true,
+            audit: true,
+        }
+    }
+}
+
+/// Connect to the database, open a configured connection, and (unless
+/// turned off) run the pending migrations and enable auditing.
+///
+/// This is how every connection should be opened, so the per-connection
+/// settings are applied the same way everywhere.
+pub async fn open_and_migrate(
+    config: &Config,
+    opts: &OpenOptions,
+) -> Result<(Database, Connection)> {
+    // Connect to the database...
+    // Note - Missing secrets are reported as config errors...
+    let db = connect_to_db(&config.conf_dir, config).await.map_err(|e| {
+        let kind = error::kind(&e).unwrap_or(ErrorKind::Connection);
+        error::Error::new(kind, format!("Could not initialize database: {}", e))
+    })?;
+    let conn = connect_configured(&db, config, opts).await?;
 
     // Run the migrations...
-    if count < 1 {
-        migrations_v1(conn).await?;
-        set_migration_count(conn, 1).await?;
+    if opts.migrate {
+        init_db(&conn)
+            .await
+            .map_err(|e| error::connection(format!("Could not initialize database: {}", e)))?;
     }
 
-    // Note - Future migrations will go here...
-    // ...
+    // Record changes in the audit log?
+    if opts.audit {
+        enable_configured_audit(&conn, config).await?;
+    }
+    Ok((db, conn))
+}
 
-    // Done!
+/// Enable auditing on a connection, if the config turns it on.
+pub async fn enable_configured_audit(conn: &Connection, config: &Config) -> Result<()> {
+    if !config.db.audit {
+        return Ok(());
+    }
+    enable_audit(conn).await.map_err(|e| {
+        error::connection(format!(
+            "Could not enable the audit log: {:#}",
+            with_schema_hint(e)
+        ))
+    })
+}
+
+/// Whether an error came from the schema missing a table or column --
+/// i.e. the database hasn't been migrated (e.g. with `--no-migrate`).
+pub fn is_missing_schema_error(err: &anyhow::Error) -> bool {
+    err.chain().any(|e| {
+        let msg = e.to_string();
+        msg.contains("no such table") || msg.contains("no such column")
+    })
+}
+
+/// Add a hint about running the migrations to an error caused by
+/// a missing table or column. Other errors are returned as-is.
+pub fn with_schema_hint(err: anyhow::Error) -> anyhow::Error {
+    if !is_missing_schema_error(&err) {
+        return err;
+    }
+    err.context(
+        "The database's schema is missing a table or column this command needs, since \
+        migrations were skipped. Run `graphctl meta migrate` (or drop `--no-migrate`) to \
+        bring it up to date.",
+    )
+}
+
+/// Open another configured connection to an already-connected database
+/// (e.g. one per worker, for a parallel import).
+///
+/// Migrations aren't run -- that's done once, by `open_and_migrate`. If
+/// the config turns auditing on, it's enabled on the connection (since
+/// the audit triggers only exist per connection).
+pub async fn open_connection(
+    db: &Database,
+    config: &Config,
+    opts: &OpenOptions,
+) -> Result<Connection> {
+    let conn = connect_configured(db, config, opts).await?;
+    enable_configured_audit(&conn, config).await?;
+    Ok(conn)
+}
+
+/// Open a connection and apply the per-connection settings.
+async fn connect_configured(
+    db: &Database,
+    config: &Config,
+    opts: &OpenOptions,
+) -> Result<Connection> {
+    let conn = db
+        .connect()
+        .map_err(|e| error::connection(format!("Could not connect to database: {}", e)))?;
+
+    // Configure the connection...
+    // Note - Remote-only connections reject the PRAGMAs (locking and
+    // constraints are handled by the server), so they're skipped...
+    if config.db.db_type != DBType::RemoteOnly {
+        configure_connection(&conn, opts.busy_timeout_ms)
+            .await
+            .map_err(|e| {
+                error::connection(format!("Could not configure database connection: {}", e))
+            })?;
+    }
+
+    // Configure the journal (for local databases and replicas)...
+    if let DBType::Local | DBType::RemoteWithReplica = config.db.db_type {
+        let journal_mode = opts.journal_mode.or(config.db.journal_mode);
+        let synchronous = opts.synchronous.or(config.db.synchronous);
+        configure_journal(&conn, journal_mode, synchronous)
+            .await
+            .map_err(|e| {
+                error::connection(format!("Could not configure database connection: {}", e))
+            })?;
+    }
+    Ok(conn)
+}
+
+/// Set the journal mode and sync level of a local database (or replica)
+/// connection. Settings that aren't given are left as SQLite's defaults.
+///
+/// Note - With `synchronous = off`, a crash or power failure can
+/// corrupt the database.
+pub async fn configure_journal(
+    conn: &Connection,
+    journal_mode: Option<JournalMode>,
+    synchronous: Option<SynchronousMode>,
+) -> Result<()> {
+    // Note - PRAGMA returns a row, so this needs to be a query...
+    if let Some(mode) = journal_mode {
+        conn.query(&format!("PRAGMA journal_mode = {};", mode.as_sql()), ())
+            .await
+            .context("Failed to set the journal mode")?;
+    }
+    if let Some(mode) = synchronous {
+        conn.execute(&format!("PRAGMA synchronous = {};", mode.as_sql()), ())
+            .await
+            .context("Failed to set the synchronous mode")?;
+    }
+    Ok(())
+}
+
+/// Returns true if the error is SQLite reporting that the
+/// database is busy/locked by another connection.
+fn is_busy_error(err: &libsql::Error) -> bool {
+    match err {
+        // Note - Mask off the extended error code (e.g. SQLITE_BUSY_SNAPSHOT)...
+        libsql::Error::SqliteFailure(code, _) => {
+            let code = code & 0xff;
+            code == libsql::ffi::SQLITE_BUSY || code == libsql::ffi::SQLITE_LOCKED
+        }
+        _ => false,
+    }
+}
+
+/// Return an error if the operation has been cancelled (e.g. by Ctrl-C).
+///
+/// Note - Returning early drops any open transaction, which rolls it back.
+fn check_cancelled(cancel: &CancellationToken) -> Result<()> {
+    if cancel.is_cancelled() {
+        return Err(error::cancelled("Operation cancelled."));
+    }
+    Ok(())
+}
+
+/// Start a write transaction.
+///
+/// The transaction is started with `BEGIN IMMEDIATE` so the write lock is
+/// taken up-front (where the busy timeout applies) rather than when the first
+/// write happens. If the database is still busy, it will be retried a few times
+/// before giving up.
+async fn begin_write(conn: &Connection) -> Result<Transaction> {
+    let mut attempt = 0;
+    loop {
+        match conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .await
+        {
+            Ok(tx) => return Ok(tx),
+            Err(err) if is_busy_error(&err) && attempt < BUSY_RETRY_ATTEMPTS => {
+                attempt += 1;
+                tokio::time::sleep(BUSY_RETRY_DELAY * attempt).await;
+            }
+            Err(err) if is_busy_error(&err) => {
+                return Err(err).context("Failed to start a write transaction (database is busy)")
+            }
+            Err(err) => return Err(err).context("Failed to start a write transaction"),
+        }
+    }
+}
+
+/// A schema migration.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct Migration {
+    /// The migration count after the migration is applied.
+    pub version: u32,
+
+    /// What the migration does.
+    pub description: &'static str,
+}
+
+/// Every schema migration this binary knows about, in the
+/// order they're applied.
+///
+/// Note - Future migrations will go here (and in `run_migration`)...
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Create the nodes, edges, and props tables",
+    },
+    Migration {
+        version: 2,
+        description: "Create the node templates table",
+    },
+    Migration {
+        version: 3,
+        description: "Create the audit log table",
+    },
+    Migration {
+        version: 4,
+        description: "Add `archived_at` to nodes and edges (for soft deletes)",
+    },
+    Migration {
+        version: 5,
+        description: "Create the label defaults table",
+    },
+    Migration {
+        version: 6,
+        description: "Add `valid_from`/`valid_to` to edges (for validity ranges)",
+    },
+];
+
+/// Run a single migration.
+async fn run_migration(conn: &Connection, migration: &Migration) -> Result<()> {
+    match migration.version {
+        1 => migrations_v1(conn).await,
+        2 => migrations_v2(conn).await,
+        3 => migrations_v3(conn).await,
+        4 => migrations_v4(conn).await,
+        5 => migrations_v5(conn).await,
+        6 => migrations_v6(conn).await,
+        v => Err(anyhow!("Unknown migration version {}", v)),
+    }
+}
+
+/// Initialize the database.
+pub async fn init_db(conn: &Connection) -> Result<()> {
+    migrate_db(conn).await?;
     Ok(())
 }
 
+/// Get the migrations that haven't been applied to the database yet.
+pub async fn pending_migrations(conn: &Connection) -> Result<Vec<&'static Migration>> {
+    let count = get_migration_count(conn).await?;
+    Ok(MIGRATIONS
+        .iter()
+        .filter(|m| i64::from(m.version) > count)
+        .collect())
+}
+
+/// Run any pending migrations (in order), returning the ones
+/// that were applied.
+///
+/// Each migration is run in a transaction along with the bump to the
+/// migration count, so one that fails (or is interrupted) part-way
+/// leaves nothing behind, and can be re-run.
+pub async fn migrate_db(conn: &Connection) -> Result<Vec<&'static Migration>> {
+    let pending = pending_migrations(conn).await?;
+    for migration in pending.iter() {
+        let tx = begin_write(conn).await?;
+        run_migration(&tx, migration)
+            .await
+            .with_context(|| format!("Failed to run migration {}", migration.version))?;
+        set_migration_count(&tx, migration.version).await?;
+        tx.commit().await?;
+    }
+    Ok(pending)
+}
+
 /// Gets the migration count from the database.
-async fn get_migration_count(conn: &Connection) -> Result<i64> {
+pub async fn get_migration_count(conn: &Connection) -> Result<i64> {
     // Create the meta table if it doesn't already exist...
     conn.execute(
         "CREATE TABLE IF NOT EXISTS _meta (
@@ -132,7 +544,7 @@ async fn get_migration_count(conn: &Connection) -> Result<i64> {
         (),
     )
     .await
-    .with_context(|| format!("Failed to create meta table"))?; // TODO - Add context...
+    .context("Failed to create meta table")?; // TODO - Add context...
 
     // Get the migration count...
     let mut rows = conn
@@ -176,6 +588,120 @@ async fn set_migration_count(conn: &Connection, count: u32) -> Result<()> {
     Ok(())
 }
 
+/// Get a text value from the `_meta` table.
+async fn get_meta_txt(conn: &Connection, key: &str) -> Result<Option<String>> {
+    let mut rows = conn
+        .prepare("SELECT val_txt FROM _meta WHERE key = ?;")
+        .await?
+        .query(libsql::params![key])
+        .await?;
+    match rows.next().await? {
+        Some(row) => Ok(row.get::<Option<String>>(0)?),
+        None => Ok(None),
+    }
+}
+
+/// Set a value in the `_meta` table, creating the key if needed.
+async fn set_meta(
+    conn: &Connection,
+    key: &str,
+    val_txt: Option<String>,
+    val_int: Option<i64>,
+) -> Result<()> {
+    conn.execute(
+        "
+        INSERT INTO _meta (key, val_txt, val_int)
+        VALUES (?1, ?2, ?3)
+        ON CONFLICT (key) DO UPDATE
+        SET val_txt = ?2, val_int = ?3;
+        ",
+        libsql::params![key, val_txt, val_int],
+    )
+    .await?;
+    Ok(())
+}
+
+/// `_meta` keys used internally, which can't be set as graph metadata.
+pub const RESERVED_META_KEYS: &[&str] = &[
+    "migration_count",
+    "schema_version",
+    LAST_SYNC_AT_KEY,
+    LAST_SYNC_FRAME_KEY,
+];
+
+/// Set a piece of graph-level metadata (e.g. a `description`).
+pub async fn set_graph_meta(conn: &Connection, key: &str, value: &str) -> Result<()> {
+    // Check the key...
+    let key = key.trim();
+    if key.is_empty() {
+        return Err(error::validation("Metadata key can't be empty."));
+    }
+    if RESERVED_META_KEYS.contains(&key) {
+        return Err(error::validation(format!(
+            "Metadata key '{}' is reserved for graphctl's own use.",
+            key
+        )));
+    }
+
+    // Set the value...
+    set_meta(conn, key, Some(value.to_string()), None).await
+}
+
+/// Get a piece of graph-level metadata.
+///
+/// Returns `None` if the key isn't set.
+pub async fn get_graph_meta(conn: &Connection, key: &str) -> Result<Option<Value>> {
+    let mut rows = conn
+        .prepare("SELECT val_txt, val_int FROM _meta WHERE key = ?;")
+        .await?
+        .query(libsql::params![key.trim()])
+        .await?;
+    match rows.next().await? {
+        Some(row) => Ok(Some(meta_value(&row, 0)?)),
+        None => Ok(None),
+    }
+}
+
+/// List all of the graph-level metadata (excluding reserved keys).
+pub async fn list_graph_meta(conn: &Connection) -> Result<BTreeMap<String, Value>> {
+    let mut rows = conn
+        .query("SELECT key, val_txt, val_int FROM _meta ORDER BY key;", ())
+        .await?;
+    let mut out = BTreeMap::new();
+    while let Some(row) = rows.next().await? {
+        let key: String = row.get(0)?;
+        if RESERVED_META_KEYS.contains(&key.as_str()) {
+            continue;
+        }
+        out.insert(key, meta_value(&row, 1)?);
+    }
+    Ok(out)
+}
+
+/// Get a `_meta` row's value, from whichever of its `val_txt` and
+/// `val_int` columns (at `idx` and `idx + 1` in the row) is set.
+fn meta_value(row: &libsql::Row, idx: i32) -> Result<Value> {
+    let val_txt: Option<String> = row.get(idx)?;
+    let val_int: Option<i64> = row.get(idx + 1)?;
+    Ok(match (val_txt, val_int) {
+        (Some(txt), _) => Value::String(txt),
+        (None, Some(int)) => Value::from(int),
+        (None, None) => Value::Null,
+    })
+}
+
+/// Refresh the query planner's statistics (with `ANALYZE`), and
+/// optionally rebuild the indexes (with `REINDEX`).
+///
+/// This is worth doing after large changes to the graph (e.g. bulk imports).
+pub async fn analyze_db(conn: &Connection, reindex: bool) -> Result<()> {
+    if reindex {
+        conn.execute("REINDEX;", ()).await?;
+    }
+    conn.execute("ANALYZE;", ()).await?;
+    Ok(())
+}
+
 pub async fn migrations_v1(conn: &Connection) -> Result<()> {
     // Create the node table...
     // TODO - Add error context...
@@ -241,150 +767,900 @@ pub async fn migrations_v1(conn: &Connection) -> Result<()> {
     Ok(())
 }
 
-/// The database representation of a node.
-#[derive(Debug, Serialize, Deserialize)]
-pub struct DbNode {
-    pub id: String,
-    pub labels: Vec<String>,
-    pub props: Option<HashMap<String, Value>>,
-    pub created_at: DateTime<Local>,
-    pub updated_at: DateTime<Local>,
-}
+pub async fn migrations_v2(conn: &Connection) -> Result<()> {
+    // Create the node template table...
+    conn.execute(
+        "
+        CREATE TABLE IF NOT EXISTS _templates (
+            name       TEXT PRIMARY KEY,
+            labels     TEXT NOT NULL,
+            props      TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        );",
+        (),
+    )
+    .await
+    .context("Failed to create templates table")?;
 
-/// The database representation of an edge.
-#[derive(Debug, Serialize, Deserialize)]
-pub struct DbEdge {
-    pub id: String,
-    pub edge_type: String,
-    pub from_node: String,
-    pub to_node: String,
-    pub directed: bool,
-    pub props: Option<HashMap<String, Value>>,
-    pub created_at: DateTime<Local>,
-    pub updated_at: DateTime<Local>,
+    // Done!
+    Ok(())
 }
 
-pub struct CreateNodeParams {
-    pub labels: Vec<String>,
-    pub props: HashMap<String, Value>,
-}
+pub async fn migrations_v3(conn: &Connection) -> Result<()> {
+    // Create the audit log table...
+    // Note - The values are stored as-is (JSON text, or a compressed blob)...
+    conn.execute(
+        "
+        CREATE TABLE IF NOT EXISTS _audit (
+            id          INTEGER PRIMARY KEY AUTOINCREMENT,
+            at          TEXT NOT NULL,
+            operation   TEXT NOT NULL,
+            target_kind TEXT NOT NULL,
+            target_id   TEXT NOT NULL,
+            key         TEXT,
+            old_value,
+            new_value
+        );",
+        (),
+    )
+    .await
+    .context("Failed to create audit table")?;
 
-pub async fn create_node(conn: &Connection, params: &CreateNodeParams) -> Result<DbNode> {
-    // Generate an ID and timestamp...
-    let id = util::new_id("n");
-    let now = Local::now();
+    // Index it by target, for looking up an item's history...
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS _audit_target_id ON _audit (target_id);",
+        (),
+    )
+    .await
+    .context("Failed to create audit index")?;
 
-    // Convert the node type and timestamp to a SQL value...
-    let labels = serde_json::to_string(&params.labels)?;
-    let sql_now = libsql::Value::Text(now.to_rfc3339());
+    // Done!
+    Ok(())
+}
 
-    // Start a transaction...
-    let tx = conn.transaction().await?;
+pub async fn migrations_v4(conn: &Connection) -> Result<()> {
+    // Add the archive timestamps...
+    for table in ["nodes", "edges"] {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN archived_at TEXT;", table),
+            (),
+        )
+        .await
+        .context(format!("Failed to add archived_at to {}", table))?;
+    }
 
-    // Insert the node...
-    tx.execute(
+    // Done!
+    Ok(())
+}
+
+pub async fn migrations_v5(conn: &Connection) -> Result<()> {
+    // Create the label defaults table...
+    conn.execute(
         "
-        INSERT INTO nodes (
-            id, 
-            labels, 
-            created_at, 
-            updated_at
-        ) VALUES (?, ?, ?, ?);
-        ",
-        libsql::params![id.clone(), labels, sql_now.clone(), sql_now.clone(),],
+        CREATE TABLE IF NOT EXISTS _label_defaults (
+            label      TEXT PRIMARY KEY,
+            props      TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        );",
+        (),
     )
-    .await?;
+    .await
+    .context("Failed to create label defaults table")?;
 
-    // Add the properties...
-    for (key, value) in params.props.iter() {
-        let sql_key = libsql::Value::Text(key.trim().to_string());
-        let sql_value = libsql::Value::Text(value.to_string());
-        tx.execute(
-            "
-            INSERT INTO node_props (
-                node_id, 
-                key, 
-                value, 
-                created_at, 
-                updated_at
-            ) VALUES (?, ?, ?, ?, ?);
-            ",
-            libsql::params![
-                id.clone(),
-                sql_key,
-                sql_value,
-                sql_now.clone(),
-                sql_now.clone(),
-            ],
+    // Done!
+    Ok(())
+}
+
+pub async fn migrations_v6(conn: &Connection) -> Result<()> {
+    // Add the edge validity range...
+    for column in ["valid_from", "valid_to"] {
+        conn.execute(
+            &format!("ALTER TABLE edges ADD COLUMN {} TEXT;", column),
+            (),
         )
-        .await?;
+        .await
+        .context(format!("Failed to add {} to edges", column))?;
     }
 
-    // Commit the transaction...
-    tx.commit().await?;
+    // Done!
+    Ok(())
+}
 
-    // Return the data...
-    Ok(DbNode {
-        id,
-        labels: params.labels.clone(),
-        created_at: now,
-        updated_at: now,
-        props: Some(params.props.clone()),
-    })
+/// The tables whose changes are recorded when auditing is enabled, as
+/// `(table, target kind, target ID column)`.
+const AUDIT_TABLES: &[(&str, &str, &str)] = &[
+    ("nodes", "node", "id"),
+    ("edges", "edge", "id"),
+    ("node_props", "node", "node_id"),
+    ("edge_props", "edge", "edge_id"),
+];
+
+/// The value recorded in the audit log for a row of `table` (where
+/// `row` is `OLD` or `NEW`) -- a prop's value, a node's labels, or
+/// an edge's fields (as JSON).
+fn audit_value(table: &str, row: &str) -> String {
+    match table {
+        "nodes" => format!("{}.labels", row),
+        "edges" => format!(
+            "json_object(
+                'edge_type', {row}.edge_type,
+                'from_node', {row}.from_node,
+                'to_node', {row}.to_node,
+                'directed', json(CASE WHEN {row}.directed THEN 'true' ELSE 'false' END)
+            )",
+            row = row,
+        ),
+        _ => format!("{}.value", row),
+    }
 }
 
-pub struct CreateEdgeParams {
-    pub edge_type: String,
-    pub from_node: String,
-    pub to_node: String,
-    pub directed: bool,
-    pub props: HashMap<String, Value>,
+/// Record changes made on this connection in the `_audit` table.
+///
+/// Changes are recorded by (temporary) triggers, so every write path --
+/// including bulk inserts, imports, and cascading deletes -- is audited,
+/// and the audit rows are written in the same transaction as the change.
+/// Since the triggers are temporary, other connections (e.g. with auditing
+/// turned off) aren't affected.
+pub async fn enable_audit(conn: &Connection) -> Result<()> {
+    for (table, kind, id_col) in AUDIT_TABLES.iter() {
+        // Only prop values are updated in place...
+        let is_prop = table.ends_with("_props");
+        let events: &[&str] = if is_prop {
+            &["INSERT", "UPDATE", "DELETE"]
+        } else {
+            &["INSERT", "DELETE"]
+        };
+
+        for event in events.iter() {
+            let operation = match (*event, is_prop) {
+                ("DELETE", false) => "delete",
+                ("DELETE", true) => "delete_prop",
+                (_, false) => "create",
+                (_, true) => "set_prop",
+            };
+            let row = if *event == "DELETE" { "OLD" } else { "NEW" };
+            let key = if is_prop {
+                format!("{}.key", row)
+            } else {
+                "NULL".to_string()
+            };
+            let old = match *event {
+                "INSERT" => "NULL".to_string(),
+                _ => audit_value(table, "OLD"),
+            };
+            let new = match *event {
+                "DELETE" => "NULL".to_string(),
+                _ => audit_value(table, "NEW"),
+            };
+            let query = format!(
+                "
+                CREATE TEMP TRIGGER IF NOT EXISTS _audit_{table}_{name}
+                AFTER {event} ON {table}
+                BEGIN
+                    INSERT INTO _audit (
+                        at, operation, target_kind, target_id, key, old_value, new_value
+                    ) VALUES (
+                        strftime('%Y-%m-%dT%H:%M:%fZ', 'now'),
+                        '{operation}', '{kind}', {row}.{id_col}, {key}, {old}, {new}
+                    );
+                END;
+                ",
+                table = table,
+                name = event.to_lowercase(),
+                event = event,
+                operation = operation,
+                kind = kind,
+                row = row,
+                id_col = id_col,
+                key = key,
+                old = old,
+                new = new,
+            );
+            conn.execute(&query, ())
+                .await
+                .context("Failed to enable auditing")?;
+        }
+    }
+    Ok(())
 }
 
-pub async fn create_edge(conn: &Connection, params: &CreateEdgeParams) -> Result<DbEdge> {
-    // Generate an ID and timestamp...
-    let id = util::new_id("e");
-    let now = Local::now();
+/// A recorded change to a node or edge.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct AuditEntry {
+    #[serde(serialize_with = "util::serialize_timestamp")]
+    pub at: DateTime<Utc>,
 
-    // Convert the timestamp to a SQL value...
-    let sql_now = libsql::Value::Text(now.to_rfc3339());
+    /// What happened -- `create`, `delete`, `set_prop`, or `delete_prop`.
+    pub operation: String,
 
-    // Start a transaction...
-    let tx = conn.transaction().await?;
+    /// What was changed -- a `node` or an `edge`.
+    pub target_kind: String,
+    pub target_id: String,
 
-    // Insert the edge...
-    tx.execute(
-        "
-        INSERT INTO edges (
-            id, 
-            edge_type, 
-            from_node, 
-            to_node, 
-            directed, 
+    /// The prop's key (for prop changes).
+    pub key: Option<String>,
+
+    /// The value before the change -- a prop's value, a node's labels,
+    /// or an edge's fields.
+    pub old_value: Option<Value>,
+
+    /// The value after the change.
+    pub new_value: Option<Value>,
+}
+
+/// Get the recorded changes to a node or edge, oldest first.
+///
+/// Works for deleted items too, since the log is never pruned.
+pub async fn get_audit_log(conn: &Connection, target_id: &str) -> Result<Vec<AuditEntry>> {
+    let mut rows = conn
+        .prepare(
+            "
+            SELECT at, operation, target_kind, target_id, key, old_value, new_value
+            FROM _audit
+            WHERE target_id = ?
+            ORDER BY id;
+            ",
+        )
+        .await?
+        .query(libsql::params![target_id])
+        .await?;
+    let value = |v: libsql::Value| match v {
+        libsql::Value::Null => Ok(None),
+        v => decode_prop_value(v).map(Some),
+    };
+    let mut entries = Vec::new();
+    while let Some(row) = rows.next().await? {
+        entries.push(AuditEntry {
+            at: row.get::<String>(0)?.parse()?,
+            operation: row.get(1)?,
+            target_kind: row.get(2)?,
+            target_id: row.get(3)?,
+            key: row.get(4)?,
+            old_value: value(row.get_value(5)?)?,
+            new_value: value(row.get_value(6)?)?,
+        });
+    }
+    Ok(entries)
+}
+
+/// A reusable node shape -- default labels and props for new nodes.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeTemplate {
+    pub name: String,
+    pub labels: Vec<String>,
+    pub props: HashMap<String, Value>,
+}
+
+impl NodeTemplate {
+    /// Merge the template's defaults with a node's labels and props.
+    ///
+    /// The labels are combined (the template's first) and the given
+    /// props override the template's.
+    pub fn apply(
+        &self,
+        labels: Vec<String>,
+        props: HashMap<String, Value>,
+    ) -> (Vec<String>, HashMap<String, Value>) {
+        let mut all_labels = self.labels.clone();
+        for label in labels {
+            if !all_labels.contains(&label) {
+                all_labels.push(label);
+            }
+        }
+        let mut all_props = self.props.clone();
+        all_props.extend(props);
+        (all_labels, all_props)
+    }
+}
+
+/// Use an existing node's labels and props as a template
+/// (e.g. to clone the node). The template is named after the node.
+impl From<DbNode> for NodeTemplate {
+    fn from(node: DbNode) -> Self {
+        NodeTemplate {
+            name: node.id,
+            labels: node.labels,
+            props: node.props.unwrap_or_default(),
+        }
+    }
+}
+
+/// Create (or replace) a node template.
+pub async fn set_node_template(conn: &Connection, template: &NodeTemplate) -> Result<()> {
+    // Check the name...
+    if template.name.trim().is_empty() {
+        return Err(error::validation("Template name can't be empty."));
+    }
+
+    // Store the template...
+    let now = Utc::now().to_rfc3339();
+    conn.execute(
+        "
+        INSERT INTO _templates (name, labels, props, created_at, updated_at)
+        VALUES (?1, ?2, ?3, ?4, ?4)
+        ON CONFLICT (name) DO UPDATE
+        SET labels = ?2, props = ?3, updated_at = ?4;
+        ",
+        libsql::params![
+            template.name.trim(),
+            serde_json::to_string(&template.labels)?,
+            serde_json::to_string(&template.props)?,
+            now,
+        ],
+    )
+    .await
+    .context("Failed to store the node template")?;
+    Ok(())
+}
+
+/// Get a node template by name.
+pub async fn get_node_template(conn: &Connection, name: &str) -> Result<NodeTemplate> {
+    let mut rows = conn
+        .prepare("SELECT labels, props FROM _templates WHERE name = ?;")
+        .await?
+        .query(libsql::params![name.trim()])
+        .await?;
+    let row = rows
+        .next()
+        .await?
+        .ok_or_else(|| error::not_found(format!("Node template '{}' does not exist.", name)))?;
+    Ok(NodeTemplate {
+        name: name.trim().to_string(),
+        labels: serde_json::from_str(&row.get::<String>(0)?)?,
+        props: serde_json::from_str(&row.get::<String>(1)?)?,
+    })
+}
+
+/// Set the default props for new nodes with a label, replacing any
+/// existing defaults. With no props, the label's defaults are removed.
+pub async fn set_label_defaults(
+    conn: &Connection,
+    label: &str,
+    props: &HashMap<String, Value>,
+) -> Result<()> {
+    // Check the label...
+    if label.trim().is_empty() {
+        return Err(error::validation("Label can't be empty."));
+    }
+
+    // Remove the defaults?
+    if props.is_empty() {
+        conn.execute(
+            "DELETE FROM _label_defaults WHERE label = ?;",
+            libsql::params![label.trim()],
+        )
+        .await?;
+        return Ok(());
+    }
+
+    // Store the defaults...
+    let now = Utc::now().to_rfc3339();
+    conn.execute(
+        "
+        INSERT INTO _label_defaults (label, props, created_at, updated_at)
+        VALUES (?1, ?2, ?3, ?3)
+        ON CONFLICT (label) DO UPDATE
+        SET props = ?2, updated_at = ?3;
+        ",
+        libsql::params![
+            label.trim(),
+            serde_json::to_string(&normalize_props(props, node_prop_key))?,
+            now,
+        ],
+    )
+    .await
+    .context("Failed to store the label defaults")?;
+    Ok(())
+}
+
+/// List the default props for every label that has them.
+pub async fn list_label_defaults(
+    conn: &Connection,
+) -> Result<BTreeMap<String, HashMap<String, Value>>> {
+    let mut rows = conn
+        .query("SELECT label, props FROM _label_defaults;", ())
+        .await?;
+    let mut out = BTreeMap::new();
+    while let Some(row) = rows.next().await? {
+        let props = serde_json::from_str(&row.get::<String>(1)?)?;
+        out.insert(row.get(0)?, props);
+    }
+    Ok(out)
+}
+
+/// Get the combined default props for a node with these labels.
+///
+/// If two labels set the same prop, the earlier label wins.
+pub async fn get_label_defaults(
+    conn: &Connection,
+    labels: &[String],
+) -> Result<HashMap<String, Value>> {
+    let mut all = list_label_defaults(conn).await?;
+    let mut out = HashMap::new();
+    for label in labels.iter().rev() {
+        if let Some(props) = all.remove(label) {
+            out.extend(props);
+        }
+    }
+    Ok(out)
+}
+
+/// The database representation of a node.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DbNode {
+    pub id: String,
+    pub labels: Vec<String>,
+    pub props: Option<HashMap<String, Value>>,
+    #[serde(serialize_with = "util::serialize_timestamp")]
+    pub created_at: DateTime<Utc>,
+    #[serde(serialize_with = "util::serialize_timestamp")]
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Convert a row of `(id, labels, created_at, updated_at)` from the
+/// `nodes` table into a `DbNode` (without its props).
+///
+/// Note - Timestamps are stored in UTC, but older databases may have
+/// timestamps with a local UTC offset. Those are converted to UTC.
+fn node_from_row(row: &libsql::Row) -> Result<DbNode> {
+    let id: String = row.get(0)?;
+    let slabels: String = row.get(1)?;
+    let labels: Vec<String> = serde_json::from_str(&slabels)?;
+    let created_at: DateTime<Utc> = row.get::<String>(2)?.parse()?;
+    let updated_at: DateTime<Utc> = row.get::<String>(3)?.parse()?;
+    Ok(DbNode {
+        id,
+        labels,
+        props: None,
+        created_at,
+        updated_at,
+    })
+}
+
+/// The database representation of an edge.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DbEdge {
+    pub id: String,
+    pub edge_type: String,
+    pub from_node: String,
+    pub to_node: String,
+    pub directed: bool,
+    pub props: Option<HashMap<String, Value>>,
+    #[serde(serialize_with = "util::serialize_timestamp")]
+    pub created_at: DateTime<Utc>,
+    #[serde(serialize_with = "util::serialize_timestamp")]
+    pub updated_at: DateTime<Utc>,
+
+    /// When the relationship became active (if it has a validity range).
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "util::serialize_opt_timestamp"
+    )]
+    pub valid_from: Option<DateTime<Utc>>,
+
+    /// When the relationship stopped being active (if it has).
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "util::serialize_opt_timestamp"
+    )]
+    pub valid_to: Option<DateTime<Utc>>,
+}
+
+/// How edge prop keys are cased when stored and filtered on.
+///
+/// Note - Unlike labels and edge types, this isn't configurable.
+/// Edge prop keys have always been stored lowercase.
+const EDGE_PROP_KEY_CASE: CasePolicy = CasePolicy::Lower;
+
+/// Build the SQL condition matching a prop (in `table`, aliased as `alias`,
+/// belonging to the item whose ID is `id_col`) whose value is a JSON array
+/// containing a value. Takes the prop's key and the JSON-encoded value
+/// as parameters.
+///
+/// Note - Values that aren't JSON arrays (including compressed values)
+/// never match. Array elements are compared as compact JSON, so this is
+/// only reliable for scalar elements (strings, numbers, bools, null).
+fn prop_contains_sql(table: &str, alias: &str, owner_col: &str, id_col: &str) -> String {
+    format!(
+        "EXISTS (
+            SELECT 1 FROM {table} {alias}, json_each(
+                CASE
+                    WHEN typeof({alias}.value) = 'text'
+                        AND json_valid({alias}.value)
+                        AND json_type({alias}.value) = 'array'
+                    THEN {alias}.value
+                    ELSE '[]'
+                END
+            ) je
+            WHERE {alias}.{owner_col} = {id_col} AND {alias}.key = ?
+            AND (
+                CASE je.type
+                    WHEN 'true' THEN 'true'
+                    WHEN 'false' THEN 'false'
+                    WHEN 'null' THEN 'null'
+                    ELSE json_quote(je.value)
+                END
+            ) = ?
+        )",
+    )
+}
+
+/// Build the SQL condition matching a prop (like `prop_contains_sql`) whose
+/// value compares with a value using `op`. Takes the prop's key and the
+/// JSON-encoded value as parameters.
+///
+/// Values are compared the way SQLite compares their JSON values, so
+/// numbers compare numerically and strings lexically (and any number is
+/// less than any string). Missing props and compressed values never match.
+fn prop_compare_sql(
+    table: &str,
+    alias: &str,
+    owner_col: &str,
+    id_col: &str,
+    op: util::CompareOp,
+) -> String {
+    format!(
+        "EXISTS (
+            SELECT 1 FROM {table} {alias}
+            WHERE {alias}.{owner_col} = {id_col} AND {alias}.key = ?
+            AND typeof({alias}.value) = 'text' AND json_valid({alias}.value)
+            AND json_extract({alias}.value, '$') {op} json_extract(?, '$')
+        )",
+        op = op.sql(),
+    )
+}
+
+/// Build the SQL condition matching a prop (in `table`, aliased as `alias`,
+/// belonging to the item whose ID is `id_col`) equal to a value, along
+/// with the value's parameter. Takes the prop's key as a parameter first.
+///
+/// Values are compared the same way they're stored (as JSON). With
+/// `ignore_case`, string values are compared on their (unquoted) content
+/// with `LOWER`, which only folds ASCII letters. Other values are still
+/// compared exactly.
+fn prop_equals_sql(
+    table: &str,
+    alias: &str,
+    owner_col: &str,
+    id_col: &str,
+    value: &Value,
+    ignore_case: bool,
+) -> (String, libsql::Value) {
+    match (value, ignore_case) {
+        (Value::String(s), true) => (
+            format!(
+                "EXISTS (
+                    SELECT 1 FROM {table} {alias}
+                    WHERE {alias}.{owner_col} = {id_col} AND {alias}.key = ?
+                    AND typeof({alias}.value) = 'text' AND json_valid({alias}.value)
+                    AND json_type({alias}.value) = 'text'
+                    AND LOWER(json_extract({alias}.value, '$')) = LOWER(?)
+                )"
+            ),
+            libsql::Value::Text(s.clone()),
+        ),
+        _ => (
+            format!(
+                "EXISTS (
+                    SELECT 1 FROM {table} {alias}
+                    WHERE {alias}.{owner_col} = {id_col} AND {alias}.key = ? AND {alias}.value = ?
+                )"
+            ),
+            libsql::Value::Text(value.to_string()),
+        ),
+    }
+}
+
+/// Normalize a node prop key, the way it's stored.
+fn node_prop_key(key: &str) -> String {
+    key.trim().to_string()
+}
+
+/// Normalize an edge prop key, the way it's stored.
+fn edge_prop_key(key: &str) -> String {
+    EDGE_PROP_KEY_CASE.apply(key.trim())
+}
+
+/// Normalize a set of props' keys (with `node_prop_key` or `edge_prop_key`),
+/// so they match what's read back from the database.
+fn normalize_props(
+    props: &HashMap<String, Value>,
+    normalize_key: fn(&str) -> String,
+) -> HashMap<String, Value> {
+    props
+        .iter()
+        .map(|(key, value)| (normalize_key(key), value.clone()))
+        .collect()
+}
+
+/// The default maximum size (in bytes) of a single prop value.
+pub const DEFAULT_MAX_PROP_BYTES: usize = 1024 * 1024;
+
+/// Make sure none of the prop values are bigger than `max_bytes`
+/// (or `DEFAULT_MAX_PROP_BYTES` if not set), once encoded for storage.
+pub fn check_prop_sizes(props: &HashMap<String, Value>, max_bytes: Option<usize>) -> Result<()> {
+    let max_bytes = max_bytes.unwrap_or(DEFAULT_MAX_PROP_BYTES);
+    for (key, value) in props.iter() {
+        let size = value.to_string().len();
+        if size > max_bytes {
+            return Err(error::validation(format!(
+                "Value of property \"{}\" is {} bytes, which is over the limit of {} bytes.",
+                key, size, max_bytes,
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// The default size (in bytes) above which prop values are
+/// compressed, when `compress_large_props` is set in the config.
+pub const DEFAULT_COMPRESS_PROPS_OVER_BYTES: usize = 4 * 1024;
+
+/// The byte that prefixes compressed prop values in storage.
+///
+/// Note - Plain values are stored as JSON text, which can never
+/// start with this byte, so the two can always be told apart.
+const COMPRESSED_PROP_MARKER: u8 = 0x01;
+
+/// Encode a prop value for storage.
+///
+/// Values are stored as JSON text, unless `compress_over` is set and
+/// the value is bigger than that, in which case it's gzip-compressed
+/// and stored as a blob prefixed with `COMPRESSED_PROP_MARKER`.
+fn encode_prop_value(value: &Value, compress_over: Option<usize>) -> Result<libsql::Value> {
+    let text = value.to_string();
+    match compress_over {
+        Some(max) if text.len() > max => {
+            let mut encoder = GzEncoder::new(vec![COMPRESSED_PROP_MARKER], Compression::default());
+            encoder.write_all(text.as_bytes())?;
+            Ok(libsql::Value::Blob(encoder.finish()?))
+        }
+        _ => Ok(libsql::Value::Text(text)),
+    }
+}
+
+/// Decode a prop value from storage (compressed or not).
+fn decode_prop_value(value: libsql::Value) -> Result<Value> {
+    match value {
+        libsql::Value::Text(text) => Ok(serde_json::from_str(&text)?),
+        libsql::Value::Blob(blob) if blob.first() == Some(&COMPRESSED_PROP_MARKER) => {
+            let mut text = String::new();
+            GzDecoder::new(&blob[1..])
+                .read_to_string(&mut text)
+                .context("Failed to decompress prop value")?;
+            Ok(serde_json::from_str(&text)?)
+        }
+        v => Err(anyhow!("Unexpected prop value in database: {:?}", v)),
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct CreateNodeParams {
+    /// The node's ID. Generated if not set.
+    pub id: Option<String>,
+
+    pub labels: Vec<String>,
+    pub props: HashMap<String, Value>,
+
+    /// The maximum size of a prop value, in bytes.
+    /// Defaults to `DEFAULT_MAX_PROP_BYTES`.
+    pub max_prop_bytes: Option<usize>,
+
+    /// Compress prop values bigger than this many bytes.
+    /// If not set, values aren't compressed.
+    pub compress_props_over: Option<usize>,
+
+    /// Reject the node if it doesn't have at least one label.
+    pub require_label: bool,
+
+    /// How the labels are cased when stored.
+    pub label_case: CasePolicy,
+}
+
+/// Apply the case policy to a set of labels, dropping any that are
+/// duplicates once cased (keeping the first occurrence's position).
+pub fn dedupe_labels(labels: &[String], label_case: CasePolicy) -> Vec<String> {
+    let mut out: Vec<String> = Vec::with_capacity(labels.len());
+    for label in labels.iter().map(|l| label_case.apply(l)) {
+        if !out.contains(&label) {
+            out.push(label);
+        }
+    }
+    out
+}
+
+pub async fn create_node(conn: &Connection, params: &CreateNodeParams) -> Result<DbNode> {
+    let (node, _) = write_node(conn, params, false).await?;
+    Ok(node)
+}
+
+/// Create a node, or if a node with `params.id` already exists, update it.
+///
+/// When updating, the node's labels are replaced and its props are
+/// merged with the given props (props that aren't given are kept).
+/// Any labels the node didn't already have add their defaults, for
+/// the props it doesn't have yet.
+///
+/// Returns the node and whether it was created (rather than updated).
+pub async fn upsert_node(conn: &Connection, params: &CreateNodeParams) -> Result<(DbNode, bool)> {
+    write_node(conn, params, true).await
+}
+
+/// Create a node (or with `upsert`, update it if it already exists).
+async fn write_node(
+    conn: &Connection,
+    params: &CreateNodeParams,
+    upsert: bool,
+) -> Result<(DbNode, bool)> {
+    // Check that there's a label, if required...
+    if params.require_label && params.labels.iter().all(|l| l.trim().is_empty()) {
+        return Err(error::validation(
+            "Nodes must have at least one label. Add one with `-l <LABEL>` \
+            (or as the first argument, e.g. `create node Person`).",
+        ));
+    }
+
+    // Get (or generate) the ID and a timestamp...
+    let id = match &params.id {
+        Some(id) if id.trim().is_empty() => {
+            return Err(error::validation("Node ID can't be empty."));
+        }
+        Some(id) => id.trim().to_string(),
+        None => util::new_id("n"),
+    };
+    let now = Utc::now();
+
+    // Apply the label case policy (dropping any duplicates)...
+    let labels = dedupe_labels(&params.labels, params.label_case);
+
+    // Convert the node type and timestamp to a SQL value...
+    let sql_labels = serde_json::to_string(&labels)?;
+    let sql_now = libsql::Value::Text(now.to_rfc3339());
+
+    // Start a transaction...
+    let tx = begin_write(conn).await?;
+
+    // Does a node with that ID already exist?
+    if params.id.is_some() && check_node_exists(&tx, &id).await? {
+        if !upsert {
+            return Err(error::validation(format!(
+                "A node with ID '{}' already exists. Pass `--upsert` to update it instead.",
+                id
+            )));
+        }
+
+        // Get the existing node...
+        let existing = get_node(
+            &tx,
+            &GetNodeParams {
+                id: id.clone(),
+                with_props: true,
+                include_archived: true,
+            },
+        )
+        .await?;
+        let existing_props = existing.props.unwrap_or_default();
+
+        // Add the defaults for any labels the node didn't already have
+        // (without overwriting its props), with the given props winning...
+        let added: Vec<String> = labels
+            .iter()
+            .filter(|l| !existing.labels.contains(l))
+            .cloned()
+            .collect();
+        let mut props: HashMap<String, Value> = get_label_defaults(&tx, &added)
+            .await?
+            .into_iter()
+            .filter(|(key, _)| !existing_props.contains_key(key))
+            .collect();
+        props.extend(normalize_props(&params.props, node_prop_key));
+        check_prop_sizes(&props, params.max_prop_bytes)?;
+
+        // Replace the labels...
+        tx.execute(
+            "UPDATE nodes SET labels = ?, updated_at = ? WHERE id = ?;",
+            libsql::params![sql_labels, sql_now, id.clone()],
+        )
+        .await?;
+
+        // Merge in the props...
+        for (key, value) in props.iter() {
+            set_node_prop(&tx, &id, key, value, &now, params.compress_props_over).await?;
+        }
+
+        // Get the updated node...
+        let node = get_node(
+            &tx,
+            &GetNodeParams {
+                id,
+                with_props: true,
+                include_archived: true,
+            },
+        )
+        .await?;
+        tx.commit().await?;
+        return Ok((node, false));
+    }
+
+    // Merge in the defaults for the node's labels...
+    let props = with_label_defaults(&tx, &labels, &params.props, params.max_prop_bytes).await?;
+
+    // Insert the node...
+    let node = DbNode {
+        id,
+        labels,
+        created_at: now,
+        updated_at: now,
+        props: Some(props),
+    };
+    insert_node_rows(&tx, &node, params.compress_props_over).await?;
+
+    // Commit the transaction...
+    tx.commit().await?;
+
+    // Return the data...
+    Ok((node, true))
+}
+
+/// Merge the defaults for a new node's labels into its props (with the
+/// given props winning), and check the sizes of the result.
+async fn with_label_defaults(
+    conn: &Connection,
+    labels: &[String],
+    props: &HashMap<String, Value>,
+    max_prop_bytes: Option<usize>,
+) -> Result<HashMap<String, Value>> {
+    let mut all = get_label_defaults(conn, labels).await?;
+    all.extend(normalize_props(props, node_prop_key));
+    check_prop_sizes(&all, max_prop_bytes)?;
+    Ok(all)
+}
+
+/// Insert a node's rows -- the node itself and its props.
+///
+/// Note - This doesn't start a transaction, so it can be
+/// used as part of a larger one.
+async fn insert_node_rows(
+    conn: &Connection,
+    node: &DbNode,
+    compress_props_over: Option<usize>,
+) -> Result<()> {
+    // Convert the labels and timestamps to SQL values...
+    let sql_labels = serde_json::to_string(&node.labels)?;
+    let sql_created_at = libsql::Value::Text(node.created_at.to_rfc3339());
+    let sql_updated_at = libsql::Value::Text(node.updated_at.to_rfc3339());
+
+    // Insert the node...
+    conn.execute(
+        "
+        INSERT INTO nodes (
+            id, 
+            labels, 
             created_at, 
             updated_at
-        ) VALUES (?, ?, ?, ?, ?, ?, ?);
+        ) VALUES (?, ?, ?, ?);
         ",
         libsql::params![
-            id.clone(),
-            params.edge_type.clone(),
-            params.from_node.clone(),
-            params.to_node.clone(),
-            params.directed as i64,
-            sql_now.clone(),
-            sql_now.clone(),
+            node.id.clone(),
+            sql_labels,
+            sql_created_at.clone(),
+            sql_updated_at.clone(),
         ],
     )
     .await?;
 
     // Add the properties...
-    for (key, value) in params.props.iter() {
-        let sql_key = libsql::Value::Text(key.trim().to_lowercase());
-        let sql_value = libsql::Value::Text(value.to_string());
-        tx.execute(
+    for (key, value) in node.props.iter().flatten() {
+        let sql_key = libsql::Value::Text(node_prop_key(key));
+        let sql_value = encode_prop_value(value, compress_props_over)?;
+        conn.execute(
             "
-            INSERT INTO edge_props (
-                edge_id, 
+            INSERT INTO node_props (
+                node_id, 
                 key, 
                 value, 
                 created_at, 
@@ -392,328 +1668,5998 @@ pub async fn create_edge(conn: &Connection, params: &CreateEdgeParams) -> Result
             ) VALUES (?, ?, ?, ?, ?);
             ",
             libsql::params![
-                id.clone(),
+                node.id.clone(),
                 sql_key,
                 sql_value,
-                sql_now.clone(),
-                sql_now.clone(),
+                sql_created_at.clone(),
+                sql_updated_at.clone(),
             ],
         )
         .await?;
     }
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+pub struct CreateEdgeParams {
+    pub edge_type: String,
+    pub from_node: String,
+    pub to_node: String,
+    pub directed: bool,
+    pub props: HashMap<String, Value>,
+
+    /// The maximum size of a prop value, in bytes.
+    /// Defaults to `DEFAULT_MAX_PROP_BYTES`.
+    pub max_prop_bytes: Option<usize>,
+
+    /// Compress prop values bigger than this many bytes.
+    /// If not set, values aren't compressed.
+    pub compress_props_over: Option<usize>,
+
+    /// How the edge type is cased when stored.
+    pub edge_type_case: CasePolicy,
+
+    /// Allow an edge from a node to itself.
+    pub allow_self_loop: bool,
+
+    /// When the relationship became active.
+    pub valid_from: Option<DateTime<Utc>>,
+
+    /// When the relationship stopped being active.
+    pub valid_to: Option<DateTime<Utc>>,
+}
+
+/// Make sure an edge's validity range isn't empty (or backwards).
+fn check_validity_range(
+    valid_from: Option<DateTime<Utc>>,
+    valid_to: Option<DateTime<Utc>>,
+) -> Result<()> {
+    if let (Some(from), Some(to)) = (valid_from, valid_to) {
+        if to <= from {
+            return Err(error::validation(format!(
+                "The edge's validity range is empty: `valid_to` ({}) must be after \
+                `valid_from` ({}).",
+                to.to_rfc3339(),
+                from.to_rfc3339()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Make sure an edge isn't a self-loop, unless they're allowed.
+fn check_self_loop(from_node: &str, to_node: &str, allow_self_loop: bool) -> Result<()> {
+    if from_node == to_node && !allow_self_loop {
+        return Err(error::validation(format!(
+            "The edge's source and target are the same node ('{}'). Pass `--allow-self-loop` \
+            (or set `allow_self_loops` in the config) if that's intended.",
+            from_node
+        )));
+    }
+    Ok(())
+}
+
+pub async fn create_edge(conn: &Connection, params: &CreateEdgeParams) -> Result<DbEdge> {
+    // Check for a self-loop, the validity range, and the prop sizes...
+    check_self_loop(&params.from_node, &params.to_node, params.allow_self_loop)?;
+    check_validity_range(params.valid_from, params.valid_to)?;
+    check_prop_sizes(&params.props, params.max_prop_bytes)?;
+
+    // Build the edge...
+    let edge = new_edge(params, &params.from_node, &params.to_node);
+
+    // Insert it in a transaction...
+    let tx = begin_write(conn).await?;
+    insert_edge_rows(&tx, &edge, params.compress_props_over).await?;
+    tx.commit().await?;
+
+    // Return the data...
+    Ok(edge)
+}
+
+/// Create a pair of directed edges -- one from `from_node` to `to_node`
+/// and one back the other way -- in a single transaction.
+///
+/// This is for directed relationships that go both ways
+/// (e.g. A follows B and B follows A).
+pub async fn create_reciprocal_edges(
+    conn: &Connection,
+    params: &CreateEdgeParams,
+) -> Result<(DbEdge, DbEdge)> {
+    // Only directed edges have two directions...
+    if !params.directed {
+        return Err(error::validation(
+            "Edges can only be created in both directions if they're directed.",
+        ));
+    }
+
+    // Check for a self-loop, the validity range, and the prop sizes...
+    check_self_loop(&params.from_node, &params.to_node, params.allow_self_loop)?;
+    check_validity_range(params.valid_from, params.valid_to)?;
+    check_prop_sizes(&params.props, params.max_prop_bytes)?;
+
+    // Build the edges...
+    let forward = new_edge(params, &params.from_node, &params.to_node);
+    let backward = new_edge(params, &params.to_node, &params.from_node);
+
+    // Insert them in a transaction...
+    let tx = begin_write(conn).await?;
+    insert_edge_rows(&tx, &forward, params.compress_props_over).await?;
+    insert_edge_rows(&tx, &backward, params.compress_props_over).await?;
+    tx.commit().await?;
+
+    // Return the data...
+    Ok((forward, backward))
+}
+
+/// Build a new edge (with a generated ID) from the params,
+/// between the given nodes.
+fn new_edge(params: &CreateEdgeParams, from_node: &str, to_node: &str) -> DbEdge {
+    let now = Utc::now();
+    DbEdge {
+        id: util::new_id("e"),
+        edge_type: params.edge_type_case.apply(&params.edge_type),
+        from_node: from_node.to_string(),
+        to_node: to_node.to_string(),
+        directed: params.directed,
+        created_at: now,
+        updated_at: now,
+        props: Some(normalize_props(&params.props, edge_prop_key)),
+        valid_from: params.valid_from,
+        valid_to: params.valid_to,
+    }
+}
+
+/// Insert an edge's rows -- the edge itself and its props.
+///
+/// Note - This doesn't start a transaction, so it can be
+/// used as part of a larger one.
+async fn insert_edge_rows(
+    conn: &Connection,
+    edge: &DbEdge,
+    compress_props_over: Option<usize>,
+) -> Result<()> {
+    // Convert the timestamps to SQL values...
+    let sql_created_at = libsql::Value::Text(edge.created_at.to_rfc3339());
+    let sql_updated_at = libsql::Value::Text(edge.updated_at.to_rfc3339());
+
+    // Insert the edge...
+    conn.execute(
+        "
+        INSERT INTO edges (
+            id, 
+            edge_type, 
+            from_node, 
+            to_node, 
+            directed, 
+            created_at, 
+            updated_at,
+            valid_from,
+            valid_to
+        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?);
+        ",
+        libsql::params![
+            edge.id.clone(),
+            edge.edge_type.clone(),
+            edge.from_node.clone(),
+            edge.to_node.clone(),
+            edge.directed as i64,
+            sql_created_at.clone(),
+            sql_updated_at.clone(),
+            edge.valid_from.map(|t| t.to_rfc3339()),
+            edge.valid_to.map(|t| t.to_rfc3339()),
+        ],
+    )
+    .await?;
+
+    // Add the properties...
+    for (key, value) in edge.props.iter().flatten() {
+        let sql_key = libsql::Value::Text(edge_prop_key(key));
+        let sql_value = encode_prop_value(value, compress_props_over)?;
+        conn.execute(
+            "
+            INSERT INTO edge_props (
+                edge_id, 
+                key, 
+                value, 
+                created_at, 
+                updated_at
+            ) VALUES (?, ?, ?, ?, ?);
+            ",
+            libsql::params![
+                edge.id.clone(),
+                sql_key,
+                sql_value,
+                sql_created_at.clone(),
+                sql_updated_at.clone(),
+            ],
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// A node in a `SubgraphSpec`.
+#[derive(Debug, Default, Deserialize)]
+pub struct SubgraphNode {
+    /// A temporary name for the node, that edges in the
+    /// same spec can use to refer to it (as `$<alias>`).
+    pub alias: String,
+
+    #[serde(default)]
+    pub labels: Vec<String>,
+
+    #[serde(default)]
+    pub props: HashMap<String, Value>,
+}
+
+/// An edge in a `SubgraphSpec`.
+#[derive(Debug, Default, Deserialize)]
+pub struct SubgraphEdge {
+    #[serde(rename = "type")]
+    pub edge_type: String,
+
+    /// The source node -- either `$<alias>` for a node in the
+    /// spec, or the ID of an existing node.
+    pub from: String,
+
+    /// The target node -- either `$<alias>` for a node in the
+    /// spec, or the ID of an existing node.
+    pub to: String,
+
+    #[serde(default)]
+    pub directed: bool,
+
+    #[serde(default)]
+    pub props: HashMap<String, Value>,
+}
+
+/// A set of nodes and the edges between them, to be created together.
+///
+/// For example:
+///
+/// ```json
+/// {
+///   "nodes": [
+///     {"alias": "n1", "labels": ["Person"], "props": {"name": "Alice"}},
+///     {"alias": "n2", "labels": ["Person"], "props": {"name": "Bob"}}
+///   ],
+///   "edges": [
+///     {"type": "KNOWS", "from": "$n1", "to": "$n2", "directed": true}
+///   ]
+/// }
+/// ```
+#[derive(Debug, Default, Deserialize)]
+pub struct SubgraphSpec {
+    #[serde(default)]
+    pub nodes: Vec<SubgraphNode>,
+
+    #[serde(default)]
+    pub edges: Vec<SubgraphEdge>,
+}
+
+#[derive(Debug, Default)]
+pub struct CreateSubgraphParams {
+    pub spec: SubgraphSpec,
+
+    /// The maximum size of a prop value, in bytes.
+    /// Defaults to `DEFAULT_MAX_PROP_BYTES`.
+    pub max_prop_bytes: Option<usize>,
+
+    /// Compress prop values bigger than this many bytes.
+    /// If not set, values aren't compressed.
+    pub compress_props_over: Option<usize>,
+
+    /// Fail if any node has no labels.
+    pub require_label: bool,
+
+    /// How node labels are cased when stored.
+    pub label_case: CasePolicy,
+
+    /// How edge types are cased when stored.
+    pub edge_type_case: CasePolicy,
+
+    /// Allow edges whose source and target are the same node.
+    pub allow_self_loop: bool,
+
+    /// Cancels the operation, rolling back anything created so far.
+    pub cancel: CancellationToken,
+}
+
+/// The nodes and edges created from a `SubgraphSpec`.
+#[derive(Debug, Serialize)]
+pub struct CreatedSubgraph {
+    /// Maps each node's alias to its generated ID.
+    pub aliases: HashMap<String, String>,
+
+    pub nodes: Vec<DbNode>,
+    pub edges: Vec<DbEdge>,
+}
+
+/// Create a set of nodes and edges in a single transaction.
+///
+/// Edges can refer to nodes in the spec by their alias (`$<alias>`),
+/// which is replaced with the node's generated ID. If any alias can't
+/// be resolved (or anything else fails), nothing is created.
+///
+/// The nodes get their labels' defaults, and the edges are checked the
+/// same way as with `create_edge`.
+pub async fn create_subgraph(
+    conn: &Connection,
+    params: &CreateSubgraphParams,
+) -> Result<CreatedSubgraph> {
+    let now = Utc::now();
+
+    // Build the nodes, generating their IDs...
+    let mut aliases = HashMap::new();
+    let mut nodes = Vec::new();
+    for spec in params.spec.nodes.iter() {
+        // Check the alias...
+        let alias = spec.alias.trim();
+        if alias.is_empty() {
+            return Err(error::validation("Every node in the spec needs an alias."));
+        }
+
+        // Check the labels and props...
+        if params.require_label && spec.labels.iter().all(|l| l.trim().is_empty()) {
+            return Err(error::validation(format!(
+                "Node '{}' must have at least one label.",
+                alias
+            )));
+        }
+        // Generate the ID...
+        let id = util::new_id("n");
+        if aliases.insert(alias.to_string(), id.clone()).is_some() {
+            return Err(error::validation(format!(
+                "Node alias '{}' is used more than once.",
+                alias
+            )));
+        }
+        nodes.push(DbNode {
+            id,
+            labels: dedupe_labels(&spec.labels, params.label_case),
+            created_at: now,
+            updated_at: now,
+            props: None,
+        });
+    }
+
+    // Resolve an edge's node reference to an ID...
+    let resolve = |node: &str| -> Result<String> {
+        match node.strip_prefix('$') {
+            Some(alias) => aliases
+                .get(alias)
+                .cloned()
+                .ok_or_else(|| error::validation(format!("Unknown node alias '{}'.", node))),
+            None => Ok(node.to_string()),
+        }
+    };
+
+    // Build the edges...
+    let mut edges = Vec::new();
+    for spec in params.spec.edges.iter() {
+        let (from_node, to_node) = (resolve(&spec.from)?, resolve(&spec.to)?);
+        check_self_loop(&from_node, &to_node, params.allow_self_loop)?;
+        check_prop_sizes(&spec.props, params.max_prop_bytes)?;
+        edges.push(DbEdge {
+            id: util::new_id("e"),
+            edge_type: params.edge_type_case.apply(&spec.edge_type),
+            from_node,
+            to_node,
+            directed: spec.directed,
+            created_at: now,
+            updated_at: now,
+            props: Some(normalize_props(&spec.props, edge_prop_key)),
+            valid_from: None,
+            valid_to: None,
+        });
+    }
+
+    // Insert everything in a single transaction...
+    // Note - The nodes' props (with their labels' defaults) are filled
+    // in here, so the defaults are read in the same transaction...
+    let tx = begin_write(conn).await?;
+    for (node, spec) in nodes.iter_mut().zip(params.spec.nodes.iter()) {
+        let props =
+            with_label_defaults(&tx, &node.labels, &spec.props, params.max_prop_bytes).await?;
+        node.props = Some(props);
+        insert_node_rows(&tx, node, params.compress_props_over).await?;
+        check_cancelled(&params.cancel)?;
+    }
+    for edge in edges.iter() {
+        // Make sure any existing nodes referred to by ID exist...
+        for node_id in [&edge.from_node, &edge.to_node] {
+            if !check_node_exists(&tx, node_id).await? {
+                return Err(error::not_found(format!(
+                    "Node '{}' does not exist.",
+                    node_id
+                )));
+            }
+        }
+        insert_edge_rows(&tx, edge, params.compress_props_over).await?;
+        check_cancelled(&params.cancel)?;
+    }
+    tx.commit().await?;
+
+    // Done!
+    Ok(CreatedSubgraph {
+        aliases,
+        nodes,
+        edges,
+    })
+}
+
+/// Insert existing nodes and edges (keeping their IDs and timestamps,
+/// e.g. from an export) in a single transaction.
+///
+/// The nodes are inserted before the edges. If anything fails (or the
+/// operation is cancelled), nothing is inserted.
+pub async fn insert_items(
+    conn: &Connection,
+    nodes: &[DbNode],
+    edges: &[DbEdge],
+    compress_props_over: Option<usize>,
+    cancel: &CancellationToken,
+) -> Result<()> {
+    let tx = begin_write(conn).await?;
+    insert_item_rows(&tx, nodes, edges, compress_props_over, cancel).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Insert existing nodes and edges, nodes first.
+///
+/// Note - This doesn't start a transaction, so it can be
+/// used as part of a larger one.
+async fn insert_item_rows(
+    conn: &Connection,
+    nodes: &[DbNode],
+    edges: &[DbEdge],
+    compress_props_over: Option<usize>,
+    cancel: &CancellationToken,
+) -> Result<()> {
+    for node in nodes.iter() {
+        insert_node_rows(conn, node, compress_props_over).await?;
+        check_cancelled(cancel)?;
+    }
+    for edge in edges.iter() {
+        insert_edge_rows(conn, edge, compress_props_over).await?;
+        check_cancelled(cancel)?;
+    }
+    Ok(())
+}
+
+/// The tables (besides the nodes, edges, and their props) that are
+/// part of the graph, and the columns copied from each.
+///
+/// Note - The audit log's IDs aren't copied, so its entries are
+/// renumbered (in order) in the new database.
+const GRAPH_EXTRA_TABLES: &[(&str, &str)] = &[
+    ("_templates", "name, labels, props, created_at, updated_at"),
+    ("_label_defaults", "label, props, created_at, updated_at"),
+    (
+        "_audit",
+        "at, operation, target_kind, target_id, key, old_value, new_value",
+    ),
+];
+
+/// Everything in the graph besides its nodes and edges -- the node
+/// templates, label defaults, graph metadata, audit log, and which
+/// items are archived.
+#[derive(Debug, Default)]
+pub struct GraphExtras {
+    /// The rows of each of the `GRAPH_EXTRA_TABLES` (in order).
+    tables: Vec<Vec<Vec<libsql::Value>>>,
+
+    /// The graph metadata, as `(key, val_txt, val_int)`.
+    meta: Vec<(String, Option<String>, Option<i64>)>,
+
+    /// The archived nodes and edges, as `(table, id, archived_at)`.
+    archived: Vec<(&'static str, String, String)>,
+}
+
+/// Read everything in the graph besides its nodes and edges (e.g. to
+/// copy it to another database, with `insert_graph_copy`).
+pub async fn read_graph_extras(conn: &Connection) -> Result<GraphExtras> {
+    let mut extras = GraphExtras::default();
+
+    // Get the rows of the other tables...
+    for (table, columns) in GRAPH_EXTRA_TABLES.iter() {
+        let query = format!("SELECT {} FROM {} ORDER BY rowid;", columns, table);
+        let mut rows = conn.query(&query, ()).await?;
+        let count = rows.column_count();
+        let mut values = Vec::new();
+        while let Some(row) = rows.next().await? {
+            values.push(
+                (0..count)
+                    .map(|i| row.get_value(i))
+                    .collect::<Result<_, _>>()?,
+            );
+        }
+        extras.tables.push(values);
+    }
+
+    // Get the graph metadata (but not graphctl's own keys)...
+    let mut rows = conn
+        .query("SELECT key, val_txt, val_int FROM _meta ORDER BY key;", ())
+        .await?;
+    while let Some(row) = rows.next().await? {
+        let key: String = row.get(0)?;
+        if !RESERVED_META_KEYS.contains(&key.as_str()) {
+            extras.meta.push((key, row.get(1)?, row.get(2)?));
+        }
+    }
+
+    // Get the archived items...
+    for table in ["nodes", "edges"] {
+        let query = format!(
+            "SELECT id, archived_at FROM {} WHERE archived_at IS NOT NULL ORDER BY id;",
+            table
+        );
+        let mut rows = conn.query(&query, ()).await?;
+        while let Some(row) = rows.next().await? {
+            extras.archived.push((table, row.get(0)?, row.get(1)?));
+        }
+    }
+    Ok(extras)
+}
+
+/// Insert a copy of a graph -- its nodes and edges (keeping their IDs
+/// and timestamps), and everything from `read_graph_extras` -- in a
+/// single transaction.
+///
+/// If anything fails (or the operation is cancelled), nothing is inserted.
+pub async fn insert_graph_copy(
+    conn: &Connection,
+    nodes: &[DbNode],
+    edges: &[DbEdge],
+    extras: &GraphExtras,
+    cancel: &CancellationToken,
+) -> Result<()> {
+    let tx = begin_write(conn).await?;
+
+    // Copy the other tables first, so anything audited while
+    // copying comes after the copied audit log...
+    for ((table, columns), rows) in GRAPH_EXTRA_TABLES.iter().zip(extras.tables.iter()) {
+        let placeholders = vec!["?"; columns.split(',').count()].join(", ");
+        let query = format!(
+            "INSERT OR REPLACE INTO {} ({}) VALUES ({});",
+            table, columns, placeholders
+        );
+        for row in rows.iter() {
+            tx.execute(&query, row.clone()).await?;
+            check_cancelled(cancel)?;
+        }
+    }
+    for (key, val_txt, val_int) in extras.meta.iter() {
+        set_meta(&tx, key, val_txt.clone(), *val_int).await?;
+    }
+
+    // Insert the nodes and edges, and re-archive the archived ones...
+    insert_item_rows(&tx, nodes, edges, None, cancel).await?;
+    for (table, id, archived_at) in extras.archived.iter() {
+        tx.execute(
+            &format!("UPDATE {} SET archived_at = ? WHERE id = ?;", table),
+            libsql::params![archived_at.as_str(), id.as_str()],
+        )
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Filters for selecting a set of nodes.
+///
+/// All of the filters are AND-ed together.
+#[derive(Debug, Default, Clone)]
+pub struct NodeFilter {
+    /// Only include nodes with this label.
+    pub has_label: Option<String>,
+
+    /// Exclude nodes with any of these labels.
+    pub exclude_labels: Vec<String>,
+
+    /// Only include nodes that have all of these props set.
+    pub has_prop: Vec<String>,
+
+    /// Only include nodes where these props have these values.
+    pub props: HashMap<String, Value>,
+
+    /// Compare string values in `props` case-insensitively.
+    pub props_ignore_case: bool,
+
+    /// Only include nodes where these props are JSON arrays
+    /// containing these values.
+    pub props_contain: Vec<(String, Value)>,
+
+    /// Only include nodes with no edges (in or out).
+    pub orphans: bool,
+
+    /// Only include nodes with at least one edge (in or out).
+    pub connected: bool,
+
+    /// Only include nodes with no incoming directed edges.
+    pub roots: bool,
+
+    /// Only include nodes with no outgoing directed edges.
+    pub leaves: bool,
+
+    /// Only include nodes with at least one edge of each of these types.
+    pub has_edge_types: Vec<String>,
+
+    /// Which of a node's edges `has_edge_types` looks at.
+    pub has_edge_direction: EdgeDirection,
+
+    /// How `has_label` is cased before matching.
+    pub label_case: CasePolicy,
+
+    /// How `has_edge_types` are cased before matching.
+    pub edge_type_case: CasePolicy,
+
+    /// Only include nodes created or updated after this time.
+    pub updated_after: Option<DateTime<Utc>>,
+
+    /// Only include nodes with at least this many edges (in or out).
+    pub min_degree: Option<usize>,
+
+    /// Only include nodes with at most this many edges (in or out).
+    pub max_degree: Option<usize>,
+
+    /// Include archived (soft-deleted) nodes.
+    pub include_archived: bool,
+
+    /// Only include nodes matching this boolean expression
+    /// (e.g. `label:Person AND (prop:age>30 OR prop:vip=true)`).
+    pub expr: Option<FilterExpr>,
+}
+
+/// Which of a node's edges to look at, by the node's position in them.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum EdgeDirection {
+    /// Edges from the node.
+    Out,
+
+    /// Edges to the node.
+    In,
+
+    /// Edges from or to the node.
+    #[default]
+    Both,
+}
+
+impl EdgeDirection {
+    /// The SQL condition matching edges (aliased as `e`) in this
+    /// direction from the node in the `nodes` table.
+    fn to_sql(self) -> &'static str {
+        match self {
+            EdgeDirection::Out => "e.from_node = nodes.id",
+            EdgeDirection::In => "e.to_node = nodes.id",
+            EdgeDirection::Both => "(e.from_node = nodes.id OR e.to_node = nodes.id)",
+        }
+    }
+}
+
+impl NodeFilter {
+    /// Build the SQL condition for the filter (to go after a `WHERE`),
+    /// along with its parameters.
+    ///
+    /// The condition references the `nodes` table by name.
+    fn to_sql(&self) -> (String, Vec<libsql::Value>) {
+        let mut conds = vec!["1 = 1".to_string()];
+        let mut params = Vec::new();
+
+        // Hide archived nodes?
+        if !self.include_archived {
+            conds.push("nodes.archived_at IS NULL".to_string());
+        }
+
+        // Filter by label...
+        if let Some(label) = &self.has_label {
+            conds.push(
+                "EXISTS (SELECT 1 FROM json_each(nodes.labels) WHERE json_each.value = ?)"
+                    .to_string(),
+            );
+            params.push(libsql::Value::Text(self.label_case.apply(label)));
+        }
+
+        // Exclude labels...
+        if !self.exclude_labels.is_empty() {
+            let placeholders = vec!["?"; self.exclude_labels.len()].join(", ");
+            conds.push(format!(
+                "NOT EXISTS (SELECT 1 FROM json_each(nodes.labels) WHERE json_each.value IN ({}))",
+                placeholders,
+            ));
+            for label in self.exclude_labels.iter() {
+                params.push(libsql::Value::Text(self.label_case.apply(label)));
+            }
+        }
+
+        // Filter by props being present...
+        for key in self.has_prop.iter() {
+            conds.push(
+                "EXISTS (SELECT 1 FROM node_props np WHERE np.node_id = nodes.id AND np.key = ?)"
+                    .to_string(),
+            );
+            params.push(libsql::Value::Text(node_prop_key(key)));
+        }
+
+        // Filter by prop values...
+        for (key, value) in self.props.iter() {
+            let (cond, param) = prop_equals_sql(
+                "node_props",
+                "np",
+                "node_id",
+                "nodes.id",
+                value,
+                self.props_ignore_case,
+            );
+            conds.push(cond);
+            params.push(libsql::Value::Text(node_prop_key(key)));
+            params.push(param);
+        }
+
+        // Filter by array props containing values...
+        for (key, value) in self.props_contain.iter() {
+            conds.push(prop_contains_sql("node_props", "np", "node_id", "nodes.id"));
+            params.push(libsql::Value::Text(node_prop_key(key)));
+            params.push(libsql::Value::Text(value.to_string()));
+        }
+
+        // Filter by whether the node has any edges...
+        // Note - Archived edges never count...
+        let has_edges = "EXISTS (
+            SELECT 1 FROM edges e
+            WHERE (e.from_node = nodes.id OR e.to_node = nodes.id) AND e.archived_at IS NULL
+        )";
+        if self.orphans {
+            conds.push(format!("NOT {}", has_edges));
+        }
+        if self.connected {
+            conds.push(has_edges.to_string());
+        }
+
+        // Filter by the types of the node's edges...
+        for edge_type in self.has_edge_types.iter() {
+            conds.push(format!(
+                "EXISTS (SELECT 1 FROM edges e WHERE ({}) AND e.edge_type = ? AND e.archived_at IS NULL)",
+                self.has_edge_direction.to_sql(),
+            ));
+            params.push(libsql::Value::Text(self.edge_type_case.apply(edge_type)));
+        }
+
+        // Filter by directed edges in or out...
+        // Note - Undirected edges don't count...
+        if self.roots {
+            conds.push(
+                "NOT EXISTS (
+                    SELECT 1 FROM edges e
+                    WHERE e.to_node = nodes.id AND e.directed AND e.archived_at IS NULL
+                )"
+                .to_string(),
+            );
+        }
+        if self.leaves {
+            conds.push(
+                "NOT EXISTS (
+                    SELECT 1 FROM edges e
+                    WHERE e.from_node = nodes.id AND e.directed AND e.archived_at IS NULL
+                )"
+                .to_string(),
+            );
+        }
+
+        // Filter by when the node was last updated...
+        // Note - `julianday` is used so timestamps with different
+        // UTC offsets are compared correctly...
+        if let Some(after) = &self.updated_after {
+            conds.push("julianday(nodes.updated_at) > julianday(?)".to_string());
+            params.push(libsql::Value::Text(after.to_rfc3339()));
+        }
+
+        // Filter by degree...
+        // Note - Each edge counts once per node it touches, whether
+        // or not it's directed (and a self-loop counts once)...
+        if self.min_degree.is_some() || self.max_degree.is_some() {
+            conds.push(
+                "nodes.id IN (
+                    SELECT n.id FROM nodes n
+                    LEFT JOIN edges e
+                        ON (e.from_node = n.id OR e.to_node = n.id) AND e.archived_at IS NULL
+                    GROUP BY n.id
+                    HAVING COUNT(e.id) >= ? AND COUNT(e.id) <= ?
+                )"
+                .to_string(),
+            );
+            params.push(libsql::Value::Integer(self.min_degree.unwrap_or(0) as i64));
+            params.push(libsql::Value::Integer(
+                self.max_degree.map_or(i64::MAX, |d| d as i64),
+            ));
+        }
+
+        // Filter by the boolean expression...
+        if let Some(expr) = &self.expr {
+            conds.push(node_filter_expr_sql(expr, self.label_case, &mut params));
+        }
+
+        (conds.join(" AND "), params)
+    }
+}
+
+/// Compile a filter expression into a SQL condition on the `nodes`
+/// table, pushing its parameters.
+///
+/// Every label, key, and value is passed as a parameter, so nothing
+/// from the expression ends up in the SQL itself.
+fn node_filter_expr_sql(
+    expr: &FilterExpr,
+    label_case: CasePolicy,
+    params: &mut Vec<libsql::Value>,
+) -> String {
+    match expr {
+        FilterExpr::Label(label) => {
+            params.push(libsql::Value::Text(label_case.apply(label)));
+            "EXISTS (SELECT 1 FROM json_each(nodes.labels) WHERE json_each.value = ?)".to_string()
+        }
+        FilterExpr::HasProp(key) => {
+            params.push(libsql::Value::Text(node_prop_key(key)));
+            "EXISTS (SELECT 1 FROM node_props np WHERE np.node_id = nodes.id AND np.key = ?)"
+                .to_string()
+        }
+        FilterExpr::Prop(cond) => {
+            params.push(libsql::Value::Text(node_prop_key(&cond.key)));
+            params.push(libsql::Value::Text(cond.value.to_string()));
+            prop_compare_sql("node_props", "np", "node_id", "nodes.id", cond.op)
+        }
+        FilterExpr::Not(inner) => {
+            format!("NOT ({})", node_filter_expr_sql(inner, label_case, params))
+        }
+        FilterExpr::And(a, b) => {
+            let a = node_filter_expr_sql(a, label_case, params);
+            let b = node_filter_expr_sql(b, label_case, params);
+            format!("(({}) AND ({}))", a, b)
+        }
+        FilterExpr::Or(a, b) => {
+            let a = node_filter_expr_sql(a, label_case, params);
+            let b = node_filter_expr_sql(b, label_case, params);
+            format!("(({}) OR ({}))", a, b)
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ListNodesParams {
+    pub filter: NodeFilter,
+    pub limit: Option<usize>,
+
+    /// Return a random sample of (up to) this many matching nodes,
+    /// rather than the first `limit`.
+    pub sample: Option<usize>,
+}
+
+/// Add a `LIMIT` (and, when sampling, a random ordering) to a list query.
+///
+/// Note - `ORDER BY RANDOM()` has to visit every matching row, so
+/// sampling a huge table is expensive. It's not affected by `--seed`.
+fn push_limit(
+    query: &mut String,
+    sql_params: &mut Vec<libsql::Value>,
+    limit: Option<usize>,
+    sample: Option<usize>,
+) {
+    let limit = match sample {
+        Some(sample) => {
+            query.push_str(" ORDER BY RANDOM()");
+            Some(sample)
+        }
+        None => limit,
+    };
+    if let Some(limit) = limit {
+        query.push_str(" LIMIT ?");
+        sql_params.push(libsql::Value::Integer(limit as i64));
+    }
+}
+
+pub async fn list_nodes(conn: &Connection, params: &ListNodesParams) -> Result<Vec<DbNode>> {
+    // Build the query...
+    let (cond, mut sql_params) = params.filter.to_sql();
+    let mut query = format!(
+        "
+        SELECT id, labels, created_at, updated_at
+        FROM nodes
+        WHERE {}
+        ",
+        cond,
+    );
+    push_limit(&mut query, &mut sql_params, params.limit, params.sample);
+
+    // Run it...
+    let mut res = conn
+        .prepare(&query)
+        .await?
+        .query(libsql::params_from_iter(sql_params))
+        .await?;
+
+    let mut nodes = Vec::new();
+    while let Some(row) = res.next().await? {
+        // Get the values...
+        let mut node = node_from_row(&row)?;
+
+        // Get the props...
+        let props = get_node_props(conn, &node.id).await?;
+        node.props = Some(props);
+
+        // Add it to the list...
+        nodes.push(node);
+    }
+
+    Ok(nodes)
+}
+
+/// Filters for selecting a set of edges.
+///
+/// All of the filters are AND-ed together.
+#[derive(Debug, Default, Clone)]
+pub struct EdgeFilter {
+    /// Only include edges of this type.
+    pub edge_type: Option<String>,
+
+    /// Only include edges that have all of these props set.
+    pub has_prop: Vec<String>,
+
+    /// Only include edges where these props have these values.
+    pub props: HashMap<String, Value>,
+
+    /// Compare string values in `props` case-insensitively.
+    pub props_ignore_case: bool,
+
+    /// Only include edges where these props are JSON arrays
+    /// containing these values.
+    pub props_contain: Vec<(String, Value)>,
+
+    /// Only include edges whose props compare with these values
+    /// (e.g. `weight > 5`).
+    pub prop_conditions: Vec<util::PropCondition>,
+
+    /// Only include edges from this node.
+    pub from_node: Option<String>,
+
+    /// Only include edges to this node.
+    pub to_node: Option<String>,
+
+    /// How `edge_type` is cased before matching.
+    pub edge_type_case: CasePolicy,
+
+    /// Only include edges where either endpoint doesn't exist.
+    pub dangling: bool,
+
+    /// Only include edges created or updated after this time.
+    pub updated_after: Option<DateTime<Utc>>,
+
+    /// Only include edges that were active at this time -- i.e. whose
+    /// validity range contains it. Edges without a range are always active.
+    pub valid_at: Option<DateTime<Utc>>,
+
+    /// Include archived (soft-deleted) edges.
+    pub include_archived: bool,
+}
+
+impl EdgeFilter {
+    /// Build the SQL condition for the filter (to go after a `WHERE`),
+    /// along with its parameters.
+    ///
+    /// The condition references the `edges` table by name.
+    fn to_sql(&self) -> (String, Vec<libsql::Value>) {
+        let mut conds = vec!["1 = 1".to_string()];
+        let mut params = Vec::new();
+
+        // Hide archived edges?
+        if !self.include_archived {
+            conds.push("edges.archived_at IS NULL".to_string());
+        }
+
+        // Filter by the edge type...
+        if let Some(edge_type) = &self.edge_type {
+            conds.push("edges.edge_type = ?".to_string());
+            params.push(libsql::Value::Text(self.edge_type_case.apply(edge_type)));
+        }
+
+        // Filter by props being present...
+        for key in self.has_prop.iter() {
+            conds.push(
+                "EXISTS (SELECT 1 FROM edge_props ep WHERE ep.edge_id = edges.id AND ep.key = ?)"
+                    .to_string(),
+            );
+            params.push(libsql::Value::Text(edge_prop_key(key)));
+        }
+
+        // Filter by prop values...
+        for (key, value) in self.props.iter() {
+            let (cond, param) = prop_equals_sql(
+                "edge_props",
+                "ep",
+                "edge_id",
+                "edges.id",
+                value,
+                self.props_ignore_case,
+            );
+            conds.push(cond);
+            params.push(libsql::Value::Text(edge_prop_key(key)));
+            params.push(param);
+        }
+
+        // Filter by array props containing values...
+        for (key, value) in self.props_contain.iter() {
+            conds.push(prop_contains_sql("edge_props", "ep", "edge_id", "edges.id"));
+            params.push(libsql::Value::Text(edge_prop_key(key)));
+            params.push(libsql::Value::Text(value.to_string()));
+        }
+
+        // Filter by prop comparisons...
+        for cond in self.prop_conditions.iter() {
+            conds.push(prop_compare_sql(
+                "edge_props",
+                "ep",
+                "edge_id",
+                "edges.id",
+                cond.op,
+            ));
+            params.push(libsql::Value::Text(edge_prop_key(&cond.key)));
+            params.push(libsql::Value::Text(cond.value.to_string()));
+        }
+
+        // Filter by the endpoints...
+        if let Some(from_node) = &self.from_node {
+            conds.push("edges.from_node = ?".to_string());
+            params.push(libsql::Value::Text(from_node.clone()));
+        }
+        if let Some(to_node) = &self.to_node {
+            conds.push("edges.to_node = ?".to_string());
+            params.push(libsql::Value::Text(to_node.clone()));
+        }
+
+        // Filter to edges with a missing endpoint...
+        if self.dangling {
+            conds.push(
+                "(
+                    NOT EXISTS (SELECT 1 FROM nodes n WHERE n.id = edges.from_node)
+                    OR NOT EXISTS (SELECT 1 FROM nodes n WHERE n.id = edges.to_node)
+                )"
+                .to_string(),
+            );
+        }
+
+        // Filter by when the edge was last updated...
+        if let Some(after) = &self.updated_after {
+            conds.push("julianday(edges.updated_at) > julianday(?)".to_string());
+            params.push(libsql::Value::Text(after.to_rfc3339()));
+        }
+
+        // Filter by the validity range...
+        // Note - A missing bound is open-ended (e.g. no `valid_to`
+        // means the relationship is still active)...
+        if let Some(at) = &self.valid_at {
+            conds.push(
+                "(edges.valid_from IS NULL OR julianday(edges.valid_from) <= julianday(?))
+                AND (edges.valid_to IS NULL OR julianday(edges.valid_to) > julianday(?))"
+                    .to_string(),
+            );
+            params.push(libsql::Value::Text(at.to_rfc3339()));
+            params.push(libsql::Value::Text(at.to_rfc3339()));
+        }
+
+        (conds.join(" AND "), params)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ListEdgesParams {
+    pub filter: EdgeFilter,
+    pub limit: Option<usize>,
+
+    /// Return a random sample of (up to) this many matching edges,
+    /// rather than the first `limit`.
+    pub sample: Option<usize>,
+}
+
+pub async fn list_edges(conn: &Connection, params: &ListEdgesParams) -> Result<Vec<DbEdge>> {
+    // Build the query...
+    let (cond, mut sql_params) = params.filter.to_sql();
+    let mut query = format!(
+        "
+        SELECT id, edge_type, from_node, to_node, directed, created_at, updated_at, valid_from, valid_to
+        FROM edges
+        WHERE {}
+        ",
+        cond,
+    );
+    push_limit(&mut query, &mut sql_params, params.limit, params.sample);
+
+    // Run it...
+    let mut res = conn
+        .prepare(&query)
+        .await?
+        .query(libsql::params_from_iter(sql_params))
+        .await?;
+
+    let mut edges = Vec::new();
+    while let Some(row) = res.next().await? {
+        // Get the values...
+        let mut e = de::from_row::<DbEdge>(&row)?;
+
+        // Get the props...
+        let props = get_edge_props(conn, &e.id).await?;
+        e.props = Some(props);
+
+        // Add it to the list...
+        edges.push(e);
+    }
+
+    Ok(edges)
+}
+
+/// The columns that edges can be grouped by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum EdgeGroupBy {
+    /// Group by the edge's type.
+    #[value(name = "type")]
+    EdgeType,
+
+    /// Group by the edge's source node.
+    #[value(name = "from_node", alias = "from-node")]
+    FromNode,
+
+    /// Group by the edge's target node.
+    #[value(name = "to_node", alias = "to-node")]
+    ToNode,
+}
+
+impl EdgeGroupBy {
+    /// The name of the column in the `edges` table.
+    ///
+    /// Note - This is the only way a group-by column gets into the
+    /// query, so only these columns are allowed.
+    fn column(&self) -> &'static str {
+        match self {
+            EdgeGroupBy::EdgeType => "edge_type",
+            EdgeGroupBy::FromNode => "from_node",
+            EdgeGroupBy::ToNode => "to_node",
+        }
+    }
+}
+
+/// Count the edges matching `filter`, grouped by a column.
+///
+/// The groups are returned with the largest first.
+pub async fn group_edges(
+    conn: &Connection,
+    filter: &EdgeFilter,
+    by: EdgeGroupBy,
+) -> Result<Vec<(String, i64)>> {
+    // Build the query...
+    let (cond, params) = filter.to_sql();
+    let query = format!(
+        "
+        SELECT edges.{col}, COUNT(*) AS n
+        FROM edges
+        WHERE {cond}
+        GROUP BY edges.{col}
+        ORDER BY n DESC, edges.{col};
+        ",
+        col = by.column(),
+        cond = cond,
+    );
+
+    // Run it...
+    let mut rows = conn
+        .prepare(&query)
+        .await?
+        .query(libsql::params_from_iter(params))
+        .await?;
+
+    // Collect the counts...
+    let mut out = Vec::new();
+    while let Some(row) = rows.next().await? {
+        let key: String = row.get(0)?;
+        let count: i64 = row.get(1)?;
+        out.push((key, count));
+    }
+    Ok(out)
+}
+
+pub async fn check_node_exists(conn: &Connection, id: &str) -> Result<bool> {
+    let res = conn
+        .prepare(
+            "
+            SELECT COUNT(*) > 0
+            FROM nodes 
+            WHERE id = ?;
+            ",
+        )
+        .await?
+        .query_row(libsql::params![id])
+        .await?;
+    Ok(res.get(0)?)
+}
+
+pub async fn check_edge_exists(conn: &Connection, id: &str) -> Result<bool> {
+    let res = conn
+        .prepare(
+            "
+            SELECT COUNT(*) > 0
+            FROM edges
+            WHERE id = ?;
+            ",
+        )
+        .await?
+        .query_row(libsql::params![id])
+        .await?;
+    Ok(res.get(0)?)
+}
+
+/// The maximum number of candidate IDs listed when an ID prefix is ambiguous.
+const MAX_ID_CANDIDATES: usize = 10;
+
+/// Resolve a full node ID from a (possibly abbreviated) ID prefix.
+///
+/// Like git's abbreviated commit hashes, this returns an error if
+/// the prefix matches no nodes, or if it matches more than one.
+pub async fn resolve_node_id(conn: &Connection, prefix: &str) -> Result<String> {
+    resolve_id(conn, "nodes", "Node", prefix).await
+}
+
+/// Resolve a full edge ID from a (possibly abbreviated) ID prefix.
+///
+/// Works the same way as `resolve_node_id`.
+pub async fn resolve_edge_id(conn: &Connection, prefix: &str) -> Result<String> {
+    resolve_id(conn, "edges", "Edge", prefix).await
+}
+
+/// Resolve a full ID in `table` from a (possibly abbreviated) ID prefix.
+async fn resolve_id(conn: &Connection, table: &str, kind: &str, prefix: &str) -> Result<String> {
+    // An empty prefix would match everything...
+    if prefix.is_empty() {
+        return Err(error::validation(format!("{} ID can't be empty.", kind)));
+    }
+
+    // Find the IDs starting with the prefix...
+    // Note - `substr` is used rather than `LIKE` so the
+    // prefix doesn't need to be escaped...
+    let query = format!(
+        "
+        SELECT id
+        FROM {}
+        WHERE substr(id, 1, length(?1)) = ?1
+        ORDER BY id
+        LIMIT ?2;
+        ",
+        table,
+    );
+    let mut rows = conn
+        .prepare(&query)
+        .await?
+        .query(libsql::params![prefix, MAX_ID_CANDIDATES as i64 + 1])
+        .await?;
+    let mut ids: Vec<String> = Vec::new();
+    while let Some(row) = rows.next().await? {
+        ids.push(row.get(0)?);
+    }
+
+    // An exact match always wins...
+    if ids.iter().any(|id| id == prefix) {
+        return Ok(prefix.to_string());
+    }
+
+    // Otherwise, there should be exactly one match...
+    match ids.len() {
+        0 => Err(error::not_found(format!(
+            "No {} found with ID (or ID prefix) '{}'.",
+            kind.to_lowercase(),
+            prefix
+        ))),
+        1 => Ok(ids.remove(0)),
+        n => {
+            let more = if n > MAX_ID_CANDIDATES { "\n  ..." } else { "" };
+            ids.truncate(MAX_ID_CANDIDATES);
+            Err(error::validation(format!(
+                "{} ID prefix '{}' is ambiguous. It matches:\n  {}{}",
+                kind,
+                prefix,
+                ids.join("\n  "),
+                more,
+            )))
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct GetNodeParams {
+    pub id: String,
+    pub with_props: bool,
+    // pub with_edges: bool,
+    /// Get the node even if it's archived.
+    pub include_archived: bool,
+}
+
+pub async fn get_node(conn: &Connection, params: &GetNodeParams) -> Result<DbNode> {
+    // Get the node...
+    let res = conn
+        .prepare(
+            "
+            SELECT id, labels, created_at, updated_at 
+            FROM nodes 
+            WHERE id = ? AND (? OR archived_at IS NULL);
+            ",
+        )
+        .await?
+        .query_row(libsql::params![params.id.clone(), params.include_archived])
+        .await;
+    let row = match res {
+        Ok(row) => row,
+        Err(libsql::Error::QueryReturnedNoRows) => {
+            return Err(missing_error(conn, "nodes", "Node", &params.id).await?);
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    // Get the values...
+    let mut node = node_from_row(&row)?;
+
+    // Get the properties?
+    if params.with_props {
+        let props = get_node_props(conn, &params.id).await?;
+        node.props = Some(props);
+    }
+
+    // Return the data!
+    Ok(node)
+}
+
+/// The "not found" error for a node or edge that couldn't be found,
+/// pointing out if it's actually there, but archived.
+async fn missing_error(
+    conn: &Connection,
+    table: &str,
+    kind: &str,
+    id: &str,
+) -> Result<anyhow::Error> {
+    let mut rows = conn
+        .prepare(&format!("SELECT 1 FROM {} WHERE id = ?;", table))
+        .await?
+        .query(libsql::params![id])
+        .await?;
+    Ok(match rows.next().await? {
+        Some(_) => error::not_found(format!(
+            "{} '{}' is archived. Pass `--include-archived` to see it, or restore it with `unarchive {}`.",
+            kind,
+            id,
+            kind.to_lowercase(),
+        )),
+        None => error::not_found(format!("{} '{}' does not exist.", kind, id)),
+    })
+}
+
+pub async fn get_node_props(conn: &Connection, node_id: &str) -> Result<HashMap<String, Value>> {
+    // Query the props in the database...
+    let mut rows = conn
+        .prepare(
+            "
+            SELECT key, value 
+            FROM node_props 
+            WHERE node_id = ?;
+            ",
+        )
+        .await?
+        .query(libsql::params![node_id])
+        .await?;
+
+    // Add them to a map...
+    let mut map = HashMap::new();
+    while let Some(row) = rows.next().await? {
+        let key: String = row.get(0)?;
+        let value = decode_prop_value(row.get_value(1)?)?;
+        map.insert(key, value);
+    }
+
+    // Return the data!
+    Ok(map)
+}
+
+#[derive(Debug, Default)]
+pub struct GetEdgeParams {
+    pub id: String,
+    pub with_props: bool,
+
+    /// Get the edge even if it's archived.
+    pub include_archived: bool,
+}
+
+pub async fn get_edge(conn: &Connection, params: &GetEdgeParams) -> Result<DbEdge> {
+    // Get the edge...
+    let res = conn
+        .prepare(
+            "
+            SELECT id, edge_type, from_node, to_node, directed, created_at, updated_at, valid_from, valid_to
+            FROM edges
+            WHERE id = ? AND (? OR archived_at IS NULL);
+            ",
+        )
+        .await?
+        .query_row(libsql::params![params.id.clone(), params.include_archived])
+        .await;
+    let row = match res {
+        Ok(row) => row,
+        Err(libsql::Error::QueryReturnedNoRows) => {
+            return Err(missing_error(conn, "edges", "Edge", &params.id).await?);
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    // Get the values...
+    let mut edge = de::from_row::<DbEdge>(&row)?;
+
+    // Get the properties?
+    if params.with_props {
+        let props = get_edge_props(conn, &params.id).await?;
+        edge.props = Some(props);
+    }
+
+    // Return the data!
+    Ok(edge)
+}
+
+pub async fn get_edge_props(conn: &Connection, edge_id: &str) -> Result<HashMap<String, Value>> {
+    // Query the props in the database...
+    let mut rows = conn
+        .prepare(
+            "
+            SELECT key, value 
+            FROM edge_props 
+            WHERE edge_id = ?;
+            ",
+        )
+        .await?
+        .query(libsql::params![edge_id])
+        .await?;
+
+    // Add them to a map...
+    let mut map = HashMap::new();
+    while let Some(row) = rows.next().await? {
+        let key: String = row.get(0)?;
+        let value = decode_prop_value(row.get_value(1)?)?;
+        map.insert(key, value);
+    }
+
+    // Return the data!
+    Ok(map)
+}
+
+pub async fn get_node_edges_in(conn: &Connection, node_id: &str) -> Result<Vec<String>> {
+    // Query the props in the database...
+    let mut rows = conn
+        .prepare(
+            "
+            SELECT id 
+            FROM edges
+            WHERE (to_node = ? OR (NOT directed AND from_node = ?)) AND archived_at IS NULL;
+            ",
+        )
+        .await?
+        .query(libsql::params![node_id, node_id,])
+        .await?;
+
+    // Add them to a map...
+    let mut out = Vec::new();
+    while let Some(row) = rows.next().await? {
+        let key: String = row.get(0)?;
+        out.push(key);
+    }
+
+    // Return the data!
+    Ok(out)
+}
+
+pub async fn get_node_edges_out(conn: &Connection, node_id: &str) -> Result<Vec<String>> {
+    // Query the props in the database...
+    let mut rows = conn
+        .prepare(
+            "
+            SELECT id 
+            FROM edges
+            WHERE (from_node = ? OR (NOT directed AND to_node = ?)) AND archived_at IS NULL;
+            ",
+        )
+        .await?
+        .query(libsql::params![node_id, node_id,])
+        .await?;
+
+    // Add them to a map...
+    let mut out = Vec::new();
+    while let Some(row) = rows.next().await? {
+        let key: String = row.get(0)?;
+        out.push(key);
+    }
+
+    // Return the data!
+    Ok(out)
+}
+
+/// Get the full edges coming into a node (rather than just the IDs).
+///
+/// If `with_props` is set, the edges' props are fetched in a
+/// single batched query.
+pub async fn get_node_edges_in_expanded(
+    conn: &Connection,
+    node_id: &str,
+    with_props: bool,
+) -> Result<Vec<DbEdge>> {
+    get_node_edges_expanded(
+        conn,
+        "edges.to_node = ?1 OR (NOT edges.directed AND edges.from_node = ?1)",
+        node_id,
+        with_props,
+    )
+    .await
+}
+
+/// Get the full edges going out of a node (rather than just the IDs).
+///
+/// If `with_props` is set, the edges' props are fetched in a
+/// single batched query.
+pub async fn get_node_edges_out_expanded(
+    conn: &Connection,
+    node_id: &str,
+    with_props: bool,
+) -> Result<Vec<DbEdge>> {
+    get_node_edges_expanded(
+        conn,
+        "edges.from_node = ?1 OR (NOT edges.directed AND edges.to_node = ?1)",
+        node_id,
+        with_props,
+    )
+    .await
+}
+
+/// Get the edges matching `cond` (which references the node ID as `?1`),
+/// optionally with their props.
+async fn get_node_edges_expanded(
+    conn: &Connection,
+    cond: &str,
+    node_id: &str,
+    with_props: bool,
+) -> Result<Vec<DbEdge>> {
+    // Get the edges...
+    let query = format!(
+        "
+        SELECT id, edge_type, from_node, to_node, directed, created_at, updated_at, valid_from, valid_to
+        FROM edges
+        WHERE ({}) AND edges.archived_at IS NULL
+        ORDER BY id;
+        ",
+        cond,
+    );
+    let mut rows = conn
+        .prepare(&query)
+        .await?
+        .query(libsql::params![node_id])
+        .await?;
+    let mut edges = Vec::new();
+    while let Some(row) = rows.next().await? {
+        edges.push(de::from_row::<DbEdge>(&row)?);
+    }
+
+    // Get the props for all of the edges at once?
+    if with_props {
+        let query = format!(
+            "
+            SELECT edge_props.edge_id, edge_props.key, edge_props.value
+            FROM edge_props
+            JOIN edges ON edges.id = edge_props.edge_id
+            WHERE ({}) AND edges.archived_at IS NULL;
+            ",
+            cond,
+        );
+        let mut rows = conn
+            .prepare(&query)
+            .await?
+            .query(libsql::params![node_id])
+            .await?;
+        let mut props: HashMap<String, HashMap<String, Value>> = HashMap::new();
+        while let Some(row) = rows.next().await? {
+            let edge_id: String = row.get(0)?;
+            let key: String = row.get(1)?;
+            let value = decode_prop_value(row.get_value(2)?)?;
+            props.entry(edge_id).or_default().insert(key, value);
+        }
+        for edge in edges.iter_mut() {
+            edge.props = Some(props.remove(&edge.id).unwrap_or_default());
+        }
+    }
+
+    // Return the data!
+    Ok(edges)
+}
+
+/// Get the `(from, to)` node IDs of every edge in the graph.
+///
+/// This is a lightweight way to load the whole graph's structure
+/// (e.g. for analysis), without the edges' props.
+pub async fn get_all_edge_endpoints(conn: &Connection) -> Result<Vec<(String, String)>> {
+    let mut rows = conn
+        .query(
+            "SELECT from_node, to_node FROM edges WHERE archived_at IS NULL;",
+            (),
+        )
+        .await?;
+    let mut edges = Vec::new();
+    while let Some(row) = rows.next().await? {
+        edges.push((row.get(0)?, row.get(1)?));
+    }
+    Ok(edges)
+}
+
+/// An edge touching a node, along with the node at its other end.
+#[derive(Debug, Serialize)]
+pub struct IncidentEdge {
+    #[serde(flatten)]
+    pub edge: DbEdge,
+
+    /// The node at the edge's other end (without its props), or
+    /// `None` if it doesn't exist.
+    pub other_node: Option<DbNode>,
+}
+
+/// Attach the node at the other end of each of a node's edges.
+///
+/// The other nodes are fetched in a single batched query, rather
+/// than one query per edge.
+pub async fn get_incident_edges(
+    conn: &Connection,
+    node_id: &str,
+    edges: Vec<DbEdge>,
+) -> Result<Vec<IncidentEdge>> {
+    // Get the other ends of the edges...
+    // Note - A self-loop's other end is the node itself...
+    let other_id = |e: &DbEdge| match e.from_node == node_id {
+        true => e.to_node.clone(),
+        false => e.from_node.clone(),
+    };
+    let mut ids: Vec<String> = edges.iter().map(other_id).collect();
+    ids.sort();
+    ids.dedup();
+
+    // Get those nodes all at once...
+    let nodes = get_nodes_by_ids(conn, &ids).await?;
+
+    // Match them back up...
+    Ok(edges
+        .into_iter()
+        .map(|edge| {
+            let other_node = nodes.get(&other_id(&edge)).map(|n| DbNode {
+                id: n.id.clone(),
+                labels: n.labels.clone(),
+                props: None,
+                created_at: n.created_at,
+                updated_at: n.updated_at,
+            });
+            IncidentEdge { edge, other_node }
+        })
+        .collect())
+}
+
+/// Get a set of nodes (without their props) by ID, in a single query.
+///
+/// IDs that don't exist are left out of the result.
+pub async fn get_nodes_by_ids(
+    conn: &Connection,
+    ids: &[String],
+) -> Result<HashMap<String, DbNode>> {
+    if ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let query = format!(
+        "SELECT id, labels, created_at, updated_at FROM nodes WHERE id IN ({});",
+        vec!["?"; ids.len()].join(", "),
+    );
+    let mut rows = conn
+        .prepare(&query)
+        .await?
+        .query(libsql::params_from_iter(ids.iter().cloned()))
+        .await?;
+    let mut nodes = HashMap::new();
+    while let Some(row) = rows.next().await? {
+        let node = node_from_row(&row)?;
+        nodes.insert(node.id.clone(), node);
+    }
+    Ok(nodes)
+}
+
+/// The number of IDs looked up per query by `get_nodes`.
+const GET_NODES_BATCH_SIZE: usize = 500;
+
+#[derive(Debug, Default)]
+pub struct GetNodesParams {
+    pub ids: Vec<String>,
+    pub with_props: bool,
+
+    /// Get nodes even if they're archived.
+    pub include_archived: bool,
+
+    /// Fail if any of the nodes don't exist (rather than leaving them out).
+    pub strict: bool,
+}
+
+/// Get a set of nodes by ID, in batched queries.
+///
+/// The nodes are returned in the order their IDs were given, without
+/// duplicates. Missing (or archived) nodes are left out, unless
+/// `strict` is set, in which case they're reported in an error.
+pub async fn get_nodes(conn: &Connection, params: &GetNodesParams) -> Result<Vec<DbNode>> {
+    // Look the nodes up, a batch at a time...
+    let mut found = HashMap::new();
+    for batch in params.ids.chunks(GET_NODES_BATCH_SIZE) {
+        let query = format!(
+            "
+            SELECT id, labels, created_at, updated_at
+            FROM nodes
+            WHERE id IN ({}) AND (? OR archived_at IS NULL);
+            ",
+            vec!["?"; batch.len()].join(", "),
+        );
+        let mut values: Vec<libsql::Value> = batch.iter().map(|id| id.clone().into()).collect();
+        values.push(params.include_archived.into());
+        let mut rows = conn
+            .prepare(&query)
+            .await?
+            .query(libsql::params_from_iter(values))
+            .await?;
+        while let Some(row) = rows.next().await? {
+            let node = node_from_row(&row)?;
+            found.insert(node.id.clone(), node);
+        }
+    }
+
+    // Any missing?
+    if params.strict {
+        let mut missing: Vec<&str> = Vec::new();
+        for id in params.ids.iter() {
+            if !found.contains_key(id) && !missing.contains(&id.as_str()) {
+                missing.push(id);
+            }
+        }
+        if !missing.is_empty() {
+            return Err(error::not_found(format!(
+                "{} node(s) don't exist: {}",
+                missing.len(),
+                missing.join(", ")
+            )));
+        }
+    }
+
+    // Put them in order, adding the props...
+    let mut nodes = Vec::with_capacity(found.len());
+    for id in params.ids.iter() {
+        if let Some(mut node) = found.remove(id) {
+            if params.with_props {
+                node.props = Some(get_node_props(conn, &node.id).await?);
+            }
+            nodes.push(node);
+        }
+    }
+    Ok(nodes)
+}
+
+/// The default cap on the number of nodes a traversal will collect.
+pub const DEFAULT_MAX_RESULTS: usize = 10_000;
+
+/// Get the IDs of all nodes that can be reached from the given node by
+/// following directed edges forwards (from `from_node` to `to_node`).
+///
+/// Undirected edges are not followed, and only edges matching `filter`
+/// (e.g. of a certain type) are. Fails if more than `max_results` nodes
+/// are reachable.
+pub async fn get_node_descendants(
+    conn: &Connection,
+    node_id: &str,
+    filter: &EdgeFilter,
+    max_results: Option<usize>,
+) -> Result<Vec<String>> {
+    get_reachable_nodes(conn, node_id, filter, max_results, Walk::Forwards).await
+}
+
+/// Get the IDs of all nodes that can reach the given node by following
+/// directed edges forwards (i.e. this follows directed edges in reverse).
+///
+/// Undirected edges are not followed, and only edges matching `filter`
+/// (e.g. of a certain type) are. Fails if more than `max_results` nodes
+/// can reach it.
+pub async fn get_node_ancestors(
+    conn: &Connection,
+    node_id: &str,
+    filter: &EdgeFilter,
+    max_results: Option<usize>,
+) -> Result<Vec<String>> {
+    get_reachable_nodes(conn, node_id, filter, max_results, Walk::Backwards).await
+}
+
+/// Get the IDs of all nodes reachable from the given node.
+///
+/// This follows directed edges forwards (like `get_node_descendants`)
+/// or, if `undirected`, follows every edge both ways -- giving the rest
+/// of the node's connected component. Only edges matching `filter` are
+/// followed. Fails if more than `max_results` nodes are reachable.
+pub async fn get_reachable_from(
+    conn: &Connection,
+    node_id: &str,
+    undirected: bool,
+    filter: &EdgeFilter,
+    max_results: Option<usize>,
+) -> Result<Vec<String>> {
+    let walk = if undirected {
+        Walk::Undirected
+    } else {
+        Walk::Forwards
+    };
+    get_reachable_nodes(conn, node_id, filter, max_results, walk).await
+}
+
+/// Which edges a traversal follows, and which way.
+#[derive(Debug, Clone, Copy)]
+enum Walk {
+    /// Directed edges, from `from_node` to `to_node`.
+    Forwards,
+
+    /// Directed edges, from `to_node` to `from_node`.
+    Backwards,
+
+    /// All edges (directed or not), either way.
+    Undirected,
+}
+
+/// Walk the edges matching `filter` from `node_id` using a recursive CTE.
+///
+/// The CTE uses `UNION` (rather than `UNION ALL`) so each node is only
+/// visited once, which also guarantees termination if the graph has a cycle.
+/// With `max_results`, the CTE's `LIMIT` stops the walk just past the cap
+/// (rather than collecting the whole set first), and an error is returned.
+async fn get_reachable_nodes(
+    conn: &Connection,
+    node_id: &str,
+    filter: &EdgeFilter,
+    max_results: Option<usize>,
+    walk: Walk,
+) -> Result<Vec<String>> {
+    // Build the query...
+    // Note - The filter's condition references the `edges` table by name...
+    let step = match walk {
+        Walk::Forwards => {
+            "SELECT edges.to_node
+            FROM edges
+            JOIN reachable r ON edges.from_node = r.id
+            WHERE edges.directed"
+        }
+        Walk::Backwards => {
+            "SELECT edges.from_node
+            FROM edges
+            JOIN reachable r ON edges.to_node = r.id
+            WHERE edges.directed"
+        }
+        Walk::Undirected => {
+            "SELECT CASE WHEN edges.from_node = r.id THEN edges.to_node ELSE edges.from_node END
+            FROM edges
+            JOIN reachable r ON edges.from_node = r.id OR edges.to_node = r.id
+            WHERE 1"
+        }
+    };
+    let (cond, filter_params) = filter.to_sql();
+    let query = format!(
+        "
+        WITH RECURSIVE reachable(id) AS (
+            SELECT ?
+            UNION
+            {step} AND {cond}
+            LIMIT ?
+        )
+        SELECT id
+        FROM reachable
+        WHERE id != ?
+        ORDER BY id;
+        ",
+        step = step,
+        cond = cond,
+    );
+
+    // Run it...
+    // Note - The limit counts the start node, plus one more to tell
+    // whether the cap was exceeded (a negative limit means no limit)...
+    let limit = max_results.map_or(-1, |m| m.saturating_add(2) as i64);
+    let mut sql_params = vec![libsql::Value::Text(node_id.to_string())];
+    sql_params.extend(filter_params);
+    sql_params.push(libsql::Value::Integer(limit));
+    sql_params.push(libsql::Value::Text(node_id.to_string()));
+    let mut rows = conn
+        .prepare(&query)
+        .await?
+        .query(libsql::params_from_iter(sql_params))
+        .await?;
+
+    // Collect the IDs...
+    let mut out = Vec::new();
+    while let Some(row) = rows.next().await? {
+        let id: String = row.get(0)?;
+        out.push(id);
+    }
+    if let Some(max) = max_results.filter(|m| out.len() > *m) {
+        return Err(error::validation(format!(
+            "Result set too large (over {} nodes). Increase `--max-results` or narrow the query \
+            (e.g. with `--edge-type`).",
+            max
+        )));
+    }
+
+    // Return the data!
+    Ok(out)
+}
+
+/// Get the nodes adjacent to a node, as `(neighbor_id, edge_id, edge_type)`.
+///
+/// Undirected edges can be traversed in both directions. Directed
+/// edges are only traversed forwards if `respect_direction` is set,
+/// otherwise they're also treated as traversable in both directions.
+/// Only edges matching `filter` are considered.
+pub async fn get_adjacent_nodes(
+    conn: &Connection,
+    node_id: &str,
+    respect_direction: bool,
+    filter: &EdgeFilter,
+) -> Result<Vec<(String, String, String)>> {
+    // Note - The neighbor is whichever end of the edge isn't the node...
+    let (cond, filter_params) = filter.to_sql();
+    let query = format!(
+        "
+        SELECT
+            CASE WHEN from_node = ? THEN to_node ELSE from_node END,
+            id,
+            edge_type
+        FROM edges
+        WHERE (from_node = ? OR (to_node = ? AND (NOT ? OR NOT directed)))
+            AND {}
+        ORDER BY id;
+        ",
+        cond,
+    );
+    let mut sql_params = vec![
+        libsql::Value::Text(node_id.to_string()),
+        libsql::Value::Text(node_id.to_string()),
+        libsql::Value::Text(node_id.to_string()),
+        libsql::Value::Integer(respect_direction as i64),
+    ];
+    sql_params.extend(filter_params);
+    let mut rows = conn
+        .prepare(&query)
+        .await?
+        .query(libsql::params_from_iter(sql_params))
+        .await?;
+
+    // Collect the neighbors...
+    let mut out = Vec::new();
+    while let Some(row) = rows.next().await? {
+        out.push((row.get(0)?, row.get(1)?, row.get(2)?));
+    }
+    Ok(out)
+}
+
+/// Changes to apply to a single node.
+#[derive(Debug, Default)]
+pub struct UpdateNodeParams {
+    pub id: String,
+
+    /// Labels to add (if the node doesn't already have them).
+    pub add_labels: Vec<String>,
+
+    /// Labels to remove.
+    pub remove_labels: Vec<String>,
+
+    /// Props to set (inserting or replacing them).
+    pub set_props: HashMap<String, Value>,
+
+    /// Keys of props to remove.
+    pub remove_props: Vec<String>,
+
+    /// The maximum size of a prop value, in bytes.
+    /// Defaults to `DEFAULT_MAX_PROP_BYTES`.
+    pub max_prop_bytes: Option<usize>,
+
+    /// Compress prop values bigger than this many bytes.
+    /// If not set, values aren't compressed.
+    pub compress_props_over: Option<usize>,
+
+    /// How the labels are cased when stored.
+    pub label_case: CasePolicy,
+}
+
+/// Update a node, in a single transaction, returning the updated node.
+pub async fn update_node(conn: &Connection, params: &UpdateNodeParams) -> Result<DbNode> {
+    check_prop_sizes(&params.set_props, params.max_prop_bytes)?;
+
+    let tx = begin_write(conn).await?;
+    let now = Utc::now();
+
+    // Get the node's current labels...
+    let node = get_node(
+        &tx,
+        &GetNodeParams {
+            id: params.id.clone(),
+            ..Default::default()
+        },
+    )
+    .await?;
+
+    // Merge in the label changes...
+    // Note - The existing labels go through the policy too, so any
+    // duplicates already stored are cleaned up...
+    let remove = dedupe_labels(&params.remove_labels, params.label_case);
+    let labels: Vec<String> = dedupe_labels(
+        &[node.labels, params.add_labels.clone()].concat(),
+        params.label_case,
+    )
+    .into_iter()
+    .filter(|l| !remove.contains(l))
+    .collect();
+
+    // Update the node's fields...
+    tx.execute(
+        "UPDATE nodes SET labels = ?, updated_at = ? WHERE id = ?;",
+        libsql::params![
+            serde_json::to_string(&labels)?,
+            now.to_rfc3339(),
+            params.id.as_str(),
+        ],
+    )
+    .await?;
+
+    // Update the props...
+    for (key, value) in params.set_props.iter() {
+        set_node_prop(
+            &tx,
+            &params.id,
+            key,
+            value,
+            &now,
+            params.compress_props_over,
+        )
+        .await?;
+    }
+    for key in params.remove_props.iter() {
+        delete_node_prop(&tx, &params.id, key).await?;
+    }
+
+    // Get the updated node...
+    let node = get_node(
+        &tx,
+        &GetNodeParams {
+            id: params.id.clone(),
+            with_props: true,
+            ..Default::default()
+        },
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(node)
+}
+
+/// The result of replacing a node.
+#[derive(Debug, Serialize)]
+pub struct ReplacedNode {
+    /// The ID of the node that was replaced (and deleted).
+    pub replaced: String,
+
+    /// The new node.
+    pub node: DbNode,
+
+    /// The number of edges moved to the new node.
+    pub edges_moved: usize,
+}
+
+/// Replace a node with a new one, in a single transaction.
+///
+/// The new node is created from `params`, every edge that references
+/// the old node is repointed at the new one (keeping the edges' IDs and
+/// props), and the old node is deleted.
+pub async fn replace_node(
+    conn: &Connection,
+    old_id: &str,
+    params: &CreateNodeParams,
+) -> Result<ReplacedNode> {
+    // Check the new node...
+    if params.require_label && params.labels.iter().all(|l| l.trim().is_empty()) {
+        return Err(error::validation(
+            "Nodes must have at least one label. Add one with `-l <LABEL>`.",
+        ));
+    }
+    check_prop_sizes(&params.props, params.max_prop_bytes)?;
+    let id = match &params.id {
+        Some(id) if id.trim().is_empty() => {
+            return Err(error::validation("Node ID can't be empty."));
+        }
+        Some(id) => id.trim().to_string(),
+        None => util::new_id("n"),
+    };
+    let now = Utc::now();
+
+    let tx = begin_write(conn).await?;
+
+    // Make sure the old node exists (and isn't archived)...
+    get_node(
+        &tx,
+        &GetNodeParams {
+            id: old_id.to_string(),
+            ..Default::default()
+        },
+    )
+    .await?;
+    if check_node_exists(&tx, &id).await? {
+        return Err(error::validation(format!(
+            "A node with ID '{}' already exists.",
+            id
+        )));
+    }
+
+    // Create the new node, with its labels' defaults...
+    let labels = dedupe_labels(&params.labels, params.label_case);
+    let props = with_label_defaults(&tx, &labels, &params.props, params.max_prop_bytes).await?;
+    let node = DbNode {
+        id,
+        labels,
+        created_at: now,
+        updated_at: now,
+        props: Some(props),
+    };
+    insert_node_rows(&tx, &node, params.compress_props_over).await?;
+
+    // Repoint the edges...
+    // Note - A self-loop is caught by both statements, but only counted once...
+    let edges_moved = tx
+        .execute(
+            "
+            UPDATE edges SET updated_at = ?1
+            WHERE from_node = ?2 OR to_node = ?2;
+            ",
+            libsql::params![now.to_rfc3339(), old_id],
+        )
+        .await? as usize;
+    tx.execute(
+        "UPDATE edges SET from_node = ? WHERE from_node = ?;",
+        libsql::params![node.id.as_str(), old_id],
+    )
+    .await?;
+    tx.execute(
+        "UPDATE edges SET to_node = ? WHERE to_node = ?;",
+        libsql::params![node.id.as_str(), old_id],
+    )
+    .await?;
+
+    // Delete the old node (which no longer has any edges)...
+    delete_node_rows(&tx, old_id).await?;
+
+    tx.commit().await?;
+    Ok(ReplacedNode {
+        replaced: old_id.to_string(),
+        node,
+        edges_moved,
+    })
+}
+
+/// Set (insert or replace) a single prop on a node.
+///
+/// Note - This doesn't update the node's `updated_at` timestamp.
+pub async fn set_node_prop(
+    conn: &Connection,
+    node_id: &str,
+    key: &str,
+    value: &Value,
+    now: &DateTime<Utc>,
+    compress_props_over: Option<usize>,
+) -> Result<()> {
+    let sql_value = encode_prop_value(value, compress_props_over)?;
+    conn.execute(
+        "
+        INSERT INTO node_props (node_id, key, value, created_at, updated_at)
+        VALUES (?1, ?2, ?3, ?4, ?4)
+        ON CONFLICT (node_id, key) DO UPDATE
+        SET value = excluded.value, updated_at = excluded.updated_at;
+        ",
+        libsql::params![node_id, node_prop_key(key), sql_value, now.to_rfc3339()],
+    )
+    .await?;
+    Ok(())
+}
+
+/// Changes to apply to the props of a set of nodes.
+#[derive(Debug, Default)]
+pub struct NodePropChanges {
+    /// Props to set (inserting or replacing them).
+    pub set_props: HashMap<String, Value>,
+
+    /// Keys of props to remove.
+    pub remove_props: Vec<String>,
+
+    /// Cancels the operation, rolling back any nodes updated so far.
+    pub cancel: CancellationToken,
+
+    /// The maximum size of a prop value, in bytes.
+    /// Defaults to `DEFAULT_MAX_PROP_BYTES`.
+    pub max_prop_bytes: Option<usize>,
+
+    /// Compress prop values bigger than this many bytes.
+    /// If not set, values aren't compressed.
+    pub compress_props_over: Option<usize>,
+}
+
+/// Apply prop changes to every node matching the filter, in a
+/// single transaction.
+///
+/// Returns the number of nodes updated.
+pub async fn update_nodes_by_filter(
+    conn: &Connection,
+    filter: &NodeFilter,
+    changes: &NodePropChanges,
+) -> Result<usize> {
+    // Check the prop sizes...
+    check_prop_sizes(&changes.set_props, changes.max_prop_bytes)?;
+
+    let tx = begin_write(conn).await?;
+    let now = Utc::now();
+
+    // Find the nodes to update...
+    let ids = find_node_ids(&tx, filter).await?;
+
+    // Update them...
+    for id in ids.iter() {
+        for (key, value) in changes.set_props.iter() {
+            set_node_prop(&tx, id, key, value, &now, changes.compress_props_over).await?;
+        }
+        for key in changes.remove_props.iter() {
+            delete_node_prop(&tx, id, key).await?;
+        }
+        tx.execute(
+            "UPDATE nodes SET updated_at = ? WHERE id = ?;",
+            libsql::params![now.to_rfc3339(), id.as_str()],
+        )
+        .await?;
+        check_cancelled(&changes.cancel)?;
+    }
+
+    tx.commit().await?;
+    Ok(ids.len())
+}
+
+/// Changes to apply to a single edge.
+#[derive(Debug, Default)]
+pub struct UpdateEdgeParams {
+    pub id: String,
+
+    /// Set the edge's type.
+    pub edge_type: Option<String>,
+
+    /// Set the edge's source node.
+    pub from_node: Option<String>,
+
+    /// Set the edge's target node.
+    pub to_node: Option<String>,
+
+    /// Set whether the edge is directed.
+    pub directed: Option<bool>,
+
+    /// Exchange the edge's source and target nodes.
+    /// Can't be combined with `from_node` or `to_node`.
+    pub swap: bool,
+
+    /// Props to set (inserting or replacing them).
+    pub set_props: HashMap<String, Value>,
+
+    /// Keys of props to remove.
+    pub remove_props: Vec<String>,
+
+    /// The maximum size of a prop value, in bytes.
+    /// Defaults to `DEFAULT_MAX_PROP_BYTES`.
+    pub max_prop_bytes: Option<usize>,
+
+    /// Compress prop values bigger than this many bytes.
+    /// If not set, values aren't compressed.
+    pub compress_props_over: Option<usize>,
+
+    /// How the edge type is cased when stored.
+    pub edge_type_case: CasePolicy,
+}
+
+/// Update an edge, in a single transaction, returning the updated edge.
+pub async fn update_edge(conn: &Connection, params: &UpdateEdgeParams) -> Result<DbEdge> {
+    // Check the changes...
+    if params.swap && (params.from_node.is_some() || params.to_node.is_some()) {
+        return Err(error::validation(
+            "`--swap` can't be combined with `--from-node` or `--to-node`.",
+        ));
+    }
+    if params
+        .edge_type
+        .as_ref()
+        .is_some_and(|t| t.trim().is_empty())
+    {
+        return Err(error::validation("Edge type can't be empty."));
+    }
+    check_prop_sizes(&params.set_props, params.max_prop_bytes)?;
+
+    let tx = begin_write(conn).await?;
+    let now = Utc::now();
+
+    // Make sure the edge (and any new endpoints) exist...
+    if !check_edge_exists(&tx, &params.id).await? {
+        return Err(error::not_found(format!(
+            "Edge '{}' does not exist.",
+            params.id
+        )));
+    }
+    for node_id in [&params.from_node, &params.to_node].into_iter().flatten() {
+        if !check_node_exists(&tx, node_id).await? {
+            return Err(error::not_found(format!(
+                "Node '{}' does not exist.",
+                node_id
+            )));
+        }
+    }
+
+    // Swap the endpoints?
+    // Note - This is a single statement, since the right-hand sides
+    // are evaluated against the row as it was before the update...
+    if params.swap {
+        tx.execute(
+            "UPDATE edges SET from_node = to_node, to_node = from_node WHERE id = ?;",
+            libsql::params![params.id.as_str()],
+        )
+        .await?;
+    }
+
+    // Update the edge's fields...
+    tx.execute(
+        "
+        UPDATE edges SET
+            edge_type = COALESCE(?1, edge_type),
+            from_node = COALESCE(?2, from_node),
+            to_node = COALESCE(?3, to_node),
+            directed = COALESCE(?4, directed),
+            updated_at = ?5
+        WHERE id = ?6;
+        ",
+        libsql::params![
+            params
+                .edge_type
+                .as_deref()
+                .map(|t| params.edge_type_case.apply(t.trim())),
+            params.from_node.clone(),
+            params.to_node.clone(),
+            params.directed,
+            now.to_rfc3339(),
+            params.id.as_str(),
+        ],
+    )
+    .await?;
+
+    // Update the props...
+    for (key, value) in params.set_props.iter() {
+        set_edge_prop(
+            &tx,
+            &params.id,
+            key,
+            value,
+            &now,
+            params.compress_props_over,
+        )
+        .await?;
+    }
+    for key in params.remove_props.iter() {
+        delete_edge_prop(&tx, &params.id, key).await?;
+    }
+
+    // Get the updated edge...
+    let edge = get_edge(
+        &tx,
+        &GetEdgeParams {
+            id: params.id.clone(),
+            with_props: true,
+            include_archived: true,
+        },
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(edge)
+}
+
+/// Set (insert or replace) a single prop on an edge.
+///
+/// Note - This doesn't update the edge's `updated_at` timestamp.
+pub async fn set_edge_prop(
+    conn: &Connection,
+    edge_id: &str,
+    key: &str,
+    value: &Value,
+    now: &DateTime<Utc>,
+    compress_props_over: Option<usize>,
+) -> Result<()> {
+    let sql_value = encode_prop_value(value, compress_props_over)?;
+    conn.execute(
+        "
+        INSERT INTO edge_props (edge_id, key, value, created_at, updated_at)
+        VALUES (?1, ?2, ?3, ?4, ?4)
+        ON CONFLICT (edge_id, key) DO UPDATE
+        SET value = excluded.value, updated_at = excluded.updated_at;
+        ",
+        libsql::params![edge_id, edge_prop_key(key), sql_value, now.to_rfc3339()],
+    )
+    .await?;
+    Ok(())
+}
+
+/// Count the nodes matching the filter.
+pub async fn count_nodes(conn: &Connection, filter: &NodeFilter) -> Result<usize> {
+    let (cond, params) = filter.to_sql();
+    let query = format!("SELECT COUNT(*) FROM nodes WHERE {};", cond);
+    count_rows(conn, &query, params).await
+}
+
+/// Count the edges matching the filter.
+pub async fn count_edges(conn: &Connection, filter: &EdgeFilter) -> Result<usize> {
+    let (cond, params) = filter.to_sql();
+    let query = format!("SELECT COUNT(*) FROM edges WHERE {};", cond);
+    count_rows(conn, &query, params).await
+}
+
+/// Run a `SELECT COUNT(*) ...` query.
+async fn count_rows(conn: &Connection, query: &str, params: Vec<libsql::Value>) -> Result<usize> {
+    let mut rows = conn
+        .prepare(query)
+        .await?
+        .query(libsql::params_from_iter(params))
+        .await?;
+    let count: i64 = match rows.next().await? {
+        Some(row) => row.get(0)?,
+        None => 0,
+    };
+    Ok(count as usize)
+}
+
+/// A range of node degrees, and how many nodes fall in it.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct DegreeBucket {
+    pub min: usize,
+    pub max: usize,
+    pub nodes: usize,
+}
+
+/// Derived metrics describing how connected the graph is.
+#[derive(Debug, Serialize)]
+pub struct GraphDensity {
+    pub nodes: usize,
+    pub edges: usize,
+
+    /// The fraction of ordered node pairs connected by an edge, where
+    /// an undirected edge connects both ways. `None` with fewer than
+    /// two nodes. Parallel edges and self-loops can push it over 1.
+    pub density: Option<f64>,
+
+    /// The mean number of edges per node. `None` with no nodes.
+    pub average_degree: Option<f64>,
+
+    /// The number of nodes per degree range (`0`, `1`, `2-3`, `4-7`, ...).
+    pub degree_distribution: Vec<DegreeBucket>,
+}
+
+/// Get the degree bucket a degree falls into -- `0`, `1`, then
+/// powers of two (`2-3`, `4-7`, ...).
+fn degree_bucket(degree: usize) -> (usize, usize) {
+    match degree {
+        0 => (0, 0),
+        d => {
+            let min = 1 << d.ilog2();
+            (min, min * 2 - 1)
+        }
+    }
+}
+
+/// Compute the graph's density, average degree, and degree distribution.
+///
+/// Degrees are counted the same way as the `--min-degree`/`--max-degree`
+/// filters. Archived nodes and edges are ignored.
+pub async fn compute_density(conn: &Connection) -> Result<GraphDensity> {
+    // Count the edges...
+    let row = conn
+        .prepare(
+            "
+            SELECT COUNT(*), COALESCE(SUM(directed), 0)
+            FROM edges
+            WHERE archived_at IS NULL;
+            ",
+        )
+        .await?
+        .query_row(())
+        .await?;
+    let edges: i64 = row.get(0)?;
+    let directed: i64 = row.get(1)?;
+    let undirected = edges - directed;
+
+    // Count the nodes by degree...
+    let mut rows = conn
+        .query(
+            "
+            SELECT degree, COUNT(*) FROM (
+                SELECT COUNT(e.id) AS degree
+                FROM nodes n
+                LEFT JOIN edges e
+                    ON (e.from_node = n.id OR e.to_node = n.id) AND e.archived_at IS NULL
+                WHERE n.archived_at IS NULL
+                GROUP BY n.id
+            )
+            GROUP BY degree
+            ORDER BY degree;
+            ",
+            (),
+        )
+        .await?;
+    let mut nodes = 0;
+    let mut total_degree = 0;
+    let mut buckets: Vec<DegreeBucket> = Vec::new();
+    while let Some(row) = rows.next().await? {
+        let degree = row.get::<i64>(0)? as usize;
+        let count = row.get::<i64>(1)? as usize;
+        nodes += count;
+        total_degree += degree * count;
+
+        // Add it to its bucket, filling in any empty buckets before it...
+        let (min, max) = degree_bucket(degree);
+        while buckets.last().is_none_or(|b| b.max < min) {
+            let next = buckets.last().map_or(0, |b| b.max + 1);
+            let (min, max) = degree_bucket(next);
+            buckets.push(DegreeBucket { min, max, nodes: 0 });
+        }
+        if let Some(b) = buckets.last_mut() {
+            debug_assert_eq!((b.min, b.max), (min, max));
+            b.nodes += count;
+        }
+    }
+
+    // Work out the metrics...
+    let pairs = nodes.saturating_sub(1) * nodes;
+    let density = (pairs > 0).then(|| (directed + 2 * undirected) as f64 / pairs as f64);
+    let average_degree = (nodes > 0).then(|| total_degree as f64 / nodes as f64);
+
+    Ok(GraphDensity {
+        nodes,
+        edges: edges as usize,
+        density,
+        average_degree,
+        degree_distribution: buckets,
+    })
+}
+
+/// Get the IDs of all nodes matching the filter.
+pub async fn find_node_ids(conn: &Connection, filter: &NodeFilter) -> Result<Vec<String>> {
+    // Build the query...
+    let (cond, params) = filter.to_sql();
+    let query = format!("SELECT id FROM nodes WHERE {};", cond);
+
+    // Run it...
+    let mut rows = conn
+        .prepare(&query)
+        .await?
+        .query(libsql::params_from_iter(params))
+        .await?;
+
+    // Collect the IDs...
+    let mut out = Vec::new();
+    while let Some(row) = rows.next().await? {
+        let id: String = row.get(0)?;
+        out.push(id);
+    }
+    Ok(out)
+}
+
+/// Find the ID of the single node matching a selector -- a set of
+/// prop key-value pairs and an optional label.
+///
+/// Errors if no nodes match or if more than one node matches.
+pub async fn find_node_by_selector(
+    conn: &Connection,
+    props: &[(String, Value)],
+    label: Option<&str>,
+) -> Result<String> {
+    // Build the filter...
+    let filter = NodeFilter {
+        has_label: label.map(|l| l.to_string()),
+        props: props.iter().cloned().collect(),
+        ..Default::default()
+    };
+
+    // Find the matching nodes...
+    let mut ids = find_node_ids(conn, &filter).await?;
+    ids.sort();
+
+    // There should be exactly one...
+    match ids.len() {
+        0 => Err(error::not_found("No node matches the selector.")),
+        1 => Ok(ids.remove(0)),
+        n => {
+            let more = if n > MAX_ID_CANDIDATES { "\n  ..." } else { "" };
+            ids.truncate(MAX_ID_CANDIDATES);
+            Err(error::validation(format!(
+                "Selector is ambiguous. It matches {} nodes:\n  {}{}",
+                n,
+                ids.join("\n  "),
+                more,
+            )))
+        }
+    }
+}
+
+/// Delete a node's rows -- its props, its edges (and their props),
+/// and the node itself.
+///
+/// This is done explicitly rather than relying on `ON DELETE CASCADE`,
+/// since foreign keys may not be enforced on the connection.
+async fn delete_node_rows(conn: &Connection, id: &str) -> Result<()> {
+    conn.execute(
+        "
+        DELETE FROM edge_props
+        WHERE edge_id IN (
+            SELECT id FROM edges WHERE from_node = ?1 OR to_node = ?1
+        );
+        ",
+        libsql::params![id],
+    )
+    .await?;
+    conn.execute(
+        "DELETE FROM edges WHERE from_node = ?1 OR to_node = ?1;",
+        libsql::params![id],
+    )
+    .await?;
+    conn.execute(
+        "DELETE FROM node_props WHERE node_id = ?;",
+        libsql::params![id],
+    )
+    .await?;
+    conn.execute("DELETE FROM nodes WHERE id = ?;", libsql::params![id])
+        .await?;
+    Ok(())
+}
+
+/// Delete a node, along with its props and edges.
+pub async fn delete_node(conn: &Connection, id: &str) -> Result<()> {
+    // Make sure it exists...
+    if !check_node_exists(conn, id).await? {
+        return Err(error::not_found("Node does not exist."));
+    }
+
+    // Delete it...
+    let tx = begin_write(conn).await?;
+    delete_node_rows(&tx, id).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Delete all nodes matching the filter (along with their props
+/// and edges) in a single transaction.
+///
+/// Returns the number of nodes deleted.
+pub async fn delete_nodes_by_filter(conn: &Connection, filter: &NodeFilter) -> Result<usize> {
+    let tx = begin_write(conn).await?;
+
+    // Find the matching nodes up-front, since deleting edges
+    // can change which nodes match (e.g. `orphans`)...
+    let ids = find_node_ids(&tx, filter).await?;
+
+    // Delete them...
+    for id in ids.iter() {
+        delete_node_rows(&tx, id).await?;
+    }
+
+    tx.commit().await?;
+    Ok(ids.len())
+}
+
+/// Remove a single prop from a node (if it's set).
+///
+/// Note - This doesn't update the node's `updated_at` timestamp.
+pub async fn delete_node_prop(conn: &Connection, node_id: &str, key: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM node_props WHERE node_id = ? AND key = ?;",
+        libsql::params![node_id, node_prop_key(key)],
+    )
+    .await?;
+    Ok(())
+}
+
+/// Get the IDs of all edges matching the filter.
+pub async fn find_edge_ids(conn: &Connection, filter: &EdgeFilter) -> Result<Vec<String>> {
+    // Build the query...
+    let (cond, params) = filter.to_sql();
+    let query = format!("SELECT id FROM edges WHERE {};", cond);
+
+    // Run it...
+    let mut rows = conn
+        .prepare(&query)
+        .await?
+        .query(libsql::params_from_iter(params))
+        .await?;
+
+    // Collect the IDs...
+    let mut out = Vec::new();
+    while let Some(row) = rows.next().await? {
+        let id: String = row.get(0)?;
+        out.push(id);
+    }
+    Ok(out)
+}
+
+/// Delete an edge's rows -- its props and the edge itself.
+async fn delete_edge_rows(conn: &Connection, id: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM edge_props WHERE edge_id = ?;",
+        libsql::params![id],
+    )
+    .await?;
+    conn.execute("DELETE FROM edges WHERE id = ?;", libsql::params![id])
+        .await?;
+    Ok(())
+}
+
+/// Delete an edge, along with its props.
+pub async fn delete_edge(conn: &Connection, id: &str) -> Result<()> {
+    // Make sure it exists...
+    if !check_edge_exists(conn, id).await? {
+        return Err(error::not_found("Edge does not exist."));
+    }
+
+    // Delete it...
+    let tx = begin_write(conn).await?;
+    delete_edge_rows(&tx, id).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Delete all edges matching the filter (along with their props)
+/// in a single transaction.
+///
+/// Returns the number of edges deleted.
+pub async fn delete_edges_by_filter(conn: &Connection, filter: &EdgeFilter) -> Result<usize> {
+    let tx = begin_write(conn).await?;
+    let ids = find_edge_ids(&tx, filter).await?;
+    for id in ids.iter() {
+        delete_edge_rows(&tx, id).await?;
+    }
+    tx.commit().await?;
+    Ok(ids.len())
+}
+
+/// Get when a node or edge was archived (or `None` if it isn't).
+///
+/// Returns a "not found" error if it doesn't exist.
+async fn get_archived_at(
+    conn: &Connection,
+    table: &str,
+    kind: &str,
+    id: &str,
+) -> Result<Option<String>> {
+    let mut rows = conn
+        .prepare(&format!("SELECT archived_at FROM {} WHERE id = ?;", table))
+        .await?
+        .query(libsql::params![id])
+        .await?;
+    match rows.next().await? {
+        Some(row) => match row.get_value(0)? {
+            libsql::Value::Text(at) => Ok(Some(at)),
+            _ => Ok(None),
+        },
+        None => Err(error::not_found(format!(
+            "{} '{}' does not exist.",
+            kind, id
+        ))),
+    }
+}
+
+/// Soft-delete a node, along with its edges.
+///
+/// The node (and its edges) are kept in the database, but are
+/// hidden from queries until they're unarchived.
+pub async fn archive_node(conn: &Connection, id: &str) -> Result<()> {
+    // Make sure it exists (and isn't already archived)...
+    if get_archived_at(conn, "nodes", "Node", id).await?.is_some() {
+        return Err(error::validation(format!(
+            "Node '{}' is already archived.",
+            id
+        )));
+    }
+
+    // Archive the node and its edges, with the same timestamp, so
+    // the edges can be restored along with it...
+    let now = Utc::now().to_rfc3339();
+    let tx = begin_write(conn).await?;
+    tx.execute(
+        "UPDATE nodes SET archived_at = ?1, updated_at = ?1 WHERE id = ?2;",
+        libsql::params![now.clone(), id],
+    )
+    .await?;
+    tx.execute(
+        "
+        UPDATE edges SET archived_at = ?1, updated_at = ?1
+        WHERE (from_node = ?2 OR to_node = ?2) AND archived_at IS NULL;
+        ",
+        libsql::params![now, id],
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Restore an archived node.
+///
+/// Edges that were archived along with the node are restored too
+/// (unless the node at their other end is still archived).
+pub async fn unarchive_node(conn: &Connection, id: &str) -> Result<()> {
+    // Make sure it exists (and is archived)...
+    let archived_at = match get_archived_at(conn, "nodes", "Node", id).await? {
+        Some(at) => at,
+        None => return Err(error::validation(format!("Node '{}' isn't archived.", id))),
+    };
+
+    // Restore the node and its edges...
+    let now = Utc::now().to_rfc3339();
+    let tx = begin_write(conn).await?;
+    tx.execute(
+        "UPDATE nodes SET archived_at = NULL, updated_at = ?1 WHERE id = ?2;",
+        libsql::params![now.clone(), id],
+    )
+    .await?;
+    tx.execute(
+        "
+        UPDATE edges SET archived_at = NULL, updated_at = ?3
+        WHERE (from_node = ?1 OR to_node = ?1)
+            AND archived_at = ?2
+            AND NOT EXISTS (
+                SELECT 1 FROM nodes n
+                WHERE n.id IN (edges.from_node, edges.to_node) AND n.archived_at IS NOT NULL
+            );
+        ",
+        libsql::params![id, archived_at, now],
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Soft-delete an edge.
+pub async fn archive_edge(conn: &Connection, id: &str) -> Result<()> {
+    // Make sure it exists (and isn't already archived)...
+    if get_archived_at(conn, "edges", "Edge", id).await?.is_some() {
+        return Err(error::validation(format!(
+            "Edge '{}' is already archived.",
+            id
+        )));
+    }
+
+    // Archive it...
+    conn.execute(
+        "UPDATE edges SET archived_at = ?1, updated_at = ?1 WHERE id = ?2;",
+        libsql::params![Utc::now().to_rfc3339(), id],
+    )
+    .await?;
+    Ok(())
+}
+
+/// Restore an archived edge.
+///
+/// Fails if either of the edge's nodes is still archived.
+pub async fn unarchive_edge(conn: &Connection, id: &str) -> Result<()> {
+    // Make sure it exists (and is archived)...
+    if get_archived_at(conn, "edges", "Edge", id).await?.is_none() {
+        return Err(error::validation(format!("Edge '{}' isn't archived.", id)));
+    }
+
+    // Make sure its nodes aren't archived...
+    let mut rows = conn
+        .prepare(
+            "
+            SELECT n.id FROM edges e
+            JOIN nodes n ON n.id IN (e.from_node, e.to_node)
+            WHERE e.id = ? AND n.archived_at IS NOT NULL;
+            ",
+        )
+        .await?
+        .query(libsql::params![id])
+        .await?;
+    if let Some(row) = rows.next().await? {
+        let node_id: String = row.get(0)?;
+        return Err(error::validation(format!(
+            "Edge '{}' connects to archived node '{}'. Unarchive the node first.",
+            id, node_id
+        )));
+    }
+
+    // Restore it...
+    conn.execute(
+        "UPDATE edges SET archived_at = NULL, updated_at = ? WHERE id = ?;",
+        libsql::params![Utc::now().to_rfc3339(), id],
+    )
+    .await?;
+    Ok(())
+}
+
+/// Find the IDs of edges where either endpoint doesn't exist.
+///
+/// These can be left behind if nodes were deleted while foreign
+/// keys weren't enforced.
+pub async fn find_dangling_edges(conn: &Connection) -> Result<Vec<String>> {
+    let filter = EdgeFilter {
+        dangling: true,
+        ..Default::default()
+    };
+    find_edge_ids(conn, &filter).await
+}
+
+/// Delete edges (and their props) where either endpoint doesn't exist.
+///
+/// Returns the number of edges deleted.
+pub async fn delete_dangling_edges(conn: &Connection) -> Result<usize> {
+    let filter = EdgeFilter {
+        dangling: true,
+        ..Default::default()
+    };
+    delete_edges_by_filter(conn, &filter).await
+}
+
+/// Remove a single prop from an edge (if it's set).
+///
+/// Note - This doesn't update the edge's `updated_at` timestamp.
+pub async fn delete_edge_prop(conn: &Connection, edge_id: &str, key: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM edge_props WHERE edge_id = ? AND key = ?;",
+        libsql::params![edge_id, edge_prop_key(key)],
+    )
+    .await?;
+    Ok(())
+}
+
+/// The `_meta` key holding the time of the last replica sync.
+const LAST_SYNC_AT_KEY: &str = "last_sync_at";
+
+/// The `_meta` key holding the replication frame index of the last replica sync.
+const LAST_SYNC_FRAME_KEY: &str = "last_sync_frame";
+
+/// The result of syncing a replica.
+#[derive(Debug, Serialize)]
+pub struct SyncResult {
+    /// The replication frame index after syncing (if any frames were applied).
+    pub frame_no: Option<u64>,
+
+    /// When this sync happened.
+    #[serde(serialize_with = "util::serialize_timestamp")]
+    pub synced_at: DateTime<Utc>,
+
+    /// When the previous sync happened (if there was one).
+    #[serde(serialize_with = "util::serialize_opt_timestamp")]
+    pub previous_sync_at: Option<DateTime<Utc>>,
+}
+
+/// Get the time of the last replica sync, as recorded in `_meta`.
+pub async fn get_last_sync_at(conn: &Connection) -> Result<Option<DateTime<Utc>>> {
+    match get_meta_txt(conn, LAST_SYNC_AT_KEY).await? {
+        Some(ts) => {
+            Ok(Some(ts.parse().with_context(|| {
+                format!("Invalid last sync timestamp \"{}\"", ts)
+            })?))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Sync a replica with its remote database, and record the
+/// sync time and frame index in `_meta`.
+///
+/// Note - The marker is written through the replica's connection, so
+/// it is shared by everyone using the same remote database.
+pub async fn sync_replica(db: &Database, conn: &Connection) -> Result<SyncResult> {
+    // Get the previous sync time...
+    let previous_sync_at = get_last_sync_at(conn).await?;
+
+    // Sync...
+    let frame_no = db.sync().await.context("Failed to sync the replica")?;
+    let synced_at = Utc::now();
+
+    // Record the sync...
+    set_meta(conn, LAST_SYNC_AT_KEY, Some(synced_at.to_rfc3339()), None).await?;
+    if let Some(f) = frame_no {
+        set_meta(conn, LAST_SYNC_FRAME_KEY, None, Some(f as i64)).await?;
+    }
+
+    Ok(SyncResult {
+        frame_no,
+        synced_at,
+        previous_sync_at,
+    })
+}
+
+/// The IDs of the nodes and edges that changed after some point in time.
+#[derive(Debug, Default, Serialize)]
+pub struct ChangedSince {
+    pub nodes: Vec<String>,
+    pub edges: Vec<String>,
+}
+
+/// Find the nodes and edges whose `updated_at` is after `since`.
+///
+/// This is based on the clocks of whichever machines wrote the data, so
+/// it's an approximation -- clock skew between writers can cause changes
+/// to be missed (or reported twice). Deletes aren't reported.
+pub async fn find_changed_since(conn: &Connection, since: &DateTime<Utc>) -> Result<ChangedSince> {
+    let since = since.to_rfc3339();
+    let mut out = ChangedSince::default();
+
+    // Note - `julianday` is used so timestamps with different
+    // UTC offsets are compared correctly...
+    for (table, ids) in [("nodes", &mut out.nodes), ("edges", &mut out.edges)] {
+        let query = format!(
+            "SELECT id FROM {} WHERE julianday(updated_at) > julianday(?) ORDER BY id;",
+            table,
+        );
+        let mut rows = conn
+            .prepare(&query)
+            .await?
+            .query(libsql::params![since.clone()])
+            .await?;
+        while let Some(row) = rows.next().await? {
+            ids.push(row.get(0)?);
+        }
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Create a fresh, migrated in-memory database connection.
+    async fn test_conn() -> Connection {
+        let mut config = Config::default();
+        config.db.db_type = DBType::Memory;
+        let (_db, conn) = open_and_migrate(&config, &OpenOptions::default())
+            .await
+            .unwrap();
+        conn
+    }
+
+    /// Create a node with the given labels and no props, returning its ID.
+    async fn add_node(conn: &Connection, labels: &[&str]) -> String {
+        let params = CreateNodeParams {
+            labels: labels.iter().map(|l| l.to_string()).collect(),
+            ..Default::default()
+        };
+        create_node(conn, &params).await.unwrap().id
+    }
+
+    /// Create an edge with no props, returning its ID.
+    async fn add_edge(
+        conn: &Connection,
+        edge_type: &str,
+        from_node: &str,
+        to_node: &str,
+        directed: bool,
+    ) -> String {
+        // Note - Self-loops are allowed, since some tests need them...
+        let params = CreateEdgeParams {
+            edge_type: edge_type.to_string(),
+            from_node: from_node.to_string(),
+            to_node: to_node.to_string(),
+            directed,
+            allow_self_loop: true,
+            ..Default::default()
+        };
+        create_edge(conn, &params).await.unwrap().id
+    }
+
+    /// Sort a list of IDs, for order-independent comparisons.
+    fn sorted(mut ids: Vec<String>) -> Vec<String> {
+        ids.sort();
+        ids
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_concurrent_writers_wait_for_lock() {
+        // Create a file-backed database (in-memory dbs can't be shared)...
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(DB_FILE_NAME);
+        let db = Builder::new_local(&path).build().await.unwrap();
+        let conn = db.connect().unwrap();
+        init_db(&conn).await.unwrap();
+
+        // Spawn two writers, each with their own database handle
+        // (as if they were separate graphctl processes)...
+        let mut handles = Vec::new();
+        for _ in 0..2 {
+            let path = path.clone();
+            handles.push(tokio::spawn(async move {
+                let db = Builder::new_local(&path).build().await.unwrap();
+                let conn = db.connect().unwrap();
+                configure_connection(&conn, DEFAULT_BUSY_TIMEOUT_MS)
+                    .await
+                    .unwrap();
+                for _ in 0..25 {
+                    create_node(
+                        &conn,
+                        &CreateNodeParams {
+                            labels: vec!["Writer".to_string()],
+                            ..Default::default()
+                        },
+                    )
+                    .await
+                    .unwrap();
+                }
+            }));
+        }
+        for h in handles {
+            h.await.unwrap();
+        }
+
+        // Both writers should have succeeded...
+        let nodes = list_nodes(&conn, &ListNodesParams::default())
+            .await
+            .unwrap();
+        assert_eq!(nodes.len(), 50);
+    }
+
+    #[tokio::test]
+    async fn test_get_node_descendants_and_ancestors() {
+        let conn = test_conn().await;
+
+        // a -> b -> c, a -> d, plus an undirected edge c -- e...
+        let a = add_node(&conn, &["Task"]).await;
+        let b = add_node(&conn, &["Task"]).await;
+        let c = add_node(&conn, &["Task"]).await;
+        let d = add_node(&conn, &["Task"]).await;
+        let e = add_node(&conn, &["Task"]).await;
+        add_edge(&conn, "DEPENDS_ON", &a, &b, true).await;
+        add_edge(&conn, "DEPENDS_ON", &b, &c, true).await;
+        add_edge(&conn, "BLOCKS", &a, &d, true).await;
+        add_edge(&conn, "RELATED", &c, &e, false).await;
+
+        let desc = get_node_descendants(&conn, &a, &EdgeFilter::default(), None)
+            .await
+            .unwrap();
+        assert_eq!(desc, sorted(vec![b.clone(), c.clone(), d.clone()]));
+
+        let filter = EdgeFilter {
+            edge_type: Some("DEPENDS_ON".to_string()),
+            ..Default::default()
+        };
+        let desc = get_node_descendants(&conn, &a, &filter, None)
+            .await
+            .unwrap();
+        assert_eq!(desc, sorted(vec![b.clone(), c.clone()]));
+
+        let anc = get_node_ancestors(&conn, &c, &EdgeFilter::default(), None)
+            .await
+            .unwrap();
+        assert_eq!(anc, sorted(vec![a.clone(), b.clone()]));
+
+        // Undirected edges aren't followed...
+        assert!(get_node_ancestors(&conn, &e, &EdgeFilter::default(), None)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_get_node_descendants_with_cycle() {
+        let conn = test_conn().await;
+
+        // a -> b -> c -> a...
+        let a = add_node(&conn, &[]).await;
+        let b = add_node(&conn, &[]).await;
+        let c = add_node(&conn, &[]).await;
+        add_edge(&conn, "NEXT", &a, &b, true).await;
+        add_edge(&conn, "NEXT", &b, &c, true).await;
+        add_edge(&conn, "NEXT", &c, &a, true).await;
+
+        let desc = get_node_descendants(&conn, &a, &EdgeFilter::default(), None)
+            .await
+            .unwrap();
+        assert_eq!(desc, sorted(vec![b, c]));
+    }
+
+    #[tokio::test]
+    async fn test_get_reachable_from() {
+        let conn = test_conn().await;
+
+        // A DAG: a -> b -> d, a -> c -> d, d -> e, plus x -> c and c -- y...
+        let a = add_node(&conn, &[]).await;
+        let b = add_node(&conn, &[]).await;
+        let c = add_node(&conn, &[]).await;
+        let d = add_node(&conn, &[]).await;
+        let e = add_node(&conn, &[]).await;
+        let x = add_node(&conn, &[]).await;
+        let y = add_node(&conn, &[]).await;
+        add_edge(&conn, "NEXT", &a, &b, true).await;
+        add_edge(&conn, "NEXT", &b, &d, true).await;
+        add_edge(&conn, "NEXT", &a, &c, true).await;
+        add_edge(&conn, "NEXT", &c, &d, true).await;
+        add_edge(&conn, "NEXT", &d, &e, true).await;
+        add_edge(&conn, "NEXT", &x, &c, true).await;
+        add_edge(&conn, "RELATED", &c, &y, false).await;
+
+        // Following directed edges, each node is counted once...
+        let ids = get_reachable_from(&conn, &a, false, &EdgeFilter::default(), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            ids,
+            sorted(vec![b.clone(), c.clone(), d.clone(), e.clone()])
+        );
+        let ids = get_reachable_from(&conn, &c, false, &EdgeFilter::default(), None)
+            .await
+            .unwrap();
+        assert_eq!(ids, sorted(vec![d.clone(), e.clone()]));
+        assert!(
+            get_reachable_from(&conn, &e, false, &EdgeFilter::default(), None)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        // ...and undirected, it's the rest of the component...
+        let ids = get_reachable_from(&conn, &e, true, &EdgeFilter::default(), None)
+            .await
+            .unwrap();
+        assert_eq!(ids, sorted(vec![a, b, c, d, x, y]));
+
+        // ...which is capped, like the other traversals...
+        let err = get_reachable_from(&conn, &e, true, &EdgeFilter::default(), Some(3))
+            .await
+            .unwrap_err();
+        assert_eq!(error::kind(&err), Some(ErrorKind::Validation));
+    }
+
+    #[tokio::test]
+    async fn test_get_reachable_from_with_cycle() {
+        let conn = test_conn().await;
+
+        // a -> b -> c -> a, c -> d, and a separate e <-> f...
+        let a = add_node(&conn, &[]).await;
+        let b = add_node(&conn, &[]).await;
+        let c = add_node(&conn, &[]).await;
+        let d = add_node(&conn, &[]).await;
+        let e = add_node(&conn, &[]).await;
+        let f = add_node(&conn, &[]).await;
+        add_edge(&conn, "NEXT", &a, &b, true).await;
+        add_edge(&conn, "NEXT", &b, &c, true).await;
+        add_edge(&conn, "NEXT", &c, &a, true).await;
+        add_edge(&conn, "NEXT", &c, &d, true).await;
+        add_edge(&conn, "NEXT", &e, &f, true).await;
+        add_edge(&conn, "NEXT", &f, &e, true).await;
+
+        // Both walks terminate, without counting the start node...
+        let ids = get_reachable_from(&conn, &b, false, &EdgeFilter::default(), None)
+            .await
+            .unwrap();
+        assert_eq!(ids, sorted(vec![a.clone(), c.clone(), d.clone()]));
+        let ids = get_reachable_from(&conn, &d, true, &EdgeFilter::default(), None)
+            .await
+            .unwrap();
+        assert_eq!(ids, sorted(vec![a, b, c]));
+        let ids = get_reachable_from(&conn, &e, true, &EdgeFilter::default(), None)
+            .await
+            .unwrap();
+        assert_eq!(ids, vec![f]);
+    }
+
+    #[tokio::test]
+    async fn test_traversal_edge_prop_filter() {
+        let conn = test_conn().await;
+
+        // Two routes from a to d: a -> b -> d over "active" edges, and
+        // a -> c -> d where a -> c is heavy but inactive...
+        let a = add_node(&conn, &[]).await;
+        let b = add_node(&conn, &[]).await;
+        let c = add_node(&conn, &[]).await;
+        let d = add_node(&conn, &[]).await;
+        let link = |from: &str, to: &str, props: Value| {
+            let params = CreateEdgeParams {
+                edge_type: "LINK".to_string(),
+                from_node: from.to_string(),
+                to_node: to.to_string(),
+                directed: true,
+                props: serde_json::from_value(props).unwrap(),
+                ..Default::default()
+            };
+            let conn = conn.clone();
+            async move { create_edge(&conn, &params).await.unwrap().id }
+        };
+        link(&a, &b, serde_json::json!({"status": "active", "weight": 2})).await;
+        link(&b, &d, serde_json::json!({"status": "active", "weight": 8})).await;
+        link(
+            &a,
+            &c,
+            serde_json::json!({"status": "inactive", "weight": 9}),
+        )
+        .await;
+        link(&c, &d, serde_json::json!({"status": "active", "weight": 6})).await;
+        let filter = |conds: &[&str]| EdgeFilter {
+            prop_conditions: conds
+                .iter()
+                .map(|c| util::parse_prop_condition(c).unwrap())
+                .collect(),
+            ..Default::default()
+        };
+
+        // Unfiltered, everything is reachable...
+        let desc = get_node_descendants(&conn, &a, &filter(&[]), None)
+            .await
+            .unwrap();
+        assert_eq!(desc, sorted(vec![b.clone(), c.clone(), d.clone()]));
+
+        // Over active edges, d is only reached through b...
+        let active = filter(&["status=active"]);
+        let desc = get_node_descendants(&conn, &a, &active, None)
+            .await
+            .unwrap();
+        assert_eq!(desc, sorted(vec![b.clone(), d.clone()]));
+        let anc = get_node_ancestors(&conn, &d, &active, None).await.unwrap();
+        assert_eq!(anc, sorted(vec![a.clone(), b.clone(), c.clone()]));
+
+        // Over heavy edges, it's only reached through c...
+        let heavy = filter(&["weight>5"]);
+        let desc = get_node_descendants(&conn, &a, &heavy, None).await.unwrap();
+        assert_eq!(desc, sorted(vec![c.clone(), d.clone()]));
+
+        // Both conditions together leave a with nowhere to go...
+        let both = filter(&["status=active", "weight>=6"]);
+        let ids = get_reachable_from(&conn, &a, false, &both, None)
+            .await
+            .unwrap();
+        assert!(ids.is_empty());
+        let ids = get_reachable_from(&conn, &d, true, &both, None)
+            .await
+            .unwrap();
+        assert_eq!(ids, sorted(vec![b.clone(), c.clone()]));
+
+        // The filter applies to adjacency and edge listings too...
+        let adj = get_adjacent_nodes(&conn, &a, true, &heavy).await.unwrap();
+        assert_eq!(
+            adj.into_iter().map(|(n, _, _)| n).collect::<Vec<_>>(),
+            vec![c.clone()]
+        );
+        let params = ListEdgesParams {
+            filter: EdgeFilter {
+                from_node: Some(a.clone()),
+                ..filter(&["status!=active"])
+            },
+            ..Default::default()
+        };
+        let edges = list_edges(&conn, &params).await.unwrap();
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0].to_node, c);
+    }
+
+    #[tokio::test]
+    async fn test_get_node_descendants_max_results() {
+        let conn = test_conn().await;
+
+        // A star -- a hub pointing at 5 leaves...
+        let hub = add_node(&conn, &[]).await;
+        for _ in 0..5 {
+            let leaf = add_node(&conn, &[]).await;
+            add_edge(&conn, "HAS", &hub, &leaf, true).await;
+        }
+
+        // At (or under) the cap is fine...
+        let desc = get_node_descendants(&conn, &hub, &EdgeFilter::default(), Some(5))
+            .await
+            .unwrap();
+        assert_eq!(desc.len(), 5);
+
+        // ...but over it trips the cap...
+        let err = get_node_descendants(&conn, &hub, &EdgeFilter::default(), Some(4))
+            .await
+            .unwrap_err();
+        assert_eq!(error::kind(&err), Some(ErrorKind::Validation));
+        assert!(err.to_string().contains("--max-results"));
+    }
+
+    #[tokio::test]
+    async fn test_list_nodes_orphans_and_connected() {
+        let conn = test_conn().await;
+
+        let a = add_node(&conn, &["Person"]).await;
+        let b = add_node(&conn, &["Person"]).await;
+        let c = add_node(&conn, &["Person"]).await;
+        let d = add_node(&conn, &["Place"]).await;
+        add_edge(&conn, "KNOWS", &a, &b, true).await;
+
+        let list = |filter: NodeFilter| {
+            let conn = &conn;
+            async move {
+                let params = ListNodesParams {
+                    filter,
+                    ..Default::default()
+                };
+                let nodes = list_nodes(conn, &params).await.unwrap();
+                sorted(nodes.into_iter().map(|n| n.id).collect())
+            }
+        };
+
+        let orphans = list(NodeFilter {
+            orphans: true,
+            ..Default::default()
+        })
+        .await;
+        assert_eq!(orphans, sorted(vec![c.clone(), d.clone()]));
+
+        let connected = list(NodeFilter {
+            connected: true,
+            ..Default::default()
+        })
+        .await;
+        assert_eq!(connected, sorted(vec![a, b]));
+
+        // Composes with the other filters...
+        let orphan_people = list(NodeFilter {
+            orphans: true,
+            has_label: Some("Person".to_string()),
+            ..Default::default()
+        })
+        .await;
+        assert_eq!(orphan_people, vec![c]);
+    }
+
+    #[tokio::test]
+    async fn test_list_nodes_exclude_labels() {
+        let conn = test_conn().await;
+
+        let a = add_node(&conn, &["Person"]).await;
+        let b = add_node(&conn, &["Person", "Internal"]).await;
+        let c = add_node(&conn, &["Internal"]).await;
+        let d = add_node(&conn, &["Place"]).await;
+        let e = add_node(&conn, &["Person", "System"]).await;
+
+        let list = |filter: NodeFilter| {
+            let conn = &conn;
+            async move {
+                let params = ListNodesParams {
+                    filter,
+                    ..Default::default()
+                };
+                let nodes = list_nodes(conn, &params).await.unwrap();
+                sorted(nodes.into_iter().map(|n| n.id).collect())
+            }
+        };
+
+        // Composes with `has_label`...
+        let people = list(NodeFilter {
+            has_label: Some("Person".to_string()),
+            exclude_labels: vec!["Internal".to_string()],
+            ..Default::default()
+        })
+        .await;
+        assert_eq!(people, sorted(vec![a.clone(), e]));
+
+        // Any of the labels excludes a node...
+        let rest = list(NodeFilter {
+            exclude_labels: vec!["Internal".to_string(), "System".to_string()],
+            ..Default::default()
+        })
+        .await;
+        assert_eq!(rest, sorted(vec![a, d]));
+        assert!(!rest.contains(&b) && !rest.contains(&c));
+    }
+
+    #[tokio::test]
+    async fn test_list_edges_valid_at() {
+        let conn = test_conn().await;
+        let a = add_node(&conn, &["Person"]).await;
+        let b = add_node(&conn, &["Company"]).await;
+
+        let ts = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+        let add = |valid_from: Option<&str>, valid_to: Option<&str>| {
+            let params = CreateEdgeParams {
+                edge_type: "WORKS_AT".to_string(),
+                from_node: a.clone(),
+                to_node: b.clone(),
+                directed: true,
+                valid_from: valid_from.map(ts),
+                valid_to: valid_to.map(ts),
+                ..Default::default()
+            };
+            let conn = &conn;
+            async move { create_edge(conn, &params).await.unwrap().id }
+        };
+        let old = add(Some("2010-01-01T00:00:00Z"), Some("2015-01-01T00:00:00Z")).await;
+        let current = add(Some("2015-01-01T00:00:00Z"), None).await;
+        let always = add(None, None).await;
+
+        let active_at = |at: &str| {
+            let conn = &conn;
+            let params = ListEdgesParams {
+                filter: EdgeFilter {
+                    valid_at: Some(ts(at)),
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            async move {
+                let edges = list_edges(conn, &params).await.unwrap();
+                sorted(edges.into_iter().map(|e| e.id).collect())
+            }
+        };
+
+        // Before, during, and after the old job...
+        assert_eq!(
+            active_at("2005-06-01T00:00:00Z").await,
+            vec![always.clone()]
+        );
+        assert_eq!(
+            active_at("2012-06-01T00:00:00Z").await,
+            sorted(vec![old.clone(), always.clone()]),
+        );
+        assert_eq!(
+            active_at("2020-06-01T00:00:00Z").await,
+            sorted(vec![current.clone(), always.clone()]),
+        );
+
+        // The range includes `valid_from` but not `valid_to`...
+        assert_eq!(
+            active_at("2015-01-01T00:00:00Z").await,
+            sorted(vec![current.clone(), always.clone()]),
+        );
+
+        // Offsets are compared as instants...
+        assert_eq!(
+            active_at("2014-12-31T20:00:00-05:00").await,
+            sorted(vec![current.clone(), always]),
+        );
+
+        // The range is read back...
+        let edge = get_edge(
+            &conn,
+            &GetEdgeParams {
+                id: old,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(edge.valid_from, Some(ts("2010-01-01T00:00:00Z")));
+        assert_eq!(edge.valid_to, Some(ts("2015-01-01T00:00:00Z")));
+
+        // An empty range is rejected...
+        let params = CreateEdgeParams {
+            edge_type: "WORKS_AT".to_string(),
+            from_node: a.clone(),
+            to_node: b.clone(),
+            valid_from: Some(ts("2015-01-01T00:00:00Z")),
+            valid_to: Some(ts("2015-01-01T00:00:00Z")),
+            ..Default::default()
+        };
+        let err = create_edge(&conn, &params).await.unwrap_err();
+        assert_eq!(error::kind(&err), Some(ErrorKind::Validation));
+    }
+
+    #[tokio::test]
+    async fn test_list_props_ignore_case() {
+        let conn = test_conn().await;
+
+        // Create some nodes...
+        let mut ids = Vec::new();
+        for props in [
+            serde_json::json!({"name": "Alice", "code": "10"}),
+            serde_json::json!({"name": "ALICE", "code": 10}),
+            serde_json::json!({"name": "Bob", "code": 10}),
+        ] {
+            let params = CreateNodeParams {
+                labels: vec!["Person".to_string()],
+                props: serde_json::from_value(props).unwrap(),
+                ..Default::default()
+            };
+            ids.push(create_node(&conn, &params).await.unwrap().id);
+        }
+
+        let list = |key: &str, value: Value, ignore_case: bool| {
+            let conn = &conn;
+            let params = ListNodesParams {
+                filter: NodeFilter {
+                    props: [(key.to_string(), value)].into(),
+                    props_ignore_case: ignore_case,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            async move {
+                let nodes = list_nodes(conn, &params).await.unwrap();
+                sorted(nodes.into_iter().map(|n| n.id).collect())
+            }
+        };
+
+        // Exact by default...
+        assert!(list("name", "alice".into(), false).await.is_empty());
+        assert_eq!(
+            list("name", "Alice".into(), false).await,
+            vec![ids[0].clone()]
+        );
+
+        // Case-insensitive on the string's content...
+        assert_eq!(
+            list("name", "alice".into(), true).await,
+            sorted(vec![ids[0].clone(), ids[1].clone()]),
+        );
+
+        // Strings and numbers still don't match each other...
+        assert_eq!(list("code", "10".into(), true).await, vec![ids[0].clone()]);
+        assert_eq!(
+            list("code", 10.into(), true).await,
+            sorted(vec![ids[1].clone(), ids[2].clone()]),
+        );
+
+        // Edges too...
+        let params = CreateEdgeParams {
+            edge_type: "KNOWS".to_string(),
+            from_node: ids[0].clone(),
+            to_node: ids[2].clone(),
+            props: [("since".to_string(), "Spring".into())].into(),
+            ..Default::default()
+        };
+        let edge = create_edge(&conn, &params).await.unwrap();
+        let params = ListEdgesParams {
+            filter: EdgeFilter {
+                props: [("since".to_string(), "SPRING".into())].into(),
+                props_ignore_case: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let edges = list_edges(&conn, &params).await.unwrap();
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0].id, edge.id);
+    }
+
+    #[tokio::test]
+    async fn test_list_nodes_filter_expr() {
+        let conn = test_conn().await;
+
+        // Create some people (and a place)...
+        let mut ids = Vec::new();
+        for (labels, props) in [
+            (vec!["Person"], serde_json::json!({"age": 45, "vip": false})),
+            (vec!["Person"], serde_json::json!({"age": 20, "vip": true})),
+            (vec!["Person"], serde_json::json!({"age": 20})),
+            (vec!["Place"], serde_json::json!({"age": 300, "vip": true})),
+        ] {
+            let params = CreateNodeParams {
+                labels: labels.iter().map(|l| l.to_string()).collect(),
+                props: serde_json::from_value(props).unwrap(),
+                ..Default::default()
+            };
+            ids.push(create_node(&conn, &params).await.unwrap().id);
+        }
+        let (old, vip, young, place) = (&ids[0], &ids[1], &ids[2], &ids[3]);
+
+        let list = |expr: &str| {
+            let conn = &conn;
+            let expr = crate::filter::parse_filter_expr(expr).unwrap();
+            async move {
+                let params = ListNodesParams {
+                    filter: NodeFilter {
+                        expr: Some(expr),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                };
+                let nodes = list_nodes(conn, &params).await.unwrap();
+                sorted(nodes.into_iter().map(|n| n.id).collect())
+            }
+        };
+
+        // Grouping...
+        assert_eq!(
+            list("label:Person AND (prop:age>30 OR prop:vip=true)").await,
+            sorted(vec![old.clone(), vip.clone()]),
+        );
+
+        // Precedence (AND before OR)...
+        assert_eq!(
+            list("label:Person AND prop:age>30 OR prop:vip=true").await,
+            sorted(vec![old.clone(), vip.clone(), place.clone()]),
+        );
+
+        // NOT and `has:`...
+        assert_eq!(
+            list("label:Person AND NOT has:vip").await,
+            vec![young.clone()]
+        );
+
+        // Composes with the flag filters...
+        let params = ListNodesParams {
+            filter: NodeFilter {
+                has_label: Some("Place".to_string()),
+                expr: Some(crate::filter::parse_filter_expr("prop:vip=true").unwrap()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let nodes = list_nodes(&conn, &params).await.unwrap();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(&nodes[0].id, place);
+
+        // Values are parameters, never SQL...
+        for expr in [
+            r#"label:"Person' OR 1=1 --""#,
+            r#"prop:"age\" = 1 OR 1=1 --"=1"#,
+            r#"has:"x'); DROP TABLE nodes; --""#,
+        ] {
+            assert!(list(expr).await.is_empty(), "{} matched", expr);
+        }
+        assert_eq!(list("has:age").await.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_delete_nodes_by_filter() {
+        let conn = test_conn().await;
+
+        let a = add_node(&conn, &["Temp"]).await;
+        let b = add_node(&conn, &["Temp"]).await;
+        let c = add_node(&conn, &["Keep"]).await;
+        add_edge(&conn, "LINK", &a, &c, true).await;
+        add_edge(&conn, "LINK", &a, &b, false).await;
+
+        let filter = NodeFilter {
+            has_label: Some("Temp".to_string()),
+            ..Default::default()
+        };
+        let deleted = delete_nodes_by_filter(&conn, &filter).await.unwrap();
+        assert_eq!(deleted, 2);
+
+        // Only the kept node remains, and the edges went with the deleted nodes...
+        let nodes = list_nodes(&conn, &ListNodesParams::default())
+            .await
+            .unwrap();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].id, c);
+        let edges = list_edges(&conn, &ListEdgesParams::default())
+            .await
+            .unwrap();
+        assert!(edges.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_delete_edges_by_filter() {
+        let conn = test_conn().await;
+
+        let a = add_node(&conn, &[]).await;
+        let b = add_node(&conn, &[]).await;
+        add_edge(&conn, "KNOWS", &a, &b, true).await;
+        add_edge(&conn, "KNOWS", &b, &a, true).await;
+        let keep = add_edge(&conn, "LIKES", &a, &b, true).await;
+
+        let filter = EdgeFilter {
+            edge_type: Some("KNOWS".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(delete_edges_by_filter(&conn, &filter).await.unwrap(), 2);
+
+        let edges = list_edges(&conn, &ListEdgesParams::default())
+            .await
+            .unwrap();
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0].id, keep);
+
+        // The nodes are untouched...
+        let nodes = list_nodes(&conn, &ListNodesParams::default())
+            .await
+            .unwrap();
+        assert_eq!(nodes.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_node_id() {
+        let conn = test_conn().await;
+        let a = add_node(&conn, &[]).await;
+        let b = add_node(&conn, &[]).await;
+
+        // A unique prefix (or the full ID) resolves...
+        assert_eq!(resolve_node_id(&conn, &a[..12]).await.unwrap(), a);
+        assert_eq!(resolve_node_id(&conn, &b).await.unwrap(), b);
+
+        // An ambiguous prefix lists the candidates...
+        let err = resolve_node_id(&conn, "n-").await.unwrap_err().to_string();
+        assert!(err.contains("ambiguous"));
+        assert!(err.contains(&a) && err.contains(&b));
+
+        // No match...
+        let err = resolve_node_id(&conn, "n-zzz")
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("No node found"));
+    }
+
+    #[tokio::test]
+    async fn test_migrate_db() {
+        // A fresh database has every migration pending...
+        let mut config = Config::default();
+        config.db.db_type = DBType::Memory;
+        let db = connect_to_db(&config.conf_dir, &config).await.unwrap();
+        let conn = db.connect().unwrap();
+        assert_eq!(get_migration_count(&conn).await.unwrap(), 0);
+        let pending = pending_migrations(&conn).await.unwrap();
+        assert_eq!(pending.len(), MIGRATIONS.len());
+
+        // Listing them doesn't apply them...
+        assert_eq!(get_migration_count(&conn).await.unwrap(), 0);
+
+        // Migrate it...
+        let applied = migrate_db(&conn).await.unwrap();
+        let versions: Vec<u32> = applied.iter().map(|m| m.version).collect();
+        assert_eq!(versions, vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(
+            get_migration_count(&conn).await.unwrap(),
+            MIGRATIONS.len() as i64
+        );
+
+        // An up-to-date database has nothing to do...
+        assert!(pending_migrations(&conn).await.unwrap().is_empty());
+        assert!(migrate_db(&conn).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_migrate_db_partial() {
+        let mut config = Config::default();
+        config.db.db_type = DBType::Memory;
+        let db = connect_to_db(&config.conf_dir, &config).await.unwrap();
+        let conn = db.connect().unwrap();
+
+        // Apply all but the last migration...
+        let (last, earlier) = MIGRATIONS.split_last().unwrap();
+        get_migration_count(&conn).await.unwrap();
+        for migration in earlier.iter() {
+            run_migration(&conn, migration).await.unwrap();
+            set_migration_count(&conn, migration.version).await.unwrap();
+        }
+
+        // A stray column makes the last migration fail part-way...
+        conn.execute("ALTER TABLE edges ADD COLUMN valid_to TEXT;", ())
+            .await
+            .unwrap();
+        assert!(migrate_db(&conn).await.is_err());
+
+        // ...which leaves nothing behind...
+        assert_eq!(
+            get_migration_count(&conn).await.unwrap(),
+            i64::from(earlier.last().unwrap().version)
+        );
+        assert!(conn
+            .query("SELECT valid_from FROM edges;", ())
+            .await
+            .is_err());
+
+        // ...so it can be re-run...
+        conn.execute("ALTER TABLE edges DROP COLUMN valid_to;", ())
+            .await
+            .unwrap();
+        let applied = migrate_db(&conn).await.unwrap();
+        assert_eq!(applied, vec![last]);
+        assert_eq!(
+            get_migration_count(&conn).await.unwrap(),
+            i64::from(last.version)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_audit_log() {
+        let conn = test_conn().await;
+        let a = add_node(&conn, &[]).await;
+        enable_audit(&conn).await.unwrap();
+
+        // Create a node, then set, change, and delete a prop...
+        let b = create_node(
+            &conn,
+            &CreateNodeParams {
+                labels: vec!["Person".to_string()],
+                props: [("name".to_string(), serde_json::json!("Bob"))].into(),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap()
+        .id;
+        let now = Utc::now();
+        set_node_prop(&conn, &b, "name", &serde_json::json!("Rob"), &now, None)
+            .await
+            .unwrap();
+        delete_node_prop(&conn, &b, "name").await.unwrap();
+        let edge = add_edge(&conn, "KNOWS", &a, &b, true).await;
+        delete_node(&conn, &b).await.unwrap();
+
+        // The node's history was recorded, in order...
+        let log = get_audit_log(&conn, &b).await.unwrap();
+        let ops: Vec<(&str, Option<&str>)> = log
+            .iter()
+            .map(|e| (e.operation.as_str(), e.key.as_deref()))
+            .collect();
+        assert_eq!(
+            ops,
+            vec![
+                ("create", None),
+                ("set_prop", Some("name")),
+                ("set_prop", Some("name")),
+                ("delete_prop", Some("name")),
+                ("delete", None),
+            ]
+        );
+        assert_eq!(log[0].new_value, Some(serde_json::json!(["Person"])));
+        assert_eq!(log[2].old_value, Some(serde_json::json!("Bob")));
+        assert_eq!(log[2].new_value, Some(serde_json::json!("Rob")));
+        assert_eq!(log[3].old_value, Some(serde_json::json!("Rob")));
+        assert_eq!(log[3].new_value, None);
+
+        // So was the edge's (deleted along with the node)...
+        let log = get_audit_log(&conn, &edge).await.unwrap();
+        let ops: Vec<&str> = log.iter().map(|e| e.operation.as_str()).collect();
+        assert_eq!(ops, vec!["create", "delete"]);
+        assert_eq!(log[0].new_value.as_ref().unwrap()["edge_type"], "KNOWS");
+
+        // Changes from before auditing was enabled aren't...
+        assert!(get_audit_log(&conn, &a).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_edge_id() {
+        let conn = test_conn().await;
+        let n = add_node(&conn, &[]).await;
+        let a = add_edge(&conn, "LINK", &n, &n, true).await;
+        let b = add_edge(&conn, "LINK", &n, &n, true).await;
+
+        // A unique prefix (or the full ID) resolves...
+        assert_eq!(resolve_edge_id(&conn, &a[..12]).await.unwrap(), a);
+        assert_eq!(resolve_edge_id(&conn, &b).await.unwrap(), b);
+
+        // Node IDs don't resolve as edges...
+        let err = resolve_edge_id(&conn, &n).await.unwrap_err();
+        assert_eq!(error::kind(&err), Some(error::ErrorKind::NotFound));
+
+        // An ambiguous prefix lists the candidates...
+        let err = resolve_edge_id(&conn, "e-").await.unwrap_err();
+        assert_eq!(error::kind(&err), Some(error::ErrorKind::Validation));
+        let err = err.to_string();
+        assert!(err.contains("ambiguous"));
+        assert!(err.contains(&a) && err.contains(&b));
+
+        // No match...
+        let err = resolve_edge_id(&conn, "e-zzz")
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("No edge found"));
+    }
+
+    #[tokio::test]
+    async fn test_archive_node() {
+        let conn = test_conn().await;
+        let a = add_node(&conn, &[]).await;
+        let b = add_node(&conn, &[]).await;
+        let e = add_edge(&conn, "LINK", &a, &b, true).await;
+
+        // Archive the node...
+        archive_node(&conn, &a).await.unwrap();
+        let err = archive_node(&conn, &a).await.unwrap_err();
+        assert_eq!(error::kind(&err), Some(error::ErrorKind::Validation));
+
+        // It (and its edge) are hidden by default...
+        let nodes = list_nodes(&conn, &ListNodesParams::default())
+            .await
+            .unwrap();
+        assert_eq!(nodes.iter().map(|n| &n.id).collect::<Vec<_>>(), vec![&b]);
+        assert!(list_edges(&conn, &ListEdgesParams::default())
+            .await
+            .unwrap()
+            .is_empty());
+        let params = GetNodeParams {
+            id: a.clone(),
+            ..Default::default()
+        };
+        let err = get_node(&conn, &params).await.unwrap_err();
+        assert_eq!(error::kind(&err), Some(error::ErrorKind::NotFound));
+        assert!(err.to_string().contains("is archived"));
+        assert!(get_node_edges_out(&conn, &a).await.unwrap().is_empty());
+
+        // ...but can still be seen...
+        let params = GetNodeParams {
+            id: a.clone(),
+            include_archived: true,
+            ..Default::default()
+        };
+        assert_eq!(get_node(&conn, &params).await.unwrap().id, a);
+        let filter = NodeFilter {
+            include_archived: true,
+            ..Default::default()
+        };
+        assert_eq!(count_nodes(&conn, &filter).await.unwrap(), 2);
+
+        // The edge can't be restored on its own...
+        let err = unarchive_edge(&conn, &e).await.unwrap_err();
+        assert_eq!(error::kind(&err), Some(error::ErrorKind::Validation));
+
+        // Restoring the node restores its edge too...
+        unarchive_node(&conn, &a).await.unwrap();
+        let params = GetNodeParams {
+            id: a.clone(),
+            ..Default::default()
+        };
+        assert_eq!(get_node(&conn, &params).await.unwrap().id, a);
+        assert_eq!(
+            get_node_edges_out(&conn, &a).await.unwrap(),
+            vec![e.clone()]
+        );
+
+        // Edges can be archived separately...
+        archive_edge(&conn, &e).await.unwrap();
+        assert!(get_node_edges_out(&conn, &a).await.unwrap().is_empty());
+        unarchive_edge(&conn, &e).await.unwrap();
+        assert_eq!(get_node_edges_out(&conn, &a).await.unwrap(), vec![e]);
+    }
+
+    #[tokio::test]
+    async fn test_update_edge_swap() {
+        let conn = test_conn().await;
+        let a = add_node(&conn, &[]).await;
+        let b = add_node(&conn, &[]).await;
+        let e = add_edge(&conn, "LINK", &a, &b, true).await;
+
+        // Swap the endpoints...
+        let params = UpdateEdgeParams {
+            id: e.clone(),
+            swap: true,
+            ..Default::default()
+        };
+        let edge = update_edge(&conn, &params).await.unwrap();
+        assert_eq!((edge.from_node.as_str(), edge.to_node.as_str()), (&*b, &*a));
+        assert!(edge.updated_at > edge.created_at);
+        assert_eq!(
+            get_node_edges_out(&conn, &b).await.unwrap(),
+            vec![e.clone()]
+        );
+        assert!(get_node_edges_out(&conn, &a).await.unwrap().is_empty());
+
+        // It can't be combined with setting an endpoint...
+        let params = UpdateEdgeParams {
+            id: e.clone(),
+            swap: true,
+            to_node: Some(a.clone()),
+            ..Default::default()
+        };
+        let err = update_edge(&conn, &params).await.unwrap_err();
+        assert_eq!(error::kind(&err), Some(error::ErrorKind::Validation));
+        let edge = get_edge(
+            &conn,
+            &GetEdgeParams {
+                id: e,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(edge.from_node, b);
+    }
+
+    #[tokio::test]
+    async fn test_find_changed_since() {
+        let conn = test_conn().await;
+        let old = add_node(&conn, &[]).await;
+
+        // Simulate a sync marker after the first node...
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        let marker = Utc::now();
+        set_meta(&conn, LAST_SYNC_AT_KEY, Some(marker.to_rfc3339()), None)
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+
+        let new = add_node(&conn, &[]).await;
+        let edge = add_edge(&conn, "LINK", &old, &new, true).await;
+
+        let since = get_last_sync_at(&conn).await.unwrap().unwrap();
+        let changed = find_changed_since(&conn, &since).await.unwrap();
+        assert_eq!(changed.nodes, vec![new]);
+        assert_eq!(changed.edges, vec![edge]);
+    }
+
+    #[tokio::test]
+    async fn test_list_modified_since_sync() {
+        let conn = test_conn().await;
+        let old = add_node(&conn, &[]).await;
+        let other = add_node(&conn, &[]).await;
+        let old_edge = add_edge(&conn, "LINK", &old, &other, true).await;
+
+        // Simulate a sync marker...
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        let marker = Utc::now();
+        set_meta(&conn, LAST_SYNC_AT_KEY, Some(marker.to_rfc3339()), None)
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+
+        // Add a node and archive an edge (which counts as an update)...
+        let new = add_node(&conn, &[]).await;
+        let new_edge = add_edge(&conn, "LINK", &new, &old, true).await;
+        archive_edge(&conn, &old_edge).await.unwrap();
+
+        // Only the new node is listed...
+        let since = get_last_sync_at(&conn).await.unwrap();
+        let params = ListNodesParams {
+            filter: NodeFilter {
+                updated_after: since,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let nodes = list_nodes(&conn, &params).await.unwrap();
+        assert_eq!(nodes.iter().map(|n| &n.id).collect::<Vec<_>>(), vec![&new]);
+
+        // Both changed edges are listed (if archived ones are included)...
+        let filter = EdgeFilter {
+            updated_after: since,
+            include_archived: true,
+            ..Default::default()
+        };
+        let mut ids = find_edge_ids(&conn, &filter).await.unwrap();
+        ids.sort();
+        let mut expected = vec![old_edge, new_edge];
+        expected.sort();
+        assert_eq!(ids, expected);
+    }
+
+    #[tokio::test]
+    async fn test_create_node_prop_size_limit() {
+        let conn = test_conn().await;
+        let big = Value::String("x".repeat(100));
+
+        // Over the limit...
+        let params = CreateNodeParams {
+            props: [("big".to_string(), big.clone())].into(),
+            max_prop_bytes: Some(50),
+            ..Default::default()
+        };
+        let err = create_node(&conn, &params).await.unwrap_err().to_string();
+        assert!(err.contains("\"big\"") && err.contains("102 bytes"));
+        let nodes = list_nodes(&conn, &ListNodesParams::default())
+            .await
+            .unwrap();
+        assert!(nodes.is_empty());
+
+        // Under the limit...
+        let params = CreateNodeParams {
+            props: [("big".to_string(), big)].into(),
+            max_prop_bytes: Some(200),
+            ..Default::default()
+        };
+        assert!(create_node(&conn, &params).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_find_node_by_selector() {
+        let conn = test_conn().await;
+        let mut ids = Vec::new();
+        for (label, name) in [("Person", "Alice"), ("Person", "Bob"), ("Pet", "Bob")] {
+            let params = CreateNodeParams {
+                labels: vec![label.to_string()],
+                props: [("name".to_string(), Value::String(name.to_string()))].into(),
+                ..Default::default()
+            };
+            ids.push(create_node(&conn, &params).await.unwrap().id);
+        }
+        let name = |n: &str| vec![("name".to_string(), Value::String(n.to_string()))];
+
+        // Unique match...
+        let id = find_node_by_selector(&conn, &name("Alice"), None)
+            .await
+            .unwrap();
+        assert_eq!(id, ids[0]);
+        let id = find_node_by_selector(&conn, &name("Bob"), Some("Pet"))
+            .await
+            .unwrap();
+        assert_eq!(id, ids[2]);
+
+        // No match...
+        let err = find_node_by_selector(&conn, &name("Alice"), Some("Pet"))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("No node matches"));
+
+        // Ambiguous match...
+        let err = find_node_by_selector(&conn, &name("Bob"), None)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("ambiguous"));
+        assert!(err.contains(&ids[1]) && err.contains(&ids[2]));
+    }
+
+    #[tokio::test]
+    async fn test_get_node_edges_expanded() {
+        let conn = test_conn().await;
+        let a = add_node(&conn, &["A"]).await;
+        let b = add_node(&conn, &["B"]).await;
+        let params = CreateEdgeParams {
+            edge_type: "knows".to_string(),
+            from_node: a.clone(),
+            to_node: b.clone(),
+            directed: true,
+            props: [("since".to_string(), serde_json::json!(2020))].into(),
+            ..Default::default()
+        };
+        let e = create_edge(&conn, &params).await.unwrap().id;
+
+        // Outgoing from A, with props...
+        let edges = get_node_edges_out_expanded(&conn, &a, true).await.unwrap();
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0].id, e);
+        assert_eq!(edges[0].edge_type, "knows");
+        assert_eq!(edges[0].to_node, b);
+        assert_eq!(edges[0].props.as_ref().unwrap()["since"], 2020);
+
+        // Incoming to B, without props...
+        let edges = get_node_edges_in_expanded(&conn, &b, false).await.unwrap();
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0].edge_type, "knows");
+        assert!(edges[0].props.is_none());
+
+        // Nothing going into A...
+        let edges = get_node_edges_in_expanded(&conn, &a, false).await.unwrap();
+        assert!(edges.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_create_node_require_label() {
+        let conn = test_conn().await;
+
+        // No label, when one is required...
+        let params = CreateNodeParams {
+            require_label: true,
+            ..Default::default()
+        };
+        let err = create_node(&conn, &params).await.unwrap_err();
+        assert!(err.to_string().contains("at least one label"));
+
+        // With a label...
+        let params = CreateNodeParams {
+            labels: vec!["Person".to_string()],
+            require_label: true,
+            ..Default::default()
+        };
+        assert!(create_node(&conn, &params).await.is_ok());
+
+        // No label, when one isn't required...
+        assert!(create_node(&conn, &CreateNodeParams::default())
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_group_edges() {
+        let conn = test_conn().await;
+        let a = add_node(&conn, &["A"]).await;
+        let b = add_node(&conn, &["B"]).await;
+        let c = add_node(&conn, &["C"]).await;
+        add_edge(&conn, "knows", &a, &b, true).await;
+        add_edge(&conn, "knows", &a, &c, true).await;
+        add_edge(&conn, "follows", &b, &c, true).await;
+
+        // By type...
+        let groups = group_edges(&conn, &EdgeFilter::default(), EdgeGroupBy::EdgeType)
+            .await
+            .unwrap();
+        assert_eq!(
+            groups,
+            vec![("knows".to_string(), 2), ("follows".to_string(), 1)]
+        );
+
+        // By source node...
+        let groups = group_edges(&conn, &EdgeFilter::default(), EdgeGroupBy::FromNode)
+            .await
+            .unwrap();
+        assert_eq!(groups, vec![(a.clone(), 2), (b.clone(), 1)]);
+
+        // Honoring the other filters...
+        let filter = EdgeFilter {
+            edge_type: Some("knows".to_string()),
+            ..Default::default()
+        };
+        let groups = group_edges(&conn, &filter, EdgeGroupBy::ToNode)
+            .await
+            .unwrap();
+        let mut expected = vec![(b, 1), (c, 1)];
+        expected.sort();
+        assert_eq!(groups, expected);
+    }
+
+    #[tokio::test]
+    async fn test_read_legacy_local_timestamps() {
+        let conn = test_conn().await;
+
+        // Add a node and edge the way older versions did -- with a local offset...
+        let legacy = "2024-01-01T12:00:00+02:00";
+        conn.execute(
+            "INSERT INTO nodes (id, labels, created_at, updated_at) VALUES ('n-1', '[]', ?1, ?1);",
+            libsql::params![legacy],
+        )
+        .await
+        .unwrap();
+        conn.execute(
+            "
+            INSERT INTO edges (id, edge_type, from_node, to_node, directed, created_at, updated_at)
+            VALUES ('e-1', 'knows', 'n-1', 'n-1', 1, ?1, ?1);
+            ",
+            libsql::params![legacy],
+        )
+        .await
+        .unwrap();
+
+        // They should be read back in UTC...
+        let expected: DateTime<Utc> = "2024-01-01T10:00:00Z".parse().unwrap();
+        let params = GetNodeParams {
+            id: "n-1".to_string(),
+            with_props: false,
+            ..Default::default()
+        };
+        let node = get_node(&conn, &params).await.unwrap();
+        assert_eq!(node.created_at, expected);
+        let params = GetEdgeParams {
+            id: "e-1".to_string(),
+            with_props: false,
+            ..Default::default()
+        };
+        let edge = get_edge(&conn, &params).await.unwrap();
+        assert_eq!(edge.updated_at, expected);
+
+        // ...and compare correctly against new UTC timestamps...
+        let since: DateTime<Utc> = "2024-01-01T10:30:00Z".parse().unwrap();
+        let changed = find_changed_since(&conn, &since).await.unwrap();
+        assert!(changed.nodes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_update_nodes_by_filter() {
+        let conn = test_conn().await;
+        let alice = add_node(&conn, &["Person"]).await;
+        let bob = add_node(&conn, &["Person"]).await;
+        let rex = add_node(&conn, &["Pet"]).await;
+
+        // Give Alice a prop to remove...
+        let now = Utc::now();
+        set_node_prop(&conn, &alice, "old", &serde_json::json!(1), &now, None)
+            .await
+            .unwrap();
+
+        // Update only the people...
+        let filter = NodeFilter {
+            has_label: Some("Person".to_string()),
+            ..Default::default()
+        };
+        let changes = NodePropChanges {
+            set_props: [("active".to_string(), serde_json::json!(true))].into(),
+            remove_props: vec!["old".to_string()],
+            ..Default::default()
+        };
+        let n = update_nodes_by_filter(&conn, &filter, &changes)
+            .await
+            .unwrap();
+        assert_eq!(n, 2);
+
+        // Check the props...
+        for id in [&alice, &bob] {
+            let props = get_node_props(&conn, id).await.unwrap();
+            assert_eq!(props.len(), 1);
+            assert_eq!(props["active"], serde_json::json!(true));
+        }
+        assert!(get_node_props(&conn, &rex).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_case_policies() {
+        for (policy, stored) in [
+            (CasePolicy::Preserve, "Person"),
+            (CasePolicy::Lower, "person"),
+            (CasePolicy::Upper, "PERSON"),
+        ] {
+            let conn = test_conn().await;
+
+            // Create a node and edge with mixed-case names...
+            let params = CreateNodeParams {
+                labels: vec!["Person".to_string()],
+                label_case: policy,
+                ..Default::default()
+            };
+            let node = create_node(&conn, &params).await.unwrap();
+            assert_eq!(node.labels, vec![stored]);
+            let params = CreateEdgeParams {
+                edge_type: "Person".to_string(),
+                from_node: node.id.clone(),
+                to_node: node.id.clone(),
+                edge_type_case: policy,
+                allow_self_loop: true,
+                ..Default::default()
+            };
+            let edge = create_edge(&conn, &params).await.unwrap();
+            assert_eq!(edge.edge_type, stored);
+
+            // They're stored with the policy applied...
+            let params = GetNodeParams {
+                id: node.id.clone(),
+                with_props: false,
+                ..Default::default()
+            };
+            assert_eq!(get_node(&conn, &params).await.unwrap().labels, vec![stored]);
+
+            // Filtering with the same policy matches any casing, except
+            // when preserving case...
+            for query in ["Person", "person", "PERSON"] {
+                let expected = match policy {
+                    CasePolicy::Preserve => usize::from(query == "Person"),
+                    _ => 1,
+                };
+                let filter = NodeFilter {
+                    has_label: Some(query.to_string()),
+                    label_case: policy,
+                    ..Default::default()
+                };
+                let ids = find_node_ids(&conn, &filter).await.unwrap();
+                assert_eq!(ids.len(), expected, "{:?} label {}", policy, query);
+                let filter = EdgeFilter {
+                    edge_type: Some(query.to_string()),
+                    edge_type_case: policy,
+                    ..Default::default()
+                };
+                let ids = find_edge_ids(&conn, &filter).await.unwrap();
+                assert_eq!(ids.len(), expected, "{:?} edge type {}", policy, query);
+            }
+        }
+    }
+
+    #[test]
+    fn test_check_required_secrets() {
+        let config = |db_type, encrypt_replica| crate::conf::DbConfig {
+            db_type,
+            encrypt_replica,
+            ..Default::default()
+        };
+
+        // Missing remote auth token...
+        for db_type in [DBType::RemoteOnly, DBType::RemoteWithReplica] {
+            let required = config(db_type, false).required_secrets();
+            let err = check_required_secrets(required, false, true).unwrap_err();
+            assert!(err.to_string().contains("--remote-db-token"));
+            assert_eq!(error::kind(&err), Some(error::ErrorKind::Config));
+            assert!(check_required_secrets(required, true, false).is_ok());
+        }
+
+        // Missing encryption key...
+        for db_type in [DBType::Local, DBType::RemoteWithReplica] {
+            let required = config(db_type, true).required_secrets();
+            let err = check_required_secrets(required, true, false).unwrap_err();
+            assert!(err.to_string().contains("--encryption-key-file"));
+            assert!(check_required_secrets(required, true, true).is_ok());
+
+            // ...which isn't needed if not encrypted...
+            let required = config(db_type, false).required_secrets();
+            assert!(check_required_secrets(required, true, false).is_ok());
+        }
+
+        // Nothing is needed for in-memory or plain local databases...
+        for db_type in [DBType::Memory, DBType::Local] {
+            let required = config(db_type, false).required_secrets();
+            assert!(check_required_secrets(required, false, false).is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_adjacent_nodes() {
+        let conn = test_conn().await;
+        let x = add_node(&conn, &[]).await;
+        let a = add_node(&conn, &[]).await;
+        let b = add_node(&conn, &[]).await;
+        let c = add_node(&conn, &[]).await;
+        let d = add_node(&conn, &[]).await;
+
+        // X -> A, B -> X, X -- C, D -- X...
+        let xa = add_edge(&conn, "out", &x, &a, true).await;
+        let bx = add_edge(&conn, "in", &b, &x, true).await;
+        let xc = add_edge(&conn, "undir", &x, &c, false).await;
+        let dx = add_edge(&conn, "undir", &d, &x, false).await;
+
+        let neighbors = |adj: Vec<(String, String, String)>| {
+            sorted(adj.into_iter().map(|(n, _, _)| n).collect())
+        };
+
+        // Respecting direction, the incoming directed edge isn't followed...
+        let adj = get_adjacent_nodes(&conn, &x, true, &EdgeFilter::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            neighbors(adj),
+            sorted(vec![a.clone(), c.clone(), d.clone()])
+        );
+
+        // Ignoring direction, every edge is followed...
+        let adj = get_adjacent_nodes(&conn, &x, false, &EdgeFilter::default())
+            .await
+            .unwrap();
+        assert_eq!(adj.len(), 4);
+        let mut edges: Vec<String> = adj.iter().map(|(_, e, _)| e.clone()).collect();
+        edges.sort();
+        assert_eq!(edges, sorted(vec![xa, bx.clone(), xc, dx]));
+        assert!(adj.contains(&(b.clone(), bx, "in".to_string())));
+        assert_eq!(
+            neighbors(adj),
+            sorted(vec![a.clone(), b, c.clone(), d.clone()])
+        );
+
+        // Undirected edges are symmetric...
+        let adj = get_adjacent_nodes(&conn, &c, true, &EdgeFilter::default())
+            .await
+            .unwrap();
+        assert_eq!(neighbors(adj), vec![x.clone()]);
+        let adj = get_adjacent_nodes(&conn, &d, true, &EdgeFilter::default())
+            .await
+            .unwrap();
+        assert_eq!(neighbors(adj), vec![x.clone()]);
+
+        // Directed edges aren't followed backwards...
+        let adj = get_adjacent_nodes(&conn, &a, true, &EdgeFilter::default())
+            .await
+            .unwrap();
+        assert!(adj.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_upsert_node() {
+        let conn = test_conn().await;
+
+        // The first upsert creates the node...
+        let params = CreateNodeParams {
+            id: Some("alice".to_string()),
+            labels: vec!["Person".to_string()],
+            props: [
+                ("name".to_string(), serde_json::json!("Alice")),
+                ("age".to_string(), serde_json::json!(30)),
+            ]
+            .into(),
+            ..Default::default()
+        };
+        let (node, created) = upsert_node(&conn, &params).await.unwrap();
+        assert!(created);
+        assert_eq!(node.id, "alice");
+
+        // The second updates it...
+        let params = CreateNodeParams {
+            id: Some("alice".to_string()),
+            labels: vec!["Admin".to_string()],
+            props: [("age".to_string(), serde_json::json!(31))].into(),
+            ..Default::default()
+        };
+        let (node, created) = upsert_node(&conn, &params).await.unwrap();
+        assert!(!created);
+        assert_eq!(node.labels, vec!["Admin"]);
+        let props = node.props.unwrap();
+        assert_eq!(props["name"], serde_json::json!("Alice"));
+        assert_eq!(props["age"], serde_json::json!(31));
+        assert_eq!(
+            list_nodes(&conn, &ListNodesParams::default())
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // Without upserting, a duplicate ID is an error...
+        let err = create_node(&conn, &params).await.unwrap_err();
+        assert!(err.to_string().contains("already exists"));
+
+        // A new label adds its defaults, without overwriting any props...
+        let defaults = [
+            ("age".to_string(), serde_json::json!(0)),
+            ("role".to_string(), serde_json::json!("member")),
+        ]
+        .into();
+        set_label_defaults(&conn, "Staff", &defaults).await.unwrap();
+        let params = CreateNodeParams {
+            id: Some("alice".to_string()),
+            labels: vec!["Admin".to_string(), "Staff".to_string()],
+            ..Default::default()
+        };
+        let (node, _) = upsert_node(&conn, &params).await.unwrap();
+        let props = node.props.unwrap();
+        assert_eq!(props["age"], serde_json::json!(31));
+        assert_eq!(props["role"], serde_json::json!("member"));
+
+        // The merged props are size-checked...
+        let params = CreateNodeParams {
+            id: Some("alice".to_string()),
+            labels: vec!["Admin".to_string()],
+            props: [("bio".to_string(), serde_json::json!("x".repeat(100)))].into(),
+            max_prop_bytes: Some(10),
+            ..Default::default()
+        };
+        assert!(upsert_node(&conn, &params).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_create_subgraph() {
+        let conn = test_conn().await;
+
+        // Create two nodes and an edge between them...
+        let spec: SubgraphSpec = serde_json::from_value(serde_json::json!({
+            "nodes": [
+                {"alias": "n1", "labels": ["Person"], "props": {"name": "Alice"}},
+                {"alias": "n2", "labels": ["Person"], "props": {"name": "Bob"}},
+            ],
+            "edges": [
+                {"type": "KNOWS", "from": "$n1", "to": "$n2", "directed": true},
+            ],
+        }))
+        .unwrap();
+        let params = CreateSubgraphParams {
+            spec,
+            ..Default::default()
+        };
+        let res = create_subgraph(&conn, &params).await.unwrap();
+        assert_eq!(res.nodes.len(), 2);
+        assert_eq!(res.edges.len(), 1);
+        let edge = get_edge(
+            &conn,
+            &GetEdgeParams {
+                id: res.edges[0].id.clone(),
+                with_props: false,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(edge.from_node, res.aliases["n1"]);
+        assert_eq!(edge.to_node, res.aliases["n2"]);
+
+        // An unknown alias aborts the whole thing...
+        let spec: SubgraphSpec = serde_json::from_value(serde_json::json!({
+            "nodes": [{"alias": "n1", "labels": ["Person"]}],
+            "edges": [{"type": "KNOWS", "from": "$n1", "to": "$n3"}],
+        }))
+        .unwrap();
+        let params = CreateSubgraphParams {
+            spec,
+            ..Default::default()
+        };
+        let err = create_subgraph(&conn, &params).await.unwrap_err();
+        assert!(err.to_string().contains("$n3"));
+        let nodes = list_nodes(&conn, &ListNodesParams::default())
+            .await
+            .unwrap();
+        assert_eq!(nodes.len(), 2);
+
+        // A self-loop is rejected, unless it's allowed...
+        let spec = || -> SubgraphSpec {
+            serde_json::from_value(serde_json::json!({
+                "nodes": [{"alias": "n1", "labels": ["Person"]}],
+                "edges": [{"type": "KNOWS", "from": "$n1", "to": "$n1"}],
+            }))
+            .unwrap()
+        };
+        let params = CreateSubgraphParams {
+            spec: spec(),
+            ..Default::default()
+        };
+        let err = create_subgraph(&conn, &params).await.unwrap_err();
+        assert_eq!(error::kind(&err), Some(ErrorKind::Validation));
+        let params = CreateSubgraphParams {
+            spec: spec(),
+            allow_self_loop: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            create_subgraph(&conn, &params).await.unwrap().edges.len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_subgraph_label_defaults() {
+        let conn = test_conn().await;
+        let defaults = [
+            ("active".to_string(), serde_json::json!(true)),
+            ("name".to_string(), serde_json::json!("?")),
+        ]
+        .into();
+        set_label_defaults(&conn, "Person", &defaults)
+            .await
+            .unwrap();
+
+        // The nodes get their labels' defaults (with the spec's props winning)...
+        let spec: SubgraphSpec = serde_json::from_value(serde_json::json!({
+            "nodes": [{"alias": "n1", "labels": ["Person"], "props": {"name": "Alice"}}],
+        }))
+        .unwrap();
+        let params = CreateSubgraphParams {
+            spec,
+            ..Default::default()
+        };
+        let res = create_subgraph(&conn, &params).await.unwrap();
+        let node = get_node(
+            &conn,
+            &GetNodeParams {
+                id: res.aliases["n1"].clone(),
+                with_props: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        let props = node.props.unwrap();
+        assert_eq!(props["active"], serde_json::json!(true));
+        assert_eq!(props["name"], serde_json::json!("Alice"));
+        assert_eq!(res.nodes[0].props, Some(props));
+    }
+
+    #[tokio::test]
+    async fn test_delete_dangling_edges() {
+        let conn = test_conn().await;
+        conn.execute("PRAGMA foreign_keys = OFF;", ())
+            .await
+            .unwrap();
+
+        // Create a connected pair of nodes...
+        let a = create_node(&conn, &CreateNodeParams::default())
+            .await
+            .unwrap();
+        let b = create_node(&conn, &CreateNodeParams::default())
+            .await
+            .unwrap();
+        let edge = create_edge(
+            &conn,
+            &CreateEdgeParams {
+                edge_type: "knows".to_string(),
+                from_node: a.id.clone(),
+                to_node: b.id.clone(),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        // Manually insert an edge to a node that doesn't exist...
+        let now = Utc::now().to_rfc3339();
+        conn.execute(
+            "
+            INSERT INTO edges (id, edge_type, from_node, to_node, directed, created_at, updated_at)
+            VALUES ('e-dangling', 'knows', ?, 'n-missing', 1, ?, ?);
+            ",
+            libsql::params![a.id.clone(), now.clone(), now],
+        )
+        .await
+        .unwrap();
+
+        // Only the dangling edge is found and deleted...
+        let ids = find_dangling_edges(&conn).await.unwrap();
+        assert_eq!(ids, vec!["e-dangling"]);
+        assert_eq!(delete_dangling_edges(&conn).await.unwrap(), 1);
+        assert!(find_dangling_edges(&conn).await.unwrap().is_empty());
+        assert!(check_edge_exists(&conn, &edge.id).await.unwrap());
+        assert!(!check_edge_exists(&conn, "e-dangling").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_graph_meta() {
+        let conn = test_conn().await;
+
+        // Set and get a key...
+        set_graph_meta(&conn, "description", "A test graph")
+            .await
+            .unwrap();
+        let value = get_graph_meta(&conn, "description").await.unwrap();
+        assert_eq!(value, Some(serde_json::json!("A test graph")));
+        assert_eq!(get_graph_meta(&conn, "missing").await.unwrap(), None);
+
+        // Only non-reserved keys are listed...
+        let meta = list_graph_meta(&conn).await.unwrap();
+        assert_eq!(meta.len(), 1);
+        assert_eq!(meta["description"], serde_json::json!("A test graph"));
+
+        // Reserved keys can't be overwritten...
+        for key in ["migration_count", "schema_version"] {
+            let err = set_graph_meta(&conn, key, "0").await.unwrap_err();
+            assert!(err.to_string().contains("reserved"));
+        }
+        assert_eq!(
+            get_graph_meta(&conn, "migration_count").await.unwrap(),
+            Some(serde_json::json!(6))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_compress_large_props() {
+        let conn = test_conn().await;
+
+        // Create a node with a large and a small prop...
+        let large = serde_json::json!("abc ".repeat(2000));
+        let small = serde_json::json!("abc");
+        let params = CreateNodeParams {
+            props: [
+                ("large".to_string(), large.clone()),
+                ("small".to_string(), small.clone()),
+            ]
+            .into(),
+            compress_props_over: Some(DEFAULT_COMPRESS_PROPS_OVER_BYTES),
+            ..Default::default()
+        };
+        let node = create_node(&conn, &params).await.unwrap();
+
+        // Only the large prop is compressed...
+        let mut rows = conn
+            .query(
+                "SELECT key, typeof(value), length(value) FROM node_props ORDER BY key;",
+                (),
+            )
+            .await
+            .unwrap();
+        let row = rows.next().await.unwrap().unwrap();
+        assert_eq!(row.get::<String>(1).unwrap(), "blob");
+        assert!(row.get::<i64>(2).unwrap() < large.to_string().len() as i64);
+        let row = rows.next().await.unwrap().unwrap();
+        assert_eq!(row.get::<String>(1).unwrap(), "text");
+
+        // Both read back unchanged...
+        let props = get_node_props(&conn, &node.id).await.unwrap();
+        assert_eq!(props["large"], large);
+        assert_eq!(props["small"], small);
+    }
+
+    #[tokio::test]
+    async fn test_roots_and_leaves() {
+        let conn = test_conn().await;
+
+        // Build a small DAG (a -> b -> c, a -> c) with an undirected
+        // edge (c -- d) that shouldn't count...
+        let mut ids = HashMap::new();
+        for name in ["a", "b", "c", "d"] {
+            let node = create_node(&conn, &CreateNodeParams::default())
+                .await
+                .unwrap();
+            ids.insert(name, node.id);
+        }
+        for (from, to, directed) in [
+            ("a", "b", true),
+            ("b", "c", true),
+            ("a", "c", true),
+            ("c", "d", false),
+        ] {
+            let params = CreateEdgeParams {
+                edge_type: "depends_on".to_string(),
+                from_node: ids[from].clone(),
+                to_node: ids[to].clone(),
+                directed,
+                ..Default::default()
+            };
+            create_edge(&conn, &params).await.unwrap();
+        }
+
+        // Get the names of the nodes matching a filter...
+        let names = |filter: NodeFilter| {
+            let conn = &conn;
+            let ids = &ids;
+            async move {
+                let found = find_node_ids(conn, &filter).await.unwrap();
+                let mut names: Vec<&str> = ids
+                    .iter()
+                    .filter(|(_, id)| found.contains(id))
+                    .map(|(name, _)| *name)
+                    .collect();
+                names.sort();
+                names
+            }
+        };
+
+        let roots = NodeFilter {
+            roots: true,
+            ..Default::default()
+        };
+        assert_eq!(names(roots).await, vec!["a", "d"]);
+        let leaves = NodeFilter {
+            leaves: true,
+            ..Default::default()
+        };
+        assert_eq!(names(leaves).await, vec!["c", "d"]);
+
+        // They compose with each other (and the other filters)...
+        let both = NodeFilter {
+            roots: true,
+            leaves: true,
+            ..Default::default()
+        };
+        assert_eq!(names(both).await, vec!["d"]);
+    }
+
+    #[tokio::test]
+    async fn test_prop_value_round_trip() {
+        let conn = test_conn().await;
+        let a = create_node(&conn, &CreateNodeParams::default())
+            .await
+            .unwrap();
+
+        // Every JSON type should read back exactly as it was stored...
+        let cases = [
+            ("string", serde_json::json!("hello")),
+            ("quoted_string", serde_json::json!("\"hello\"")),
+            ("empty_string", serde_json::json!("")),
+            ("int", serde_json::json!(-42)),
+            ("float", serde_json::json!(1.5)),
+            ("bool", serde_json::json!(true)),
+            ("null", serde_json::json!(null)),
+            ("object", serde_json::json!({"a": {"b": [1, "two"]}})),
+            ("array", serde_json::json!([1, "two", null, false])),
+        ];
+        for (name, value) in cases.iter() {
+            // Node props...
+            let props: HashMap<String, Value> = [(format!(" {} ", name), value.clone())].into();
+            let node = create_node(
+                &conn,
+                &CreateNodeParams {
+                    props: props.clone(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+            let stored = get_node_props(&conn, &node.id).await.unwrap();
+            assert_eq!(&stored[*name], value, "node prop {}", name);
+            assert_eq!(node.props.as_ref(), Some(&stored), "node prop {}", name);
+
+            // Edge props...
+            let edge = create_edge(
+                &conn,
+                &CreateEdgeParams {
+                    edge_type: "knows".to_string(),
+                    from_node: a.id.clone(),
+                    to_node: node.id.clone(),
+                    props: [(name.to_uppercase(), value.clone())].into(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+            let stored = get_edge_props(&conn, &edge.id).await.unwrap();
+            assert_eq!(&stored[*name], value, "edge prop {}", name);
+            assert_eq!(edge.props.as_ref(), Some(&stored), "edge prop {}", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_reciprocal_edges() {
+        let conn = test_conn().await;
+        let a = create_node(&conn, &CreateNodeParams::default())
+            .await
+            .unwrap();
+        let b = create_node(&conn, &CreateNodeParams::default())
+            .await
+            .unwrap();
+
+        // Create the pair...
+        let params = CreateEdgeParams {
+            edge_type: "follows".to_string(),
+            from_node: a.id.clone(),
+            to_node: b.id.clone(),
+            directed: true,
+            ..Default::default()
+        };
+        let (forward, backward) = create_reciprocal_edges(&conn, &params).await.unwrap();
+        assert_eq!(
+            (forward.from_node.as_str(), forward.to_node.as_str()),
+            (a.id.as_str(), b.id.as_str())
+        );
+        assert_eq!(
+            (backward.from_node.as_str(), backward.to_node.as_str()),
+            (b.id.as_str(), a.id.as_str())
+        );
+
+        // Both were committed...
+        let edges = list_edges(&conn, &ListEdgesParams::default())
+            .await
+            .unwrap();
+        assert_eq!(edges.len(), 2);
+        assert!(edges.iter().all(|e| e.directed && e.edge_type == "follows"));
+
+        // Undirected edges can't be created both ways...
+        let params = CreateEdgeParams {
+            directed: false,
+            ..params
+        };
+        assert!(create_reciprocal_edges(&conn, &params).await.is_err());
+        assert_eq!(
+            list_edges(&conn, &ListEdgesParams::default())
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_has_edge_type() {
+        let conn = test_conn().await;
+
+        // Build a small graph (a -knows-> b -likes-> c)...
+        let mut ids = HashMap::new();
+        for name in ["a", "b", "c", "d"] {
+            let node = create_node(&conn, &CreateNodeParams::default())
+                .await
+                .unwrap();
+            ids.insert(name, node.id);
+        }
+        for (from, edge_type, to) in [("a", "knows", "b"), ("b", "likes", "c")] {
+            let params = CreateEdgeParams {
+                edge_type: edge_type.to_string(),
+                from_node: ids[from].clone(),
+                to_node: ids[to].clone(),
+                directed: true,
+                ..Default::default()
+            };
+            create_edge(&conn, &params).await.unwrap();
+        }
+
+        // Check which nodes match, for each direction...
+        let cases = [
+            (vec!["knows"], EdgeDirection::Out, vec!["a"]),
+            (vec!["knows"], EdgeDirection::In, vec!["b"]),
+            (vec!["knows"], EdgeDirection::Both, vec!["a", "b"]),
+            (vec!["likes"], EdgeDirection::Both, vec!["b", "c"]),
+            (vec!["knows", "likes"], EdgeDirection::Both, vec!["b"]),
+            (vec!["knows", "likes"], EdgeDirection::Out, vec![]),
+        ];
+        for (types, direction, expected) in cases {
+            let filter = NodeFilter {
+                has_edge_types: types.iter().map(|t| t.to_string()).collect(),
+                has_edge_direction: direction,
+                ..Default::default()
+            };
+            let found = find_node_ids(&conn, &filter).await.unwrap();
+            let mut names: Vec<&str> = ids
+                .iter()
+                .filter(|(_, id)| found.contains(id))
+                .map(|(name, _)| *name)
+                .collect();
+            names.sort();
+            assert_eq!(names, expected, "{:?} {:?}", types, direction);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancel_create_subgraph() {
+        let conn = test_conn().await;
+
+        // Build a spec with a bunch of nodes...
+        let spec = SubgraphSpec {
+            nodes: (0..100)
+                .map(|i| SubgraphNode {
+                    alias: format!("n{}", i),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+
+        // Cancel it, so it stops after the first insert...
+        let params = CreateSubgraphParams {
+            spec,
+            ..Default::default()
+        };
+        params.cancel.cancel();
+        let err = create_subgraph(&conn, &params).await.unwrap_err();
+        assert_eq!(error::kind(&err), Some(error::ErrorKind::Cancelled));
+
+        // Nothing was left behind...
+        let nodes = list_nodes(&conn, &ListNodesParams::default())
+            .await
+            .unwrap();
+        assert!(nodes.is_empty());
+
+        // And the connection is still usable...
+        create_node(&conn, &CreateNodeParams::default())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_analyze_db() {
+        let conn = test_conn().await;
+        create_node(&conn, &CreateNodeParams::default())
+            .await
+            .unwrap();
+        analyze_db(&conn, true).await.unwrap();
+
+        // The statistics table should now exist...
+        let mut rows = conn
+            .query(
+                "SELECT COUNT(*) FROM sqlite_master WHERE name = 'sqlite_stat1';",
+                (),
+            )
+            .await
+            .unwrap();
+        let row = rows.next().await.unwrap().unwrap();
+        assert_eq!(row.get::<i64>(0).unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_props_contain() {
+        let conn = test_conn().await;
+
+        // Create some nodes with (and without) array props...
+        let mut ids = HashMap::new();
+        for (name, tags) in [
+            ("ab", serde_json::json!(["a", "b"])),
+            ("bc", serde_json::json!(["b", "c", 1, true])),
+            ("str", serde_json::json!("a")),
+        ] {
+            let params = CreateNodeParams {
+                props: [("tags".to_string(), tags)].into(),
+                ..Default::default()
+            };
+            ids.insert(name, create_node(&conn, &params).await.unwrap().id);
+        }
+
+        // Check which nodes contain each value...
+        let cases = [
+            (serde_json::json!("a"), vec!["ab"]),
+            (serde_json::json!("b"), vec!["ab", "bc"]),
+            (serde_json::json!(1), vec!["bc"]),
+            (serde_json::json!(true), vec!["bc"]),
+            (serde_json::json!("z"), vec![]),
+        ];
+        for (value, expected) in cases {
+            let filter = NodeFilter {
+                props_contain: vec![("tags".to_string(), value.clone())],
+                ..Default::default()
+            };
+            let found = find_node_ids(&conn, &filter).await.unwrap();
+            let mut names: Vec<&str> = ids
+                .iter()
+                .filter(|(_, id)| found.contains(id))
+                .map(|(name, _)| *name)
+                .collect();
+            names.sort();
+            assert_eq!(names, expected, "{}", value);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_missing_node_and_edge() {
+        let conn = test_conn().await;
+        let a = add_node(&conn, &["Person"]).await;
+
+        // A present node still works...
+        let params = GetNodeParams {
+            id: a.clone(),
+            with_props: true,
+            ..Default::default()
+        };
+        assert_eq!(get_node(&conn, &params).await.unwrap().id, a);
+
+        // A missing node is a "not found" error...
+        let params = GetNodeParams {
+            id: "n-missing".to_string(),
+            with_props: true,
+            ..Default::default()
+        };
+        let err = get_node(&conn, &params).await.unwrap_err();
+        assert_eq!(error::kind(&err), Some(error::ErrorKind::NotFound));
+        assert_eq!(err.to_string(), "Node 'n-missing' does not exist.");
+
+        // ...and so is a missing edge...
+        let params = GetEdgeParams {
+            id: "e-missing".to_string(),
+            with_props: false,
+            ..Default::default()
+        };
+        let err = get_edge(&conn, &params).await.unwrap_err();
+        assert_eq!(error::kind(&err), Some(error::ErrorKind::NotFound));
+        assert_eq!(err.to_string(), "Edge 'e-missing' does not exist.");
+    }
 
-    // Commit the transaction...
-    tx.commit().await?;
+    #[tokio::test]
+    async fn test_node_templates() {
+        let conn = test_conn().await;
 
-    // Return the data...
-    Ok(DbEdge {
-        id,
-        edge_type: params.edge_type.clone(),
-        from_node: params.from_node.clone(),
-        to_node: params.to_node.clone(),
-        directed: params.directed,
-        created_at: now,
-        updated_at: now,
-        props: Some(params.props.clone()),
-    })
-}
+        // Create a template...
+        let template = NodeTemplate {
+            name: "employee".to_string(),
+            labels: vec!["Person".to_string(), "Employee".to_string()],
+            props: [
+                ("status".to_string(), serde_json::json!("active")),
+                ("level".to_string(), serde_json::json!(1)),
+            ]
+            .into(),
+        };
+        set_node_template(&conn, &template).await.unwrap();
+        assert_eq!(
+            get_node_template(&conn, "employee").await.unwrap(),
+            template
+        );
 
-pub struct ListNodesParams;
+        // Merge it with overrides...
+        let (labels, props) = template.apply(
+            vec!["Manager".to_string(), "Person".to_string()],
+            [
+                ("name".to_string(), serde_json::json!("Bob")),
+                ("level".to_string(), serde_json::json!(3)),
+            ]
+            .into(),
+        );
+        assert_eq!(labels, vec!["Person", "Employee", "Manager"]);
+        assert_eq!(props["status"], serde_json::json!("active"));
+        assert_eq!(props["name"], serde_json::json!("Bob"));
+        assert_eq!(props["level"], serde_json::json!(3));
 
-pub async fn list_nodes(conn: &Connection, params: &ListNodesParams) -> Result<Vec<DbNode>> {
-    let mut res = conn
-        .prepare(
-            "
-            SELECT id, labels, created_at, updated_at
-            FROM nodes;
-            "
+        // Missing templates are "not found" errors...
+        let err = get_node_template(&conn, "missing").await.unwrap_err();
+        assert_eq!(error::kind(&err), Some(error::ErrorKind::NotFound));
+    }
+
+    #[tokio::test]
+    async fn test_dedupe_labels() {
+        let conn = test_conn().await;
+        let labels: Vec<String> = ["Person", "Person", "person", "Admin"]
+            .iter()
+            .map(|l| l.to_string())
+            .collect();
+
+        // Preserving case, only exact duplicates are dropped...
+        let params = CreateNodeParams {
+            labels: labels.clone(),
+            ..Default::default()
+        };
+        let node = create_node(&conn, &params).await.unwrap();
+        assert_eq!(node.labels, vec!["Person", "person", "Admin"]);
+
+        // ...while with a case policy, case variants are too...
+        let params = CreateNodeParams {
+            labels,
+            label_case: CasePolicy::Lower,
+            ..Default::default()
+        };
+        let node = create_node(&conn, &params).await.unwrap();
+        assert_eq!(node.labels, vec!["person", "admin"]);
+        let stored = get_node(
+            &conn,
+            &GetNodeParams {
+                id: node.id.clone(),
+                ..Default::default()
+            },
         )
-        .await?
-        .query(libsql::params![])
-        .await?;
-    
-    let mut nodes = Vec::new();
-    while let Some(row) = res.next().await? {
-        // let node = de::from_row::<DbNode>(&row)?;
+        .await
+        .unwrap();
+        assert_eq!(stored.labels, vec!["person", "admin"]);
 
-        // Get the values...
-        let id: String = row.get(0)?;
-        let slabels: String = row.get(1)?;
-        let labels: Vec<String> = serde_json::from_str(&slabels)?;
-        let created_at: DateTime<Local> = row.get::<String>(2)?.parse()?;
-        let updated_at: DateTime<Local> = row.get::<String>(3)?.parse()?;
-       
-        // Get the props...
-        let props = get_node_props(conn, &id).await?;
+        // Adding labels the node already has doesn't duplicate them...
+        let params = UpdateNodeParams {
+            id: node.id.clone(),
+            add_labels: vec!["PERSON".to_string(), "User".to_string()],
+            remove_labels: vec!["Admin".to_string()],
+            label_case: CasePolicy::Lower,
+            ..Default::default()
+        };
+        let node = update_node(&conn, &params).await.unwrap();
+        assert_eq!(node.labels, vec!["person", "user"]);
+    }
 
-        // Add it to the list...
-        nodes.push(DbNode {
+    #[tokio::test]
+    async fn test_replace_node() {
+        let conn = test_conn().await;
+        let node = |label: &str| CreateNodeParams {
+            labels: vec![label.to_string()],
+            ..Default::default()
+        };
+        let old = create_node(&conn, &node("Old")).await.unwrap();
+        let other = create_node(&conn, &node("Other")).await.unwrap();
+        let edge = |from: &str, to: &str| CreateEdgeParams {
+            edge_type: "knows".to_string(),
+            from_node: from.to_string(),
+            to_node: to.to_string(),
+            directed: true,
+            ..Default::default()
+        };
+        let out = create_edge(&conn, &edge(&old.id, &other.id)).await.unwrap();
+        let inc = create_edge(&conn, &edge(&other.id, &old.id)).await.unwrap();
+        let self_loop = CreateEdgeParams {
+            allow_self_loop: true,
+            ..edge(&old.id, &old.id)
+        };
+        let self_loop = create_edge(&conn, &self_loop).await.unwrap();
+
+        // Replace the node...
+        let params = CreateNodeParams {
+            labels: vec!["New".to_string()],
+            props: [("name".to_string(), serde_json::json!("Alice"))].into(),
+            ..Default::default()
+        };
+        let res = replace_node(&conn, &old.id, &params).await.unwrap();
+        assert_eq!(res.edges_moved, 3);
+        assert_eq!(res.node.labels, vec!["New"]);
+        let new_id = res.node.id;
+
+        // The edges now point at the new node...
+        let get = |id: String| GetEdgeParams {
             id,
-            labels,
-            props: Some(props),
-            created_at,
-            updated_at,
-        });
+            ..Default::default()
+        };
+        let e = get_edge(&conn, &get(out.id)).await.unwrap();
+        assert_eq!((e.from_node, e.to_node), (new_id.clone(), other.id.clone()));
+        let e = get_edge(&conn, &get(inc.id)).await.unwrap();
+        assert_eq!((e.from_node, e.to_node), (other.id.clone(), new_id.clone()));
+        let e = get_edge(&conn, &get(self_loop.id)).await.unwrap();
+        assert_eq!((e.from_node, e.to_node), (new_id.clone(), new_id.clone()));
+
+        // ...and the old node is gone...
+        assert!(!check_node_exists(&conn, &old.id).await.unwrap());
+        assert!(replace_node(&conn, &old.id, &params).await.is_err());
     }
 
-    Ok(nodes)
-}
+    #[tokio::test]
+    async fn test_move_data_dir() {
+        let conf_dir = tempfile::tempdir().unwrap();
+        let old_dir = tempfile::tempdir().unwrap();
+        let new_dir = tempfile::tempdir().unwrap();
+        let mut config = Config {
+            conf_dir: conf_dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        config.db.db_type = DBType::Local;
+        config.db.data_dir = Some(old_dir.path().to_path_buf());
+        let opts = OpenOptions {
+            journal_mode: Some(JournalMode::Wal),
+            ..Default::default()
+        };
 
-pub struct ListEdgesParams;
+        // Write a node in the old directory...
+        let id = {
+            let (_db, conn) = open_and_migrate(&config, &opts).await.unwrap();
+            add_node(&conn, &["Person"]).await
+        };
 
-pub async fn list_edges(conn: &Connection, params: &ListEdgesParams) -> Result<Vec<DbEdge>> {
-    let mut res = conn
-        .prepare(
-            "
-            SELECT id, edge_type, from_node, to_node, directed, created_at, updated_at
-            FROM edges;
-            "
-        )
-        .await?
-        .query(libsql::params![])
-        .await?;
-    
-    let mut edges = Vec::new();
-    while let Some(row) = res.next().await? {
-        // Get the values...
-        let mut e = de::from_row::<DbEdge>(&row)?;
+        // Move the files over...
+        let files = crate::conf::list_db_files(old_dir.path()).unwrap();
+        assert!(files.contains(&old_dir.path().join(DB_FILE_NAME)));
+        let dest = new_dir.path().join("graph-data");
+        util::move_files(&files, &dest).unwrap();
+        assert!(crate::conf::list_db_files(old_dir.path())
+            .unwrap()
+            .is_empty());
 
-        // Get the props...
-        let props = get_edge_props(conn, &e.id).await?;
-        e.props = Some(props);
+        // ...and the node reads back from the new directory...
+        config.db.data_dir = Some(dest.clone());
+        assert_eq!(config.db_file(), dest.join(DB_FILE_NAME));
+        let opts = OpenOptions {
+            migrate: false,
+            ..opts
+        };
+        let (_db, conn) = open_and_migrate(&config, &opts).await.unwrap();
+        let params = GetNodeParams {
+            id: id.clone(),
+            ..Default::default()
+        };
+        let node = get_node(&conn, &params).await.unwrap();
+        assert_eq!(node.id, id);
+        assert_eq!(node.labels, vec!["Person".to_string()]);
+    }
 
-        // Add it to the list...
-        edges.push(e);
+    #[tokio::test]
+    async fn test_open_without_migrating() {
+        let mut config = Config::default();
+        config.db.db_type = DBType::Memory;
+        let opts = OpenOptions {
+            migrate: false,
+            ..Default::default()
+        };
+        let (_db, conn) = open_and_migrate(&config, &opts).await.unwrap();
+
+        // Queries fail, with a hint...
+        let err = list_nodes(&conn, &ListNodesParams::default())
+            .await
+            .unwrap_err();
+        assert!(is_missing_schema_error(&err));
+        let err = with_schema_hint(err);
+        assert!(err.to_string().contains("graphctl meta migrate"));
+
+        // ...rather than creating the tables...
+        let mut rows = conn
+            .query(
+                "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table';",
+                (),
+            )
+            .await
+            .unwrap();
+        let row = rows.next().await.unwrap().unwrap();
+        assert_eq!(row.get::<i64>(0).unwrap(), 0);
+
+        // Other errors are left alone...
+        let err = error::validation("Bad input.");
+        assert_eq!(with_schema_hint(err).to_string(), "Bad input.");
     }
 
-    Ok(edges)
-}
+    #[tokio::test]
+    async fn test_open_and_migrate() {
+        let mut config = Config::default();
+        config.db.db_type = DBType::Memory;
+        let (_db, conn) = open_and_migrate(&config, &OpenOptions::default())
+            .await
+            .unwrap();
 
-pub async fn check_node_exists(conn: &Connection, id: &str) -> Result<bool> {
-    let res = conn
-        .prepare(
-            "
-            SELECT COUNT(*) > 0
-            FROM nodes 
-            WHERE id = ?;
-            ",
-        )
-        .await?
-        .query_row(libsql::params![id])
-        .await?;
-    Ok(res.get(0)?)
-}
+        // Foreign keys are enforced...
+        let mut rows = conn.query("PRAGMA foreign_keys;", ()).await.unwrap();
+        let row = rows.next().await.unwrap().unwrap();
+        assert_eq!(row.get::<i64>(0).unwrap(), 1);
+        let err = conn
+            .execute(
+                "INSERT INTO node_props (node_id, key, value, created_at, updated_at)
+                VALUES ('n-missing', 'k', '1', '', '');",
+                (),
+            )
+            .await;
+        assert!(err.is_err());
 
-pub async fn check_edge_exists(conn: &Connection, id: &str) -> Result<bool> {
-    let res = conn
-        .prepare(
-            "
-            SELECT COUNT(*) > 0
-            FROM edges
-            WHERE id = ?;
-            ",
-        )
-        .await?
-        .query_row(libsql::params![id])
-        .await?;
-    Ok(res.get(0)?)
-}
+        // ...and the migrations have run...
+        let count = get_migration_count(&conn).await.unwrap();
+        assert_eq!(count, MIGRATIONS.len() as i64);
+    }
 
-pub struct GetNodeParams {
-    pub id: String,
-    pub with_props: bool,
-    // pub with_edges: bool,
-}
+    #[tokio::test]
+    async fn test_label_defaults() {
+        let conn = test_conn().await;
+        let defaults = [
+            ("status".to_string(), serde_json::json!("active")),
+            ("verified".to_string(), serde_json::json!(false)),
+        ]
+        .into();
+        set_label_defaults(&conn, "Person", &defaults)
+            .await
+            .unwrap();
 
-pub async fn get_node(conn: &Connection, params: &GetNodeParams) -> Result<DbNode> {
-    // Get the node...
-    let row = conn
-        .prepare(
-            "
-            SELECT id, node_type, created_at, updated_at 
-            FROM nodes 
-            WHERE id = ?;
-            ",
-        )
-        .await?
-        .query_row(libsql::params![params.id.clone()])
-        .await?;
+        // A node with the label gets the defaults...
+        let params = CreateNodeParams {
+            labels: vec!["Person".to_string()],
+            props: [("name".to_string(), serde_json::json!("Alice"))].into(),
+            ..Default::default()
+        };
+        let props = create_node(&conn, &params).await.unwrap().props.unwrap();
+        assert_eq!(props["status"], serde_json::json!("active"));
+        assert_eq!(props["verified"], serde_json::json!(false));
+        assert_eq!(props["name"], serde_json::json!("Alice"));
 
-    // Get the values...
-    let mut node = de::from_row::<DbNode>(&row)?;
+        // ...unless a prop is given explicitly...
+        let params = CreateNodeParams {
+            labels: vec!["Person".to_string()],
+            props: [("status".to_string(), serde_json::json!("inactive"))].into(),
+            ..Default::default()
+        };
+        let node = create_node(&conn, &params).await.unwrap();
+        assert_eq!(
+            node.props.as_ref().unwrap()["status"],
+            serde_json::json!("inactive")
+        );
 
-    // Get the properties?
-    if params.with_props {
-        let props = get_node_props(conn, &params.id).await?;
-        node.props = Some(props);
+        // ...and that's what's stored...
+        let stored = get_node_props(&conn, &node.id).await.unwrap();
+        assert_eq!(stored["status"], serde_json::json!("inactive"));
+        assert_eq!(stored["verified"], serde_json::json!(false));
+
+        // A label without defaults gets nothing extra...
+        let params = CreateNodeParams {
+            labels: vec!["Pet".to_string()],
+            ..Default::default()
+        };
+        let node = create_node(&conn, &params).await.unwrap();
+        assert!(node.props.unwrap().is_empty());
+
+        // Setting no props removes the defaults...
+        set_label_defaults(&conn, "Person", &HashMap::new())
+            .await
+            .unwrap();
+        assert!(list_label_defaults(&conn).await.unwrap().is_empty());
     }
 
-    // Return the data!
-    Ok(node)
-}
+    #[tokio::test]
+    async fn test_clone_node() {
+        let conn = test_conn().await;
 
-pub async fn get_node_props(conn: &Connection, node_id: &str) -> Result<HashMap<String, Value>> {
-    // Query the props in the database...
-    let mut rows = conn
-        .prepare(
-            "
-            SELECT key, value 
-            FROM node_props 
-            WHERE node_id = ?;
-            ",
+        // Create the source node...
+        let source = create_node(
+            &conn,
+            &CreateNodeParams {
+                labels: vec!["Person".to_string()],
+                props: [
+                    ("name".to_string(), serde_json::json!("Alice")),
+                    ("age".to_string(), serde_json::json!(30)),
+                ]
+                .into(),
+                ..Default::default()
+            },
         )
-        .await?
-        .query(libsql::params![node_id])
-        .await?;
+        .await
+        .unwrap();
 
-    // Add them to a map...
-    let mut map = HashMap::new();
-    while let Some(row) = rows.next().await? {
-        let key: String = row.get(0)?;
-        let value: String = row.get(1)?;
-        map.insert(key, serde_json::from_str(&value)?);
+        // Clone it, with an extra label and prop...
+        let (labels, props) = NodeTemplate::from(
+            get_node(
+                &conn,
+                &GetNodeParams {
+                    id: source.id.clone(),
+                    with_props: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap(),
+        )
+        .apply(
+            vec!["Employee".to_string()],
+            [("name".to_string(), serde_json::json!("Bob"))].into(),
+        );
+        let clone = create_node(
+            &conn,
+            &CreateNodeParams {
+                labels,
+                props,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_ne!(clone.id, source.id);
+        assert_eq!(clone.labels, vec!["Person", "Employee"]);
+        let props = clone.props.unwrap();
+        assert_eq!(props["age"], serde_json::json!(30));
+        assert_eq!(props["name"], serde_json::json!("Bob"));
     }
 
-    // Return the data!
-    Ok(map)
-}
+    #[tokio::test]
+    async fn test_compute_density() {
+        let conn = test_conn().await;
 
-pub struct GetEdgeParams {
-    pub id: String,
-    pub with_props: bool,
-}
+        // No nodes -- nothing is defined...
+        let res = compute_density(&conn).await.unwrap();
+        assert_eq!((res.nodes, res.edges), (0, 0));
+        assert_eq!(res.density, None);
+        assert_eq!(res.average_degree, None);
+        assert!(res.degree_distribution.is_empty());
 
-pub async fn get_edge(conn: &Connection, params: &GetEdgeParams) -> Result<DbEdge> {
-    // Get the edge...
-    let row = conn
-        .prepare(
-            "
-            SELECT id, edge_type, from_node, to_node, directed, created_at, updated_at
-            FROM edges
-            WHERE id = ?;
-            ",
-        )
-        .await?
-        .query_row(libsql::params![params.id.clone()])
-        .await?;
+        // One node -- there are no pairs, so density is undefined...
+        let a = add_node(&conn, &[]).await;
+        let res = compute_density(&conn).await.unwrap();
+        assert_eq!(res.density, None);
+        assert_eq!(res.average_degree, Some(0.0));
+        assert_eq!(
+            res.degree_distribution,
+            vec![DegreeBucket {
+                min: 0,
+                max: 0,
+                nodes: 1
+            }]
+        );
 
-    // Get the values...
-    let mut edge = de::from_row::<DbEdge>(&row)?;
+        // A directed edge and an undirected one between 4 nodes
+        // (3 ordered pairs of 12 are connected)...
+        let b = add_node(&conn, &[]).await;
+        let c = add_node(&conn, &[]).await;
+        add_node(&conn, &[]).await;
+        add_edge(&conn, "LINK", &a, &b, true).await;
+        add_edge(&conn, "LINK", &a, &c, false).await;
+        let res = compute_density(&conn).await.unwrap();
+        assert_eq!((res.nodes, res.edges), (4, 2));
+        assert_eq!(res.density, Some(0.25));
+        assert_eq!(res.average_degree, Some(1.0));
+        let buckets: Vec<_> = res
+            .degree_distribution
+            .iter()
+            .map(|b| (b.min, b.max, b.nodes))
+            .collect();
+        assert_eq!(buckets, vec![(0, 0, 1), (1, 1, 2), (2, 3, 1)]);
+    }
 
-    // Get the properties?
-    if params.with_props {
-        let props = get_edge_props(conn, &params.id).await?;
-        edge.props = Some(props);
+    #[test]
+    fn test_degree_bucket() {
+        assert_eq!(degree_bucket(0), (0, 0));
+        assert_eq!(degree_bucket(1), (1, 1));
+        assert_eq!(degree_bucket(3), (2, 3));
+        assert_eq!(degree_bucket(4), (4, 7));
+        assert_eq!(degree_bucket(100), (64, 127));
     }
 
-    // Return the data!
-    Ok(edge)
-}
+    #[tokio::test]
+    async fn test_degree_filter() {
+        let conn = test_conn().await;
 
-pub async fn get_edge_props(conn: &Connection, edge_id: &str) -> Result<HashMap<String, Value>> {
-    // Query the props in the database...
-    let mut rows = conn
-        .prepare(
-            "
-            SELECT key, value 
-            FROM edge_props 
-            WHERE edge_id = ?;
-            ",
-        )
-        .await?
-        .query(libsql::params![edge_id])
-        .await?;
+        // Build a small graph -- a hub with 4 edges, a node with 2,
+        // leaves with 1 or 2, and an orphan...
+        let hub = add_node(&conn, &["Node"]).await;
+        let mid = add_node(&conn, &["Node"]).await;
+        let a = add_node(&conn, &["Node"]).await;
+        let b = add_node(&conn, &["Node"]).await;
+        let c = add_node(&conn, &["Node"]).await;
+        let orphan = add_node(&conn, &["Node"]).await;
+        for (from, to, directed) in [
+            (&hub, &mid, true),
+            (&hub, &a, false),
+            (&b, &hub, true),
+            (&c, &hub, false),
+            (&mid, &a, true),
+        ] {
+            let params = CreateEdgeParams {
+                edge_type: "link".to_string(),
+                from_node: from.clone(),
+                to_node: to.clone(),
+                directed,
+                ..Default::default()
+            };
+            create_edge(&conn, &params).await.unwrap();
+        }
 
-    // Add them to a map...
-    let mut map = HashMap::new();
-    while let Some(row) = rows.next().await? {
-        let key: String = row.get(0)?;
-        let value: String = row.get(1)?;
-        map.insert(key, serde_json::from_str(&value)?);
+        // Only the hub (4) and the nodes with 2 edges are in [2, 4]...
+        let filter = NodeFilter {
+            min_degree: Some(2),
+            max_degree: Some(4),
+            ..Default::default()
+        };
+        let mut ids = find_node_ids(&conn, &filter).await.unwrap();
+        ids.sort();
+        let mut expected = vec![hub.clone(), mid.clone(), a.clone()];
+        expected.sort();
+        assert_eq!(ids, expected);
+
+        // Open-ended bounds...
+        let filter = NodeFilter {
+            max_degree: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(find_node_ids(&conn, &filter).await.unwrap(), vec![orphan]);
+        let filter = NodeFilter {
+            min_degree: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(find_node_ids(&conn, &filter).await.unwrap(), vec![hub]);
     }
 
-    // Return the data!
-    Ok(map)
-}
+    #[tokio::test]
+    async fn test_typed_prop_filters() {
+        let conn = test_conn().await;
 
-pub async fn get_node_edges_in(conn: &Connection, node_id: &str) -> Result<Vec<String>> {
-    // Query the props in the database...
-    let mut rows = conn
-        .prepare(
-            "
-            SELECT id 
-            FROM edges
-            WHERE to_node = ? OR (NOT directed AND from_node = ?);
-            ",
-        )
-        .await?
-        .query(libsql::params![node_id, node_id,])
-        .await?;
+        // Create a node with a number, a string, a bool, and a
+        // numeric-looking string...
+        let params = CreateNodeParams {
+            props: util::parse_props(
+                &[
+                    "age=30".to_string(),
+                    "name=Bob".to_string(),
+                    "active=true".to_string(),
+                    r#"zip="02139""#.to_string(),
+                ],
+                &util::ParsePropsOptions::default(),
+            )
+            .unwrap(),
+            ..Default::default()
+        };
+        let id = create_node(&conn, &params).await.unwrap().id;
 
-    // Add them to a map...
-    let mut out = Vec::new();
-    while let Some(row) = rows.next().await? {
-        let key: String = row.get(0)?;
-        out.push(key);
+        // Check which filters match...
+        let cases = [
+            ("age=30", util::PropType::Auto, true),
+            ("age=30", util::PropType::Number, true),
+            ("age=30", util::PropType::String, false),
+            ("name=Bob", util::PropType::Auto, true),
+            ("name=Bob", util::PropType::String, true),
+            ("active=true", util::PropType::Auto, true),
+            ("active=true", util::PropType::Bool, true),
+            ("active=true", util::PropType::String, false),
+            ("zip=02139", util::PropType::Auto, true),
+            ("zip=02139", util::PropType::String, true),
+            ("age=31", util::PropType::Number, false),
+        ];
+        for (arg, prop_type, matches) in cases {
+            let props = util::parse_prop_filters(&[arg.to_string()], prop_type).unwrap();
+            let filter = NodeFilter {
+                props: props.equals,
+                ..Default::default()
+            };
+            let found = find_node_ids(&conn, &filter).await.unwrap();
+            assert_eq!(
+                found == vec![id.clone()],
+                matches,
+                "{} ({:?})",
+                arg,
+                prop_type
+            );
+        }
     }
 
-    // Return the data!
-    Ok(out)
-}
+    #[tokio::test]
+    async fn test_create_edge_self_loop() {
+        let conn = test_conn().await;
+        let a = add_node(&conn, &[]).await;
+        let params = CreateEdgeParams {
+            edge_type: "LINK".to_string(),
+            from_node: a.clone(),
+            to_node: a.clone(),
+            directed: true,
+            ..Default::default()
+        };
 
-pub async fn get_node_edges_out(conn: &Connection, node_id: &str) -> Result<Vec<String>> {
-    // Query the props in the database...
-    let mut rows = conn
-        .prepare(
-            "
-            SELECT id 
-            FROM edges
-            WHERE from_node = ? OR (NOT directed AND to_node = ?);
-            ",
-        )
-        .await?
-        .query(libsql::params![node_id, node_id,])
-        .await?;
+        // Rejected by default...
+        let err = create_edge(&conn, &params).await.unwrap_err();
+        assert_eq!(error::kind(&err), Some(ErrorKind::Validation));
+        assert!(err.to_string().contains("--allow-self-loop"));
+        assert!(create_reciprocal_edges(&conn, &params).await.is_err());
+        let count = count_edges(&conn, &EdgeFilter::default()).await.unwrap();
+        assert_eq!(count, 0);
 
-    // Add them to a map...
-    let mut out = Vec::new();
-    while let Some(row) = rows.next().await? {
-        let key: String = row.get(0)?;
-        out.push(key);
+        // ...but allowed when opted in...
+        let params = CreateEdgeParams {
+            allow_self_loop: true,
+            ..params
+        };
+        let edge = create_edge(&conn, &params).await.unwrap();
+        assert_eq!((edge.from_node, edge.to_node), (a.clone(), a));
     }
 
-    // Return the data!
-    Ok(out)
-}
+    #[tokio::test]
+    async fn test_empty_graph_reads() {
+        let conn = test_conn().await;
 
-pub async fn update_node(conn: &Connection) -> Result<DbNode> {
-    todo!();
-}
+        // Lists and counts are empty...
+        let nodes = list_nodes(&conn, &ListNodesParams::default())
+            .await
+            .unwrap();
+        assert!(nodes.is_empty());
+        let edges = list_edges(&conn, &ListEdgesParams::default())
+            .await
+            .unwrap();
+        assert!(edges.is_empty());
+        let params = ListNodesParams {
+            sample: Some(3),
+            ..Default::default()
+        };
+        assert!(list_nodes(&conn, &params).await.unwrap().is_empty());
+        assert_eq!(count_nodes(&conn, &NodeFilter::default()).await.unwrap(), 0);
+        assert_eq!(count_edges(&conn, &EdgeFilter::default()).await.unwrap(), 0);
+        let groups = group_edges(&conn, &EdgeFilter::default(), EdgeGroupBy::EdgeType)
+            .await
+            .unwrap();
+        assert!(groups.is_empty());
+        assert!(find_node_ids(&conn, &NodeFilter::default())
+            .await
+            .unwrap()
+            .is_empty());
 
-pub async fn set_node_prop(conn: &Connection) -> Result<()> {
-    todo!();
-}
+        // Gets are "not found" errors...
+        let params = GetNodeParams {
+            id: "n-missing".to_string(),
+            ..Default::default()
+        };
+        let err = get_node(&conn, &params).await.unwrap_err();
+        assert_eq!(error::kind(&err), Some(ErrorKind::NotFound));
+        let params = GetEdgeParams {
+            id: "e-missing".to_string(),
+            ..Default::default()
+        };
+        let err = get_edge(&conn, &params).await.unwrap_err();
+        assert_eq!(error::kind(&err), Some(ErrorKind::NotFound));
+        for err in [
+            resolve_node_id(&conn, "n-").await.unwrap_err(),
+            resolve_edge_id(&conn, "e-").await.unwrap_err(),
+        ] {
+            assert_eq!(error::kind(&err), Some(ErrorKind::NotFound));
+        }
+        let params = GetNodesParams {
+            ids: vec!["n-missing".to_string()],
+            ..Default::default()
+        };
+        assert!(get_nodes(&conn, &params).await.unwrap().is_empty());
 
-pub async fn update_edge(conn: &Connection) -> Result<DbEdge> {
-    todo!();
-}
+        // Traversals find nothing...
+        assert!(
+            get_node_descendants(&conn, "n-missing", &EdgeFilter::default(), Some(10))
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            get_node_ancestors(&conn, "n-missing", &EdgeFilter::default(), Some(10))
+                .await
+                .unwrap()
+                .is_empty()
+        );
 
-pub async fn set_edge_prop(conn: &Connection) -> Result<()> {
-    todo!();
-}
+        // Graph-level reads report zeros (or nothing)...
+        let density = compute_density(&conn).await.unwrap();
+        assert_eq!((density.nodes, density.edges), (0, 0));
+        assert!(density.degree_distribution.is_empty());
+        assert!(list_graph_meta(&conn).await.unwrap().is_empty());
+        assert!(list_label_defaults(&conn).await.unwrap().is_empty());
+        assert!(get_graph_meta(&conn, "description")
+            .await
+            .unwrap()
+            .is_none());
+    }
 
-pub async fn delete_node(conn: &Connection) -> Result<()> {
-    todo!();
-}
+    #[tokio::test]
+    async fn test_get_nodes() {
+        let conn = test_conn().await;
+        let a = add_node(&conn, &["A"]).await;
+        let b = add_node(&conn, &["B"]).await;
+        let ids = vec![b.clone(), "n-missing".to_string(), a.clone(), b.clone()];
 
-pub async fn delete_node_prop(conn: &Connection) -> Result<()> {
-    todo!();
-}
+        // Leniently, the missing node is left out (and duplicates dropped)...
+        let params = GetNodesParams {
+            ids,
+            with_props: true,
+            ..Default::default()
+        };
+        let nodes = get_nodes(&conn, &params).await.unwrap();
+        let got: Vec<&str> = nodes.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(got, vec![b.as_str(), a.as_str()]);
+        assert!(nodes.iter().all(|n| n.props.is_some()));
 
-pub async fn delete_edge(conn: &Connection) -> Result<()> {
-    todo!();
-}
+        // ...but strictly, it's an error...
+        let params = GetNodesParams {
+            strict: true,
+            ..params
+        };
+        let err = get_nodes(&conn, &params).await.unwrap_err();
+        assert_eq!(error::kind(&err), Some(ErrorKind::NotFound));
+        assert!(err.to_string().contains("n-missing"));
+
+        // Archived nodes count as missing...
+        archive_node(&conn, &a).await.unwrap();
+        let params = GetNodesParams {
+            ids: vec![a.clone()],
+            ..Default::default()
+        };
+        assert!(get_nodes(&conn, &params).await.unwrap().is_empty());
+        let params = GetNodesParams {
+            include_archived: true,
+            ..params
+        };
+        assert_eq!(get_nodes(&conn, &params).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_get_incident_edges() {
+        let conn = test_conn().await;
+        let a = add_node(&conn, &["A"]).await;
+        let b = add_node(&conn, &["B"]).await;
+        let c = add_node(&conn, &["C", "D"]).await;
+        for (from, to, directed) in [(&a, &b, true), (&c, &a, false), (&a, &a, true)] {
+            let params = CreateEdgeParams {
+                edge_type: "link".to_string(),
+                from_node: from.clone(),
+                to_node: to.clone(),
+                directed,
+                props: [("w".to_string(), serde_json::json!(1))].into(),
+                allow_self_loop: true,
+                ..Default::default()
+            };
+            create_edge(&conn, &params).await.unwrap();
+        }
 
-pub async fn delete_edge_prop(conn: &Connection) -> Result<()> {
-    todo!();
+        // Get the batched version...
+        let edges = get_node_edges_out_expanded(&conn, &a, true).await.unwrap();
+        assert_eq!(edges.len(), 3);
+        let batched = get_incident_edges(&conn, &a, edges).await.unwrap();
+
+        // Assemble it naively, one query per item...
+        let edges = get_node_edges_out_expanded(&conn, &a, false).await.unwrap();
+        for (edge, got) in edges.iter().zip(batched.iter()) {
+            let other_id = if edge.from_node == a {
+                &edge.to_node
+            } else {
+                &edge.from_node
+            };
+            let params = GetNodeParams {
+                id: other_id.clone(),
+                with_props: false,
+                ..Default::default()
+            };
+            let other = get_node(&conn, &params).await.unwrap();
+            let props = get_edge_props(&conn, &edge.id).await.unwrap();
+
+            // They should match...
+            assert_eq!(got.edge.id, edge.id);
+            assert_eq!(got.edge.props.as_ref(), Some(&props));
+            let got_other = got.other_node.as_ref().unwrap();
+            assert_eq!(got_other.id, other.id);
+            assert_eq!(got_other.labels, other.labels);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_list_nodes_sample() {
+        let conn = test_conn().await;
+        for _ in 0..50 {
+            add_node(&conn, &["A"]).await;
+        }
+        add_node(&conn, &["B"]).await;
+
+        // The sample size is respected...
+        let sample = |label: &str, n: usize| {
+            let conn = &conn;
+            let params = ListNodesParams {
+                filter: NodeFilter {
+                    has_label: Some(label.to_string()),
+                    ..Default::default()
+                },
+                sample: Some(n),
+                ..Default::default()
+            };
+            async move {
+                let nodes = list_nodes(conn, &params).await.unwrap();
+                nodes.into_iter().map(|n| n.id).collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(sample("A", 10).await.len(), 10);
+
+        // ...within the filtered set...
+        assert_eq!(sample("B", 10).await.len(), 1);
+
+        // ...and repeated samples differ...
+        let first = sample("A", 10).await;
+        let mut differs = false;
+        for _ in 0..5 {
+            differs |= sample("A", 10).await != first;
+        }
+        assert!(differs);
+    }
+
+    #[tokio::test]
+    async fn test_configure_journal() {
+        // Create a file-backed database (in-memory dbs don't use WAL)...
+        let dir = tempfile::tempdir().unwrap();
+        let db = Builder::new_local(dir.path().join(DB_FILE_NAME))
+            .build()
+            .await
+            .unwrap();
+        let conn = db.connect().unwrap();
+
+        // Read a pragma's value back...
+        let pragma = |name: &'static str| {
+            let conn = &conn;
+            async move {
+                let mut rows = conn.query(&format!("PRAGMA {};", name), ()).await.unwrap();
+                let row = rows.next().await.unwrap().unwrap();
+                row.get_value(0).unwrap()
+            }
+        };
+
+        // Nothing changes without settings...
+        configure_journal(&conn, None, None).await.unwrap();
+        assert_eq!(
+            pragma("journal_mode").await,
+            libsql::Value::Text("delete".into())
+        );
+        assert_eq!(pragma("synchronous").await, libsql::Value::Integer(2));
+
+        // Apply them...
+        configure_journal(&conn, Some(JournalMode::Wal), Some(SynchronousMode::Normal))
+            .await
+            .unwrap();
+        assert_eq!(
+            pragma("journal_mode").await,
+            libsql::Value::Text("wal".into())
+        );
+        assert_eq!(pragma("synchronous").await, libsql::Value::Integer(1));
+    }
 }
@@ -0,0 +1,323 @@
+//! Handles comparing two graphs.
+use crate::db::{DbEdge, DbNode};
+use crate::export::GraphDump;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+
+/// A value that differs between the two graphs.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ValueChange {
+    pub before: Value,
+    pub after: Value,
+}
+
+/// The prop-level differences for a single node or edge.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct PropDiff {
+    pub added: BTreeMap<String, Value>,
+    pub removed: BTreeMap<String, Value>,
+    pub changed: BTreeMap<String, ValueChange>,
+}
+
+impl PropDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// A node or edge that exists in both graphs but differs.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ChangedItem {
+    pub id: String,
+
+    /// Differences in the item's non-prop fields (e.g. labels or edge type).
+    pub fields: BTreeMap<String, ValueChange>,
+
+    /// Differences in the item's props.
+    pub props: PropDiff,
+}
+
+/// The differences for one kind of item (nodes or edges).
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct ItemDiff {
+    /// IDs only in the "after" graph.
+    pub added: Vec<String>,
+
+    /// IDs only in the "before" graph.
+    pub removed: Vec<String>,
+
+    /// Items in both graphs with different content.
+    pub changed: Vec<ChangedItem>,
+}
+
+impl ItemDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// The differences between two graphs.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct GraphDiff {
+    pub nodes: ItemDiff,
+    pub edges: ItemDiff,
+}
+
+impl GraphDiff {
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty() && self.edges.is_empty()
+    }
+
+    /// Render a short, human-readable summary of the diff.
+    pub fn summary(&self) -> String {
+        let mut lines = Vec::new();
+        for (kind, diff) in [("Nodes", &self.nodes), ("Edges", &self.edges)] {
+            lines.push(format!(
+                "{}: {} added, {} removed, {} changed",
+                kind,
+                diff.added.len(),
+                diff.removed.len(),
+                diff.changed.len(),
+            ));
+            for id in diff.added.iter() {
+                lines.push(format!("  + {}", id));
+            }
+            for id in diff.removed.iter() {
+                lines.push(format!("  - {}", id));
+            }
+            for item in diff.changed.iter() {
+                let mut what: Vec<String> = item.fields.keys().cloned().collect();
+                what.extend(item.props.added.keys().map(|k| format!("+prop:{}", k)));
+                what.extend(item.props.removed.keys().map(|k| format!("-prop:{}", k)));
+                what.extend(item.props.changed.keys().map(|k| format!("~prop:{}", k)));
+                lines.push(format!("  ~ {} ({})", item.id, what.join(", ")));
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+/// The comparable content of a node or edge -- its ID, its
+/// non-prop fields, and its props.
+///
+/// Timestamps are ignored, since they'll naturally differ
+/// between graphs.
+struct Item<'a> {
+    id: &'a str,
+    fields: BTreeMap<String, Value>,
+    props: Option<&'a HashMap<String, Value>>,
+}
+
+fn node_item(node: &DbNode) -> Item<'_> {
+    let mut labels = node.labels.clone();
+    labels.sort();
+    Item {
+        id: &node.id,
+        fields: [("labels".to_string(), json!(labels))].into(),
+        props: node.props.as_ref(),
+    }
+}
+
+fn edge_item(edge: &DbEdge) -> Item<'_> {
+    Item {
+        id: &edge.id,
+        fields: [
+            ("edge_type".to_string(), json!(edge.edge_type)),
+            ("from_node".to_string(), json!(edge.from_node)),
+            ("to_node".to_string(), json!(edge.to_node)),
+            ("directed".to_string(), json!(edge.directed)),
+        ]
+        .into(),
+        props: edge.props.as_ref(),
+    }
+}
+
+/// Compare two sets of props.
+fn diff_props(
+    before: Option<&HashMap<String, Value>>,
+    after: Option<&HashMap<String, Value>>,
+) -> PropDiff {
+    let empty = HashMap::new();
+    let before = before.unwrap_or(&empty);
+    let after = after.unwrap_or(&empty);
+
+    let mut diff = PropDiff::default();
+    for (key, value) in after.iter() {
+        match before.get(key) {
+            None => {
+                diff.added.insert(key.clone(), value.clone());
+            }
+            Some(old) if old != value => {
+                diff.changed.insert(
+                    key.clone(),
+                    ValueChange {
+                        before: old.clone(),
+                        after: value.clone(),
+                    },
+                );
+            }
+            Some(_) => {}
+        }
+    }
+    for (key, value) in before.iter() {
+        if !after.contains_key(key) {
+            diff.removed.insert(key.clone(), value.clone());
+        }
+    }
+    diff
+}
+
+/// Compare two sets of items by ID.
+fn diff_items(before: Vec<Item>, after: Vec<Item>) -> ItemDiff {
+    let before: BTreeMap<&str, Item> = before.into_iter().map(|i| (i.id, i)).collect();
+    let after: BTreeMap<&str, Item> = after.into_iter().map(|i| (i.id, i)).collect();
+
+    let mut diff = ItemDiff::default();
+    for (id, item) in after.iter() {
+        let old = match before.get(id) {
+            Some(old) => old,
+            None => {
+                diff.added.push(id.to_string());
+                continue;
+            }
+        };
+
+        // Compare the fields...
+        let mut fields = BTreeMap::new();
+        for (key, value) in item.fields.iter() {
+            let old_value = old.fields.get(key).cloned().unwrap_or(Value::Null);
+            if &old_value != value {
+                fields.insert(
+                    key.clone(),
+                    ValueChange {
+                        before: old_value,
+                        after: value.clone(),
+                    },
+                );
+            }
+        }
+
+        // Compare the props...
+        let props = diff_props(old.props, item.props);
+
+        if !fields.is_empty() || !props.is_empty() {
+            diff.changed.push(ChangedItem {
+                id: id.to_string(),
+                fields,
+                props,
+            });
+        }
+    }
+    for id in before.keys() {
+        if !after.contains_key(id) {
+            diff.removed.push(id.to_string());
+        }
+    }
+    diff
+}
+
+/// Compare two graphs, describing the changes needed to go
+/// from `before` to `after`.
+///
+/// Nodes and edges are matched up by ID.
+pub fn diff_graphs(before: &GraphDump, after: &GraphDump) -> GraphDiff {
+    GraphDiff {
+        nodes: diff_items(
+            before.nodes.iter().map(node_item).collect(),
+            after.nodes.iter().map(node_item).collect(),
+        ),
+        edges: diff_items(
+            before.edges.iter().map(edge_item).collect(),
+            after.edges.iter().map(edge_item).collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn node(id: &str, labels: &[&str], props: &[(&str, Value)]) -> DbNode {
+        DbNode {
+            id: id.to_string(),
+            labels: labels.iter().map(|l| l.to_string()).collect(),
+            props: Some(
+                props
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.clone()))
+                    .collect(),
+            ),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn edge(id: &str, edge_type: &str, from: &str, to: &str) -> DbEdge {
+        DbEdge {
+            id: id.to_string(),
+            edge_type: edge_type.to_string(),
+            from_node: from.to_string(),
+            to_node: to.to_string(),
+            directed: true,
+            props: Some(HashMap::new()),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            valid_from: None,
+            valid_to: None,
+        }
+    }
+
+    #[test]
+    fn test_diff_graphs() {
+        let before = GraphDump {
+            nodes: vec![
+                node(
+                    "node-a",
+                    &["Person"],
+                    &[("name", json!("Alice")), ("age", json!(30))],
+                ),
+                node("node-b", &["Person"], &[]),
+                node("node-c", &["Person"], &[]),
+            ],
+            edges: vec![edge("edge-1", "knows", "node-a", "node-b")],
+        };
+        let after = GraphDump {
+            nodes: vec![
+                node(
+                    "node-a",
+                    &["Person"],
+                    &[("name", json!("Alicia")), ("x", json!(1))],
+                ),
+                node("node-b", &["Person"], &[]),
+                node("node-d", &["Pet"], &[]),
+            ],
+            edges: vec![edge("edge-1", "likes", "node-a", "node-b")],
+        };
+        let diff = diff_graphs(&before, &after);
+
+        // Nodes...
+        assert_eq!(diff.nodes.added, vec!["node-d"]);
+        assert_eq!(diff.nodes.removed, vec!["node-c"]);
+        assert_eq!(diff.nodes.changed.len(), 1);
+        let changed = &diff.nodes.changed[0];
+        assert_eq!(changed.id, "node-a");
+        assert!(changed.fields.is_empty());
+        assert_eq!(changed.props.added["x"], json!(1));
+        assert_eq!(changed.props.removed["age"], json!(30));
+        assert_eq!(changed.props.changed["name"].before, json!("Alice"));
+        assert_eq!(changed.props.changed["name"].after, json!("Alicia"));
+
+        // Edges...
+        assert!(diff.edges.added.is_empty() && diff.edges.removed.is_empty());
+        assert_eq!(diff.edges.changed.len(), 1);
+        assert_eq!(
+            diff.edges.changed[0].fields["edge_type"].after,
+            json!("likes")
+        );
+
+        // A graph doesn't differ from itself...
+        assert!(diff_graphs(&after, &after).is_empty());
+    }
+}
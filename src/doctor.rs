@@ -0,0 +1,150 @@
+//! Runs diagnostic checks against the graphctl setup.
+use anyhow::{anyhow, Context, Result};
+use graphctl::conf::{self, Config, DBType};
+use graphctl::{db, secrets};
+use std::path::Path;
+
+/// A single named check, and its outcome.
+pub struct Check {
+    pub name: String,
+    pub result: Result<()>,
+}
+
+impl Check {
+    /// Render the check as a line of the checklist.
+    pub fn render(&self) -> String {
+        match &self.result {
+            Ok(()) => format!("[PASS] {}", self.name),
+            Err(err) => format!("[FAIL] {}: {:#}", self.name, err),
+        }
+    }
+}
+
+/// A check to run, which is only evaluated once it's reached.
+type CheckFn<'a> = (String, Box<dyn Fn() -> Result<()> + 'a>);
+
+/// Run a list of checks, in order.
+fn run(checks: Vec<CheckFn>) -> Vec<Check> {
+    checks
+        .into_iter()
+        .map(|(name, f)| Check { name, result: f() })
+        .collect()
+}
+
+/// Run all of the checks for the given config directory.
+///
+/// Checks that depend on an earlier, failed check (e.g. connecting
+/// when the config file can't be read) are not run.
+pub async fn run_checks(conf_dir: &Path, busy_timeout_ms: u64) -> Vec<Check> {
+    // Check the config directory and file...
+    let mut results = run(vec![
+        (
+            format!("Config directory exists ({})", conf_dir.display()),
+            Box::new(|| check_is_dir(conf_dir)),
+        ),
+        (
+            "Config directory is writable".to_string(),
+            Box::new(|| check_writable(conf_dir)),
+        ),
+    ]);
+    let cfg = Config::read_from_file(conf_dir);
+    results.push(Check {
+        name: format!(
+            "Config file is readable ({})",
+            conf::get_config_file(conf_dir).display()
+        ),
+        result: cfg.as_ref().map(|_| ()).map_err(|err| anyhow!("{:#}", err)),
+    });
+    let cfg = match cfg {
+        Ok(cfg) => cfg,
+        Err(_) => return results,
+    };
+    let data_dir = cfg.db_dir();
+
+    // Check the pieces needed for the configured db type...
+    let mut checks: Vec<CheckFn> = Vec::new();
+    if matches!(cfg.db.db_type, DBType::Local | DBType::RemoteWithReplica) {
+        checks.push((
+            format!("Data directory exists ({})", data_dir.display()),
+            Box::new(|| check_is_dir(&data_dir)),
+        ));
+        checks.push((
+            "Data directory is writable".to_string(),
+            Box::new(|| check_writable(&data_dir)),
+        ));
+    }
+    if matches!(
+        cfg.db.db_type,
+        DBType::RemoteOnly | DBType::RemoteWithReplica
+    ) {
+        checks.push((
+            "Remote database URL is set".to_string(),
+            Box::new(|| match &cfg.db.remote_db_path {
+                Some(_) => Ok(()),
+                None => Err(anyhow!("No remote database URL in the config file")),
+            }),
+        ));
+    }
+    if cfg.db.required_secrets().remote_db_token {
+        checks.push((
+            "Remote auth token is in the keyring".to_string(),
+            Box::new(|| secrets::get_remote_db_auth_token().map(|_| ())),
+        ));
+    }
+    if cfg.db.encrypt_replica {
+        checks.push((
+            "Encryption key is available (env var or keyring)".to_string(),
+            Box::new(|| secrets::resolve_local_db_encryption_key(None).map(|_| ())),
+        ));
+    }
+    let failed = {
+        let res = run(checks);
+        let failed = res.iter().any(|c| c.result.is_err());
+        results.extend(res);
+        failed
+    };
+    if failed {
+        return results;
+    }
+
+    // Finally, try connecting and running a trivial query...
+    results.push(Check {
+        name: "Database connection works".to_string(),
+        result: check_connection(&cfg, busy_timeout_ms).await,
+    });
+    results
+}
+
+/// Make sure the path exists and is a directory.
+fn check_is_dir(path: &Path) -> Result<()> {
+    if !path.exists() {
+        return Err(anyhow!("Doesn't exist"));
+    }
+    if !path.is_dir() {
+        return Err(anyhow!("Exists but isn't a directory"));
+    }
+    Ok(())
+}
+
+/// Make sure a file can be created in the directory.
+fn check_writable(path: &Path) -> Result<()> {
+    let probe = path.join(".graphctl-doctor");
+    std::fs::write(&probe, b"").context("Could not create a file")?;
+    std::fs::remove_file(&probe).context("Could not remove a file")?;
+    Ok(())
+}
+
+/// Connect to the database and run `SELECT 1`.
+///
+/// Note - Migrations aren't run, since the doctor shouldn't change anything...
+async fn check_connection(cfg: &Config, busy_timeout_ms: u64) -> Result<()> {
+    let opts = db::OpenOptions {
+        busy_timeout_ms,
+        migrate: false,
+        audit: false,
+        ..Default::default()
+    };
+    let (_db, conn) = db::open_and_migrate(cfg, &opts).await?;
+    conn.query("SELECT 1;", ()).await?;
+    Ok(())
+}
@@ -0,0 +1,139 @@
+//! Handles classifying errors, so the CLI can exit with a
+//! different code for each class of failure.
+//!
+//! | Code | Meaning                                            |
+//! |------|----------------------------------------------------|
+//! | 0    | Success                                            |
+//! | 1    | Any other error                                    |
+//! | 2    | Config error (missing, unreadable, or invalid)     |
+//! | 3    | Not found (e.g. no node or edge with that ID)      |
+//! | 4    | Connection error (couldn't open/reach the db)      |
+//! | 5    | Validation error (bad arguments or input)          |
+//! | 130  | Cancelled (e.g. by Ctrl-C)                         |
+use std::fmt;
+
+/// The exit code for errors that don't fit into another class.
+pub const EXIT_OTHER: i32 = 1;
+
+/// A class of error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The config is missing, unreadable, or invalid.
+    Config,
+
+    /// The requested node or edge doesn't exist.
+    NotFound,
+
+    /// The database couldn't be opened or reached.
+    Connection,
+
+    /// The user's input is invalid.
+    Validation,
+
+    /// The operation was cancelled (e.g. by Ctrl-C).
+    Cancelled,
+}
+
+impl ErrorKind {
+    /// The exit code the CLI should use for this kind of error.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::Config => 2,
+            ErrorKind::NotFound => 3,
+            ErrorKind::Connection => 4,
+            ErrorKind::Validation => 5,
+            ErrorKind::Cancelled => 130,
+        }
+    }
+}
+
+/// An error tagged with its class.
+///
+/// These are wrapped in `anyhow::Error`s, so they can still
+/// have context added to them.
+#[derive(Debug)]
+pub struct Error {
+    pub kind: ErrorKind,
+    message: String,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind, message: impl fmt::Display) -> Self {
+        Error {
+            kind,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Create a config error.
+pub fn config(message: impl fmt::Display) -> anyhow::Error {
+    Error::new(ErrorKind::Config, message).into()
+}
+
+/// Create a not found error.
+pub fn not_found(message: impl fmt::Display) -> anyhow::Error {
+    Error::new(ErrorKind::NotFound, message).into()
+}
+
+/// Create a connection error.
+pub fn connection(message: impl fmt::Display) -> anyhow::Error {
+    Error::new(ErrorKind::Connection, message).into()
+}
+
+/// Create a validation error.
+pub fn validation(message: impl fmt::Display) -> anyhow::Error {
+    Error::new(ErrorKind::Validation, message).into()
+}
+
+/// Create a cancelled error.
+pub fn cancelled(message: impl fmt::Display) -> anyhow::Error {
+    Error::new(ErrorKind::Cancelled, message).into()
+}
+
+/// Get the class of an error, if it (or anything in its chain
+/// of causes) has one.
+pub fn kind(err: &anyhow::Error) -> Option<ErrorKind> {
+    err.chain()
+        .find_map(|e| e.downcast_ref::<Error>())
+        .map(|e| e.kind)
+}
+
+/// Get the exit code the CLI should use for an error.
+pub fn exit_code(err: &anyhow::Error) -> i32 {
+    kind(err).map(ErrorKind::exit_code).unwrap_or(EXIT_OTHER)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_exit_code() {
+        // Untagged errors...
+        assert_eq!(exit_code(&anyhow::anyhow!("oops")), EXIT_OTHER);
+
+        // Tagged errors...
+        assert_eq!(exit_code(&config("bad config")), 2);
+        assert_eq!(exit_code(&not_found("no node")), 3);
+        assert_eq!(exit_code(&connection("no db")), 4);
+        assert_eq!(exit_code(&validation("bad input")), 5);
+        assert_eq!(exit_code(&cancelled("cancelled")), 130);
+
+        // Tagged errors with context added...
+        let err = Err::<(), _>(not_found("no node"))
+            .context("Failed to get node")
+            .unwrap_err();
+        assert_eq!(kind(&err), Some(ErrorKind::NotFound));
+        assert_eq!(err.to_string(), "Failed to get node");
+    }
+}
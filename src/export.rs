@@ -0,0 +1,1513 @@
+//! Handles exporting the graph to (and reading it back from) a dump.
+use crate::db::{self, DbEdge, DbNode};
+use crate::error::{self, ErrorKind};
+use crate::util;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use libsql::Connection;
+use quick_xml::events::{BytesDecl, BytesText, Event};
+use quick_xml::Writer;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+
+/// A full dump of the graph's nodes and edges (including their props).
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct GraphDump {
+    pub nodes: Vec<DbNode>,
+    pub edges: Vec<DbEdge>,
+}
+
+/// Export every node and edge in the graph.
+pub async fn export_graph(conn: &Connection) -> Result<GraphDump> {
+    export_graph_since(conn, None).await
+}
+
+/// Export the nodes and edges created or updated after `since`
+/// (or every node and edge, if it's `None`).
+///
+/// Deletes aren't captured -- a node or edge that's been deleted
+/// is simply missing from the export.
+pub async fn export_graph_since(
+    conn: &Connection,
+    since: Option<DateTime<Utc>>,
+) -> Result<GraphDump> {
+    let params = ExportParams {
+        since,
+        ..Default::default()
+    };
+    export_graph_filtered(conn, &params).await
+}
+
+/// Which edges are exported, when the nodes are filtered.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportEdges {
+    /// Only edges with both ends in the exported nodes.
+    #[default]
+    Within,
+
+    /// Edges with at least one end in the exported nodes.
+    Touching,
+
+    /// No edges.
+    None,
+}
+
+#[derive(Debug, Default)]
+pub struct ExportParams {
+    /// Only export nodes and edges created or updated after this time.
+    pub since: Option<DateTime<Utc>>,
+
+    /// Only export the nodes matching this filter. If not set,
+    /// every node is exported.
+    pub node_filter: Option<db::NodeFilter>,
+
+    /// With a node filter, which edges are exported. Without
+    /// one, every edge is exported (unless this is `None`).
+    pub edges: ExportEdges,
+}
+
+/// Export a (possibly filtered) slice of the graph.
+///
+/// Edges are matched against every node the filter selects, not just
+/// the ones changed after `since`, so an incremental export of a slice
+/// still includes new edges between older nodes.
+pub async fn export_graph_filtered(conn: &Connection, params: &ExportParams) -> Result<GraphDump> {
+    // Get the nodes...
+    let node_filter = db::NodeFilter {
+        updated_after: params.since,
+        ..params.node_filter.clone().unwrap_or_default()
+    };
+    let list_params = db::ListNodesParams {
+        filter: node_filter,
+        ..Default::default()
+    };
+    let nodes = db::list_nodes(conn, &list_params).await?;
+
+    // Should any edges be exported?
+    if params.edges == ExportEdges::None {
+        return Ok(GraphDump {
+            nodes,
+            edges: vec![],
+        });
+    }
+
+    // Get the edges...
+    let list_params = db::ListEdgesParams {
+        filter: db::EdgeFilter {
+            updated_after: params.since,
+            include_archived: params
+                .node_filter
+                .as_ref()
+                .is_some_and(|f| f.include_archived),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut edges = db::list_edges(conn, &list_params).await?;
+
+    // Only keep the edges attached to the slice of nodes...
+    if let Some(filter) = &params.node_filter {
+        let ids: HashSet<String> = db::find_node_ids(conn, filter).await?.into_iter().collect();
+        edges.retain(|e| {
+            let (from, to) = (ids.contains(&e.from_node), ids.contains(&e.to_node));
+            match params.edges {
+                ExportEdges::Within => from && to,
+                ExportEdges::Touching => from || to,
+                ExportEdges::None => false,
+            }
+        });
+    }
+
+    // Done!
+    Ok(GraphDump { nodes, edges })
+}
+
+/// Copy the whole graph from one database to another, e.g. when moving
+/// it from a local database to a remote one.
+///
+/// Every node and edge is copied (keeping their IDs and timestamps, and
+/// whether they're archived), along with the node templates, label
+/// defaults, graph metadata, and audit log.
+///
+/// The destination has to be empty. Everything is copied in a single
+/// transaction, so a failure part-way through copies nothing.
+pub async fn copy_graph(
+    from: &Connection,
+    to: &Connection,
+    cancel: &CancellationToken,
+) -> Result<ImportResult> {
+    let all_nodes = db::NodeFilter {
+        include_archived: true,
+        ..Default::default()
+    };
+    let all_edges = db::EdgeFilter {
+        include_archived: true,
+        ..Default::default()
+    };
+
+    // Make sure the destination is empty...
+    let nodes = db::count_nodes(to, &all_nodes).await?;
+    let edges = db::count_edges(to, &all_edges).await?;
+    if nodes > 0 || edges > 0 {
+        return Err(error::validation(format!(
+            "The destination database isn't empty (it has {} nodes and {} edges).",
+            nodes, edges
+        )));
+    }
+
+    // Export the graph (including archived items) and the rest of
+    // its state...
+    let params = ExportParams {
+        node_filter: Some(all_nodes),
+        ..Default::default()
+    };
+    let dump = export_graph_filtered(from, &params).await?;
+    let extras = db::read_graph_extras(from).await?;
+
+    // ...and copy it into the destination...
+    db::insert_graph_copy(to, &dump.nodes, &dump.edges, &extras, cancel)
+        .await
+        .context("Failed to copy the graph")?;
+
+    // Done!
+    Ok(ImportResult {
+        nodes: dump.nodes.len(),
+        edges: dump.edges.len(),
+        ..Default::default()
+    })
+}
+
+/// A single line of an NDJSON dump -- a node or an edge, tagged
+/// with its kind.
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum DumpLine<'a> {
+    Node(&'a DbNode),
+    Edge(&'a DbEdge),
+}
+
+/// A single line of an NDJSON dump, when reading it back in.
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum DumpItem {
+    Node(DbNode),
+    Edge(DbEdge),
+}
+
+/// The default number of lines committed at a time, when importing
+/// with a checkpoint.
+pub const DEFAULT_IMPORT_BATCH_SIZE: usize = 1000;
+
+#[derive(Debug, Default)]
+pub struct ImportParams {
+    /// A file recording how many lines have been imported so far.
+    ///
+    /// If set, lines are committed in batches (rather than all at once)
+    /// and the file is updated after each batch. Re-running with the same
+    /// checkpoint skips the lines that were already imported.
+    pub checkpoint: Option<PathBuf>,
+
+    /// With a checkpoint (or a parallel import), the number of lines
+    /// committed at a time. Defaults to `DEFAULT_IMPORT_BATCH_SIZE`.
+    pub batch_size: Option<usize>,
+
+    /// Compress prop values bigger than this many bytes.
+    /// If not set, values aren't compressed.
+    pub compress_props_over: Option<usize>,
+
+    /// Cancels the import, rolling back the current batch.
+    pub cancel: CancellationToken,
+}
+
+/// What an import did.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct ImportResult {
+    /// The number of nodes imported.
+    pub nodes: usize,
+
+    /// The number of edges imported.
+    pub edges: usize,
+
+    /// The number of lines skipped, since the checkpoint said
+    /// they'd already been imported.
+    pub resumed_from_line: usize,
+}
+
+/// Read the number of lines already imported from a checkpoint file
+/// (or 0, if it doesn't exist yet).
+fn read_checkpoint(path: &Path) -> Result<usize> {
+    if !path.exists() {
+        return Ok(0);
+    }
+    let data = std::fs::read_to_string(path)
+        .context(format!("Failed to read checkpoint file {:?}", path))?;
+    data.trim().parse().map_err(|_| {
+        error::validation(format!(
+            "Invalid checkpoint file {:?}. Delete it to start the import over.",
+            path
+        ))
+    })
+}
+
+/// Import an NDJSON dump (as written by `write_ndjson_dump`), keeping
+/// the nodes' and edges' IDs and timestamps.
+///
+/// Without a checkpoint, everything is imported in a single transaction,
+/// so a failure part-way through imports nothing. With a checkpoint, each
+/// batch is committed separately -- a failure keeps the batches that were
+/// already committed, and re-running resumes after them.
+pub async fn import_ndjson(
+    conn: &Connection,
+    data: &str,
+    params: &ImportParams,
+) -> Result<ImportResult> {
+    let lines: Vec<&str> = data.lines().collect();
+
+    // Where should the import start, and how many lines at a time?
+    let start = match &params.checkpoint {
+        Some(path) => read_checkpoint(path)?.min(lines.len()),
+        None => 0,
+    };
+    let batch_size = match &params.checkpoint {
+        Some(_) => params
+            .batch_size
+            .unwrap_or(DEFAULT_IMPORT_BATCH_SIZE)
+            .max(1),
+        None => lines.len().max(1),
+    };
+
+    // Import the lines, a batch at a time...
+    let mut res = ImportResult {
+        resumed_from_line: start,
+        ..Default::default()
+    };
+    let mut batch_start = start;
+    while batch_start < lines.len() {
+        let batch_end = (batch_start + batch_size).min(lines.len());
+
+        // Parse the batch...
+        let mut nodes = Vec::new();
+        let mut edges = Vec::new();
+        for (i, line) in lines[batch_start..batch_end].iter().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let item = serde_json::from_str(line).map_err(|e| {
+                error::validation(format!("Invalid dump line {}: {}", batch_start + i + 1, e))
+            })?;
+            match item {
+                DumpItem::Node(node) => nodes.push(node),
+                DumpItem::Edge(edge) => edges.push(edge),
+            }
+        }
+
+        // Insert it...
+        db::insert_items(
+            conn,
+            &nodes,
+            &edges,
+            params.compress_props_over,
+            &params.cancel,
+        )
+        .await
+        .context(format!(
+            "Failed to import lines {}-{}",
+            batch_start + 1,
+            batch_end
+        ))?;
+        res.nodes += nodes.len();
+        res.edges += edges.len();
+
+        // Record the progress...
+        if let Some(path) = &params.checkpoint {
+            std::fs::write(path, format!("{}\n", batch_end))
+                .context(format!("Failed to write checkpoint file {:?}", path))?;
+        }
+        batch_start = batch_end;
+    }
+
+    // Done!
+    Ok(res)
+}
+
+/// The items in a batch of a parallel import.
+enum BatchItems {
+    Nodes(Vec<DbNode>),
+    Edges(Vec<DbEdge>),
+}
+
+/// A batch of a parallel import, with the line numbers (starting
+/// from 1) of its first and last items.
+struct ImportBatch {
+    first_line: usize,
+    last_line: usize,
+    items: BatchItems,
+}
+
+/// Split items (with their line numbers) into batches.
+fn into_batches<T>(
+    items: Vec<(usize, T)>,
+    batch_size: usize,
+    wrap: fn(Vec<T>) -> BatchItems,
+) -> VecDeque<ImportBatch> {
+    let mut batches = VecDeque::new();
+    let mut items = items.into_iter().peekable();
+    while items.peek().is_some() {
+        let chunk: Vec<(usize, T)> = items.by_ref().take(batch_size).collect();
+        batches.push_back(ImportBatch {
+            first_line: chunk[0].0,
+            last_line: chunk[chunk.len() - 1].0,
+            items: wrap(chunk.into_iter().map(|(_, item)| item).collect()),
+        });
+    }
+    batches
+}
+
+/// Insert batches using one worker per connection, each taking the next
+/// batch off the shared queue and committing it in its own transaction.
+///
+/// The first failure stops the other workers (rolling back their current
+/// batch) and is returned.
+async fn insert_batches_parallel(
+    conns: &[Connection],
+    batches: VecDeque<ImportBatch>,
+    compress_props_over: Option<usize>,
+    cancel: &CancellationToken,
+) -> Result<()> {
+    let queue = Arc::new(Mutex::new(batches));
+    let stop = cancel.child_token();
+    let mut workers = JoinSet::new();
+    for conn in conns {
+        let conn = conn.clone();
+        let queue = queue.clone();
+        let stop = stop.clone();
+        workers.spawn(async move {
+            loop {
+                // Get the next batch (if there's one left)...
+                let Some(batch) = queue.lock().unwrap().pop_front() else {
+                    return Ok(());
+                };
+
+                // Insert it...
+                let (nodes, edges, kind) = match &batch.items {
+                    BatchItems::Nodes(nodes) => (&nodes[..], &[][..], "nodes"),
+                    BatchItems::Edges(edges) => (&[][..], &edges[..], "edges"),
+                };
+                let res = db::insert_items(&conn, nodes, edges, compress_props_over, &stop).await;
+                if let Err(err) = res {
+                    stop.cancel();
+                    return Err(err.context(format!(
+                        "Failed to import {} from lines {}-{}",
+                        kind, batch.first_line, batch.last_line
+                    )));
+                }
+            }
+        });
+    }
+
+    // Wait for the workers, keeping the error that stopped them...
+    // Note - Once one worker fails, the others stop with a "cancelled"
+    // error, which isn't the interesting one...
+    let mut first_err: Option<anyhow::Error> = None;
+    while let Some(res) = workers.join_next().await {
+        let Err(err) = res.context("An import worker panicked")? else {
+            continue;
+        };
+        let is_cancel = error::kind(&err) == Some(ErrorKind::Cancelled);
+        let prev_is_cancel = first_err
+            .as_ref()
+            .is_some_and(|prev| error::kind(prev) == Some(ErrorKind::Cancelled));
+        if first_err.is_none() || (prev_is_cancel && !is_cancel) {
+            first_err = Some(err);
+        }
+    }
+    match first_err {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+/// Import an NDJSON dump (like `import_ndjson`), inserting batches in
+/// parallel -- one worker per connection.
+///
+/// This is for remote databases, where a sequential import spends most
+/// of its time waiting on round trips. All of the nodes are imported
+/// before any of the edges, so edges never arrive before their
+/// endpoints.
+///
+/// Note - Each batch is committed in its own transaction, so a failure
+/// keeps the batches that were already committed (in no particular
+/// order). Checkpoints aren't supported.
+pub async fn import_ndjson_parallel(
+    conns: &[Connection],
+    data: &str,
+    params: &ImportParams,
+) -> Result<ImportResult> {
+    if conns.is_empty() {
+        return Err(error::validation(
+            "A parallel import needs at least one worker.",
+        ));
+    }
+    if params.checkpoint.is_some() {
+        return Err(error::validation(
+            "Checkpoints aren't supported for parallel imports.",
+        ));
+    }
+    let batch_size = params
+        .batch_size
+        .unwrap_or(DEFAULT_IMPORT_BATCH_SIZE)
+        .max(1);
+
+    // Parse the whole file up front, so a bad line imports nothing...
+    let mut nodes = Vec::new();
+    let mut edges = Vec::new();
+    for (i, line) in data.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let item = serde_json::from_str(line)
+            .map_err(|e| error::validation(format!("Invalid dump line {}: {}", i + 1, e)))?;
+        match item {
+            DumpItem::Node(node) => nodes.push((i + 1, node)),
+            DumpItem::Edge(edge) => edges.push((i + 1, edge)),
+        }
+    }
+    let res = ImportResult {
+        nodes: nodes.len(),
+        edges: edges.len(),
+        ..Default::default()
+    };
+
+    // Import the nodes, then the edges...
+    let node_batches = into_batches(nodes, batch_size, BatchItems::Nodes);
+    insert_batches_parallel(
+        conns,
+        node_batches,
+        params.compress_props_over,
+        &params.cancel,
+    )
+    .await?;
+    let edge_batches = into_batches(edges, batch_size, BatchItems::Edges);
+    insert_batches_parallel(
+        conns,
+        edge_batches,
+        params.compress_props_over,
+        &params.cancel,
+    )
+    .await?;
+
+    // Done!
+    Ok(res)
+}
+
+/// A problem with a single line of an import file.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ImportError {
+    /// The line number (starting from 1).
+    pub line: usize,
+
+    /// The path of the field with the problem (e.g. `props.name`), if
+    /// it's about a specific field.
+    pub field: Option<String>,
+
+    pub message: String,
+}
+
+#[derive(Debug, Default)]
+pub struct ValidateImportParams {
+    /// The maximum size of a prop value, in bytes.
+    /// Defaults to `DEFAULT_MAX_PROP_BYTES`.
+    pub max_prop_bytes: Option<usize>,
+
+    /// Report nodes without any labels.
+    pub require_label: bool,
+}
+
+/// The fields every node line needs.
+const NODE_FIELDS: [&str; 4] = ["id", "labels", "created_at", "updated_at"];
+
+/// The fields every edge line needs.
+const EDGE_FIELDS: [&str; 7] = [
+    "id",
+    "edge_type",
+    "from_node",
+    "to_node",
+    "directed",
+    "created_at",
+    "updated_at",
+];
+
+/// Check an NDJSON dump (as read by `import_ndjson`) without importing
+/// anything, returning every problem found rather than just the first.
+///
+/// Besides checking that each line parses, this checks for duplicate or
+/// existing IDs, oversized props, missing labels (if required), and edges
+/// whose endpoints aren't in the file or the graph.
+pub async fn validate_ndjson(
+    conn: &Connection,
+    data: &str,
+    params: &ValidateImportParams,
+) -> Result<Vec<ImportError>> {
+    let mut errors = Vec::new();
+    let mut err = |line: usize, field: Option<&str>, message: String| {
+        errors.push(ImportError {
+            line,
+            field: field.map(|f| f.to_string()),
+            message,
+        })
+    };
+
+    // Parse the lines, checking their fields...
+    let mut nodes = Vec::new();
+    let mut edges = Vec::new();
+    for (i, line) in data.lines().enumerate() {
+        let line_no = i + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value: Value = match serde_json::from_str(line) {
+            Ok(v) => v,
+            Err(e) => {
+                err(line_no, None, format!("Invalid JSON: {}", e));
+                continue;
+            }
+        };
+        let required: &[&str] = match value.get("kind").and_then(Value::as_str) {
+            Some("node") => &NODE_FIELDS,
+            Some("edge") => &EDGE_FIELDS,
+            _ => {
+                err(
+                    line_no,
+                    Some("kind"),
+                    "Expected `kind` to be \"node\" or \"edge\".".to_string(),
+                );
+                continue;
+            }
+        };
+        let missing: Vec<&&str> = required
+            .iter()
+            .filter(|f| value.get(**f).is_none_or(Value::is_null))
+            .collect();
+        for field in missing.iter() {
+            err(line_no, Some(field), "Missing required field.".to_string());
+        }
+        if !missing.is_empty() {
+            continue;
+        }
+        match serde_json::from_value(value) {
+            Ok(DumpItem::Node(node)) => nodes.push((line_no, node)),
+            Ok(DumpItem::Edge(edge)) => edges.push((line_no, edge)),
+            Err(e) => err(line_no, None, e.to_string()),
+        }
+    }
+
+    // Check the nodes...
+    let mut node_ids = HashSet::new();
+    let ids: Vec<String> = nodes.iter().map(|(_, n)| n.id.clone()).collect();
+    let existing = db::get_nodes_by_ids(conn, &ids).await?;
+    for (line_no, node) in nodes.iter() {
+        if node.id.trim().is_empty() {
+            err(*line_no, Some("id"), "Node ID can't be empty.".to_string());
+        } else if !node_ids.insert(node.id.as_str()) {
+            err(
+                *line_no,
+                Some("id"),
+                format!("Node '{}' appears more than once.", node.id),
+            );
+        } else if existing.contains_key(&node.id) {
+            err(
+                *line_no,
+                Some("id"),
+                format!("Node '{}' already exists.", node.id),
+            );
+        }
+        if params.require_label && node.labels.iter().all(|l| l.trim().is_empty()) {
+            err(
+                *line_no,
+                Some("labels"),
+                "Nodes must have at least one label.".to_string(),
+            );
+        }
+        for (field, msg) in check_line_props(node.props.as_ref(), params.max_prop_bytes) {
+            err(*line_no, Some(&field), msg);
+        }
+    }
+
+    // Check the edges...
+    let mut edge_ids = HashSet::new();
+    let ids: Vec<String> = edges
+        .iter()
+        .flat_map(|(_, e)| [e.from_node.clone(), e.to_node.clone()])
+        .filter(|id| !node_ids.contains(id.as_str()))
+        .collect();
+    let endpoints = db::get_nodes_by_ids(conn, &ids).await?;
+    for (line_no, edge) in edges.iter() {
+        if edge.id.trim().is_empty() {
+            err(*line_no, Some("id"), "Edge ID can't be empty.".to_string());
+        } else if !edge_ids.insert(edge.id.as_str()) {
+            err(
+                *line_no,
+                Some("id"),
+                format!("Edge '{}' appears more than once.", edge.id),
+            );
+        } else if db::check_edge_exists(conn, &edge.id).await? {
+            err(
+                *line_no,
+                Some("id"),
+                format!("Edge '{}' already exists.", edge.id),
+            );
+        }
+        if edge.edge_type.trim().is_empty() {
+            err(
+                *line_no,
+                Some("edge_type"),
+                "Edge type can't be empty.".to_string(),
+            );
+        }
+        for (field, id) in [("from_node", &edge.from_node), ("to_node", &edge.to_node)] {
+            if !node_ids.contains(id.as_str()) && !endpoints.contains_key(id) {
+                err(
+                    *line_no,
+                    Some(field),
+                    format!("Node '{}' isn't in the file or the graph.", id),
+                );
+            }
+        }
+        for (field, msg) in check_line_props(edge.props.as_ref(), params.max_prop_bytes) {
+            err(*line_no, Some(&field), msg);
+        }
+    }
+
+    // Report them in file order...
+    errors.sort_by_key(|e| e.line);
+    Ok(errors)
+}
+
+/// Check each prop value's size, returning the field path and
+/// message for each one that's over the limit.
+fn check_line_props(
+    props: Option<&HashMap<String, Value>>,
+    max_bytes: Option<usize>,
+) -> Vec<(String, String)> {
+    let mut props: Vec<(&String, &Value)> = props.into_iter().flatten().collect();
+    props.sort_by_key(|(key, _)| *key);
+    props
+        .into_iter()
+        .filter_map(|(key, value)| {
+            let single = HashMap::from([(key.clone(), value.clone())]);
+            db::check_prop_sizes(&single, max_bytes)
+                .err()
+                .map(|e| (format!("props.{}", key), e.to_string()))
+        })
+        .collect()
+}
+
+/// Write a dump as NDJSON, one node or edge per line (nodes first).
+///
+/// Each line ends in a newline, so dumps can be appended to
+/// the same file.
+pub fn write_ndjson_dump<W: Write>(w: &mut W, dump: &GraphDump) -> Result<()> {
+    for node in dump.nodes.iter() {
+        serde_json::to_writer(&mut *w, &DumpLine::Node(node))?;
+        w.write_all(b"\n")?;
+    }
+    for edge in dump.edges.iter() {
+        serde_json::to_writer(&mut *w, &DumpLine::Edge(edge))?;
+        w.write_all(b"\n")?;
+    }
+    w.flush()?;
+    Ok(())
+}
+
+/// Read a dump (as written by `export_graph`) from a JSON file.
+pub fn read_dump(path: &Path) -> Result<GraphDump> {
+    let data =
+        std::fs::read_to_string(path).context(format!("Failed to read dump file {:?}", path))?;
+    let dump =
+        serde_json::from_str(&data).context(format!("Failed to parse dump file {:?}", path))?;
+    Ok(dump)
+}
+
+/// The GraphML XML namespace.
+const GRAPHML_NS: &str = "http://graphml.graphdrawing.org/xmlns";
+
+/// A GraphML `<key>` -- the declaration of a data attribute
+/// for nodes or edges.
+struct GraphmlKey {
+    id: String,
+    domain: &'static str,
+    name: String,
+    attr_type: &'static str,
+}
+
+/// Get the GraphML type for a set of values (falling back to
+/// `string`, which is also used for JSON arrays and objects).
+fn graphml_type(values: &[&Value]) -> &'static str {
+    let values: Vec<&&Value> = values.iter().filter(|v| !v.is_null()).collect();
+    if values.is_empty() {
+        "string"
+    } else if values.iter().all(|v| v.is_boolean()) {
+        "boolean"
+    } else if values.iter().all(|v| v.is_i64()) {
+        "long"
+    } else if values.iter().all(|v| v.is_number()) {
+        "double"
+    } else {
+        "string"
+    }
+}
+
+/// Format a prop value as GraphML data.
+fn graphml_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+/// Declare a key for every prop key used by a set of nodes or edges.
+fn graphml_prop_keys<'a>(
+    props: impl Iterator<Item = Option<&'a HashMap<String, Value>>>,
+    domain: &'static str,
+    keys: &mut Vec<GraphmlKey>,
+) {
+    // Group the values by key...
+    let mut values: BTreeMap<&str, Vec<&Value>> = BTreeMap::new();
+    for (key, value) in props.flatten().flatten() {
+        values.entry(key).or_default().push(value);
+    }
+
+    // Add a key for each...
+    for (name, values) in values.iter() {
+        keys.push(GraphmlKey {
+            id: format!("d{}", keys.len()),
+            domain,
+            name: name.to_string(),
+            attr_type: graphml_type(values),
+        });
+    }
+}
+
+/// Render a dump as GraphML (e.g. for importing into Gephi).
+///
+/// Node labels and edge types become `labels` and `edge_type`
+/// data attributes, and each prop key gets its own attribute. The
+/// graph defaults to directed edges unless all of its edges are
+/// undirected, and each edge also sets `directed` explicitly.
+pub fn to_graphml(dump: &GraphDump) -> Result<String> {
+    // Declare the keys...
+    let mut keys = vec![
+        GraphmlKey {
+            id: "labels".to_string(),
+            domain: "node",
+            name: "labels".to_string(),
+            attr_type: "string",
+        },
+        GraphmlKey {
+            id: "edge_type".to_string(),
+            domain: "edge",
+            name: "edge_type".to_string(),
+            attr_type: "string",
+        },
+    ];
+    graphml_prop_keys(
+        dump.nodes.iter().map(|n| n.props.as_ref()),
+        "node",
+        &mut keys,
+    );
+    graphml_prop_keys(
+        dump.edges.iter().map(|e| e.props.as_ref()),
+        "edge",
+        &mut keys,
+    );
+    // Note - This skips the labels and edge type keys, in case a prop
+    // has the same name...
+    let key_id = |domain: &str, name: &str| {
+        keys.iter()
+            .skip(2)
+            .find(|k| k.domain == domain && k.name == name)
+            .map(|k| k.id.clone())
+            .unwrap_or_default()
+    };
+
+    // Write the header...
+    let mut w = Writer::new_with_indent(Vec::new(), b' ', 2);
+    w.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+    w.create_element("graphml")
+        .with_attribute(("xmlns", GRAPHML_NS))
+        .write_inner_content(|w| {
+            // Write the key declarations...
+            for key in keys.iter() {
+                w.create_element("key")
+                    .with_attributes([
+                        ("id", key.id.as_str()),
+                        ("for", key.domain),
+                        ("attr.name", key.name.as_str()),
+                        ("attr.type", key.attr_type),
+                    ])
+                    .write_empty()?;
+            }
+
+            // Write the graph...
+            let all_undirected = !dump.edges.is_empty() && dump.edges.iter().all(|e| !e.directed);
+            let edgedefault = if all_undirected {
+                "undirected"
+            } else {
+                "directed"
+            };
+            w.create_element("graph")
+                .with_attributes([("id", "G"), ("edgedefault", edgedefault)])
+                .write_inner_content(|w| {
+                    // Write the nodes...
+                    for node in dump.nodes.iter() {
+                        let mut data = vec![("labels".to_string(), node.labels.join(","))];
+                        let props: BTreeMap<_, _> = node.props.iter().flatten().collect();
+                        for (key, value) in props.into_iter().filter(|(_, v)| !v.is_null()) {
+                            data.push((key_id("node", key), graphml_value(value)));
+                        }
+                        w.create_element("node")
+                            .with_attribute(("id", node.id.as_str()))
+                            .write_inner_content(|w| write_graphml_data(w, &data))?;
+                    }
+
+                    // Write the edges...
+                    for edge in dump.edges.iter() {
+                        let mut data = vec![("edge_type".to_string(), edge.edge_type.clone())];
+                        let props: BTreeMap<_, _> = edge.props.iter().flatten().collect();
+                        for (key, value) in props.into_iter().filter(|(_, v)| !v.is_null()) {
+                            data.push((key_id("edge", key), graphml_value(value)));
+                        }
+                        w.create_element("edge")
+                            .with_attributes([
+                                ("id", edge.id.as_str()),
+                                ("source", edge.from_node.as_str()),
+                                ("target", edge.to_node.as_str()),
+                                ("directed", if edge.directed { "true" } else { "false" }),
+                            ])
+                            .write_inner_content(|w| write_graphml_data(w, &data))?;
+                    }
+                    Ok::<_, quick_xml::Error>(())
+                })?;
+            Ok::<_, quick_xml::Error>(())
+        })?;
+
+    // Done!
+    Ok(String::from_utf8(w.into_inner())?)
+}
+
+/// Write a node or edge's `<data>` elements, given `(key ID, value)` pairs.
+fn write_graphml_data(w: &mut Writer<Vec<u8>>, data: &[(String, String)]) -> quick_xml::Result<()> {
+    for (key, value) in data.iter() {
+        w.create_element("data")
+            .with_attribute(("key", key.as_str()))
+            .write_text_content(BytesText::new(value))?;
+    }
+    Ok(())
+}
+
+/// Turn a node ID into a valid Mermaid identifier, replacing
+/// anything that isn't a letter, digit, or underscore.
+fn mermaid_id(id: &str) -> String {
+    id.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Escape text for a (quoted) Mermaid label.
+fn mermaid_text(text: &str) -> String {
+    text.replace('"', "#quot;")
+}
+
+/// Render nodes and edges as a Mermaid flowchart, e.g. for
+/// embedding in Markdown.
+///
+/// Nodes are shown by their labels (or their ID, if they have none).
+/// Directed edges are drawn with `-->`, undirected ones with `---`,
+/// and both are labelled with their edge type.
+pub fn to_mermaid(dump: &GraphDump) -> String {
+    let mut lines = vec!["graph LR".to_string()];
+    for node in dump.nodes.iter() {
+        let text = if node.labels.is_empty() {
+            node.id.clone()
+        } else {
+            util::format_labels(&node.labels)
+        };
+        lines.push(format!(
+            "    {}[\"{}\"]",
+            mermaid_id(&node.id),
+            mermaid_text(&text)
+        ));
+    }
+    for edge in dump.edges.iter() {
+        let arrow = if edge.directed { "-->" } else { "---" };
+        lines.push(format!(
+            "    {} {}|\"{}\"| {}",
+            mermaid_id(&edge.from_node),
+            arrow,
+            mermaid_text(&edge.edge_type),
+            mermaid_id(&edge.to_node),
+        ));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conf::{Config, DBType};
+    use quick_xml::Reader;
+    use serde_json::json;
+
+    /// Open a fresh (in-memory) database, with the migrations run.
+    async fn test_conn() -> Connection {
+        let mut config = Config::default();
+        config.db.db_type = DBType::Memory;
+        let (_db, conn) = db::open_and_migrate(&config, &db::OpenOptions::default())
+            .await
+            .unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_to_graphml() {
+        let node = |id: &str, props: Value| DbNode {
+            id: id.to_string(),
+            labels: vec!["Person".to_string()],
+            props: serde_json::from_value(props).unwrap(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+        let dump = GraphDump {
+            nodes: vec![
+                node("n-1", json!({"name": "Alice & <Bob>", "age": 30})),
+                node("n-2", json!({"name": "Carol", "tags": ["a", "b"]})),
+            ],
+            edges: vec![DbEdge {
+                id: "e-1".to_string(),
+                edge_type: "knows".to_string(),
+                from_node: "n-1".to_string(),
+                to_node: "n-2".to_string(),
+                directed: false,
+                props: Some([("since".to_string(), json!(2020))].into()),
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+                valid_from: None,
+                valid_to: None,
+            }],
+        };
+        let xml = to_graphml(&dump).unwrap();
+
+        // Make sure it's well-formed, counting the elements...
+        let mut reader = Reader::from_str(&xml);
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        loop {
+            match reader.read_event().unwrap() {
+                Event::Start(e) | Event::Empty(e) => {
+                    let name = String::from_utf8(e.name().as_ref().to_vec()).unwrap();
+                    *counts.entry(name).or_default() += 1;
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        assert_eq!(counts["graphml"], 1);
+        assert_eq!(counts["node"], 2);
+        assert_eq!(counts["edge"], 1);
+
+        // labels, edge_type, age, name, tags, since...
+        assert_eq!(counts["key"], 6);
+        assert!(xml.contains(r#"attr.name="age" attr.type="long""#));
+        assert!(xml.contains(r#"edgedefault="undirected""#));
+        assert!(xml.contains("Alice &amp; &lt;Bob&gt;"));
+    }
+
+    #[tokio::test]
+    async fn test_import_ndjson_parallel() {
+        // Build a dump where some edges come before their endpoints...
+        let ts = r#""created_at":"2024-01-01T00:00:00Z","updated_at":"2024-01-01T00:00:00Z""#;
+        let node = |i: usize| {
+            format!(
+                r#"{{"kind":"node","id":"n-{}","labels":["X"],"props":{{"i":{}}},{}}}"#,
+                i, i, ts
+            )
+        };
+        let edge = |i: usize, from: usize, to: usize| {
+            format!(
+                r#"{{"kind":"edge","id":"e-{}","edge_type":"NEXT","from_node":"n-{}","to_node":"n-{}","directed":true,"props":{{}},{}}}"#,
+                i, from, to, ts
+            )
+        };
+        let mut lines = vec![edge(0, 0, 9)];
+        lines.extend((0..10).map(node));
+        lines.extend((1..9).map(|i| edge(i, i, i + 1)));
+        let data = lines.join("\n");
+
+        // Open a local database (in-memory connections don't share data)...
+        let open = |dir: &Path, audit: bool| {
+            let mut config = Config {
+                conf_dir: dir.to_path_buf(),
+                ..Default::default()
+            };
+            config.db.db_type = DBType::Local;
+            config.db.audit = audit;
+            std::fs::create_dir_all(dir.join(crate::conf::DB_DIR_NAME)).unwrap();
+            async move {
+                let opts = db::OpenOptions::default();
+                let (db, conn) = db::open_and_migrate(&config, &opts).await.unwrap();
+                let mut conns = Vec::new();
+                for _ in 0..3 {
+                    conns.push(db::open_connection(&db, &config, &opts).await.unwrap());
+                }
+                (db, conn, conns)
+            }
+        };
+
+        // Import it sequentially and in parallel...
+        let seq_dir = tempfile::tempdir().unwrap();
+        let (_seq_db, seq_conn, _) = open(seq_dir.path(), false).await;
+        import_ndjson(&seq_conn, &data, &ImportParams::default())
+            .await
+            .unwrap();
+        let par_dir = tempfile::tempdir().unwrap();
+        let (_par_db, par_conn, conns) = open(par_dir.path(), true).await;
+        let params = ImportParams {
+            batch_size: Some(2),
+            ..Default::default()
+        };
+        let res = import_ndjson_parallel(&conns, &data, &params)
+            .await
+            .unwrap();
+        assert_eq!((res.nodes, res.edges), (10, 9));
+
+        // ...the workers' writes are audited...
+        for id in ["n-3", "e-4"] {
+            let log = db::get_audit_log(&par_conn, id).await.unwrap();
+            assert_eq!(log[0].operation, "create", "{} should be audited", id);
+        }
+
+        // ...and the graphs are the same...
+        let dump = |conn: Connection| async move {
+            let mut buf = Vec::new();
+            write_ndjson_dump(&mut buf, &export_graph(&conn).await.unwrap()).unwrap();
+            String::from_utf8(buf).unwrap()
+        };
+        assert_eq!(dump(seq_conn).await, dump(par_conn).await);
+
+        // An edge to a missing node fails, naming its lines...
+        let data = format!("{}\n{}", node(20), edge(20, 20, 99));
+        let err = import_ndjson_parallel(&conns, &data, &params)
+            .await
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("Failed to import edges from lines 2-2"));
+    }
+
+    #[tokio::test]
+    async fn test_export_empty_graph() {
+        let conn = test_conn().await;
+
+        // Every format works, with nothing in it...
+        let dump = export_graph(&conn).await.unwrap();
+        assert!(dump.nodes.is_empty() && dump.edges.is_empty());
+        let mut buf = Vec::new();
+        write_ndjson_dump(&mut buf, &dump).unwrap();
+        assert!(buf.is_empty());
+        let xml = to_graphml(&dump).unwrap();
+        assert!(xml.contains("<graph id=\"G\""));
+        assert_eq!(to_mermaid(&dump), "graph LR");
+
+        // ...and importing an empty file imports nothing...
+        let res = import_ndjson(&conn, "", &ImportParams::default())
+            .await
+            .unwrap();
+        assert_eq!(res, ImportResult::default());
+    }
+
+    #[test]
+    fn test_to_mermaid() {
+        let node = |id: &str, labels: &[&str]| DbNode {
+            id: id.to_string(),
+            labels: labels.iter().map(|l| l.to_string()).collect(),
+            props: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+        let edge = |id: &str, edge_type: &str, from: &str, to: &str, directed: bool| DbEdge {
+            id: id.to_string(),
+            edge_type: edge_type.to_string(),
+            from_node: from.to_string(),
+            to_node: to.to_string(),
+            directed,
+            props: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            valid_from: None,
+            valid_to: None,
+        };
+        let dump = GraphDump {
+            nodes: vec![
+                node("n-1", &["Person"]),
+                node("n-2", &["Person", "Admin"]),
+                node("n-3", &[]),
+            ],
+            edges: vec![
+                edge("e-1", "knows", "n-1", "n-2", true),
+                edge("e-2", "say \"hi\"", "n-2", "n-3", false),
+            ],
+        };
+        let out = to_mermaid(&dump);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "graph LR");
+        assert!(lines.contains(&r#"    n_1[":Person"]"#));
+        assert!(lines.contains(&r#"    n_2[":Person:Admin"]"#));
+        assert!(lines.contains(&r#"    n_3["n-3"]"#));
+        assert!(lines.contains(&r#"    n_1 -->|"knows"| n_2"#));
+        assert!(lines.contains(&r#"    n_2 ---|"say #quot;hi#quot;"| n_3"#));
+    }
+
+    #[tokio::test]
+    async fn test_append_ndjson_since() {
+        let conn = test_conn().await;
+        let add_node = |name: &str| db::CreateNodeParams {
+            props: [("name".to_string(), json!(name))].into(),
+            ..Default::default()
+        };
+
+        // Export a node...
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dump.ndjson");
+        db::create_node(&conn, &add_node("a")).await.unwrap();
+        let dump = export_graph_since(&conn, None).await.unwrap();
+        let mut file = std::fs::File::create(&path).unwrap();
+        write_ndjson_dump(&mut file, &dump).unwrap();
+        let since = Utc::now();
+
+        // Add more nodes (and an edge), then append...
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        let b = db::create_node(&conn, &add_node("b")).await.unwrap();
+        let c = db::create_node(&conn, &add_node("c")).await.unwrap();
+        let edge = db::CreateEdgeParams {
+            edge_type: "knows".to_string(),
+            from_node: b.id.clone(),
+            to_node: c.id.clone(),
+            directed: true,
+            ..Default::default()
+        };
+        db::create_edge(&conn, &edge).await.unwrap();
+        let dump = export_graph_since(&conn, Some(since)).await.unwrap();
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        write_ndjson_dump(&mut file, &dump).unwrap();
+
+        // Only the new nodes were appended...
+        let lines: Vec<Value> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        let names: Vec<&Value> = lines
+            .iter()
+            .filter(|l| l["kind"] == "node")
+            .map(|l| &l["props"]["name"])
+            .collect();
+        assert_eq!(names, vec!["a", "b", "c"]);
+        assert_eq!(lines.last().unwrap()["kind"], "edge");
+        assert_eq!(lines.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_validate_ndjson() {
+        let conn = test_conn().await;
+
+        // A file with three problems -- a node without labels, a node
+        // missing its timestamps, and an edge to a node that doesn't exist...
+        let ts = r#""created_at":"2024-01-01T00:00:00Z","updated_at":"2024-01-01T00:00:00Z""#;
+        let lines = [
+            format!(r#"{{"kind":"node","id":"n-1","labels":["X"],{}}}"#, ts),
+            format!(r#"{{"kind":"node","id":"n-2","labels":[],{}}}"#, ts),
+            r#"{"kind":"node","id":"n-3","labels":["X"]}"#.to_string(),
+            format!(
+                r#"{{"kind":"edge","id":"e-1","edge_type":"knows","from_node":"n-1","to_node":"n-9","directed":true,{}}}"#,
+                ts
+            ),
+        ];
+        let params = ValidateImportParams {
+            require_label: true,
+            ..Default::default()
+        };
+        let errors = validate_ndjson(&conn, &lines.join("\n"), &params)
+            .await
+            .unwrap();
+        let found: Vec<(usize, Option<&str>)> = errors
+            .iter()
+            .map(|e| (e.line, e.field.as_deref()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (2, Some("labels")),
+                (3, Some("created_at")),
+                (3, Some("updated_at")),
+                (4, Some("to_node")),
+            ]
+        );
+
+        // Nothing was imported...
+        let count = db::count_nodes(&conn, &db::NodeFilter::default())
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_import_ndjson_resume() {
+        let conn = test_conn().await;
+
+        // Build a dump with a bad line in the middle...
+        let line = |i: usize| {
+            format!(
+                r#"{{"kind":"node","id":"n-{}","labels":["X"],"props":{{"i":{}}},"created_at":"2024-01-01T00:00:00Z","updated_at":"2024-01-01T00:00:00Z"}}"#,
+                i, i
+            )
+        };
+        let mut lines: Vec<String> = (0..5).map(line).collect();
+        lines[3] = "{not json".to_string();
+
+        // The import fails part-way, keeping the first batch...
+        let dir = tempfile::tempdir().unwrap();
+        let params = ImportParams {
+            checkpoint: Some(dir.path().join("checkpoint")),
+            batch_size: Some(2),
+            ..Default::default()
+        };
+        let err = import_ndjson(&conn, &lines.join("\n"), &params)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("line 4"));
+        assert_eq!(
+            read_checkpoint(params.checkpoint.as_ref().unwrap()).unwrap(),
+            2
+        );
+        let count = db::count_nodes(&conn, &db::NodeFilter::default())
+            .await
+            .unwrap();
+        assert_eq!(count, 2);
+
+        // Fix the line and resume (re-importing the first batch would fail)...
+        lines[3] = line(3);
+        let res = import_ndjson(&conn, &lines.join("\n"), &params)
+            .await
+            .unwrap();
+        assert_eq!(
+            res,
+            ImportResult {
+                nodes: 3,
+                edges: 0,
+                resumed_from_line: 2,
+            }
+        );
+        let count = db::count_nodes(&conn, &db::NodeFilter::default())
+            .await
+            .unwrap();
+        assert_eq!(count, 5);
+
+        // Without a checkpoint, a failure imports nothing...
+        let conn = test_conn().await;
+        lines[4] = "{not json".to_string();
+        let params = ImportParams::default();
+        assert!(import_ndjson(&conn, &lines.join("\n"), &params)
+            .await
+            .is_err());
+        let count = db::count_nodes(&conn, &db::NodeFilter::default())
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_export_filtered() {
+        let conn = test_conn().await;
+
+        // Two people and a place, with an edge between the people
+        // and one to the place...
+        let add_node = |label: &str| db::CreateNodeParams {
+            labels: vec![label.to_string()],
+            ..Default::default()
+        };
+        let a = db::create_node(&conn, &add_node("Person")).await.unwrap();
+        let b = db::create_node(&conn, &add_node("Person")).await.unwrap();
+        let c = db::create_node(&conn, &add_node("Place")).await.unwrap();
+        let add_edge = |from: &str, to: &str| db::CreateEdgeParams {
+            edge_type: "link".to_string(),
+            from_node: from.to_string(),
+            to_node: to.to_string(),
+            directed: true,
+            ..Default::default()
+        };
+        let knows = db::create_edge(&conn, &add_edge(&a.id, &b.id))
+            .await
+            .unwrap();
+        let visits = db::create_edge(&conn, &add_edge(&a.id, &c.id))
+            .await
+            .unwrap();
+
+        let export = |edges: ExportEdges| {
+            let conn = &conn;
+            async move {
+                let params = ExportParams {
+                    node_filter: Some(db::NodeFilter {
+                        has_label: Some("Person".to_string()),
+                        ..Default::default()
+                    }),
+                    edges,
+                    ..Default::default()
+                };
+                let dump = export_graph_filtered(conn, &params).await.unwrap();
+                let mut nodes: Vec<String> = dump.nodes.into_iter().map(|n| n.id).collect();
+                let mut edges: Vec<String> = dump.edges.into_iter().map(|e| e.id).collect();
+                nodes.sort();
+                edges.sort();
+                (nodes, edges)
+            }
+        };
+        let mut people = vec![a.id.clone(), b.id.clone()];
+        people.sort();
+
+        // Only edges between the exported nodes...
+        assert_eq!(
+            export(ExportEdges::Within).await,
+            (people.clone(), vec![knows.id.clone()])
+        );
+
+        // Edges with a dangling end too...
+        let mut both = vec![knows.id.clone(), visits.id.clone()];
+        both.sort();
+        assert_eq!(
+            export(ExportEdges::Touching).await,
+            (people.clone(), both.clone())
+        );
+
+        // No edges...
+        assert_eq!(export(ExportEdges::None).await, (people, vec![]));
+
+        // Without a filter, everything is exported...
+        let dump = export_graph(&conn).await.unwrap();
+        assert_eq!((dump.nodes.len(), dump.edges.len()), (3, 2));
+    }
+
+    #[tokio::test]
+    async fn test_copy_graph_round_trip() {
+        // Open a (file-backed) local database...
+        let open_local = |dir: &Path| {
+            let mut config = Config {
+                conf_dir: dir.to_path_buf(),
+                ..Default::default()
+            };
+            config.db.db_type = DBType::Local;
+            async move {
+                std::fs::create_dir_all(config.conf_dir.join(crate::conf::DB_DIR_NAME)).unwrap();
+                db::open_and_migrate(&config, &db::OpenOptions::default())
+                    .await
+                    .unwrap()
+            }
+        };
+        let cancel = CancellationToken::new();
+
+        // Fill a local database (with auditing on)...
+        let dir = tempfile::tempdir().unwrap();
+        let (_local_db, local) = open_local(dir.path()).await;
+        db::enable_audit(&local).await.unwrap();
+        let node = |name: &str| db::CreateNodeParams {
+            labels: vec!["Person".to_string()],
+            props: [("name".to_string(), json!(name))].into(),
+            ..Default::default()
+        };
+        let a = db::create_node(&local, &node("a")).await.unwrap();
+        let b = db::create_node(&local, &node("b")).await.unwrap();
+        let edge = db::CreateEdgeParams {
+            edge_type: "knows".to_string(),
+            from_node: a.id.clone(),
+            to_node: b.id.clone(),
+            directed: true,
+            props: [("since".to_string(), json!(2020))].into(),
+            ..Default::default()
+        };
+        db::create_edge(&local, &edge).await.unwrap();
+        let c = db::create_node(&local, &node("c")).await.unwrap();
+        db::archive_node(&local, &c.id).await.unwrap();
+
+        // ...along with the rest of the graph's state...
+        let template = db::NodeTemplate {
+            name: "person".to_string(),
+            labels: vec!["Person".to_string()],
+            props: [("active".to_string(), json!(true))].into(),
+        };
+        db::set_node_template(&local, &template).await.unwrap();
+        let defaults = [("score".to_string(), json!(0))].into();
+        db::set_label_defaults(&local, "Person", &defaults)
+            .await
+            .unwrap();
+        db::set_graph_meta(&local, "description", "People")
+            .await
+            .unwrap();
+
+        // Push it to a stand-in "remote" (in-memory) database...
+        let remote = test_conn().await;
+        let res = copy_graph(&local, &remote, &cancel).await.unwrap();
+        assert_eq!((res.nodes, res.edges), (3, 1));
+
+        // A non-empty destination is rejected...
+        let err = copy_graph(&local, &remote, &cancel).await.unwrap_err();
+        assert_eq!(error::kind(&err), Some(error::ErrorKind::Validation));
+
+        // Pull it back down into a new local database...
+        let dir2 = tempfile::tempdir().unwrap();
+        let (_local_db2, local2) = open_local(dir2.path()).await;
+        copy_graph(&remote, &local2, &cancel).await.unwrap();
+
+        // Nothing changed along the way...
+        let before = export_graph(&local).await.unwrap();
+        let after = export_graph(&local2).await.unwrap();
+        assert!(crate::diff::diff_graphs(&before, &after).is_empty());
+        assert_eq!(
+            serde_json::to_value(&before).unwrap(),
+            serde_json::to_value(&after).unwrap()
+        );
+
+        // ...the archived node is still there, and still archived...
+        let all = db::NodeFilter {
+            include_archived: true,
+            ..Default::default()
+        };
+        assert_eq!(db::count_nodes(&local2, &all).await.unwrap(), 3);
+        assert_eq!(before.nodes.len(), 2);
+
+        // ...and so are the templates, label defaults, metadata, and audit log...
+        let copied = db::get_node_template(&local2, "person").await.unwrap();
+        assert_eq!(
+            (copied.labels, copied.props),
+            (template.labels, template.props)
+        );
+        assert_eq!(
+            db::list_label_defaults(&local2).await.unwrap(),
+            db::list_label_defaults(&local).await.unwrap(),
+        );
+        assert_eq!(
+            db::get_graph_meta(&local2, "description").await.unwrap(),
+            Some(json!("People"))
+        );
+        assert_eq!(
+            db::get_audit_log(&local2, &c.id).await.unwrap(),
+            db::get_audit_log(&local, &c.id).await.unwrap(),
+        );
+        assert!(!db::get_audit_log(&local2, &a.id).await.unwrap().is_empty());
+    }
+}
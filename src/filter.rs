@@ -0,0 +1,343 @@
+//! Boolean filter expressions for `list nodes --filter`.
+//!
+//! The grammar (keywords are case-insensitive, `AND` binds tighter
+//! than `OR`, and `NOT` binds tightest):
+//!
+//! ```text
+//! expr    := or
+//! or      := and ( "OR" and )*
+//! and     := unary ( "AND" unary )*
+//! unary   := "NOT" unary | primary
+//! primary := "(" expr ")" | term
+//! term    := "label:" value
+//!          | "has:" key
+//!          | "prop:" key op value      (op is =, !=, <, <=, >, or >=)
+//! ```
+//!
+//! Values can be double-quoted (as JSON strings) to include spaces,
+//! parentheses, or keywords -- e.g. `prop:name="Jane Doe"`.
+//!
+//! For example: `label:Person AND (prop:age>30 OR prop:vip=true)`.
+use crate::error;
+use crate::util::{self, PropCondition};
+use anyhow::Result;
+
+/// How deeply expressions can be nested (to keep the recursive
+/// parser from overflowing the stack on silly input).
+const MAX_DEPTH: usize = 64;
+
+/// A parsed filter expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterExpr {
+    /// Nodes with this label.
+    Label(String),
+
+    /// Nodes with this prop set.
+    HasProp(String),
+
+    /// Nodes whose prop compares with a value.
+    Prop(PropCondition),
+
+    Not(Box<FilterExpr>),
+    And(Box<FilterExpr>, Box<FilterExpr>),
+    Or(Box<FilterExpr>, Box<FilterExpr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    Term(String),
+}
+
+/// Split an expression into tokens.
+///
+/// Anything that isn't whitespace or a parenthesis is part of a term,
+/// and double-quoted sections (with `\` escapes) are kept whole.
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::LParen);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::RParen);
+            }
+            _ => {
+                // Read a term...
+                let mut word = String::new();
+                let mut in_quotes = false;
+                while let Some(&c) = chars.peek() {
+                    if !in_quotes && (c.is_whitespace() || c == '(' || c == ')') {
+                        break;
+                    }
+                    chars.next();
+                    word.push(c);
+                    if in_quotes && c == '\\' {
+                        if let Some(escaped) = chars.next() {
+                            word.push(escaped);
+                        }
+                    } else if c == '"' {
+                        in_quotes = !in_quotes;
+                    }
+                }
+                if in_quotes {
+                    return Err(error::validation(format!(
+                        "Unterminated quote in filter term '{}'.",
+                        word
+                    )));
+                }
+
+                // Is it a keyword?
+                let token = match word.to_ascii_uppercase().as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => Token::Term(word),
+                };
+                tokens.push(token);
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Unquote a term's value, if it's a JSON string.
+fn unquote(value: &str) -> Result<String> {
+    if value.starts_with('"') {
+        serde_json::from_str(value)
+            .map_err(|_| error::validation(format!("Invalid quoted value {}.", value)))
+    } else {
+        Ok(value.to_string())
+    }
+}
+
+/// Parse a single term (e.g. `label:Person` or `prop:age>30`).
+fn parse_term(term: &str) -> Result<FilterExpr> {
+    let (kind, rest) = term.split_once(':').ok_or_else(|| {
+        error::validation(format!(
+            "Invalid filter term '{}'. Expected `label:<label>`, `has:<key>`, or \
+            `prop:<key><op><value>`.",
+            term
+        ))
+    })?;
+    if rest.is_empty() {
+        return Err(error::validation(format!(
+            "Missing value in filter term '{}'.",
+            term
+        )));
+    }
+    match kind.to_ascii_lowercase().as_str() {
+        "label" => Ok(FilterExpr::Label(unquote(rest)?)),
+        "has" => Ok(FilterExpr::HasProp(unquote(rest)?)),
+        "prop" => Ok(FilterExpr::Prop(util::parse_prop_condition(rest)?)),
+        _ => Err(error::validation(format!(
+            "Unknown filter term type '{}' in '{}'. Expected `label`, `has`, or `prop`.",
+            kind, term
+        ))),
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<FilterExpr> {
+        let mut expr = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            expr = FilterExpr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<FilterExpr> {
+        let mut expr = self.parse_unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            expr = FilterExpr::And(Box::new(expr), Box::new(self.parse_unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> Result<FilterExpr> {
+        // Guard against deeply nested input...
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(error::validation(format!(
+                "Filter expression is nested too deeply (max {}).",
+                MAX_DEPTH
+            )));
+        }
+
+        let expr = match self.next() {
+            Some(Token::Not) => FilterExpr::Not(Box::new(self.parse_unary()?)),
+            Some(Token::LParen) => {
+                let expr = self.parse_or()?;
+                if self.next() != Some(Token::RParen) {
+                    return Err(error::validation(
+                        "Unbalanced parentheses in filter expression (missing `)`).",
+                    ));
+                }
+                expr
+            }
+            Some(Token::Term(term)) => parse_term(&term)?,
+            Some(Token::RParen) => {
+                return Err(error::validation("Unexpected `)` in filter expression."))
+            }
+            Some(Token::And) | Some(Token::Or) => {
+                return Err(error::validation(
+                    "Expected a filter term, but found `AND`/`OR`.",
+                ))
+            }
+            None => return Err(error::validation("Unexpected end of filter expression.")),
+        };
+
+        self.depth -= 1;
+        Ok(expr)
+    }
+}
+
+/// Parse a filter expression.
+pub fn parse_filter_expr(input: &str) -> Result<FilterExpr> {
+    let tokens = tokenize(input)?;
+    if tokens.is_empty() {
+        return Err(error::validation("Empty filter expression."));
+    }
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        depth: 0,
+    };
+    let expr = parser.parse_or()?;
+
+    // Make sure everything was used...
+    match parser.peek() {
+        None => Ok(expr),
+        Some(Token::RParen) => Err(error::validation(
+            "Unbalanced parentheses in filter expression (extra `)`).",
+        )),
+        Some(_) => Err(error::validation(
+            "Expected `AND` or `OR` between filter terms.",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::CompareOp;
+    use serde_json::json;
+
+    fn label(l: &str) -> FilterExpr {
+        FilterExpr::Label(l.to_string())
+    }
+
+    fn prop(key: &str, op: CompareOp, value: serde_json::Value) -> FilterExpr {
+        FilterExpr::Prop(PropCondition {
+            key: key.to_string(),
+            op,
+            value,
+        })
+    }
+
+    fn and(a: FilterExpr, b: FilterExpr) -> FilterExpr {
+        FilterExpr::And(Box::new(a), Box::new(b))
+    }
+
+    fn or(a: FilterExpr, b: FilterExpr) -> FilterExpr {
+        FilterExpr::Or(Box::new(a), Box::new(b))
+    }
+
+    #[test]
+    fn test_parse_precedence() {
+        // AND binds tighter than OR...
+        assert_eq!(
+            parse_filter_expr("label:A OR label:B AND label:C").unwrap(),
+            or(label("A"), and(label("B"), label("C"))),
+        );
+        assert_eq!(
+            parse_filter_expr("label:A and label:B or label:C").unwrap(),
+            or(and(label("A"), label("B")), label("C")),
+        );
+
+        // NOT binds tighter than AND...
+        assert_eq!(
+            parse_filter_expr("NOT label:A AND label:B").unwrap(),
+            and(FilterExpr::Not(Box::new(label("A"))), label("B")),
+        );
+    }
+
+    #[test]
+    fn test_parse_grouping() {
+        assert_eq!(
+            parse_filter_expr("label:Person AND (prop:age>30 OR prop:vip=true)").unwrap(),
+            and(
+                label("Person"),
+                or(
+                    prop("age", CompareOp::Gt, json!(30)),
+                    prop("vip", CompareOp::Eq, json!(true)),
+                ),
+            ),
+        );
+        assert_eq!(
+            parse_filter_expr("((has:email))").unwrap(),
+            FilterExpr::HasProp("email".to_string()),
+        );
+
+        // Quoted values keep their spaces, parens, and keywords...
+        assert_eq!(
+            parse_filter_expr(r#"prop:name="Jane (OR) Doe" AND label:"My Label""#).unwrap(),
+            and(
+                prop("name", CompareOp::Eq, json!("Jane (OR) Doe")),
+                label("My Label"),
+            ),
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for input in [
+            "",
+            "label:A AND",
+            "(label:A",
+            "label:A)",
+            "label:A label:B",
+            "AND label:A",
+            "label:",
+            "name:A",
+            "Person",
+            r#"label:"A"#,
+            "prop:age",
+        ] {
+            assert!(parse_filter_expr(input).is_err(), "{:?} should fail", input);
+        }
+
+        // Too deeply nested...
+        let deep = format!("{}label:A{}", "(".repeat(100), ")".repeat(100));
+        assert!(parse_filter_expr(&deep).is_err());
+    }
+}
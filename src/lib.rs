@@ -0,0 +1,127 @@
+//! A graph database, backed by `libsql`.
+//!
+//! This is the storage engine behind the `graphctl` CLI. The [`db`] module
+//! has the individual graph operations (which work on a `libsql::Connection`),
+//! while [`GraphDb`] bundles up opening, configuring, and migrating a
+//! database so it's ready to use.
+//!
+//! # Example
+//!
+//! ```
+//! use graphctl::conf::{Config, DBType};
+//! use graphctl::db::{CreateNodeParams, ListNodesParams};
+//! use graphctl::GraphDb;
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> anyhow::Result<()> {
+//! // Open a (throwaway) in-memory graph...
+//! let mut config = Config::default();
+//! config.db.db_type = DBType::Memory;
+//! let graph = GraphDb::open(&config).await?;
+//!
+//! // Add a node and read it back...
+//! let node = graph
+//!     .create_node(&CreateNodeParams {
+//!         labels: vec!["Person".to_string()],
+//!         props: [("name".to_string(), "Alice".into())].into(),
+//!         ..Default::default()
+//!     })
+//!     .await?;
+//! let nodes = graph.list_nodes(&ListNodesParams::default()).await?;
+//! assert_eq!(nodes.len(), 1);
+//! assert_eq!(nodes[0].id, node.id);
+//! # Ok(())
+//! # }
+//! ```
+pub mod analysis;
+pub mod conf;
+pub mod db;
+pub mod diff;
+pub mod error;
+pub mod export;
+pub mod filter;
+pub mod secrets;
+pub mod util;
+
+use anyhow::Result;
+use conf::Config;
+use db::{
+    CreateEdgeParams, CreateNodeParams, DbEdge, DbNode, EdgeFilter, GetEdgeParams, GetNodeParams,
+    ListEdgesParams, ListNodesParams, NodeFilter,
+};
+use libsql::{Connection, Database};
+
+/// An open, migrated graph database.
+pub struct GraphDb {
+    db: Database,
+    conn: Connection,
+}
+
+impl GraphDb {
+    /// Connect to the database described by the config, apply the
+    /// connection settings, and run any pending migrations.
+    pub async fn open(config: &Config) -> Result<Self> {
+        let (db, conn) = db::open_and_migrate(config, &db::OpenOptions::default()).await?;
+        Ok(Self { db, conn })
+    }
+
+    /// The underlying database.
+    pub fn database(&self) -> &Database {
+        &self.db
+    }
+
+    /// The underlying connection, for use with the functions in [`db`].
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// Create a node. See [`db::create_node`].
+    pub async fn create_node(&self, params: &CreateNodeParams) -> Result<DbNode> {
+        db::create_node(&self.conn, params).await
+    }
+
+    /// Create an edge. See [`db::create_edge`].
+    pub async fn create_edge(&self, params: &CreateEdgeParams) -> Result<DbEdge> {
+        db::create_edge(&self.conn, params).await
+    }
+
+    /// Get a node. See [`db::get_node`].
+    pub async fn get_node(&self, params: &GetNodeParams) -> Result<DbNode> {
+        db::get_node(&self.conn, params).await
+    }
+
+    /// Get an edge. See [`db::get_edge`].
+    pub async fn get_edge(&self, params: &GetEdgeParams) -> Result<DbEdge> {
+        db::get_edge(&self.conn, params).await
+    }
+
+    /// List nodes. See [`db::list_nodes`].
+    pub async fn list_nodes(&self, params: &ListNodesParams) -> Result<Vec<DbNode>> {
+        db::list_nodes(&self.conn, params).await
+    }
+
+    /// List edges. See [`db::list_edges`].
+    pub async fn list_edges(&self, params: &ListEdgesParams) -> Result<Vec<DbEdge>> {
+        db::list_edges(&self.conn, params).await
+    }
+
+    /// Delete a node, along with its props and edges. See [`db::delete_node`].
+    pub async fn delete_node(&self, id: &str) -> Result<()> {
+        db::delete_node(&self.conn, id).await
+    }
+
+    /// Delete an edge, along with its props. See [`db::delete_edge`].
+    pub async fn delete_edge(&self, id: &str) -> Result<()> {
+        db::delete_edge(&self.conn, id).await
+    }
+
+    /// Delete all nodes matching a filter. See [`db::delete_nodes_by_filter`].
+    pub async fn delete_nodes(&self, filter: &NodeFilter) -> Result<usize> {
+        db::delete_nodes_by_filter(&self.conn, filter).await
+    }
+
+    /// Delete all edges matching a filter. See [`db::delete_edges_by_filter`].
+    pub async fn delete_edges(&self, filter: &EdgeFilter) -> Result<usize> {
+        db::delete_edges_by_filter(&self.conn, filter).await
+    }
+}
@@ -1,192 +1,563 @@
 mod cli;
-mod conf;
-mod db;
+mod doctor;
+mod output;
 mod prompt;
-mod secrets;
-mod util;
-
-use std::collections::HashMap;
+mod trace;
+mod version;
 
 use anyhow::{anyhow, Context, Result};
-use clap::Parser;
-use cli::{CfgCmd, Cli, Commands, CreateCmd, DeleteCmd, GetCmd, ListCmd, UpdateCmd};
-use conf::Config;
-use db::{connect_to_db, init_db};
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, ValueEnum};
+use cli::{
+    CfgCmd, Cli, Commands, CreateCmd, DeleteCmd, EdgeFilterArgs, ExistsCmd, ExportFormat, GetCmd,
+    InitOutput, ListCmd, MetaCmd, NodeFilterArgs, ReplaceCmd, UnarchiveCmd, UpdateCmd,
+};
+use graphctl::conf::{self, Config};
+use graphctl::db;
+use graphctl::error::{self, ErrorKind};
+use graphctl::{analysis, diff, export, filter, secrets, util};
 use serde_json::json;
+use std::collections::HashSet;
+use std::io::Write;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio_util::sync::CancellationToken;
+
+/// How long an operation has to stop after being cancelled,
+/// before it's dropped.
+const CANCEL_GRACE_PERIOD: Duration = Duration::from_secs(2);
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() {
     // Load the CLI...
-    let app = Cli::parse();
+    // Note - Usage errors are treated as validation errors...
+    let (app, conf_dir_source) = parse_cli().unwrap_or_else(|err| {
+        if err.use_stderr() {
+            let _ = err.print();
+            std::process::exit(ErrorKind::Validation.exit_code());
+        }
+        err.exit()
+    });
+
+    // Cancel the in-flight operation on Ctrl-C...
+    let cancel = CancellationToken::new();
+    tokio::spawn({
+        let cancel = cancel.clone();
+        async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                eprintln!("Cancelling...");
+                cancel.cancel();
+            }
+        }
+    });
+
+    // Run the command, exiting with a code based on the kind of error...
+    // Note - Operations that don't check for cancellation themselves are
+    // dropped after a grace period, which rolls back any open transaction...
+    let no_migrate = app.no_migrate;
+    let res = tokio::select! {
+        res = run(app, conf_dir_source, cancel.clone()) => res,
+        _ = async {
+            cancel.cancelled().await;
+            tokio::time::sleep(CANCEL_GRACE_PERIOD).await;
+        } => Err(error::cancelled("Operation cancelled.")),
+    };
+    if let Err(mut err) = res {
+        // Stop quietly if the output was closed early (e.g. piped into `head`)...
+        if output::is_broken_pipe(&err) {
+            std::process::exit(0);
+        }
+
+        // Explain errors from an unmigrated schema...
+        if no_migrate {
+            err = db::with_schema_hint(err);
+        }
+        eprintln!("Error: {:?}", err);
+        std::process::exit(error::exit_code(&err));
+    }
+}
+
+/// Parse the CLI args, noting where the config directory came from.
+fn parse_cli() -> Result<(Cli, conf::ConfigDirSource), clap::Error> {
+    let matches = Cli::command().try_get_matches()?;
+    let source = match matches.value_source("config_dir") {
+        Some(ValueSource::CommandLine) => conf::ConfigDirSource::Flag,
+        Some(ValueSource::EnvVariable) => conf::ConfigDirSource::Env,
+        _ => conf::ConfigDirSource::Default,
+    };
+    let app = Cli::from_arg_matches(&matches)?;
+    Ok((app, source))
+}
+
+async fn run(
+    app: Cli,
+    conf_dir_source: conf::ConfigDirSource,
+    cancel: CancellationToken,
+) -> Result<()> {
+    // Use deterministic IDs, if seeded...
+    if let Some(seed) = app.seed {
+        util::set_id_seed(seed);
+    }
+
+    // How connections are opened...
+    let open_opts = db::OpenOptions {
+        busy_timeout_ms: app.busy_timeout,
+        journal_mode: app.journal_mode,
+        synchronous: app.synchronous,
+        migrate: true,
+        audit: true,
+    };
+
+    // Is this a version command?
+    // Note - This is handled first, so it works without a config dir...
+    if let Commands::Version(args) = &app.cmd {
+        let data = match args.verbose {
+            true => json!(version::build_info()),
+            false => json!({ "version": version::VERSION }),
+        };
+        output::print_json(&data)?;
+        return Ok(());
+    }
 
     // Load the config...
     let conf_dir = match conf::get_config_dir(app.config_dir) {
         Some(cd) => cd,
         None => {
             eprintln!("Error: Could not determine config directory.");
-            std::process::exit(1);
+            std::process::exit(ErrorKind::Config.exit_code());
         }
     };
 
+    // Remove a stale lock?
+    let lock_file = conf::get_lock_file(&conf_dir);
+    if app.force_unlock {
+        util::LockFile::force_unlock(&lock_file)?;
+    }
+
     // Is this a init command?
-    if matches!(app.cmd, Commands::Cfg { cmd: CfgCmd::Init }) {
+    if let Commands::Cfg {
+        cmd: CfgCmd::Init(args),
+    } = &app.cmd
+    {
+        // Read the template config first, so a bad one doesn't leave
+        // a config dir behind...
+        let template = args
+            .from_config
+            .as_deref()
+            .map(|path| Config::read_template(path, &conf_dir))
+            .transpose()?;
+
         // Check that the config dir doesn't already exist...
-        if conf_dir.exists() {
+        // Note - An existing, empty directory is fine to set up (as
+        // is one that only holds another init's lock file, so that
+        // init reports the lock)...
+        let is_empty_dir = std::fs::read_dir(&conf_dir).is_ok_and(|d| {
+            d.flatten()
+                .all(|e| e.file_name() == std::ffi::OsStr::new(conf::LOCK_FILE_NAME))
+        });
+        if conf_dir.exists() && !is_empty_dir {
             eprintln!(
-                "Error: Config directory \"{}\" already exists.",
+                "Error: Config directory \"{}\" already exists (set by {}).",
                 conf_dir.display(),
+                conf_dir_source,
             );
-            std::process::exit(1);
+            std::process::exit(ErrorKind::Config.exit_code());
         }
 
-        // Prompt for the database type...
-        let db_type = prompt::prompt_for_db_type()?;
+        // Create the config directory and take the lock...
+        // Note - From here on, errors are returned (rather than exiting)
+        // so the lock is released...
+        std::fs::create_dir_all(&conf_dir).map_err(|err| {
+            error::config(format!(
+                "Could not create config directory \"{}\": {}",
+                conf_dir.display(),
+                err,
+            ))
+        })?;
+        let _lock = util::LockFile::acquire(&lock_file)?;
+
+        // Get the database type (prompting if not passed or in the template)...
+        let db_type = match (args.db_type, &template) {
+            (Some(t), _) => t,
+            (None, Some(template)) => template.db.db_type,
+            (None, None) => prompt::prompt_for_db_type()?,
+        };
+        let template_db = template.as_ref().map(|t| &t.db);
 
-        // Get the remote path if needed...
-        let remote_db_path = match db_type {
+        // Get the remote URL if needed...
+        // Note - An auth token embedded in the URL is split out, so it's
+        // stored in the keyring rather than written to the config file...
+        let remote_url = match db_type {
             conf::DBType::RemoteOnly | conf::DBType::RemoteWithReplica => {
-                Some(prompt::prompt_for_remote_db_url()?)
+                let url = match (
+                    &args.remote_db_url,
+                    template_db.and_then(|d| d.remote_db_path.as_ref()),
+                ) {
+                    (Some(url), _) | (None, Some(url)) => url.clone(),
+                    (None, None) => prompt::prompt_for_remote_db_url()?,
+                };
+                Some(conf::RemoteUrl::parse(&url))
             }
             _ => None,
         };
+        let remote_db_path = remote_url.as_ref().map(|u| u.url.clone());
 
-        // Get the encryption key if needed...
-        match db_type {
-            conf::DBType::RemoteOnly | conf::DBType::RemoteWithReplica => {
-                // Promopt for the encryption key...
-                let encryption_key = prompt::prompt_for_remote_db_auth_token()?;
-
-                // Store the encryption key...
-                secrets::set_remote_db_auth_token(&encryption_key)?;
-            }
-            _ => (),
+        // Get the remote auth token if needed (prompting if it isn't
+        // passed or in the URL), and store it...
+        if let Some(remote_url) = remote_url {
+            let token = match (&args.remote_db_token, remote_url.auth_token) {
+                (Some(token), _) => token.clone(),
+                (None, Some(token)) => token,
+                (None, None) => prompt::prompt_for_remote_db_auth_token()?,
+            };
+            secrets::set_remote_db_auth_token(&token)?;
         }
 
         // Should the local db be encrypted?
-        let encrypt_local = match db_type {
-            conf::DBType::Local => prompt::prompt_for_encrypt_local()?,
-            conf::DBType::RemoteWithReplica => prompt::prompt_for_encrypt_replica()?,
-            _ => false,
+        let encrypt = args.encrypt().or(template_db.map(|d| d.encrypt_replica));
+        let encrypt_local = match (db_type, encrypt) {
+            (conf::DBType::RemoteOnly | conf::DBType::Memory, _) => false,
+            (_, Some(encrypt)) => encrypt,
+            (conf::DBType::Local, None) => prompt::prompt_for_encrypt_local()?,
+            (conf::DBType::RemoteWithReplica, None) => prompt::prompt_for_encrypt_replica()?,
         };
 
         // If encrypting, generate a random key and store it...
-        if encrypt_local {
+        // Note - Unless the key is being passed in by file or env var...
+        let has_key_source = app.encryption_key_file.is_some()
+            || std::env::var_os(secrets::ENCRYPTION_KEY_ENV_VAR).is_some();
+        if encrypt_local && !has_key_source {
             let key = secrets::generate_random_hex_string()?;
             secrets::set_local_db_encryption_key(&key)?;
         }
 
-        // Store that data in the config...
+        // Store that data in the config (on top of the template's settings)...
+        let base = template.unwrap_or_default();
         let cfg = Config {
             conf_dir,
             db: conf::DbConfig {
                 db_type,
                 remote_db_path,
                 encrypt_replica: encrypt_local,
+                encryption_key_file: app.encryption_key_file.clone(),
+                ..base.db
             },
+            ..base
         };
 
-        // Create the config directory...
-        if let Err(err) = std::fs::create_dir_all(&cfg.conf_dir) {
-            eprintln!(
-                "Error: Could not create config directory \"{}\": {}",
-                cfg.conf_dir.display(),
-                err,
-            );
-            std::process::exit(1);
-        }
-
         // Write the config file...
-        if let Err(err) = cfg.write_to_file() {
-            eprintln!("Error: Could not write config file: {}", err,);
-            std::process::exit(1);
-        }
+        cfg.write_to_file()
+            .map_err(|err| error::config(format!("Could not write config file: {}", err)))?;
 
         // Make the data directory...
-        let data_dir = cfg.conf_dir.join(conf::DB_DIR_NAME);
-        if let Err(err) = std::fs::create_dir(&data_dir) {
-            eprintln!(
-                "Error: Could not create data directory \"{}\": {}",
+        // Note - A template's `data_dir` may already exist...
+        let data_dir = cfg.db_dir();
+        std::fs::create_dir_all(&data_dir).map_err(|err| {
+            error::config(format!(
+                "Could not create data directory \"{}\": {}",
                 data_dir.display(),
                 err,
-            );
-            std::process::exit(1);
-        }
+            ))
+        })?;
 
-        // Create the db...
-        let db = match connect_to_db(&cfg.conf_dir, &cfg).await {
-            Ok(d) => d,
-            Err(e) => {
-                eprintln!("Error: Could not initialize database: {}", e);
-                std::process::exit(1);
-            }
-        };
+        // Create the db and run the migrations...
+        db::open_and_migrate(&cfg, &open_opts).await?;
 
-        // Create a connection...
-        let conn = match db.connect() {
-            Ok(c) => c,
-            Err(e) => {
-                eprintln!("Error: Could not connect to database: {}", e);
-                std::process::exit(1);
-            }
-        };
-
-        // Run the migrations...
-        if let Err(err) = init_db(&conn).await {
-            eprintln!("Error: Could not initialize database: {}", err);
-            std::process::exit(1);
+        // Print a summary of the setup, if requested...
+        if let Some(InitOutput::Json) = args.output {
+            let db_file = match cfg.db.db_type {
+                conf::DBType::RemoteOnly | conf::DBType::Memory => None,
+                _ => Some(cfg.db_file()),
+            };
+            let data = json!({
+                "config_dir": cfg.conf_dir,
+                "db_type": cfg.db.db_type,
+                "encrypted": cfg.db.encrypt_replica,
+                "db_file": db_file,
+            });
+            output::print_json(&data)?;
         }
 
         // Done!
         return Ok(());
     }
 
+    // Is this a doctor command?
+    if let Commands::Doctor = app.cmd {
+        // Run the checks and print the results...
+        let checks = doctor::run_checks(&conf_dir, app.busy_timeout).await;
+        for check in checks.iter() {
+            output::print_line(&check.render())?;
+        }
+
+        // Fail if any of the checks failed...
+        let failed = checks.iter().filter(|c| c.result.is_err()).count();
+        if failed > 0 {
+            return Err(anyhow!(
+                "{} of {} doctor checks failed.",
+                failed,
+                checks.len()
+            ));
+        }
+        return Ok(());
+    }
+
     // Now make the config variable immutable...
-    let cfg = Config::read_from_file(&conf_dir)
-        .context("Could not read config file.")?;
-
-    // Make sure the config directory already exists...
-    if !cfg.conf_dir.exists() {
-        eprintln!(
-            "Error: Config directory \"{}\" doesn't exist. Run `graphctl init` to create it",
-            cfg.conf_dir.display(),
-        );
-        std::process::exit(1);
+    // Note - With `--memory`, the config file is ignored...
+    let cfg = if app.memory {
+        Config {
+            conf_dir,
+            db: conf::DbConfig {
+                db_type: conf::DBType::Memory,
+                remote_db_path: None,
+                encrypt_replica: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    } else {
+        // Make sure the config directory already exists...
+        if !conf_dir.exists() {
+            return Err(error::config(format!(
+                "Config directory \"{}\" doesn't exist.\n{}",
+                conf_dir.display(),
+                conf::config_dir_hint(&conf_dir, conf_dir_source),
+            )));
+        }
+
+        // Make sure the config directory is a directory...
+        if !conf_dir.is_dir() {
+            return Err(error::config(format!(
+                "Config directory \"{}\" exists but isn't a directory.\n{}",
+                conf_dir.display(),
+                conf::config_dir_hint(&conf_dir, conf_dir_source),
+            )));
+        }
+
+        // Make sure the config file exists...
+        let conf_file = conf::get_config_file(&conf_dir);
+        if !conf_file.is_file() {
+            return Err(error::config(format!(
+                "Config file \"{}\" doesn't exist.\n{}",
+                conf_file.display(),
+                conf::config_dir_hint(&conf_dir, conf_dir_source),
+            )));
+        }
+
+        let mut cfg = Config::read_from_file(&conf_dir)
+            .map_err(|e| error::config(format!("Could not read config file: {}", e)))?;
+        cfg.db.encryption_key_file = app.encryption_key_file.clone();
+        cfg
+    };
+
+    // Is this a show config command?
+    // Note - This is handled before connecting, so it still works
+    // if the database can't be reached...
+    if let Commands::Cfg {
+        cmd: CfgCmd::Show(args),
+    } = &app.cmd
+    {
+        // Which secrets are needed?
+        let required = cfg.db.required_secrets();
+
+        // Build the summary...
+        // Note - Secrets are only checked for, never shown...
+        let data = json!({
+            "config_dir": cfg.conf_dir,
+            "config_file": conf::get_config_file(&cfg.conf_dir),
+            "db_type": cfg.db.db_type,
+            "remote_db_url": cfg.db.remote_url().map(|url| url.url),
+            "remote_db_url_has_token": cfg.db.remote_url().map(|url| url.auth_token.is_some()),
+            "data_dir": match cfg.db.db_type {
+                conf::DBType::Local | conf::DBType::RemoteWithReplica => {
+                    Some(cfg.db_dir())
+                }
+                _ => None,
+            },
+            "encrypted": cfg.db.encrypt_replica,
+            "remote_db_token_set": required
+                .remote_db_token
+                .then(secrets::has_remote_db_auth_token),
+            "encryption_key_set": required.encryption_key.then(|| {
+                secrets::has_local_db_encryption_key_source(cfg.db.encryption_key_file.as_deref())
+            }),
+            "max_prop_bytes": app
+                .max_prop_bytes
+                .or(cfg.db.max_prop_bytes)
+                .unwrap_or(db::DEFAULT_MAX_PROP_BYTES),
+            "require_node_label": cfg.db.require_node_label,
+            "allow_self_loops": cfg.db.allow_self_loops,
+            "label_case": cfg.db.label_case,
+            "edge_type_case": cfg.db.edge_type_case,
+            "compress_large_props": cfg.db.compress_large_props,
+            "journal_mode": app.journal_mode.or(cfg.db.journal_mode),
+            "synchronous": app.synchronous.or(cfg.db.synchronous),
+            "timezone": app.timezone.unwrap_or(cfg.timezone),
+            "list_limit": cfg.list_limit(None, false),
+        });
+        let data = data.as_object().cloned().unwrap_or_default();
+        output::print_record(&data, &args.format)?;
+        return Ok(());
     }
 
-    // Make sure the config directory is a directory...
-    if !cfg.conf_dir.is_dir() {
-        eprintln!(
-            "Error: Config directory \"{}\" exists but isn't a directory.
-Remove it and then run `graphctl init` to create it",
-            cfg.conf_dir.display(),
+    // Is this a set data dir command?
+    // Note - This is handled before connecting, so the database files
+    // aren't open while they're moved...
+    if let Commands::Cfg {
+        cmd: CfgCmd::SetDataDir(args),
+    } = &app.cmd
+    {
+        if app.memory {
+            return Err(error::validation(
+                "In-memory databases don't have a data directory.",
+            ));
+        }
+
+        // Where are the files moving from and to?
+        let from = cfg.db_dir();
+        let to = std::path::absolute(&args.path)
+            .context(format!("Invalid data directory {:?}", args.path))?;
+        if to == from {
+            return Err(error::validation(format!(
+                "The data directory is already \"{}\".",
+                to.display()
+            )));
+        }
+        let mut new_cfg = cfg.clone();
+        new_cfg.db.data_dir = (to != conf::get_db_dir(&cfg.conf_dir)).then(|| to.clone());
+
+        // Which files are there to move?
+        // Note - Remote-only databases don't keep anything locally, so
+        // only the config changes (e.g. for a later switch to a replica)...
+        let has_local_data = matches!(
+            cfg.db.db_type,
+            conf::DBType::Local | conf::DBType::RemoteWithReplica
+        );
+        let files = match has_local_data {
+            true => conf::list_db_files(&from)
+                .context(format!("Failed to list the files in {:?}", from))?,
+            false => Vec::new(),
+        };
+        let note = (!has_local_data).then_some(
+            "Remote-only databases have no local data to move. Only the config was updated.",
         );
-        std::process::exit(1);
-    }
 
-    // Create the db...
-    let db = match connect_to_db(&cfg.conf_dir, &cfg).await {
-        Ok(d) => d,
-        Err(e) => {
-            eprintln!("Error: Could not initialize database: {}", e);
-            std::process::exit(1);
+        // Is this a dry run?
+        if args.dry_run {
+            let data = json!({
+                "dry_run": true,
+                "from": from,
+                "to": to,
+                "files": files,
+                "note": note,
+            });
+            output::print_json(&data)?;
+            return Ok(());
         }
-    };
 
-    // Create a connection...
-    let conn = match db.connect() {
-        Ok(c) => c,
-        Err(e) => {
-            eprintln!("Error: Could not connect to database: {}", e);
-            std::process::exit(1);
+        // Confirm before moving anything...
+        if !files.is_empty()
+            && !args.yes
+            && !prompt::prompt_for_confirm_move_data(files.len(), &from, &to)?
+        {
+            return Err(anyhow!("Move cancelled."));
         }
-    };
+        let _lock = util::LockFile::acquire(&lock_file)?;
+
+        // Move the files...
+        let moved = util::move_files(&files, &to)?;
 
-    // Run the migrations...
-    if let Err(err) = init_db(&conn).await {
-        eprintln!("Error: Could not initialize database: {}", err);
-        std::process::exit(1);
+        // Make sure the database still opens from its new home (moving the
+        // files back if not), then switch the config over...
+        let switched = async {
+            if has_local_data {
+                let opts = db::OpenOptions {
+                    migrate: false,
+                    audit: false,
+                    ..open_opts
+                };
+                let (_db, conn) = db::open_and_migrate(&new_cfg, &opts).await?;
+                db::count_nodes(&conn, &db::NodeFilter::default()).await?;
+            }
+            new_cfg.write_to_file()
+        };
+        if let Err(err) = switched.await {
+            util::move_files(&moved, &from).context(format!(
+                "Failed to move the database files back to {:?}",
+                from
+            ))?;
+            return Err(
+                err.context("Could not switch to the new data directory (nothing was changed)")
+            );
+        }
+
+        // Print the result...
+        let data = json!({
+            "from": from,
+            "to": to,
+            "moved": moved,
+            "note": note,
+        });
+        output::print_json(&data)?;
+        return Ok(());
     }
 
+    // Connect to the db and run the migrations (unless `meta migrate`
+    // is running them explicitly, or they're turned off)...
+    let explicit_migrate = matches!(
+        app.cmd,
+        Commands::Meta {
+            cmd: MetaCmd::Migrate(_)
+        }
+    );
+    // Note - With `meta migrate`, auditing is enabled once the schema is
+    // up to date...
+    if let Some(warning) = db::remote_auth_token_warning(&cfg) {
+        eprintln!("Warning: {}", warning);
+    }
+    let (db, conn) = db::open_and_migrate(
+        &cfg,
+        &db::OpenOptions {
+            migrate: !explicit_migrate && !app.no_migrate,
+            audit: !explicit_migrate,
+            ..open_opts
+        },
+    )
+    .await?;
+
+    // Set the timezone used to display timestamps...
+    util::set_display_timezone(app.timezone.unwrap_or(cfg.timezone));
+
+    // How props are shown in tables...
+    output::set_expand_props(app.expand_props);
+
+    // How `key=value` props are parsed...
+    let parse_props_opts = util::ParsePropsOptions {
+        allow_duplicates: app.allow_duplicate_props,
+        trim_values: app.trim_values,
+    };
+
+    // The max prop size can be set by flag or in the config...
+    let max_prop_bytes = app.max_prop_bytes.or(cfg.db.max_prop_bytes);
+
+    // How many nodes a traversal can collect (0 means no limit)...
+    let max_results = Some(app.max_results).filter(|m| *m > 0);
+
+    // Large prop values are only compressed if enabled in the config...
+    let compress_props_over = cfg.db.compress_large_props.then(|| {
+        cfg.db
+            .compress_props_over_bytes
+            .unwrap_or(db::DEFAULT_COMPRESS_PROPS_OVER_BYTES)
+    });
+
+    // Take the lock for init-like and destructive commands...
+    // Note - An in-memory database isn't shared, so it doesn't need one...
+    let _lock = if needs_lock(&app.cmd) && !app.memory {
+        Some(util::LockFile::acquire(&lock_file)?)
+    } else {
+        None
+    };
+
     // Handle the other commands...
     match app.cmd {
         Commands::Create { cmd } => match cmd {
@@ -194,233 +565,1307 @@ Remove it and then run `graphctl init` to create it",
                 // TODO - Add output formatting options...
 
                 // Split the props into key-value pairs...
-                let mut props = HashMap::new();
-                for p in args.prop {
-                    // Split the key-value pair on on the equals sign...
-                    let mut parts = p.splitn(2, '=');
-
-                    // Get the key, strip, and convert to lowercase...
-                    let key = parts
-                        .next()
-                        .ok_or(anyhow!("Failed to parse key-value pair."))
-                        .context(format!("argument={}", p))?
-                        .trim()
-                        .to_string();
-
-                    // Make sure the key is not empty...
-                    if key.is_empty() {
-                        return Err(anyhow!("Empty key in key-value pair."));
+                let props = util::parse_props(&args.prop, &parse_props_opts)?;
+
+                // Merge in the template's (or the cloned node's) defaults?
+                let template = match (&args.from_template, &args.clone_from) {
+                    (Some(name), _) => Some(db::get_node_template(&conn, name).await?),
+                    (_, Some(id)) => {
+                        let params = db::GetNodeParams {
+                            id: id.clone(),
+                            with_props: true,
+                            ..Default::default()
+                        };
+                        let source = db::get_node(&conn, &params)
+                            .await
+                            .with_context(|| format!("Failed to get node to clone: {}", id))?;
+                        Some(db::NodeTemplate::from(source))
                     }
+                    (None, None) => None,
+                };
+                let (labels, props) = match template {
+                    Some(template) => template.apply(args.labels(), props),
+                    None => (args.labels(), props),
+                };
 
-                    // Get the value...
-                    let value = parts
-                        .next()
-                        .ok_or(anyhow!("Failed to parse key-value pair."))
-                        .context(format!("argument={}", p))?;
-
-                    // Try to parse it as JSON first,
-                    // otherwise just use the string...
-                    let value = match serde_json::from_str(value) {
-                        Ok(v) => v,
-                        Err(_) => serde_json::Value::String(value.to_string()),
-                    };
+                let params = db::CreateNodeParams {
+                    id: args.id.clone(),
+                    labels,
+                    props,
+                    max_prop_bytes,
+                    compress_props_over,
+                    require_label: args.require_label || cfg.db.require_node_label,
+                    label_case: cfg.db.label_case,
+                };
 
-                    // Add it to the props map...
-                    props.insert(key, value);
+                // Upsert the node, reporting what happened...
+                if args.upsert {
+                    let (node, created) = db::upsert_node(&conn, &params).await?;
+                    if args.ids_only {
+                        return output::print_ids([node.id.as_str()]);
+                    }
+                    let data = json!({
+                        "action": if created { "created" } else { "updated" },
+                        "node": node,
+                    });
+                    output::print_json(&data)?;
+                    return Ok(());
                 }
 
                 // Add the node to the database...
-                let res = db::create_node(
-                    &conn,
-                    &db::CreateNodeParams {
-                        labels: args.label,
-                        props,
-                    },
-                )
-                .await?;
+                let res = db::create_node(&conn, &params).await?;
 
                 // Print the result...
-                println!("{}", serde_json::to_string_pretty(&res)?);
+                if args.ids_only {
+                    output::print_ids([res.id.as_str()])?;
+                } else {
+                    output::print_json(&res)?;
+                }
             }
             CreateCmd::Edge(args) => {
                 // TODO - Add output formatting options...
 
+                // Is the edge directed?
+                let directed = cfg.edge_directed(args.direction());
+                if args.both_directions && !directed {
+                    return Err(error::validation(
+                        "`--both-directions` only works with directed edges. Pass `--directed`.",
+                    ));
+                }
+
+                // Get the source and target node IDs...
+                let from_node =
+                    node_id_or_selector(&conn, &cfg.db, args.from_node, args.from_selector)
+                        .await
+                        .context("Failed to get the source node")?;
+                let to_node = node_id_or_selector(&conn, &cfg.db, args.to_node, args.to_selector)
+                    .await
+                    .context("Failed to get the target node")?;
+
                 // Check that the source and target nodes exist...
-                if !db::check_node_exists(&conn, &args.from_node).await? {
-                    return Err(anyhow!("Source node does not exist."));
+                if !db::check_node_exists(&conn, &from_node).await? {
+                    return Err(error::not_found("Source node does not exist."));
                 }
-                if args.from_node != args.to_node
-                    && !db::check_node_exists(&conn, &args.to_node).await?
-                {
-                    return Err(anyhow!("Source node does not exist."));
+                if from_node != to_node && !db::check_node_exists(&conn, &to_node).await? {
+                    return Err(error::not_found("Target node does not exist."));
                 }
 
                 // Split the props into key-value pairs...
-                let mut props = HashMap::new();
-                for p in args.prop.iter() {
-                    // Split the key-value pair on on the equals sign...
-                    let mut parts = p.splitn(2, '=');
-
-                    // Get the key, strip, and convert to lowercase...
-                    let key = parts
-                        .next()
-                        .ok_or(anyhow!("Failed to parse key-value pair."))
-                        .context(format!("argument={}", p))?
-                        .trim()
-                        .to_string();
-
-                    // Make sure the key is not empty...
-                    if key.is_empty() {
-                        return Err(anyhow!("Empty key in key-value pair."));
-                    }
+                let props = util::parse_props(&args.prop, &parse_props_opts)?;
 
-                    // Get the value...
-                    let value = parts
-                        .next()
-                        .ok_or(anyhow!("Failed to parse key-value pair."))
-                        .context(format!("argument={}", p))?;
-
-                    // Try to parse it as JSON first,
-                    // otherwise just use the string...
-                    let value = match serde_json::from_str(value) {
-                        Ok(v) => v,
-                        Err(_) => serde_json::Value::String(value.to_string()),
-                    };
+                let params = db::CreateEdgeParams {
+                    edge_type: args.edge_type,
+                    from_node,
+                    to_node,
+                    directed,
+                    props,
+                    max_prop_bytes,
+                    compress_props_over,
+                    edge_type_case: cfg.db.edge_type_case,
+                    allow_self_loop: args.allow_self_loop || cfg.db.allow_self_loops,
+                    valid_from: args.valid_from,
+                    valid_to: args.valid_to,
+                };
 
-                    // Add it to the props map...
-                    props.insert(key, value);
+                // Create an edge in each direction?
+                if args.both_directions {
+                    let (forward, backward) = db::create_reciprocal_edges(&conn, &params).await?;
+                    if args.ids_only {
+                        return output::print_ids([forward.id.as_str(), backward.id.as_str()]);
+                    }
+                    output::print_json(&[forward, backward])?;
+                    return Ok(());
                 }
 
                 // Create the edge...
-                let res = db::create_edge(
+                let res = db::create_edge(&conn, &params).await?;
+
+                // Print the result...
+                if args.ids_only {
+                    output::print_ids([res.id.as_str()])?;
+                } else {
+                    output::print_json(&res)?;
+                }
+            }
+            CreateCmd::Subgraph(args) => {
+                // Read the spec...
+                let data = if args.file.as_os_str() == "-" {
+                    let mut data = String::new();
+                    tokio::io::stdin()
+                        .read_to_string(&mut data)
+                        .await
+                        .context("Failed to read stdin")?;
+                    data
+                } else {
+                    std::fs::read_to_string(&args.file)
+                        .context(format!("Failed to read spec file {:?}", args.file))?
+                };
+                let spec: db::SubgraphSpec = serde_json::from_str(&data)
+                    .map_err(|e| error::validation(format!("Invalid subgraph spec: {}", e)))?;
+
+                // Create the nodes and edges...
+                let res = db::create_subgraph(
                     &conn,
-                    &db::CreateEdgeParams {
-                        edge_type: args.edge_type,
-                        from_node: args.from_node,
-                        to_node: args.to_node,
-                        directed: args.directed,
-                        props,
+                    &db::CreateSubgraphParams {
+                        spec,
+                        max_prop_bytes,
+                        compress_props_over,
+                        require_label: cfg.db.require_node_label,
+                        label_case: cfg.db.label_case,
+                        edge_type_case: cfg.db.edge_type_case,
+                        allow_self_loop: args.allow_self_loop || cfg.db.allow_self_loops,
+                        cancel,
                     },
                 )
                 .await?;
 
                 // Print the result...
-                println!("{}", serde_json::to_string_pretty(&res)?);
+                output::print_json(&res)?;
             }
         },
         Commands::List { cmd } => match cmd {
-            ListCmd::Nodes(_args) => {
+            ListCmd::Nodes(args) => {
+                // Compile the prop key patterns first...
+                let (include, exclude) = args.prop_keys.compile()?;
+
+                // Check the degree window...
+                if let (Some(min), Some(max)) = (args.min_degree, args.max_degree) {
+                    if min > max {
+                        return Err(error::validation(format!(
+                            "`--min-degree` ({}) can't be more than `--max-degree` ({}).",
+                            min, max,
+                        )));
+                    }
+                }
+
+                // Just print the count?
+                // Note - The default limit doesn't apply here, only `--limit`...
+                let updated_after = if args.modified_since_sync {
+                    Some(last_sync_marker(&conn).await?)
+                } else {
+                    None
+                };
+                let filter = db::NodeFilter {
+                    has_edge_types: args.has_edge_type,
+                    has_edge_direction: args.has_edge_direction,
+                    min_degree: args.min_degree,
+                    max_degree: args.max_degree,
+                    updated_after,
+                    ..node_filter(args.filter, &cfg.db)?
+                };
+                if args.count {
+                    let total = db::count_nodes(&conn, &filter).await?;
+                    let count = match args.sample.or(args.limit).filter(|l| *l > 0) {
+                        Some(limit) => total.min(limit),
+                        None => total,
+                    };
+                    let data = json!({ "count": count });
+                    let data = data.as_object().cloned().unwrap_or_default();
+                    output::print_record(&data, &args.format)?;
+                    return Ok(());
+                }
+
                 // Get the node list...
-                let res = db::list_nodes(&conn, &db::ListNodesParams {}).await?;
+                // Note - A sample replaces the limit...
+                let limit = match args.sample {
+                    Some(_) => None,
+                    None => cfg.list_limit(args.limit, args.all),
+                };
+                let params = db::ListNodesParams {
+                    filter,
+                    limit,
+                    sample: args.sample,
+                };
+                let mut res = db::list_nodes(&conn, &params).await?;
+
+                // Warn if the results were cut off...
+                // Note - The total is what `--count` would report...
+                let total = match limit.filter(|l| res.len() == *l) {
+                    Some(_) => Some(db::count_nodes(&conn, &params.filter).await?),
+                    None => None,
+                };
+                if let Some(total) = total.filter(|t| *t > res.len()) {
+                    eprintln!(
+                        "Showing {} of {} nodes. Use `--all` to show them all.",
+                        res.len(),
+                        total,
+                    );
+                }
+
+                // Filter the props...
+                for node in res.iter_mut() {
+                    if let Some(props) = node.props.as_mut() {
+                        util::filter_props(props, include.as_ref(), exclude.as_ref());
+                    }
+                }
 
                 // Print the result...
-                println!("{}", serde_json::to_string_pretty(&res)?);
+                if args.ids_only {
+                    output::print_ids(res.iter().map(|n| n.id.as_str()))?;
+                } else if args.omit_timestamps {
+                    let items = output::without_timestamps(&res)?;
+                    output::print_list(&items, "node", &args.format, args.stats, total)?;
+                } else {
+                    output::print_list(&res, "node", &args.format, args.stats, total)?;
+                }
             }
-            ListCmd::Edges(_args) => {
+            ListCmd::Edges(args) => {
+                // Build the filter...
+                let updated_after = if args.modified_since_sync {
+                    Some(last_sync_marker(&conn).await?)
+                } else {
+                    None
+                };
+                let filter = db::EdgeFilter {
+                    updated_after,
+                    ..edge_filter(args.filter, &cfg.db)?
+                };
+
+                // Count the edges by group, rather than listing them?
+                if let Some(by) = args.group_by {
+                    let groups = db::group_edges(&conn, &filter, by).await?;
+                    let key_name = by.to_possible_value().map(|v| v.get_name().to_string());
+                    output::print_counts(&groups, &key_name.unwrap_or_default(), &args.format)?;
+                    return Ok(());
+                }
+
+                // Compile the prop key patterns first...
+                let (include, exclude) = args.prop_keys.compile()?;
+
+                // Just print the count?
+                // Note - The default limit doesn't apply here, only `--limit`...
+                if args.count {
+                    let total = db::count_edges(&conn, &filter).await?;
+                    let count = match args.sample.or(args.limit).filter(|l| *l > 0) {
+                        Some(limit) => total.min(limit),
+                        None => total,
+                    };
+                    let data = json!({ "count": count });
+                    let data = data.as_object().cloned().unwrap_or_default();
+                    output::print_record(&data, &args.format)?;
+                    return Ok(());
+                }
+
                 // Get the edge list...
-                let res = db::list_edges(&conn, &db::ListEdgesParams {}).await?;
+                // Note - A sample replaces the limit...
+                let limit = match args.sample {
+                    Some(_) => None,
+                    None => cfg.list_limit(args.limit, args.all),
+                };
+                let params = db::ListEdgesParams {
+                    filter,
+                    limit,
+                    sample: args.sample,
+                };
+                let mut res = db::list_edges(&conn, &params).await?;
+
+                // Warn if the results were cut off...
+                // Note - The total is what `--count` would report...
+                let total = match limit.filter(|l| res.len() == *l) {
+                    Some(_) => Some(db::count_edges(&conn, &params.filter).await?),
+                    None => None,
+                };
+                if let Some(total) = total.filter(|t| *t > res.len()) {
+                    eprintln!(
+                        "Showing {} of {} edges. Use `--all` to show them all.",
+                        res.len(),
+                        total,
+                    );
+                }
+
+                // Filter the props...
+                for edge in res.iter_mut() {
+                    if let Some(props) = edge.props.as_mut() {
+                        util::filter_props(props, include.as_ref(), exclude.as_ref());
+                    }
+                }
 
                 // Print the result...
-                println!("{}", serde_json::to_string_pretty(&res)?);
+                if args.ids_only {
+                    output::print_ids(res.iter().map(|e| e.id.as_str()))?;
+                } else if args.omit_timestamps {
+                    let items = output::without_timestamps(&res)?;
+                    output::print_list(&items, "edge", &args.format, args.stats, total)?;
+                } else {
+                    output::print_list(&res, "edge", &args.format, args.stats, total)?;
+                }
             }
         },
-        Commands::Get { cmd } => match cmd {
+        Commands::Get { cmd, trace } => match cmd {
             GetCmd::Node(args) => {
-                // Get the node...
-                let res = db::get_node(
-                    &conn,
-                    &db::GetNodeParams {
-                        id: args.id.clone(),
+                let mut trace = trace::Trace::new(trace);
+
+                // Compile the prop key patterns first...
+                let (include, exclude) = args.prop_keys.compile()?;
+
+                // Get a set of nodes from a file of IDs?
+                if let Some(path) = &args.ids_file {
+                    if let cli::GetNodeFormat::Mermaid = args.format {
+                        return Err(error::validation(
+                            "`--format mermaid` can't be used with `--ids-file`.",
+                        ));
+                    }
+                    let ids: Vec<String> = read_file_or_stdin(path, "IDs file")
+                        .await?
+                        .lines()
+                        .map(|l| l.trim().to_string())
+                        .filter(|l| !l.is_empty())
+                        .collect();
+                    let params = db::GetNodesParams {
+                        ids,
                         with_props: args.props,
-                    },
-                )
-                .await?;
+                        include_archived: args.include_archived,
+                        strict: args.strict,
+                    };
+                    let mut nodes = trace
+                        .step("nodes (batched)", db::get_nodes(&conn, &params))
+                        .await?;
+
+                    // Warn about any that weren't found...
+                    let found: HashSet<&str> = nodes.iter().map(|n| n.id.as_str()).collect();
+                    let mut missing: Vec<&str> = params
+                        .ids
+                        .iter()
+                        .map(|id| id.as_str())
+                        .filter(|id| !found.contains(id))
+                        .collect();
+                    missing.sort();
+                    missing.dedup();
+                    if !missing.is_empty() {
+                        eprintln!(
+                            "Warning: {} node(s) not found: {}",
+                            missing.len(),
+                            missing.join(", ")
+                        );
+                    }
+
+                    // Print them...
+                    for node in nodes.iter_mut() {
+                        if let Some(props) = node.props.as_mut() {
+                            util::filter_props(props, include.as_ref(), exclude.as_ref());
+                        }
+                    }
+                    let format = match args.format {
+                        cli::GetNodeFormat::Ndjson => cli::OutputFormat::Ndjson,
+                        _ => cli::OutputFormat::Json,
+                    };
+                    if args.omit_timestamps {
+                        let items = output::without_timestamps(&nodes)?;
+                        output::print_list(&items, "node", &format, false, None)?;
+                    } else {
+                        output::print_list(&nodes, "node", &format, false, None)?;
+                    }
+                    trace.print();
+                    return Ok(());
+                }
+
+                // Resolve the (possibly abbreviated) ID...
+                let id_arg = args.id.unwrap_or_default();
+                let id = trace
+                    .step("resolve id", db::resolve_node_id(&conn, &id_arg))
+                    .await?;
+
+                // Get the node (and then its props, so they're timed separately)...
+                let mut res = trace
+                    .step(
+                        "node",
+                        db::get_node(
+                            &conn,
+                            &db::GetNodeParams {
+                                id: id.clone(),
+                                with_props: false,
+                                include_archived: args.include_archived,
+                            },
+                        ),
+                    )
+                    .await?;
+                if args.props {
+                    let mut props = trace.step("props", db::get_node_props(&conn, &id)).await?;
+                    util::filter_props(&mut props, include.as_ref(), exclude.as_ref());
+                    res.props = Some(props);
+                }
+
+                // Render the node's neighborhood as Mermaid, if requested...
+                if let cli::GetNodeFormat::Mermaid = args.format {
+                    let mut edges = trace
+                        .step(
+                            "edges in",
+                            db::get_node_edges_in_expanded(&conn, &id, false),
+                        )
+                        .await?;
+                    edges.extend(
+                        trace
+                            .step(
+                                "edges out",
+                                db::get_node_edges_out_expanded(&conn, &id, false),
+                            )
+                            .await?,
+                    );
+                    edges.sort_by(|a, b| a.id.cmp(&b.id));
+                    edges.dedup_by(|a, b| a.id == b.id);
+
+                    // Get the nodes at the other ends...
+                    let mut ids: Vec<String> = edges
+                        .iter()
+                        .flat_map(|e| [e.from_node.clone(), e.to_node.clone()])
+                        .filter(|n| n != &id)
+                        .collect();
+                    ids.sort();
+                    ids.dedup();
+                    let mut neighbors = trace
+                        .step("neighbors", db::get_nodes_by_ids(&conn, &ids))
+                        .await?;
+                    let mut nodes = vec![res];
+                    nodes.extend(ids.iter().filter_map(|n| neighbors.remove(n)));
+
+                    let dump = export::GraphDump { nodes, edges };
+                    output::print_line(&export::to_mermaid(&dump))?;
+                    trace.print();
+                    return Ok(());
+                }
 
                 // Get the node's edges in and out...
-                let edges_in = match args.edges_in {
+                // Note - By default these are just the edge IDs, unless expanded...
+                let edge_props = args.props || args.include_edges_props;
+                let edges_in = match (args.edges_in, args.expand_edges) {
+                    (false, _) => None,
+                    (true, false) => Some(json!(
+                        trace
+                            .step("edges in", db::get_node_edges_in(&conn, &id))
+                            .await?
+                    )),
+                    (true, true) => {
+                        let edges = trace
+                            .step(
+                                "edges in (expanded)",
+                                db::get_node_edges_in_expanded(&conn, &id, edge_props),
+                            )
+                            .await?;
+                        Some(match args.include_edges_props {
+                            true => json!(
+                                trace
+                                    .step(
+                                        "edges in (other ends)",
+                                        db::get_incident_edges(&conn, &id, edges)
+                                    )
+                                    .await?
+                            ),
+                            false => json!(edges),
+                        })
+                    }
+                };
+                let edges_out = match (args.edges_out, args.expand_edges) {
+                    (false, _) => None,
+                    (true, false) => Some(json!(
+                        trace
+                            .step("edges out", db::get_node_edges_out(&conn, &id))
+                            .await?
+                    )),
+                    (true, true) => {
+                        let edges = trace
+                            .step(
+                                "edges out (expanded)",
+                                db::get_node_edges_out_expanded(&conn, &id, edge_props),
+                            )
+                            .await?;
+                        Some(match args.include_edges_props {
+                            true => json!(
+                                trace
+                                    .step(
+                                        "edges out (other ends)",
+                                        db::get_incident_edges(&conn, &id, edges)
+                                    )
+                                    .await?
+                            ),
+                            false => json!(edges),
+                        })
+                    }
+                };
+
+                // Get the node's ancestors and descendants...
+                let walk_filter = db::EdgeFilter {
+                    edge_type: args.edge_type,
+                    prop_conditions: parse_prop_conditions(&args.edge_prop)?,
+                    edge_type_case: cfg.db.edge_type_case,
+                    ..Default::default()
+                };
+                let ancestors = match args.ancestors {
                     false => None,
-                    true => Some(db::get_node_edges_in(&conn, &args.id.clone()).await?),
+                    true => Some(
+                        trace
+                            .step(
+                                "ancestors",
+                                db::get_node_ancestors(&conn, &id, &walk_filter, max_results),
+                            )
+                            .await?,
+                    ),
                 };
-                let edges_out = match args.edges_out {
+                let descendants = match args.descendants {
                     false => None,
-                    true => Some(db::get_node_edges_out(&conn, &args.id.clone()).await?),
+                    true => Some(
+                        trace
+                            .step(
+                                "descendants",
+                                db::get_node_descendants(&conn, &id, &walk_filter, max_results),
+                            )
+                            .await?,
+                    ),
                 };
 
                 // Print the result...
-                let data = json!({
+                let mut data = json!({
                     "id": res.id,
                     "labels": res.labels,
                     "props": res.props,
                     "edges_in": edges_in,
                     "edges_out": edges_out,
-                    "created_at": res.created_at,
-                    "updated_at": res.updated_at,
+                    "ancestors": ancestors,
+                    "descendants": descendants,
+                    "created_at": util::format_timestamp(&res.created_at),
+                    "updated_at": util::format_timestamp(&res.updated_at),
                 });
-                println!("{}", serde_json::to_string_pretty(&data)?);
+                if args.omit_timestamps {
+                    output::strip_timestamps(&mut data);
+                }
+                let data = match &args.json_path {
+                    Some(path) => util::apply_json_path(&data, path)?,
+                    None => data,
+                };
+                if let cli::GetNodeFormat::Ndjson = args.format {
+                    output::print_line(&serde_json::to_string(&data)?)?;
+                } else {
+                    output::print_json(&data)?;
+                }
+                trace.print();
             }
             GetCmd::Edge(args) => {
-                // Get the edge...
-                let res = db::get_edge(
-                    &conn,
-                    &db::GetEdgeParams {
-                        id: args.id,
-                        with_props: args.props,
-                    },
-                )
-                .await?;
+                let mut trace = trace::Trace::new(trace);
+
+                // Compile the prop key patterns first...
+                let (include, exclude) = args.prop_keys.compile()?;
+
+                // Resolve the (possibly abbreviated) ID...
+                let id = trace
+                    .step("resolve id", db::resolve_edge_id(&conn, &args.id))
+                    .await?;
+
+                // Get the edge (and then its props, so they're timed separately)...
+                let mut res = trace
+                    .step(
+                        "edge",
+                        db::get_edge(
+                            &conn,
+                            &db::GetEdgeParams {
+                                id: id.clone(),
+                                with_props: false,
+                                include_archived: args.include_archived,
+                            },
+                        ),
+                    )
+                    .await?;
+                if args.props {
+                    let mut props = trace.step("props", db::get_edge_props(&conn, &id)).await?;
+                    util::filter_props(&mut props, include.as_ref(), exclude.as_ref());
+                    res.props = Some(props);
+                }
 
                 // Print the result...
-                println!("{}", serde_json::to_string_pretty(&res)?);
+                let mut data = serde_json::to_value(&res)?;
+                if args.omit_timestamps {
+                    output::strip_timestamps(&mut data);
+                }
+                let data = match &args.json_path {
+                    Some(path) => util::apply_json_path(&data, path)?,
+                    None => data,
+                };
+                output::print_json(&data)?;
+                trace.print();
             }
         },
+        Commands::Exists { cmd } => {
+            // Check for the node or edge...
+            let (exists, quiet) = match cmd {
+                ExistsCmd::Node(args) => {
+                    (db::check_node_exists(&conn, &args.id).await?, args.quiet)
+                }
+                ExistsCmd::Edge(args) => {
+                    (db::check_edge_exists(&conn, &args.id).await?, args.quiet)
+                }
+            };
+
+            // Print the result...
+            if !quiet {
+                output::print_line(&exists.to_string())?;
+            }
+
+            // Exit with a "not found" code if it doesn't exist...
+            if !exists {
+                std::process::exit(ErrorKind::NotFound.exit_code());
+            }
+        }
         Commands::Update { cmd } => match cmd {
             UpdateCmd::Node(args) => {
-                println!("Updating a node. Args: {:?}", args);
+                // Resolve the (possibly abbreviated) ID...
+                let id = db::resolve_node_id(&conn, &args.id).await?;
+
+                // Update the node...
+                let params = db::UpdateNodeParams {
+                    id,
+                    add_labels: args.add_label,
+                    remove_labels: args.remove_label,
+                    set_props: util::parse_props(&args.set_prop, &parse_props_opts)?,
+                    remove_props: args.remove_prop,
+                    max_prop_bytes,
+                    compress_props_over,
+                    label_case: cfg.db.label_case,
+                };
+                let node = db::update_node(&conn, &params).await?;
+
+                // Print the result...
+                output::print_json(&node)?;
+            }
+            UpdateCmd::Nodes(args) => {
+                let filter = node_filter(args.filter, &cfg.db)?;
+
+                // Just count the matches?
+                if args.dry_run {
+                    let ids = db::find_node_ids(&conn, &filter).await?;
+                    let data = json!({ "dry_run": true, "matched": ids.len() });
+                    output::print_json(&data)?;
+                    return Ok(());
+                }
+
+                // Update them...
+                let changes = db::NodePropChanges {
+                    set_props: util::parse_props(&args.set_prop, &parse_props_opts)?,
+                    remove_props: args.remove_prop,
+                    max_prop_bytes,
+                    compress_props_over,
+                    cancel,
+                };
+                let count = db::update_nodes_by_filter(&conn, &filter, &changes).await?;
+
+                // Print the result...
+                output::print_json(&json!({ "updated": count }))?;
             }
             UpdateCmd::Edge(args) => {
-                println!("Updating an edge. Args: {:?}", args);
+                // Resolve the (possibly abbreviated) ID...
+                let id = db::resolve_edge_id(&conn, &args.id).await?;
+
+                // Update the edge...
+                let directed = if args.set_directed {
+                    Some(true)
+                } else if args.set_undirected {
+                    Some(false)
+                } else {
+                    None
+                };
+                let params = db::UpdateEdgeParams {
+                    id,
+                    edge_type: args.edge_type,
+                    from_node: args.from_node,
+                    to_node: args.to_node,
+                    directed,
+                    swap: args.swap,
+                    set_props: util::parse_props(&args.set_prop, &parse_props_opts)?,
+                    remove_props: args.remove_prop,
+                    max_prop_bytes,
+                    compress_props_over,
+                    edge_type_case: cfg.db.edge_type_case,
+                };
+                let edge = db::update_edge(&conn, &params).await?;
+
+                // Print the result...
+                output::print_json(&edge)?;
             }
         },
         Commands::Delete { cmd } => match cmd {
             DeleteCmd::Node(args) => {
-                println!("Deleting a node. Args: {:?}", args);
+                // Resolve the (possibly abbreviated) ID...
+                let id = db::resolve_node_id(&conn, &args.id).await?;
+
+                // Delete (or archive) the node...
+                let data = if args.soft {
+                    db::archive_node(&conn, &id).await?;
+                    json!({ "archived": 1 })
+                } else {
+                    db::delete_node(&conn, &id).await?;
+                    json!({ "deleted": 1 })
+                };
+
+                // Print the result...
+                output::print_json(&data)?;
             }
             DeleteCmd::Edge(args) => {
-                println!("Deleting an edge. Args: {:?}", args);
+                // Resolve the (possibly abbreviated) ID...
+                let id = db::resolve_edge_id(&conn, &args.id).await?;
+
+                // Delete (or archive) the edge...
+                let data = if args.soft {
+                    db::archive_edge(&conn, &id).await?;
+                    json!({ "archived": 1 })
+                } else {
+                    db::delete_edge(&conn, &id).await?;
+                    json!({ "deleted": 1 })
+                };
+
+                // Print the result...
+                output::print_json(&data)?;
+            }
+            DeleteCmd::Nodes(args) => {
+                // Find the matching nodes...
+                let filter = node_filter(args.filter, &cfg.db)?;
+                let ids = db::find_node_ids(&conn, &filter).await?;
+
+                // Is this a dry run?
+                if args.dry_run {
+                    let data = json!({ "dry_run": true, "matched": ids.len(), "ids": ids });
+                    output::print_json(&data)?;
+                    return Ok(());
+                }
+
+                // Confirm before deleting...
+                if !args.yes && !prompt::prompt_for_confirm_delete(ids.len(), "nodes")? {
+                    return Err(anyhow!("Delete cancelled."));
+                }
+
+                // Delete them...
+                let count = db::delete_nodes_by_filter(&conn, &filter).await?;
+
+                // Print the result...
+                output::print_json(&json!({ "deleted": count }))?;
+            }
+            DeleteCmd::Edges(args) => {
+                // Find the matching edges...
+                let filter = db::EdgeFilter {
+                    dangling: args.dangling,
+                    ..edge_filter(args.filter, &cfg.db)?
+                };
+                let ids = db::find_edge_ids(&conn, &filter).await?;
+
+                // Is this a dry run?
+                if args.dry_run {
+                    let data = json!({ "dry_run": true, "matched": ids.len(), "ids": ids });
+                    output::print_json(&data)?;
+                    return Ok(());
+                }
+
+                // Confirm before deleting...
+                if !args.yes && !prompt::prompt_for_confirm_delete(ids.len(), "edges")? {
+                    return Err(anyhow!("Delete cancelled."));
+                }
+
+                // Delete them...
+                let count = db::delete_edges_by_filter(&conn, &filter).await?;
+
+                // Print the result...
+                output::print_json(&json!({ "deleted": count }))?;
             }
         },
-        Commands::Meta => todo!("Meta command not yet implemented"),
+        Commands::Unarchive { cmd } => match cmd {
+            UnarchiveCmd::Node(args) => {
+                // Resolve the (possibly abbreviated) ID...
+                let id = db::resolve_node_id(&conn, &args.id).await?;
+
+                // Restore the node...
+                db::unarchive_node(&conn, &id).await?;
+
+                // Print the result...
+                output::print_json(&json!({ "unarchived": 1 }))?;
+            }
+            UnarchiveCmd::Edge(args) => {
+                // Resolve the (possibly abbreviated) ID...
+                let id = db::resolve_edge_id(&conn, &args.id).await?;
+
+                // Restore the edge...
+                db::unarchive_edge(&conn, &id).await?;
+
+                // Print the result...
+                output::print_json(&json!({ "unarchived": 1 }))?;
+            }
+        },
+        Commands::Replace { cmd } => match cmd {
+            ReplaceCmd::Node(args) => {
+                // Resolve the (possibly abbreviated) ID...
+                let id = db::resolve_node_id(&conn, &args.id).await?;
+
+                // Start from the old node's labels and props?
+                let props = util::parse_props(&args.prop, &parse_props_opts)?;
+                let (labels, props) = if args.keep {
+                    let params = db::GetNodeParams {
+                        id: id.clone(),
+                        with_props: true,
+                        ..Default::default()
+                    };
+                    let old = db::get_node(&conn, &params).await?;
+                    db::NodeTemplate::from(old).apply(args.label, props)
+                } else {
+                    (args.label, props)
+                };
+
+                // Replace the node...
+                let params = db::CreateNodeParams {
+                    id: args.new_id,
+                    labels,
+                    props,
+                    max_prop_bytes,
+                    compress_props_over,
+                    require_label: args.require_label || cfg.db.require_node_label,
+                    label_case: cfg.db.label_case,
+                };
+                let res = db::replace_node(&conn, &id, &params).await?;
+
+                // Print the result...
+                output::print_json(&res)?;
+            }
+        },
+        Commands::Sync(args) => {
+            // Only replicas can be synced...
+            if !matches!(cfg.db.db_type, conf::DBType::RemoteWithReplica) {
+                return Err(error::config(
+                    "Sync is only available with the \"remote-with-replica\" database type.",
+                ));
+            }
+
+            // Sync...
+            let res = db::sync_replica(&db, &conn).await?;
+
+            // Find what changed since the previous sync?
+            let changed = match (args.report, res.previous_sync_at) {
+                (true, Some(since)) => Some(db::find_changed_since(&conn, &since).await?),
+                (true, None) => Some(db::ChangedSince::default()),
+                (false, _) => None,
+            };
+
+            // Print the result...
+            let data = json!({
+                "frame_no": res.frame_no,
+                "synced_at": util::format_timestamp(&res.synced_at),
+                "previous_sync_at": res.previous_sync_at.as_ref().map(util::format_timestamp),
+                "changed": changed,
+            });
+            output::print_json(&data)?;
+        }
+        Commands::Export(args) => {
+            // Appending only makes sense for line-based output...
+            if args.append && !matches!(args.format, ExportFormat::Ndjson) {
+                return Err(error::validation(
+                    "`--append` is only supported with `--format ndjson`.",
+                ));
+            }
+
+            // Export the graph (or a slice of it)...
+            let node_filter = if args.filter.is_set() {
+                Some(node_filter(args.filter, &cfg.db)?)
+            } else {
+                None
+            };
+            let params = export::ExportParams {
+                since: args.since,
+                node_filter,
+                edges: args.edges,
+            };
+            let dump = export::export_graph_filtered(&conn, &params).await?;
+            let data = match args.format {
+                ExportFormat::Json if args.omit_timestamps => {
+                    let mut data = serde_json::to_value(&dump)?;
+                    output::strip_timestamps(&mut data);
+                    format!("{}\n", serde_json::to_string_pretty(&data)?)
+                }
+                ExportFormat::Json => format!("{}\n", serde_json::to_string_pretty(&dump)?),
+                ExportFormat::Graphml => format!("{}\n", export::to_graphml(&dump)?),
+                ExportFormat::Ndjson => {
+                    let mut buf = Vec::new();
+                    export::write_ndjson_dump(&mut buf, &dump)?;
+                    let data = String::from_utf8(buf)?;
+                    if args.omit_timestamps {
+                        let mut lines = String::new();
+                        for line in data.lines() {
+                            let mut item: serde_json::Value = serde_json::from_str(line)?;
+                            output::strip_timestamps(&mut item);
+                            lines.push_str(&serde_json::to_string(&item)?);
+                            lines.push('\n');
+                        }
+                        lines
+                    } else {
+                        data
+                    }
+                }
+            };
+
+            // Write it out...
+            match args.output {
+                Some(path) if args.append => std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&path)
+                    .and_then(|mut f| f.write_all(data.as_bytes()))
+                    .context(format!("Failed to append export to {:?}", path))?,
+                Some(path) => std::fs::write(&path, data)
+                    .context(format!("Failed to write export to {:?}", path))?,
+                None => output::print_str(&data)?,
+            }
+        }
+        Commands::Import(args) => {
+            // Read the dump...
+            let data = read_file_or_stdin(&args.from_file, "import file").await?;
+
+            // Just check it?
+            if args.validate_only {
+                let params = export::ValidateImportParams {
+                    max_prop_bytes,
+                    require_label: cfg.db.require_node_label,
+                };
+                let errors = export::validate_ndjson(&conn, &data, &params).await?;
+                let data = json!({ "valid": errors.is_empty(), "errors": errors });
+                output::print_json(&data)?;
+                if !errors.is_empty() {
+                    return Err(error::validation(format!(
+                        "Found {} problem(s) in the import file.",
+                        errors.len()
+                    )));
+                }
+                return Ok(());
+            }
+
+            // Import it...
+            if args.batch_size.is_some() && args.checkpoint.is_none() && args.workers.is_none() {
+                return Err(error::validation(
+                    "--batch-size needs --checkpoint or --workers.",
+                ));
+            }
+            let params = export::ImportParams {
+                checkpoint: args.checkpoint,
+                batch_size: args.batch_size,
+                compress_props_over,
+                cancel,
+            };
+            let res = match args.workers {
+                Some(workers) => {
+                    if workers == 0 {
+                        return Err(error::validation("--workers must be at least 1."));
+                    }
+
+                    // Note - Each in-memory connection would be its own
+                    // (empty) database...
+                    if cfg.db.db_type == conf::DBType::Memory {
+                        return Err(error::validation(
+                            "--workers isn't supported for in-memory databases.",
+                        ));
+                    }
+
+                    // Open a connection per worker...
+                    let mut conns = Vec::with_capacity(workers);
+                    for _ in 0..workers {
+                        conns.push(db::open_connection(&db, &cfg, &open_opts).await?);
+                    }
+                    export::import_ndjson_parallel(&conns, &data, &params).await?
+                }
+                None => export::import_ndjson(&conn, &data, &params).await?,
+            };
+            output::print_json(&res)?;
+        }
+        Commands::Diff(args) => {
+            // Export the current graph...
+            let ours = export::export_graph(&conn).await?;
+
+            // Load the other graph -- either from another config dir or a dump file...
+            let theirs = if args.against.is_dir() {
+                let other = Config::read_from_file(&args.against).map_err(|e| {
+                    error::config(format!(
+                        "Could not read the other graph's config file: {}",
+                        e
+                    ))
+                })?;
+                let opts = db::OpenOptions {
+                    migrate: false,
+                    audit: false,
+                    ..open_opts
+                };
+                let (_other_db, other_conn) = db::open_and_migrate(&other, &opts)
+                    .await
+                    .context("Could not connect to the other graph's database")?;
+                export::export_graph(&other_conn).await?
+            } else {
+                export::read_dump(&args.against)?
+            };
+
+            // Compare them...
+            // Note - The diff describes how to get from the other graph to this one...
+            let res = diff::diff_graphs(&theirs, &ours);
+
+            // Print the result...
+            if args.summary {
+                output::print_line(&res.summary())?;
+            } else {
+                output::print_json(&res)?;
+            }
+        }
+        Commands::Meta { cmd } => match cmd {
+            MetaCmd::Set(args) => {
+                db::set_graph_meta(&conn, &args.key, &args.value).await?;
+                let data = json!({ "key": args.key.trim(), "value": args.value });
+                output::print_json(&data)?;
+            }
+            MetaCmd::Get(args) => {
+                let value = db::get_graph_meta(&conn, &args.key).await?.ok_or_else(|| {
+                    error::not_found(format!("Metadata key '{}' isn't set.", args.key))
+                })?;
+                let data = json!({ "key": args.key.trim(), "value": value });
+                output::print_json(&data)?;
+            }
+            MetaCmd::SetTemplate(args) => {
+                let template = db::NodeTemplate {
+                    name: args.name.trim().to_string(),
+                    labels: args.label,
+                    props: util::parse_props(&args.prop, &parse_props_opts)?,
+                };
+                db::set_node_template(&conn, &template).await?;
+                output::print_json(&template)?;
+            }
+            MetaCmd::SetLabelDefaults(args) => {
+                let label = cfg.db.label_case.apply(args.label.trim());
+                let props = util::parse_props(&args.prop, &parse_props_opts)?;
+                db::set_label_defaults(&conn, &label, &props).await?;
+                let data = json!({ "label": label, "props": props });
+                output::print_json(&data)?;
+            }
+            MetaCmd::ListLabelDefaults => {
+                let defaults = db::list_label_defaults(&conn).await?;
+                output::print_json(&defaults)?;
+            }
+            MetaCmd::Components(args) => {
+                // Warn before loading a very large graph...
+                let node_ids = db::find_node_ids(&conn, &db::NodeFilter::default()).await?;
+                if node_ids.len() > analysis::LARGE_GRAPH_NODES {
+                    eprintln!(
+                        "Warning: The graph has {} nodes. Finding its components loads the whole graph into memory, so this may be slow.",
+                        node_ids.len(),
+                    );
+                }
+
+                // Find the components...
+                let edges = db::get_all_edge_endpoints(&conn).await?;
+                let res = analysis::connected_components(&node_ids, &edges, args.per_node);
+                output::print_json(&res)?;
+            }
+            MetaCmd::Density(args) => {
+                let res = db::compute_density(&conn).await?;
+                let data = serde_json::to_value(&res)?;
+                let data = data.as_object().cloned().unwrap_or_default();
+                output::print_record(&data, &args.format)?;
+            }
+            MetaCmd::ReachableFrom(args) => {
+                let id = db::resolve_node_id(&conn, &args.id).await?;
+                let filter = db::EdgeFilter {
+                    edge_type: args.edge_type,
+                    prop_conditions: parse_prop_conditions(&args.edge_prop)?,
+                    edge_type_case: cfg.db.edge_type_case,
+                    ..Default::default()
+                };
+                let ids = db::get_reachable_from(&conn, &id, args.undirected, &filter, max_results)
+                    .await?;
+                let mut data = json!({
+                    "id": id,
+                    "undirected": args.undirected,
+                    "count": ids.len(),
+                });
+                if args.ids {
+                    data["ids"] = json!(ids);
+                }
+                output::print_json(&data)?;
+            }
+            MetaCmd::List => {
+                let meta = db::list_graph_meta(&conn).await?;
+                output::print_json(&meta)?;
+            }
+            MetaCmd::Migrate(args) => {
+                // Get the current state...
+                let current = db::get_migration_count(&conn).await?;
+                let target = db::MIGRATIONS.last().map_or(0, |m| m.version);
+
+                // Is this a dry run?
+                if args.dry_run {
+                    let pending = db::pending_migrations(&conn).await?;
+                    let data = json!({
+                        "dry_run": true,
+                        "current": current,
+                        "target": target,
+                        "pending": pending,
+                    });
+                    output::print_json(&data)?;
+                    return Ok(());
+                }
+
+                // Run the migrations...
+                let applied = db::migrate_db(&conn).await?;
+                db::enable_configured_audit(&conn, &cfg).await?;
+                let data = json!({
+                    "current": current,
+                    "target": target,
+                    "applied": applied,
+                });
+                output::print_json(&data)?;
+            }
+            MetaCmd::Audit(args) => {
+                // Note if there's nothing being recorded...
+                let log = db::get_audit_log(&conn, &args.id).await?;
+                if log.is_empty() && !cfg.db.audit {
+                    eprintln!(
+                        "Auditing is off. Set `audit: true` in the config to record changes."
+                    );
+                }
+                output::print_json(&log)?;
+            }
+            MetaCmd::Reindex(args) => {
+                // Remote databases are maintained by the server...
+                if let conf::DBType::RemoteOnly = cfg.db.db_type {
+                    let data = json!({
+                        "skipped": true,
+                        "reason": "The database is remote, so its statistics and indexes are maintained server-side.",
+                    });
+                    output::print_json(&data)?;
+                    return Ok(());
+                }
+
+                // Analyze (and maybe reindex) the database...
+                let start = std::time::Instant::now();
+                db::analyze_db(&conn, args.reindex).await?;
+                let data = json!({
+                    "analyzed": true,
+                    "reindexed": args.reindex,
+                    "elapsed_ms": start.elapsed().as_millis() as u64,
+                });
+                output::print_json(&data)?;
+            }
+        },
+        Commands::Doctor => unreachable!("Already handled doctor command"),
+        Commands::Version(_) => unreachable!("Already handled version command"),
         Commands::Cfg { cmd } => match cmd {
-            CfgCmd::Init => unreachable!("Already handled init command"),
+            CfgCmd::Init(_) => unreachable!("Already handled init command"),
+            CfgCmd::Show(_) => unreachable!("Already handled show command"),
+            CfgCmd::SetDataDir(_) => unreachable!("Already handled set data dir command"),
             CfgCmd::GetDbType(args) => {
-                println!("Getting DB type. Args: {:?}", args);
+                output::print_line(&format!("Getting DB type. Args: {:?}", args))?;
             }
             CfgCmd::SetDbType(args) => {
-                println!("Setting DB type. Args: {:?}", args);
+                output::print_line(&format!("Setting DB type. Args: {:?}", args))?;
             }
             CfgCmd::GetRemoteDbUrl(args) => {
-                println!("Getting remote DB URL. Args: {:?}", args);
+                output::print_line(&format!("Getting remote DB URL. Args: {:?}", args))?;
             }
             CfgCmd::SetRemoteDbUrl(args) => {
-                println!("Setting remote DB URL. Args: {:?}", args);
+                output::print_line(&format!("Setting remote DB URL. Args: {:?}", args))?;
             }
             CfgCmd::GetRemoteDbToken(args) => {
-                println!("Getting remote DB auth token. Args: {:?}", args);
+                output::print_line(&format!("Getting remote DB auth token. Args: {:?}", args))?;
             }
             CfgCmd::SetRemoteDbToken(args) => {
-                println!("Setting remote DB auth token. Args: {:?}", args);
+                output::print_line(&format!("Setting remote DB auth token. Args: {:?}", args))?;
             }
             CfgCmd::GetEncryptionKey(args) => {
-                println!(
+                output::print_line(&format!(
                     "Getting local db / local replica encryption key. Args: {:?}",
                     args
-                );
+                ))?;
             }
             CfgCmd::SetEncryptionKey(args) => {
-                println!(
+                output::print_line(&format!(
                     "Setting local db / local replica encryption key. Args: {:?}",
                     args
-                );
+                ))?;
+            }
+            CfgCmd::MigrateDbType(args) => {
+                // Only moves between a local file and a remote database are supported...
+                // Note - Replicas use the same local file as local databases, so
+                // they can't be copied to (or from) directly...
+                let from = cfg.db.db_type;
+                match (from, args.to) {
+                    (conf::DBType::Local, conf::DBType::RemoteOnly)
+                    | (conf::DBType::RemoteOnly, conf::DBType::Local) => {}
+                    (from, to) => {
+                        return Err(error::validation(format!(
+                            "Migrating from `{}` to `{}` isn't supported. Only `local` <-> `remote-only` \
+                            is (switch to `remote-with-replica` from `remote-only` with a fresh local file).",
+                            from.name(),
+                            to.name(),
+                        )));
+                    }
+                }
+
+                // Get the new config...
+                let mut new_cfg = cfg.clone();
+                new_cfg.db.db_type = args.to;
+                if let Some(url) = args.remote_db_url {
+                    new_cfg.db.remote_db_path = Some(url);
+                }
+
+                // Count what's being copied (archived items included)...
+                let nodes = db::NodeFilter {
+                    include_archived: true,
+                    ..Default::default()
+                };
+                let edges = db::EdgeFilter {
+                    include_archived: true,
+                    ..Default::default()
+                };
+                let nodes = db::count_nodes(&conn, &nodes).await?;
+                let edges = db::count_edges(&conn, &edges).await?;
+
+                // Is this a dry run?
+                if args.dry_run {
+                    let data = json!({
+                        "dry_run": true,
+                        "from": from,
+                        "to": args.to,
+                        "nodes": nodes,
+                        "edges": edges,
+                    });
+                    output::print_json(&data)?;
+                    return Ok(());
+                }
+
+                // Confirm before copying...
+                if !args.yes
+                    && !prompt::prompt_for_confirm_migrate(
+                        nodes,
+                        edges,
+                        from.name(),
+                        args.to.name(),
+                    )?
+                {
+                    return Err(error::cancelled("Migration cancelled."));
+                }
+
+                // Connect to the new database...
+                let (_new_db, new_conn) = db::open_and_migrate(&new_cfg, &open_opts)
+                    .await
+                    .context("Could not connect to the new database")?;
+
+                // Copy the graph over...
+                let res = export::copy_graph(&conn, &new_conn, &cancel).await?;
+
+                // Switch the config over to it...
+                // Note - An auth token embedded in the URL goes in the
+                // keyring, not the config file...
+                if let Some(url) = new_cfg.db.remote_url() {
+                    if let Some(token) = &url.auth_token {
+                        secrets::set_remote_db_auth_token(token)?;
+                    }
+                    new_cfg.db.remote_db_path = Some(url.url);
+                }
+                new_cfg
+                    .write_to_file()
+                    .context("Copied the graph, but failed to update the config file")?;
+
+                // Print the result...
+                let data = json!({
+                    "from": from,
+                    "to": args.to,
+                    "nodes": res.nodes,
+                    "edges": res.edges,
+                });
+                output::print_json(&data)?;
             }
         },
     }
@@ -428,3 +1873,116 @@ Remove it and then run `graphctl init` to create it",
     // Done!
     Ok(())
 }
+
+/// Get a node ID either directly or by resolving a selector.
+async fn node_id_or_selector(
+    conn: &libsql::Connection,
+    db_cfg: &conf::DbConfig,
+    id: Option<String>,
+    selector: Option<String>,
+) -> Result<String> {
+    match (id, selector) {
+        (Some(id), _) => Ok(id),
+        (None, Some(selector)) => {
+            let sel = util::parse_selector(&selector)?;
+            let label = sel.label.map(|l| db_cfg.label_case.apply(&l));
+            db::find_node_by_selector(conn, &sel.props, label.as_deref()).await
+        }
+        (None, None) => Err(error::validation("A node ID or selector is required.")),
+    }
+}
+
+/// Convert the node filter CLI args into a database filter.
+fn node_filter(args: NodeFilterArgs, db_cfg: &conf::DbConfig) -> Result<db::NodeFilter> {
+    let props = util::parse_prop_filters(&args.prop, args.prop_type)?;
+    let expr = args
+        .filter
+        .as_deref()
+        .map(filter::parse_filter_expr)
+        .transpose()?;
+    Ok(db::NodeFilter {
+        has_label: args.has_label,
+        exclude_labels: args.exclude_label,
+        has_prop: args.has_prop,
+        props: props.equals,
+        props_ignore_case: args.ignore_case,
+        props_contain: props.contains,
+        orphans: args.orphans,
+        connected: args.connected,
+        roots: args.roots,
+        leaves: args.leaves,
+        include_archived: args.include_archived,
+        label_case: db_cfg.label_case,
+        edge_type_case: db_cfg.edge_type_case,
+        expr,
+        ..Default::default()
+    })
+}
+
+/// Whether a command should hold the lock file while it runs -- i.e.
+/// it makes sweeping changes that shouldn't race with another command.
+fn needs_lock(cmd: &Commands) -> bool {
+    matches!(
+        cmd,
+        Commands::Delete { .. }
+            | Commands::Replace { .. }
+            | Commands::Import(_)
+            | Commands::Update {
+                cmd: UpdateCmd::Nodes(_)
+            }
+            | Commands::Create {
+                cmd: CreateCmd::Subgraph(_)
+            }
+            | Commands::Meta {
+                cmd: MetaCmd::Migrate(_)
+            }
+            | Commands::Cfg {
+                cmd: CfgCmd::MigrateDbType(_)
+            }
+    )
+}
+
+/// Read a whole file, or stdin if the path is `-`.
+async fn read_file_or_stdin(path: &std::path::Path, what: &str) -> Result<String> {
+    if path.as_os_str() == "-" {
+        let mut data = String::new();
+        tokio::io::stdin()
+            .read_to_string(&mut data)
+            .await
+            .context("Failed to read stdin")?;
+        Ok(data)
+    } else {
+        std::fs::read_to_string(path).context(format!("Failed to read {} {:?}", what, path))
+    }
+}
+
+/// Get the time of the last replica sync, for `--modified-since-sync`.
+async fn last_sync_marker(conn: &libsql::Connection) -> Result<chrono::DateTime<chrono::Utc>> {
+    db::get_last_sync_at(conn).await?.ok_or_else(|| {
+        error::validation("No sync has been recorded yet. Run `graphctl sync` first.")
+    })
+}
+
+/// Parse `--edge-prop` conditions (e.g. `weight>5`).
+fn parse_prop_conditions(args: &[String]) -> Result<Vec<util::PropCondition>> {
+    args.iter().map(|a| util::parse_prop_condition(a)).collect()
+}
+
+/// Convert the edge filter CLI args into a database filter.
+fn edge_filter(args: EdgeFilterArgs, db_cfg: &conf::DbConfig) -> Result<db::EdgeFilter> {
+    let props = util::parse_prop_filters(&args.prop, args.prop_type)?;
+    Ok(db::EdgeFilter {
+        edge_type: args.has_label,
+        has_prop: args.has_prop,
+        props: props.equals,
+        props_ignore_case: args.ignore_case,
+        props_contain: props.contains,
+        prop_conditions: parse_prop_conditions(&args.edge_prop)?,
+        from_node: args.source_node,
+        to_node: args.target_node,
+        valid_at: args.valid_at,
+        include_archived: args.include_archived,
+        edge_type_case: db_cfg.edge_type_case,
+        ..Default::default()
+    })
+}
@@ -0,0 +1,650 @@
+//! Handles printing command results in the different output formats.
+use crate::cli::OutputFormat;
+use anyhow::Result;
+use graphctl::util;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::io::{BufWriter, StdoutLock, Write};
+use std::sync::OnceLock;
+
+/// The size of the stdout buffer (see `buffered_stdout`).
+const STDOUT_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Whether tables show props expanded (see `set_expand_props`).
+static EXPAND_PROPS: OnceLock<bool> = OnceLock::new();
+
+/// Set whether tables show each prop on its own line, with nested
+/// values pretty-printed, rather than as one line of JSON.
+///
+/// Note - This can only be set once per process.
+pub fn set_expand_props(expand: bool) {
+    let _ = EXPAND_PROPS.set(expand);
+}
+
+/// Get a buffered handle to stdout.
+///
+/// Note - Stdout is line-buffered, so `println!` flushes after every
+/// line, which is slow for thousands of rows. Output written here is
+/// only flushed when the buffer fills (and once at the end, by the
+/// `write_*` functions).
+fn buffered_stdout() -> BufWriter<StdoutLock<'static>> {
+    BufWriter::with_capacity(STDOUT_BUFFER_SIZE, std::io::stdout().lock())
+}
+
+/// Whether an error came from writing to a closed pipe (e.g. when
+/// the output is piped into `head`).
+pub fn is_broken_pipe(err: &anyhow::Error) -> bool {
+    err.chain().any(|e| {
+        let kind = match e.downcast_ref::<std::io::Error>() {
+            Some(e) => Some(e.kind()),
+            None => e
+                .downcast_ref::<serde_json::Error>()
+                .and_then(|e| e.io_error_kind()),
+        };
+        kind == Some(std::io::ErrorKind::BrokenPipe)
+    })
+}
+
+/// Print a string to stdout as-is (e.g. a whole export), without
+/// panicking if stdout is closed.
+pub fn print_str(s: &str) -> Result<()> {
+    let mut stdout = buffered_stdout();
+    stdout.write_all(s.as_bytes())?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Print a line to stdout, without panicking if stdout is closed.
+pub fn print_line(s: &str) -> Result<()> {
+    let mut stdout = buffered_stdout();
+    writeln!(stdout, "{}", s)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Print a value to stdout as pretty-printed JSON, without panicking
+/// if stdout is closed.
+pub fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    let mut stdout = buffered_stdout();
+    serde_json::to_writer_pretty(&mut stdout, value)?;
+    writeln!(stdout)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Write items as NDJSON -- one compact JSON value per line.
+///
+/// Every line (including the last) ends with a newline and there
+/// are no blank lines, so an empty list writes nothing at all.
+pub fn write_ndjson<W: Write, T: Serialize>(w: &mut W, items: &[T]) -> Result<()> {
+    for item in items.iter() {
+        serde_json::to_writer(&mut *w, item)?;
+        w.write_all(b"\n")?;
+    }
+    w.flush()?;
+    Ok(())
+}
+
+/// Serialize a value as JSON -- on one line for `json-compact` (and
+/// NDJSON), otherwise pretty-printed.
+pub fn json_string<T: Serialize + ?Sized>(value: &T, format: &OutputFormat) -> Result<String> {
+    let s = match format {
+        OutputFormat::JsonCompact | OutputFormat::Ndjson => serde_json::to_string(value)?,
+        OutputFormat::Json | OutputFormat::Table => serde_json::to_string_pretty(value)?,
+    };
+    Ok(s)
+}
+
+/// Write bare IDs, one per line (e.g. to pipe into another command).
+pub fn write_ids<'a, W: Write>(w: &mut W, ids: impl IntoIterator<Item = &'a str>) -> Result<()> {
+    for id in ids {
+        writeln!(w, "{}", id)?;
+    }
+    w.flush()?;
+    Ok(())
+}
+
+/// Print bare IDs to stdout, one per line.
+pub fn print_ids<'a>(ids: impl IntoIterator<Item = &'a str>) -> Result<()> {
+    write_ids(&mut buffered_stdout(), ids)
+}
+
+/// Format a JSON value for a table cell.
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => "-".to_string(),
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+/// Format a field's value for a table cell.
+///
+/// Node labels are shown Cypher-style (e.g. `:Person:Employee`)
+/// rather than as a JSON array.
+fn field_cell(field: &str, value: &Value) -> String {
+    let labels = match (field, value) {
+        ("labels", Value::Array(items)) => items
+            .iter()
+            .map(|v| v.as_str().map(String::from))
+            .collect::<Option<Vec<String>>>(),
+        _ => None,
+    };
+    match labels {
+        Some(labels) if !labels.is_empty() => util::format_labels(&labels),
+        Some(_) => cell(&Value::Null),
+        None => cell(value),
+    }
+}
+
+/// Format props for a table cell, with each prop on its own line
+/// and nested values pretty-printed (indented) below their key.
+fn expanded_props_cell(value: &Value) -> String {
+    match value {
+        Value::Object(props) if !props.is_empty() => props
+            .iter()
+            .map(|(key, v)| match v {
+                Value::Object(_) | Value::Array(_) => format!(
+                    "{}: {}",
+                    key,
+                    serde_json::to_string_pretty(v).unwrap_or_else(|_| v.to_string())
+                ),
+                v => format!("{}: {}", key, cell(v)),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        v => cell(v),
+    }
+}
+
+/// The fields holding a node's or edge's timestamps.
+const TIMESTAMP_FIELDS: [&str; 2] = ["created_at", "updated_at"];
+
+/// Remove the `created_at`/`updated_at` timestamps from a node or
+/// edge, and from any nodes or edges nested in it (e.g. expanded
+/// edges, or the items in an export).
+///
+/// Props are left as-is, even if they have the same names.
+pub fn strip_timestamps(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for field in TIMESTAMP_FIELDS {
+                map.remove(field);
+            }
+            for (key, v) in map.iter_mut() {
+                if key != "props" {
+                    strip_timestamps(v);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(strip_timestamps),
+        _ => {}
+    }
+}
+
+/// Convert records to JSON values, without their timestamps
+/// (see `strip_timestamps`).
+pub fn without_timestamps<T: Serialize>(items: &[T]) -> Result<Vec<Value>> {
+    items
+        .iter()
+        .map(|item| {
+            let mut value = serde_json::to_value(item)?;
+            strip_timestamps(&mut value);
+            Ok(value)
+        })
+        .collect()
+}
+
+/// Describe a number of items, e.g. `1 node` or `12 nodes`.
+fn count_label(count: usize, noun: &str) -> String {
+    match count {
+        1 => format!("1 {}", noun),
+        n => format!("{} {}s", n, noun),
+    }
+}
+
+/// Print a list of records (e.g. nodes or edges), where `noun` is
+/// what a single record is called.
+///
+/// As JSON, this is a pretty-printed array (or, as compact JSON, an
+/// array on one line). As NDJSON, it's one compact object per line.
+/// As a table, each record is a row and each top-level field is a
+/// column. The table is followed by a count of the rows (on stderr,
+/// so it doesn't end up in piped output).
+///
+/// With `stats`, JSON output is wrapped in an object -- `{"items":
+/// [...], "stats": {"count": ...}}` -- so it stays a single document.
+/// NDJSON output is followed by a `{"_stats": {"count": ...}}` line.
+///
+/// If the list was cut off by a limit, `total` is the number of
+/// records that matched (as `--count` reports it). It's added to the
+/// stats, and the table's count reads e.g. `100 of 2000 nodes`.
+pub fn print_list<T: Serialize>(
+    items: &[T],
+    noun: &str,
+    format: &OutputFormat,
+    stats: bool,
+    total: Option<usize>,
+) -> Result<()> {
+    write_list(&mut buffered_stdout(), items, format, stats, total)?;
+    if let OutputFormat::Table = format {
+        eprintln!("{}", list_footer(items.len(), total, noun));
+    }
+    Ok(())
+}
+
+/// Describe a listed set of records -- e.g. `12 nodes`, or `100 of
+/// 2000 nodes` if more matched than were listed.
+fn list_footer(count: usize, total: Option<usize>, noun: &str) -> String {
+    match total.filter(|t| *t > count) {
+        Some(total) => format!("{} of {}", count, count_label(total, noun)),
+        None => count_label(count, noun),
+    }
+}
+
+/// Write a list of records (see `print_list`), flushing once at the end.
+pub fn write_list<W: Write, T: Serialize>(
+    w: &mut W,
+    items: &[T],
+    format: &OutputFormat,
+    stats: bool,
+    total: Option<usize>,
+) -> Result<()> {
+    let stats = stats.then(|| match total.filter(|t| *t > items.len()) {
+        Some(total) => json!({ "count": items.len(), "total": total }),
+        None => json!({ "count": items.len() }),
+    });
+    match format {
+        OutputFormat::Json | OutputFormat::JsonCompact => match stats {
+            Some(stats) => {
+                // Note - A struct (rather than `json!`) keeps the
+                // items' field order...
+                #[derive(Serialize)]
+                struct WithStats<'a, T> {
+                    items: &'a [T],
+                    stats: Value,
+                }
+                let out = WithStats { items, stats };
+                writeln!(w, "{}", json_string(&out, format)?)?;
+            }
+            None => writeln!(w, "{}", json_string(items, format)?)?,
+        },
+        OutputFormat::Ndjson => {
+            write_ndjson(w, items)?;
+            if let Some(stats) = stats {
+                write_ndjson(w, &[json!({ "_stats": stats })])?;
+            }
+        }
+        OutputFormat::Table => {
+            let expand_props = EXPAND_PROPS.get().copied().unwrap_or_default();
+            for line in table_lines(items, expand_props)? {
+                writeln!(w, "{}", line)?;
+            }
+        }
+    }
+    w.flush()?;
+    Ok(())
+}
+
+/// Format records as the lines of a table, with a row per record and
+/// a column per top-level field.
+///
+/// Cells can span several lines (e.g. expanded props), in which case
+/// the row is as tall as its tallest cell.
+///
+/// With no records, there are no lines (not even a header), since
+/// the columns aren't known.
+fn table_lines<T: Serialize>(items: &[T], expand_props: bool) -> Result<Vec<String>> {
+    if items.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Get the rows and the columns (in the order they're first seen)...
+    let rows: Vec<Map<String, Value>> = items
+        .iter()
+        .map(|item| match serde_json::to_value(item) {
+            Ok(Value::Object(row)) => Ok(row),
+            Ok(v) => Ok([("value".to_string(), v)].into_iter().collect()),
+            Err(e) => Err(e),
+        })
+        .collect::<Result<_, _>>()?;
+    let mut columns: Vec<&String> = Vec::new();
+    for key in rows.iter().flat_map(|r| r.keys()) {
+        if !columns.contains(&key) {
+            columns.push(key);
+        }
+    }
+
+    // Format the cells and get each column's width...
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|r| {
+            columns
+                .iter()
+                .map(|c| {
+                    let value = r.get(*c).unwrap_or(&Value::Null);
+                    if expand_props && c.as_str() == "props" {
+                        expanded_props_cell(value)
+                    } else {
+                        field_cell(c, value)
+                    }
+                })
+                .collect()
+        })
+        .collect();
+    let widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            cells
+                .iter()
+                .flat_map(|r| r[i].lines().map(str::len))
+                .chain([c.len()])
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+
+    // Format the header and the rows, a line at a time...
+    let header: Vec<String> = columns.iter().map(|c| c.to_uppercase()).collect();
+    let mut lines = Vec::new();
+    for row in [header].iter().chain(cells.iter()) {
+        let row_lines: Vec<Vec<&str>> = row.iter().map(|v| v.lines().collect()).collect();
+        let height = row_lines
+            .iter()
+            .map(Vec::len)
+            .max()
+            .unwrap_or_default()
+            .max(1);
+        for i in 0..height {
+            let line: Vec<String> = row_lines
+                .iter()
+                .zip(widths.iter())
+                .map(|(v, w)| format!("{:<w$}", v.get(i).copied().unwrap_or_default(), w = w))
+                .collect();
+            lines.push(line.join("  ").trim_end().to_string());
+        }
+    }
+    Ok(lines)
+}
+
+/// Print a list of `(key, count)` pairs.
+///
+/// As JSON, this is a single object mapping keys to counts. As NDJSON,
+/// it's one `{"<key_name>": ..., "count": ...}` object per line. As a
+/// table, it's two columns.
+pub fn print_counts(counts: &[(String, i64)], key_name: &str, format: &OutputFormat) -> Result<()> {
+    let mut w = buffered_stdout();
+    match format {
+        OutputFormat::Json | OutputFormat::JsonCompact => {
+            let data: Map<String, Value> = counts
+                .iter()
+                .map(|(key, count)| (key.clone(), json!(count)))
+                .collect();
+            writeln!(w, "{}", json_string(&data, format)?)?;
+        }
+        OutputFormat::Ndjson => {
+            let rows: Vec<Value> = counts
+                .iter()
+                .map(|(key, count)| json!({ key_name: key, "count": count }))
+                .collect();
+            write_ndjson(&mut w, &rows)?;
+        }
+        OutputFormat::Table => {
+            let width = counts
+                .iter()
+                .map(|(key, _)| key.len())
+                .chain([key_name.len()])
+                .max()
+                .unwrap_or_default();
+            writeln!(
+                w,
+                "{:<width$}  COUNT",
+                key_name.to_uppercase(),
+                width = width
+            )?;
+            for (key, count) in counts.iter() {
+                writeln!(w, "{:<width$}  {}", key, count, width = width)?;
+            }
+        }
+    }
+    w.flush()?;
+    Ok(())
+}
+
+/// Print a single record (a JSON object).
+///
+/// As JSON, it's pretty-printed. As compact JSON or NDJSON, it's
+/// printed on one line.
+/// As a table, each field is a `KEY  VALUE` row.
+pub fn print_record(record: &Map<String, Value>, format: &OutputFormat) -> Result<()> {
+    let mut w = buffered_stdout();
+    match format {
+        OutputFormat::Json | OutputFormat::JsonCompact | OutputFormat::Ndjson => {
+            writeln!(w, "{}", json_string(record, format)?)?
+        }
+        OutputFormat::Table => {
+            let width = record.keys().map(|k| k.len()).max().unwrap_or_default();
+            for (key, value) in record.iter() {
+                writeln!(
+                    w,
+                    "{:<width$}  {}",
+                    key,
+                    field_cell(key, value),
+                    width = width
+                )?;
+            }
+        }
+    }
+    w.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_string() {
+        let items = vec![json!({"id": "a", "labels": ["x"]}), json!({"id": "b"})];
+
+        // Pretty JSON spans several lines, indented...
+        let out = json_string(&items, &OutputFormat::Json).unwrap();
+        assert!(out.starts_with("[\n  {\n    \"id\": \"a\","));
+        assert_eq!(serde_json::from_str::<Value>(&out).unwrap(), json!(items));
+
+        // ...while compact JSON (like NDJSON) is a single line...
+        for format in [OutputFormat::JsonCompact, OutputFormat::Ndjson] {
+            let out = json_string(&items, &format).unwrap();
+            assert_eq!(out, r#"[{"id":"a","labels":["x"]},{"id":"b"}]"#);
+        }
+    }
+
+    #[test]
+    fn test_write_ndjson() {
+        let items = vec![
+            json!({"id": "a", "props": {"name": "Alice\nSmith"}}),
+            json!({"id": "b", "labels": ["x", "y"]}),
+        ];
+        let mut buf = Vec::new();
+        write_ndjson(&mut buf, &items).unwrap();
+        let out = String::from_utf8(buf).unwrap();
+
+        // One compact object per line, with a trailing newline...
+        assert!(out.ends_with('\n'));
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), items.len());
+        for (line, item) in lines.iter().zip(items.iter()) {
+            assert!(!line.is_empty());
+            assert_eq!(&serde_json::from_str::<Value>(line).unwrap(), item);
+        }
+
+        // Nothing at all for an empty list...
+        let mut buf = Vec::new();
+        write_ndjson::<_, Value>(&mut buf, &[]).unwrap();
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_write_list_buffered() {
+        let items: Vec<Value> = (0..2000)
+            .map(|i| json!({"id": format!("n-{}", i), "props": {"i": i}}))
+            .collect();
+        for format in [
+            OutputFormat::Json,
+            OutputFormat::JsonCompact,
+            OutputFormat::Ndjson,
+            OutputFormat::Table,
+        ] {
+            // Write it unbuffered...
+            let mut direct = Vec::new();
+            write_list(&mut direct, &items, &format, true, None).unwrap();
+
+            // ...and through a (small) buffer, which flushes many times...
+            let mut buffered = BufWriter::with_capacity(64, Vec::new());
+            write_list(&mut buffered, &items, &format, true, None).unwrap();
+            let buffered = buffered.into_inner().unwrap();
+
+            assert!(!direct.is_empty());
+            assert_eq!(buffered, direct, "{:?}", format);
+        }
+
+        // With stats, JSON output is still a single document...
+        for format in [OutputFormat::Json, OutputFormat::JsonCompact] {
+            let mut out = Vec::new();
+            write_list(&mut out, &items, &format, true, None).unwrap();
+            let out: Value = serde_json::from_slice(&out).unwrap();
+            assert_eq!(out["items"].as_array().unwrap().len(), 2000);
+            assert_eq!(out["stats"], json!({"count": 2000}));
+        }
+
+        // A cut-off list's stats include the total...
+        let mut out = Vec::new();
+        write_list(&mut out, &items, &OutputFormat::Json, true, Some(5000)).unwrap();
+        let out: Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(out["stats"], json!({"count": 2000, "total": 5000}));
+    }
+
+    #[test]
+    fn test_is_broken_pipe() {
+        let pipe = || std::io::Error::from(std::io::ErrorKind::BrokenPipe);
+        assert!(is_broken_pipe(&anyhow::Error::from(pipe())));
+        assert!(is_broken_pipe(
+            &anyhow::Error::from(pipe()).context("Failed to write")
+        ));
+
+        // Including from serializing straight into the pipe...
+        let err = serde_json::Error::io(pipe());
+        assert!(is_broken_pipe(&anyhow::Error::from(err)));
+
+        // Other errors aren't...
+        let err = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        assert!(!is_broken_pipe(&anyhow::Error::from(err)));
+        assert!(!is_broken_pipe(&anyhow::anyhow!("Broken pipe")));
+    }
+
+    #[test]
+    fn test_write_list_closed_pipe() {
+        // A reader that stops after the first few lines (like `head`)...
+        let (mut reader, writer) = std::io::pipe().unwrap();
+        let head = std::thread::spawn(move || {
+            let mut buf = [0; 1024];
+            std::io::Read::read(&mut reader, &mut buf).unwrap()
+        });
+
+        // Writing lots of output to it fails with a broken pipe
+        // (rather than panicking)...
+        let items: Vec<Value> = (0..100_000).map(|i| json!({"id": i})).collect();
+        let mut w = BufWriter::new(writer);
+        let err = write_list(&mut w, &items, &OutputFormat::Ndjson, false, None).unwrap_err();
+        assert!(is_broken_pipe(&err), "{:?}", err);
+        assert!(head.join().unwrap() > 0);
+    }
+
+    #[test]
+    fn test_write_ids() {
+        let mut buf = Vec::new();
+        write_ids(&mut buf, ["n-1", "n-2"]).unwrap();
+        let out = String::from_utf8(buf).unwrap();
+        assert_eq!(out, "n-1\nn-2\n");
+        assert!(!out.contains(['"', '{', '[']));
+    }
+
+    #[test]
+    fn test_strip_timestamps() {
+        let node = json!({
+            "id": "n-1",
+            "props": {"created_at": "yesterday"},
+            "edges_out": [{"id": "e-1", "created_at": "2024-01-01T00:00:00Z"}],
+            "created_at": "2024-01-01T00:00:00Z",
+            "updated_at": "2024-01-01T00:00:00Z",
+        });
+
+        // Without the flag, the timestamps are all there...
+        let items = [node.clone()];
+        let out = serde_json::to_value(&items).unwrap();
+        assert!(out[0].get("created_at").is_some() && out[0].get("updated_at").is_some());
+        assert!(out[0]["edges_out"][0].get("created_at").is_some());
+
+        // With it, they're gone (but props are kept as-is)...
+        let out = without_timestamps(&items).unwrap();
+        assert!(out[0].get("created_at").is_none() && out[0].get("updated_at").is_none());
+        assert!(out[0]["edges_out"][0].get("created_at").is_none());
+        assert_eq!(out[0]["edges_out"][0]["id"], json!("e-1"));
+        assert_eq!(out[0]["props"]["created_at"], json!("yesterday"));
+    }
+
+    #[test]
+    fn test_field_cell() {
+        let labels = json!(["Person", "Employee"]);
+        assert_eq!(field_cell("labels", &labels), ":Person:Employee");
+        assert_eq!(field_cell("labels", &json!([])), "-");
+        assert_eq!(field_cell("other", &labels), r#"["Person","Employee"]"#);
+    }
+
+    #[test]
+    fn test_table_lines_expand_props() {
+        let nodes = vec![json!({
+            "id": "n-1",
+            "props": {"address": {"city": "Paris", "zip": "75001"}, "name": "Alice"},
+        })];
+
+        // Collapsed, the props are one line of JSON...
+        let lines = table_lines(&nodes, false).unwrap();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].contains(r#"{"address":{"city":"Paris","zip":"75001"},"name":"Alice"}"#));
+
+        // Nothing at all for an empty list...
+        assert!(table_lines::<Value>(&[], true).unwrap().is_empty());
+
+        // Expanded, each prop gets a line, with nested values indented...
+        let lines = table_lines(&nodes, true).unwrap();
+        assert_eq!(
+            lines,
+            vec![
+                "ID   PROPS",
+                "n-1  address: {",
+                r#"       "city": "Paris","#,
+                r#"       "zip": "75001""#,
+                "     }",
+                "     name: Alice",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_count_label() {
+        assert_eq!(count_label(0, "node"), "0 nodes");
+        assert_eq!(count_label(1, "node"), "1 node");
+        assert_eq!(count_label(12, "edge"), "12 edges");
+    }
+
+    #[test]
+    fn test_list_footer() {
+        assert_eq!(list_footer(12, None, "node"), "12 nodes");
+        assert_eq!(list_footer(12, Some(12), "node"), "12 nodes");
+
+        // A cut-off list shows the total it was cut from...
+        assert_eq!(list_footer(100, Some(2000), "edge"), "100 of 2000 edges");
+    }
+}
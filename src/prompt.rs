@@ -1,9 +1,32 @@
-use crate::conf::DBType;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use dialoguer::{Confirm, Input, Password, Select};
+use graphctl::conf::DBType;
+use std::io::IsTerminal;
+use std::path::Path;
+
+/// Make sure it's possible to prompt the user, otherwise return an
+/// error naming the flag that can be used instead.
+///
+/// Without this check, `dialoguer` will wait forever for input when
+/// stdin isn't a terminal (e.g. in CI or when input is piped in).
+fn require_tty(flag: &str) -> Result<()> {
+    if !std::io::stdin().is_terminal() {
+        return Err(anyhow!(
+            "Interactive prompt required but no TTY available; pass {} instead",
+            flag,
+        ));
+    }
+    Ok(())
+}
 
 pub fn prompt_for_db_type() -> Result<DBType> {
-    let choices = &["Local", "Remote with Replica", "Remote Only"];
+    require_tty("--db-type")?;
+    let choices = &[
+        "Local",
+        "Remote with Replica",
+        "Remote Only",
+        "In-Memory (not persisted)",
+    ];
     let selection = Select::new()
         .with_prompt("Select the database type")
         .items(&choices[..])
@@ -13,11 +36,13 @@ pub fn prompt_for_db_type() -> Result<DBType> {
         0 => Ok(DBType::Local),
         1 => Ok(DBType::RemoteWithReplica),
         2 => Ok(DBType::RemoteOnly),
+        3 => Ok(DBType::Memory),
         _ => unreachable!(),
     }
 }
 
 pub fn prompt_for_remote_db_url() -> Result<String> {
+    require_tty("--remote-db-url")?;
     let path = Input::new()
         .with_prompt("Enter the URL of the remote DB")
         .interact()?;
@@ -25,6 +50,7 @@ pub fn prompt_for_remote_db_url() -> Result<String> {
 }
 
 pub fn prompt_for_remote_db_auth_token() -> Result<String> {
+    require_tty("--remote-db-token")?;
     let password = Password::new()
         .with_prompt("Enter the DB auth token")
         .interact()?;
@@ -32,6 +58,7 @@ pub fn prompt_for_remote_db_auth_token() -> Result<String> {
 }
 
 pub fn prompt_for_encrypt_local() -> Result<bool> {
+    require_tty("--encrypt or --no-encrypt")?;
     let encrypt = Confirm::new()
         .with_prompt("Encrypt the local DB?")
         .interact()?;
@@ -39,8 +66,49 @@ pub fn prompt_for_encrypt_local() -> Result<bool> {
 }
 
 pub fn prompt_for_encrypt_replica() -> Result<bool> {
+    require_tty("--encrypt or --no-encrypt")?;
     let encrypt = Confirm::new()
         .with_prompt("Encrypt the replica?")
         .interact()?;
     Ok(encrypt)
 }
+
+pub fn prompt_for_confirm_migrate(
+    nodes: usize,
+    edges: usize,
+    from: &str,
+    to: &str,
+) -> Result<bool> {
+    require_tty("--yes")?;
+    let confirm = Confirm::new()
+        .with_prompt(format!(
+            "Copy {} nodes and {} edges from the {} database to the {} database?",
+            nodes, edges, from, to
+        ))
+        .default(false)
+        .interact()?;
+    Ok(confirm)
+}
+
+pub fn prompt_for_confirm_move_data(count: usize, from: &Path, to: &Path) -> Result<bool> {
+    require_tty("--yes")?;
+    let confirm = Confirm::new()
+        .with_prompt(format!(
+            "Move {} database file(s) from {} to {}?",
+            count,
+            from.display(),
+            to.display()
+        ))
+        .default(false)
+        .interact()?;
+    Ok(confirm)
+}
+
+pub fn prompt_for_confirm_delete(count: usize, kind: &str) -> Result<bool> {
+    require_tty("--yes")?;
+    let confirm = Confirm::new()
+        .with_prompt(format!("Delete {} matching {}?", count, kind))
+        .default(false)
+        .interact()?;
+    Ok(confirm)
+}
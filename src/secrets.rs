@@ -1,7 +1,9 @@
-///! Provides access to secrets stored in the system keyring.
-use anyhow::{anyhow, Result};
+//! Provides access to secrets stored in the system keyring.
+use crate::error;
+use anyhow::{anyhow, Context, Result};
 use keyring::Entry;
 use ring::rand::{SecureRandom, SystemRandom};
+use std::path::Path;
 
 const SERVICE_NAME: &str = "graphctl";
 
@@ -9,6 +11,23 @@ const REMOTE_DB_AUTH_TOKEN_KEY: &str = "db_auth_token";
 
 const LOCAL_DB_ENCRYPTION_KEY: &str = "db_encryption_key";
 
+/// The keyring backend secrets are stored with, on this platform.
+pub const KEYRING_BACKEND: &str = if cfg!(target_os = "macos") {
+    "macos-keychain"
+} else if cfg!(target_os = "ios") {
+    "ios-keychain"
+} else if cfg!(target_os = "windows") {
+    "windows-credential-manager"
+} else if cfg!(any(target_os = "linux", target_os = "freebsd")) {
+    "secret-service"
+} else {
+    "mock"
+};
+
+/// The env var the local database encryption key can be read from
+/// (e.g. in Docker or CI, where there's no keyring).
+pub const ENCRYPTION_KEY_ENV_VAR: &str = "GRAPHCTL_ENCRYPTION_KEY";
+
 fn get_secret(key: &str) -> Result<String> {
     let entry = Entry::new(SERVICE_NAME, key)?;
     let secret = entry.get_password()?;
@@ -21,6 +40,17 @@ fn set_secret(key: &str, val: &str) -> Result<()> {
     Ok(())
 }
 
+/// Check that the keyring can be reached (without reading any secrets).
+///
+/// A missing entry is fine -- it means the keyring answered.
+pub fn check_keyring() -> Result<()> {
+    let entry = Entry::new(SERVICE_NAME, "keyring_check")?;
+    match entry.get_password() {
+        Ok(_) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(err) => Err(err.into()),
+    }
+}
+
 /// Returns the remote database authentication token.
 pub fn get_remote_db_auth_token() -> Result<String> {
     get_secret(REMOTE_DB_AUTH_TOKEN_KEY)
@@ -31,6 +61,70 @@ pub fn get_local_db_encryption_key() -> Result<String> {
     get_secret(LOCAL_DB_ENCRYPTION_KEY)
 }
 
+/// Returns the local database encryption key, from (in order) the
+/// key file (if given), the `GRAPHCTL_ENCRYPTION_KEY` env var, or
+/// the keyring.
+///
+/// The key is checked to be 64 hex characters.
+pub fn resolve_local_db_encryption_key(key_file: Option<&Path>) -> Result<String> {
+    resolve_encryption_key(key_file, env_encryption_key(), get_local_db_encryption_key)
+}
+
+/// Get the encryption key from the env var, if it's set.
+fn env_encryption_key() -> Option<String> {
+    std::env::var(ENCRYPTION_KEY_ENV_VAR)
+        .ok()
+        .filter(|k| !k.trim().is_empty())
+}
+
+/// Pick the encryption key from its sources, in order of precedence.
+fn resolve_encryption_key(
+    key_file: Option<&Path>,
+    env_key: Option<String>,
+    keyring_key: impl FnOnce() -> Result<String>,
+) -> Result<String> {
+    let key = match (key_file, env_key) {
+        (Some(path), _) => std::fs::read_to_string(path)
+            .context(format!("Failed to read encryption key file {:?}", path))
+            .map_err(|e| error::config(format!("{:#}", e)))?,
+        (None, Some(key)) => key,
+        (None, None) => keyring_key()?,
+    };
+    let key = key.trim().to_string();
+    validate_encryption_key(&key)?;
+    Ok(key)
+}
+
+/// Check that an encryption key is 64 hex characters (as generated
+/// by `generate_random_hex_string`).
+pub fn validate_encryption_key(key: &str) -> Result<()> {
+    if key.len() != 64 || !key.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(error::config(
+            "Invalid encryption key. It should be 64 hex characters.",
+        ));
+    }
+    Ok(())
+}
+
+/// Returns whether a local database encryption key is available from
+/// any source (the key file, the env var, or the keyring), without
+/// reading it out.
+pub fn has_local_db_encryption_key_source(key_file: Option<&Path>) -> bool {
+    key_file.is_some() || env_encryption_key().is_some() || has_local_db_encryption_key()
+}
+
+/// Returns whether the remote database authentication token is
+/// set, without reading it out.
+pub fn has_remote_db_auth_token() -> bool {
+    get_remote_db_auth_token().is_ok()
+}
+
+/// Returns whether the local database encryption key is set,
+/// without reading it out.
+pub fn has_local_db_encryption_key() -> bool {
+    get_local_db_encryption_key().is_ok()
+}
+
 /// Sets the remote database authentication token.
 pub fn set_remote_db_auth_token(token: &str) -> Result<()> {
     set_secret(REMOTE_DB_AUTH_TOKEN_KEY, token)
@@ -48,3 +142,49 @@ pub fn generate_random_hex_string() -> Result<String> {
         .map_err(|err| anyhow!("Failed to generate random bytes: {}", err))?;
     Ok(hex::encode(buf))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY_A: &str = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+    const KEY_B: &str = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+    const KEY_C: &str = "0123456789abcdef0123456789ABCDEF0123456789abcdef0123456789abcdef";
+
+    #[test]
+    fn test_resolve_encryption_key() {
+        let keyring = || Ok(KEY_C.to_string());
+        let dir = tempfile::tempdir().unwrap();
+        let key_file = dir.path().join("key");
+        std::fs::write(&key_file, format!("{}\n", KEY_A)).unwrap();
+
+        // The key file wins...
+        let key = resolve_encryption_key(Some(&key_file), Some(KEY_B.to_string()), keyring);
+        assert_eq!(key.unwrap(), KEY_A);
+
+        // ...then the env var...
+        let key = resolve_encryption_key(None, Some(KEY_B.to_string()), keyring);
+        assert_eq!(key.unwrap(), KEY_B);
+
+        // ...then the keyring...
+        let key = resolve_encryption_key(None, None, keyring);
+        assert_eq!(key.unwrap(), KEY_C);
+
+        // A missing key file is an error (not a fallback)...
+        let missing = dir.path().join("missing");
+        assert!(resolve_encryption_key(Some(&missing), None, keyring).is_err());
+    }
+
+    #[test]
+    fn test_validate_encryption_key() {
+        assert!(validate_encryption_key(KEY_C).is_ok());
+        for key in ["", "abc", &KEY_A[1..], &format!("{}g", &KEY_A[1..])] {
+            let err = validate_encryption_key(key).unwrap_err();
+            assert_eq!(error::kind(&err), Some(error::ErrorKind::Config));
+        }
+        let err =
+            resolve_encryption_key(None, Some("not-hex".to_string()), || Ok(KEY_A.to_string()))
+                .unwrap_err();
+        assert!(err.to_string().contains("64 hex characters"));
+    }
+}
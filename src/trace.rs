@@ -0,0 +1,95 @@
+//! Lightweight timing of the steps of a command (for `get --trace`).
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+/// Records how long each step of a command takes.
+///
+/// When it's disabled, steps are run without being timed.
+#[derive(Debug)]
+pub struct Trace {
+    enabled: bool,
+    start: Instant,
+    steps: Vec<(String, Duration)>,
+}
+
+impl Trace {
+    pub fn new(enabled: bool) -> Self {
+        Trace {
+            enabled,
+            start: Instant::now(),
+            steps: Vec::new(),
+        }
+    }
+
+    /// Run a step, recording how long it took.
+    pub async fn step<T>(&mut self, name: impl Into<String>, fut: impl Future<Output = T>) -> T {
+        if !self.enabled {
+            return fut.await;
+        }
+        let start = Instant::now();
+        let res = fut.await;
+        self.steps.push((name.into(), start.elapsed()));
+        res
+    }
+
+    /// Format the timing breakdown -- one line per step, followed
+    /// by the total (which includes anything between the steps).
+    pub fn report(&self) -> String {
+        let total = self.start.elapsed();
+        let width = self
+            .steps
+            .iter()
+            .map(|(name, _)| name.len())
+            .max()
+            .unwrap_or(0)
+            .max("total".len());
+        let mut lines = vec!["Trace:".to_string()];
+        for (name, elapsed) in self.steps.iter() {
+            lines.push(format_line(name, *elapsed, width));
+        }
+        lines.push(format_line("total", total, width));
+        lines.join("\n")
+    }
+
+    /// Print the timing breakdown to stderr (if enabled).
+    pub fn print(&self) {
+        if self.enabled {
+            eprintln!("{}", self.report());
+        }
+    }
+}
+
+fn format_line(name: &str, elapsed: Duration, width: usize) -> String {
+    format!(
+        "  {:<width$}  {:>10.3} ms",
+        name,
+        elapsed.as_secs_f64() * 1000.0,
+        width = width,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_trace() {
+        // Steps are recorded in order, with a total...
+        let mut trace = Trace::new(true);
+        let n = trace.step("node", async { 1 }).await;
+        let m = trace.step("edges out", async { 2 }).await;
+        assert_eq!(n + m, 3);
+        let report = trace.report();
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].trim_start().starts_with("node "));
+        assert!(lines[2].trim_start().starts_with("edges out "));
+        assert!(lines[3].trim_start().starts_with("total "));
+        assert!(lines.iter().skip(1).all(|l| l.ends_with(" ms")));
+
+        // Disabled, nothing is recorded...
+        let mut trace = Trace::new(false);
+        trace.step("node", async {}).await;
+        assert!(trace.steps.is_empty());
+    }
+}
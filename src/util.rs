@@ -1,17 +1,881 @@
-use uuid::Uuid;
+use crate::conf::DisplayTimezone;
+use crate::error;
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use clap::ValueEnum;
+use jsonpath_rust::JsonPath;
+use regex::Regex;
+use serde::Serializer;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use uuid::{Builder, Uuid};
 
+/// The timezone timestamps are displayed in (see `set_display_timezone`).
+static DISPLAY_TIMEZONE: OnceLock<DisplayTimezone> = OnceLock::new();
+
+/// The generator used by `new_id`, if seeded (see `set_id_seed`).
+static ID_GENERATOR: OnceLock<IdGenerator> = OnceLock::new();
+
+/// Generates IDs -- a prefix followed by a UUIDv4.
+///
+/// By default the UUIDs are random, but a seeded generator produces
+/// the same sequence of IDs every time (e.g. for reproducible tests).
+#[derive(Debug, Default)]
+pub struct IdGenerator {
+    /// The seeded RNG's state. If `None`, IDs are random.
+    state: Option<Mutex<u64>>,
+}
+
+impl IdGenerator {
+    /// Create a generator of random IDs.
+    pub fn random() -> Self {
+        IdGenerator { state: None }
+    }
+
+    /// Create a generator of deterministic IDs, from a seed.
+    pub fn seeded(seed: u64) -> Self {
+        IdGenerator {
+            state: Some(Mutex::new(seed)),
+        }
+    }
+
+    /// Generate the next ID, with the given prefix.
+    pub fn new_id(&self, prefix: &str) -> String {
+        let uuid = match &self.state {
+            Some(state) => {
+                let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+                let mut bytes = [0u8; 16];
+                bytes[..8].copy_from_slice(&splitmix64(&mut state).to_le_bytes());
+                bytes[8..].copy_from_slice(&splitmix64(&mut state).to_le_bytes());
+                Builder::from_random_bytes(bytes).into_uuid()
+            }
+            None => Uuid::new_v4(),
+        };
+        format!("{}-{}", prefix, uuid)
+    }
+}
+
+/// Get the next value from a SplitMix64 RNG.
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Make `new_id` generate deterministic IDs from a seed.
+///
+/// This can only be set once. IDs are random if never set.
+pub fn set_id_seed(seed: u64) {
+    let _ = ID_GENERATOR.set(IdGenerator::seeded(seed));
+}
+
+/// Generate a new ID with the given prefix (e.g. `n-<uuid>`).
 pub fn new_id(prefix: &str) -> String {
-    format!("{}-{}", prefix, Uuid::new_v4())
+    match ID_GENERATOR.get() {
+        Some(ids) => ids.new_id(prefix),
+        None => format!("{}-{}", prefix, Uuid::new_v4()),
+    }
+}
+
+/// Set the timezone used when serializing timestamps for display.
+///
+/// This can only be set once. Defaults to UTC if never set.
+pub fn set_display_timezone(tz: DisplayTimezone) {
+    let _ = DISPLAY_TIMEZONE.set(tz);
+}
+
+/// Format a timestamp in the display timezone.
+pub fn format_timestamp(ts: &DateTime<Utc>) -> String {
+    match DISPLAY_TIMEZONE.get().copied().unwrap_or_default() {
+        DisplayTimezone::Utc => ts.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+        DisplayTimezone::Local => ts
+            .with_timezone(&Local)
+            .to_rfc3339_opts(SecondsFormat::AutoSi, true),
+    }
+}
+
+/// Serialize a timestamp in the display timezone (for `serialize_with`).
+pub fn serialize_timestamp<S: Serializer>(ts: &DateTime<Utc>, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&format_timestamp(ts))
+}
+
+/// Serialize an optional timestamp in the display timezone (for `serialize_with`).
+pub fn serialize_opt_timestamp<S: Serializer>(
+    ts: &Option<DateTime<Utc>>,
+    s: S,
+) -> Result<S::Ok, S::Error> {
+    match ts {
+        Some(ts) => serialize_timestamp(ts, s),
+        None => s.serialize_none(),
+    }
+}
+
+/// Format node labels the way Cypher does, e.g. `:Person:Employee`
+/// (for human-readable output).
+pub fn format_labels(labels: &[String]) -> String {
+    labels.iter().map(|l| format!(":{}", l)).collect()
+}
+
+/// How a prop filter's value should be typed before it's compared
+/// with the stored (JSON-encoded) value.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PropType {
+    /// Parse the value as JSON if possible, otherwise use it as a string.
+    #[default]
+    Auto,
+
+    /// Always use the value as a string (e.g. so `zip=02139` or
+    /// `code=123` match string props).
+    String,
+
+    /// The value must be a number.
+    Number,
+
+    /// The value must be `true` or `false`.
+    Bool,
+}
+
+/// Parse a raw prop value (from a `key=value` argument) as the given type.
+pub fn parse_prop_value(raw: &str, prop_type: PropType) -> Result<Value> {
+    match prop_type {
+        PropType::Auto => Ok(serde_json::from_str(raw).unwrap_or(Value::String(raw.to_string()))),
+        PropType::String => Ok(Value::String(raw.to_string())),
+        PropType::Number => match serde_json::from_str(raw.trim()) {
+            Ok(Value::Number(n)) => Ok(Value::Number(n)),
+            _ => Err(error::validation(format!(
+                "Value '{}' isn't a number.",
+                raw
+            ))),
+        },
+        PropType::Bool => match raw.trim() {
+            "true" => Ok(Value::Bool(true)),
+            "false" => Ok(Value::Bool(false)),
+            _ => Err(error::validation(format!(
+                "Value '{}' isn't a bool (`true` or `false`).",
+                raw
+            ))),
+        },
+    }
+}
+
+/// Options for parsing `key=value` prop arguments.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ParsePropsOptions {
+    /// Allow a key to be given more than once (the last value wins).
+    /// Otherwise, it's an error, since it's usually a mistake.
+    pub allow_duplicates: bool,
+
+    /// Trim the surrounding whitespace from plain (non-JSON) string
+    /// values, e.g. `name= Alice ` becomes `"Alice"`. JSON values
+    /// (including quoted strings, like `name=" Alice "`) are kept as-is.
+    pub trim_values: bool,
+}
+
+/// Parse a list of `key=value` arguments into a map of props.
+///
+/// Values are parsed as JSON if possible, otherwise they're
+/// treated as strings. Keys are always trimmed.
+pub fn parse_props(args: &[String], opts: &ParsePropsOptions) -> Result<HashMap<String, Value>> {
+    parse_typed_props(args, PropType::Auto, opts)
+}
+
+/// Parse a list of `key=value` arguments into a map of props, with
+/// the values parsed as `prop_type`.
+fn parse_typed_props(
+    args: &[String],
+    prop_type: PropType,
+    opts: &ParsePropsOptions,
+) -> Result<HashMap<String, Value>> {
+    let mut props = HashMap::new();
+    for p in args.iter() {
+        // Split the key-value pair on on the equals sign...
+        let mut parts = p.splitn(2, '=');
+
+        // Get the key and strip it...
+        let key = parts
+            .next()
+            .ok_or(error::validation("Failed to parse key-value pair."))
+            .context(format!("argument={}", p))?
+            .trim()
+            .to_string();
+
+        // Make sure the key is not empty...
+        if key.is_empty() {
+            return Err(error::validation("Empty key in key-value pair."));
+        }
+
+        // Get the value...
+        let value = parts
+            .next()
+            .ok_or(error::validation("Failed to parse key-value pair."))
+            .context(format!("argument={}", p))?;
+
+        // Parse it as the right type...
+        let raw = value;
+        let value = parse_prop_value(raw, prop_type).context(format!("argument={}", p))?;
+
+        // Trim plain string values?
+        let is_json = prop_type != PropType::String && serde_json::from_str::<Value>(raw).is_ok();
+        let value = match value {
+            Value::String(s) if opts.trim_values && !is_json => Value::String(s.trim().to_string()),
+            v => v,
+        };
+
+        // Add it to the props map (checking for duplicates)...
+        if props.insert(key.clone(), value).is_some() && !opts.allow_duplicates {
+            return Err(error::validation(format!(
+                "Duplicate property key '{}'. Pass `--allow-duplicate-props` to keep the last value.",
+                key
+            )));
+        }
+    }
+    Ok(props)
+}
+
+/// Prop filters, parsed from `key=value` and `key=?value` arguments.
+#[derive(Debug, Default, PartialEq)]
+pub struct PropFilters {
+    /// Props that must equal these values (from `key=value`).
+    pub equals: HashMap<String, Value>,
+
+    /// Array props that must contain these values (from `key=?value`).
+    pub contains: Vec<(String, Value)>,
+}
+
+/// Parse a list of prop filter arguments.
+///
+/// `key=value` matches props equal to the value, while `key=?value`
+/// matches props that are JSON arrays containing the value.
+///
+/// Values are typed as `prop_type`, then compared with the stored
+/// values as JSON -- so `age=30` matches the number `30` and
+/// `name=Bob` matches the string `"Bob"`.
+pub fn parse_prop_filters(args: &[String], prop_type: PropType) -> Result<PropFilters> {
+    // Note - Filter values are matched as given...
+    let opts = ParsePropsOptions {
+        allow_duplicates: true,
+        ..Default::default()
+    };
+    let mut filters = PropFilters::default();
+    for arg in args.iter() {
+        // Is it a "contains" filter?
+        if let Some((key, value)) = arg.split_once("=?") {
+            let (key, value) =
+                parse_typed_props(&[format!("{}={}", key, value)], prop_type, &opts)?
+                    .into_iter()
+                    .next()
+                    .ok_or(error::validation("Failed to parse key-value pair."))?;
+            filters.contains.push((key, value));
+            continue;
+        }
+
+        // Otherwise it's an "equals" filter...
+        filters.equals.extend(parse_typed_props(
+            std::slice::from_ref(arg),
+            prop_type,
+            &opts,
+        )?);
+    }
+    Ok(filters)
+}
+
+/// How a prop condition compares the stored value with its value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CompareOp {
+    /// The operators, as written in an argument. Two-character operators
+    /// come first, so they're matched before their one-character prefixes.
+    const ALL: [(&'static str, CompareOp); 6] = [
+        ("!=", CompareOp::Ne),
+        ("<=", CompareOp::Le),
+        (">=", CompareOp::Ge),
+        ("=", CompareOp::Eq),
+        ("<", CompareOp::Lt),
+        (">", CompareOp::Gt),
+    ];
+
+    /// The SQL operator.
+    pub fn sql(&self) -> &'static str {
+        match self {
+            CompareOp::Eq => "=",
+            CompareOp::Ne => "!=",
+            CompareOp::Lt => "<",
+            CompareOp::Le => "<=",
+            CompareOp::Gt => ">",
+            CompareOp::Ge => ">=",
+        }
+    }
+}
+
+/// A prop filter with a comparison (e.g. `weight>5`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropCondition {
+    pub key: String,
+    pub op: CompareOp,
+    pub value: Value,
+}
+
+/// Parse a prop condition like `weight>5` or `status!=done`.
+///
+/// The operator is one of `=`, `!=`, `<`, `<=`, `>`, or `>=`, and the
+/// key is everything before the first one. Values are parsed as JSON if
+/// possible, otherwise they're treated as strings.
+pub fn parse_prop_condition(arg: &str) -> Result<PropCondition> {
+    // Find the operator...
+    let (pos, op_str, op) = arg
+        .char_indices()
+        .find_map(|(i, _)| {
+            CompareOp::ALL
+                .iter()
+                .find(|(s, _)| arg[i..].starts_with(s))
+                .map(|(s, op)| (i, *s, *op))
+        })
+        .ok_or_else(|| {
+            error::validation(format!(
+                "Invalid prop condition '{}'. Expected `key<op>value`, where <op> is one of \
+                =, !=, <, <=, >, or >=.",
+                arg
+            ))
+        })?;
+
+    // Split out the key and value...
+    let key = arg[..pos].trim().to_string();
+    if key.is_empty() {
+        return Err(error::validation(format!(
+            "Empty key in prop condition '{}'.",
+            arg
+        )));
+    }
+    let value = parse_prop_value(&arg[pos + op_str.len()..], PropType::Auto)?;
+    Ok(PropCondition { key, op, value })
+}
+
+/// Apply a JSONPath expression (e.g. `$.props.address.city`) to a value.
+///
+/// If the path matches exactly one value, that value is returned.
+/// Otherwise, an array of all of the matches is returned.
+pub fn apply_json_path(value: &Value, path: &str) -> Result<Value> {
+    let mut matches = value
+        .query(path)
+        .map_err(|e| error::validation(format!("Invalid JSON path \"{}\": {}", path, e)))?;
+    match matches.len() {
+        1 => Ok(matches.remove(0).clone()),
+        _ => Ok(Value::Array(matches.into_iter().cloned().collect())),
+    }
+}
+
+/// Compile a regex used to filter prop keys (see `filter_props`).
+pub fn compile_prop_pattern(pattern: &str) -> Result<Regex> {
+    Regex::new(pattern)
+        .map_err(|e| error::validation(format!("Invalid prop pattern \"{}\": {}", pattern, e)))
+}
+
+/// Filter a map of props by key.
+///
+/// If `include` is set, only keys matching it are kept. Then, if
+/// `exclude` is set, keys matching it are removed.
+pub fn filter_props(
+    props: &mut HashMap<String, Value>,
+    include: Option<&Regex>,
+    exclude: Option<&Regex>,
+) {
+    props.retain(|key, _| {
+        include.is_none_or(|re| re.is_match(key)) && !exclude.is_some_and(|re| re.is_match(key))
+    });
+}
+
+/// A parsed node selector (see `parse_selector`).
+#[derive(Debug, Default, PartialEq)]
+pub struct Selector {
+    pub label: Option<String>,
+    pub props: Vec<(String, Value)>,
+}
+
+/// Parse a node selector of the form `label:Person,name:Alice`.
+///
+/// The `label` key is treated specially and filters by the node's
+/// label. Every other key is treated as a prop, with its value parsed
+/// the same way as `parse_props`.
+pub fn parse_selector(s: &str) -> Result<Selector> {
+    let mut sel = Selector::default();
+    for part in s.split(',') {
+        // Split the key-value pair on the first colon...
+        let (key, value) = part
+            .split_once(':')
+            .ok_or(error::validation("Failed to parse selector part."))
+            .context(format!("part={}", part))?;
+
+        // Make sure the key is not empty...
+        let key = key.trim();
+        if key.is_empty() {
+            return Err(error::validation("Empty key in selector."));
+        }
+
+        // Is it the label?
+        if key == "label" {
+            if sel.label.is_some() {
+                return Err(error::validation("Selector can only have one label."));
+            }
+            sel.label = Some(value.trim().to_string());
+            continue;
+        }
+
+        // Otherwise it's a prop...
+        let value = match serde_json::from_str(value) {
+            Ok(v) => v,
+            Err(_) => Value::String(value.to_string()),
+        };
+        sel.props.push((key.to_string(), value));
+    }
+
+    // Make sure it selects *something*...
+    if sel.props.is_empty() {
+        return Err(error::validation(
+            "Selector needs at least one prop to match on.",
+        ));
+    }
+    Ok(sel)
+}
+
+/// An advisory lock, held by creating a file (containing the
+/// holder's PID) that's removed when the lock is dropped.
+///
+/// Note - If graphctl is killed, the file is left behind and has
+/// to be removed with `LockFile::force_unlock`.
+#[derive(Debug)]
+pub struct LockFile {
+    path: PathBuf,
+}
+
+impl LockFile {
+    /// Take the lock, failing if it's already held.
+    pub fn acquire(path: &Path) -> Result<Self> {
+        let mut file = match std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+        {
+            Ok(f) => f,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                let holder = std::fs::read_to_string(path).unwrap_or_default();
+                // Note - Reported as a connection error, since the
+                // database can't be used until the lock is released...
+                return Err(error::connection(format!(
+                    "Another graphctl operation is in progress (PID {}, lock file \"{}\"). \
+                    If it isn't, pass `--force-unlock` to remove the stale lock.",
+                    if holder.trim().is_empty() {
+                        "unknown"
+                    } else {
+                        holder.trim()
+                    },
+                    path.display(),
+                )));
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to create lock file {:?}", path))
+            }
+        };
+        write!(file, "{}", std::process::id())?;
+        Ok(LockFile {
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Remove a (possibly stale) lock, if there is one.
+    pub fn force_unlock(path: &Path) -> Result<()> {
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(e).with_context(|| format!("Failed to remove lock file {:?}", path))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Move a file, falling back to copying it (and then removing the
+/// original) when it's moving to another filesystem.
+///
+/// Fails if there's already a file at `to`, rather than replacing it.
+pub fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if to.exists() {
+        return Err(error::validation(format!("{:?} already exists.", to)));
+    }
+    match std::fs::rename(from, to) {
+        Ok(()) => return Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {}
+        Err(e) => return Err(e).with_context(|| format!("Failed to move {:?} to {:?}", from, to)),
+    }
+
+    // Copy it, making sure the copy is on disk before the original
+    // is removed...
+    let copied = std::fs::copy(from, to).and_then(|_| std::fs::File::open(to)?.sync_all());
+    if let Err(e) = copied {
+        let _ = std::fs::remove_file(to);
+        return Err(e).with_context(|| format!("Failed to copy {:?} to {:?}", from, to));
+    }
+    std::fs::remove_file(from)
+        .with_context(|| format!("Copied {:?} to {:?}, but failed to remove it", from, to))
+}
+
+/// Move files into a directory (creating it if needed), keeping their
+/// names, and returning their new paths.
+///
+/// If a file can't be moved, the ones that already were are moved back
+/// (as far as possible) before the error is returned.
+pub fn move_files(files: &[PathBuf], to_dir: &Path) -> Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(to_dir)
+        .with_context(|| format!("Failed to create directory {:?}", to_dir))?;
+    let mut moved: Vec<(&PathBuf, PathBuf)> = Vec::new();
+    for from in files.iter() {
+        let name = from
+            .file_name()
+            .ok_or_else(|| anyhow!("{:?} isn't a file.", from))?;
+        let to = to_dir.join(name);
+        if let Err(err) = move_file(from, &to) {
+            for (orig, new) in moved.iter().rev() {
+                let _ = move_file(new, orig);
+            }
+            return Err(err);
+        }
+        moved.push((from, to));
+    }
+    Ok(moved.into_iter().map(|(_, to)| to).collect())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_prop_value() {
+        use serde_json::json;
+        assert_eq!(parse_prop_value("30", PropType::Auto).unwrap(), json!(30));
+        assert_eq!(
+            parse_prop_value("Bob", PropType::Auto).unwrap(),
+            json!("Bob")
+        );
+        assert_eq!(
+            parse_prop_value("30", PropType::String).unwrap(),
+            json!("30")
+        );
+        assert_eq!(
+            parse_prop_value("2.5", PropType::Number).unwrap(),
+            json!(2.5)
+        );
+        assert_eq!(
+            parse_prop_value("true", PropType::Bool).unwrap(),
+            json!(true)
+        );
+        assert!(parse_prop_value("abc", PropType::Number).is_err());
+        assert!(parse_prop_value("yes", PropType::Bool).is_err());
+    }
+
+    #[test]
+    fn test_move_files() {
+        let from = tempfile::tempdir().unwrap();
+        let to = tempfile::tempdir().unwrap();
+        let files: Vec<PathBuf> = ["a.db", "a.db-wal"]
+            .iter()
+            .map(|name| {
+                let path = from.path().join(name);
+                std::fs::write(&path, name).unwrap();
+                path
+            })
+            .collect();
+
+        // If one of them is in the way, nothing is moved...
+        std::fs::write(to.path().join("a.db-wal"), "other").unwrap();
+        assert!(move_files(&files, to.path()).is_err());
+        assert!(files.iter().all(|f| f.exists()));
+        assert!(!to.path().join("a.db").exists());
+
+        // ...otherwise they're all moved, into a new directory if need be...
+        let dest = to.path().join("nested").join("data");
+        let moved = move_files(&files, &dest).unwrap();
+        assert_eq!(moved, vec![dest.join("a.db"), dest.join("a.db-wal")]);
+        assert!(files.iter().all(|f| !f.exists()));
+        assert_eq!(std::fs::read_to_string(&moved[1]).unwrap(), "a.db-wal");
+    }
+
+    #[test]
+    fn test_format_labels() {
+        let labels = vec!["Person".to_string(), "Employee".to_string()];
+        assert_eq!(format_labels(&labels), ":Person:Employee");
+        assert_eq!(format_labels(&[]), "");
+    }
+
+    #[test]
+    fn test_seeded_ids() {
+        // The same seed gives the same IDs...
+        let run = |seed| {
+            let ids = IdGenerator::seeded(seed);
+            vec![ids.new_id("n"), ids.new_id("n"), ids.new_id("e")]
+        };
+        assert_eq!(run(42), run(42));
+        assert_ne!(run(42), run(43));
+
+        // They're still prefixed UUIDv4s, and don't repeat...
+        let ids = run(42);
+        assert_ne!(ids[0], ids[1]);
+        let uuid = Uuid::parse_str(ids[2].strip_prefix("e-").unwrap()).unwrap();
+        assert_eq!(uuid.get_version_num(), 4);
+    }
+
     #[test]
     fn test_new_id() {
         let id = new_id("node");
         assert_eq!(id.len(), 4 + 1 + 36);
         assert_eq!(&id[0..4], "node");
     }
+
+    #[test]
+    fn test_parse_props() {
+        let args = vec![
+            "name=Alice".to_string(),
+            " age =30".to_string(),
+            "tags=[\"a\",\"b\"]".to_string(),
+            "eq=a=b".to_string(),
+        ];
+        let props = parse_props(&args, &ParsePropsOptions::default()).unwrap();
+        assert_eq!(props["name"], Value::String("Alice".to_string()));
+        assert_eq!(props["age"], serde_json::json!(30));
+        assert_eq!(props["tags"], serde_json::json!(["a", "b"]));
+        assert_eq!(props["eq"], Value::String("a=b".to_string()));
+
+        let opts = ParsePropsOptions::default();
+        assert!(parse_props(&["=x".to_string()], &opts).is_err());
+        assert!(parse_props(&["novalue".to_string()], &opts).is_err());
+    }
+
+    #[test]
+    fn test_parse_props_duplicates() {
+        let args = vec![
+            "name=Alice".to_string(),
+            "age=30".to_string(),
+            " name=Bob".to_string(),
+        ];
+
+        // Duplicate keys are an error by default...
+        let err = parse_props(&args, &ParsePropsOptions::default()).unwrap_err();
+        assert_eq!(error::kind(&err), Some(error::ErrorKind::Validation));
+        assert!(err.to_string().contains("Duplicate property key 'name'"));
+
+        // ...unless allowed, in which case the last one wins...
+        let opts = ParsePropsOptions {
+            allow_duplicates: true,
+            ..Default::default()
+        };
+        let props = parse_props(&args, &opts).unwrap();
+        assert_eq!(props["name"], Value::String("Bob".to_string()));
+        assert_eq!(props["age"], serde_json::json!(30));
+    }
+
+    #[test]
+    fn test_parse_props_trim_values() {
+        let args = vec![
+            "name= Alice ".to_string(),
+            "quoted=\" Bob \"".to_string(),
+            "age= 30 ".to_string(),
+            "obj= {\"a\": 1} ".to_string(),
+        ];
+
+        // Values are kept as-is by default...
+        let props = parse_props(&args, &ParsePropsOptions::default()).unwrap();
+        assert_eq!(props["name"], serde_json::json!(" Alice "));
+
+        // Plain strings are trimmed, but JSON values aren't changed...
+        let opts = ParsePropsOptions {
+            trim_values: true,
+            ..Default::default()
+        };
+        let props = parse_props(&args, &opts).unwrap();
+        assert_eq!(props["name"], serde_json::json!("Alice"));
+        assert_eq!(props["quoted"], serde_json::json!(" Bob "));
+        assert_eq!(props["age"], serde_json::json!(30));
+        assert_eq!(props["obj"], serde_json::json!({"a": 1}));
+    }
+
+    #[test]
+    fn test_parse_prop_filters() {
+        let args = vec![
+            "name=Alice".to_string(),
+            "tags=?a".to_string(),
+            "nums=?3".to_string(),
+        ];
+        let filters = parse_prop_filters(&args, PropType::Auto).unwrap();
+        assert_eq!(filters.equals["name"], serde_json::json!("Alice"));
+        assert_eq!(
+            filters.contains,
+            vec![
+                ("tags".to_string(), serde_json::json!("a")),
+                ("nums".to_string(), serde_json::json!(3)),
+            ]
+        );
+        assert!(parse_prop_filters(&["=?a".to_string()], PropType::Auto).is_err());
+    }
+
+    #[test]
+    fn test_parse_prop_condition() {
+        let cond = |key: &str, op: CompareOp, value: Value| PropCondition {
+            key: key.to_string(),
+            op,
+            value,
+        };
+        let cases = [
+            (
+                "weight>5",
+                cond("weight", CompareOp::Gt, serde_json::json!(5)),
+            ),
+            (
+                "weight >= 2.5",
+                cond("weight", CompareOp::Ge, serde_json::json!(2.5)),
+            ),
+            (
+                "status!=done",
+                cond("status", CompareOp::Ne, serde_json::json!("done")),
+            ),
+            ("rank<=3", cond("rank", CompareOp::Le, serde_json::json!(3))),
+            (
+                "active=true",
+                cond("active", CompareOp::Eq, serde_json::json!(true)),
+            ),
+            // The first operator splits the key from the value...
+            (
+                "expr=a<b",
+                cond("expr", CompareOp::Eq, serde_json::json!("a<b")),
+            ),
+        ];
+        for (arg, expected) in cases {
+            assert_eq!(parse_prop_condition(arg).unwrap(), expected, "{}", arg);
+        }
+        assert!(parse_prop_condition("weight").is_err());
+        assert!(parse_prop_condition(">5").is_err());
+    }
+
+    #[test]
+    fn test_parse_selector() {
+        let sel = parse_selector("label:Person,name:Alice,age:30").unwrap();
+        assert_eq!(sel.label, Some("Person".to_string()));
+        assert_eq!(
+            sel.props,
+            vec![
+                ("name".to_string(), Value::String("Alice".to_string())),
+                ("age".to_string(), serde_json::json!(30)),
+            ]
+        );
+
+        assert!(parse_selector("label:Person").is_err());
+        assert!(parse_selector("name").is_err());
+        assert!(parse_selector(":Alice").is_err());
+    }
+
+    #[test]
+    fn test_apply_json_path() {
+        let value = serde_json::json!({
+            "id": "n-1",
+            "props": {
+                "address": {"city": "Paris"},
+                "tags": ["a", "b"],
+            },
+        });
+        assert_eq!(
+            apply_json_path(&value, "$.props.address.city").unwrap(),
+            serde_json::json!("Paris")
+        );
+        assert_eq!(
+            apply_json_path(&value, "$.props.tags[*]").unwrap(),
+            serde_json::json!(["a", "b"])
+        );
+        assert_eq!(
+            apply_json_path(&value, "$.props.missing").unwrap(),
+            serde_json::json!([])
+        );
+        assert!(apply_json_path(&value, "$.props[").is_err());
+    }
+
+    #[test]
+    fn test_filter_props() {
+        let props: HashMap<String, Value> = [
+            ("meta_created_by".to_string(), serde_json::json!("alice")),
+            ("meta_source".to_string(), serde_json::json!("import")),
+            ("name".to_string(), serde_json::json!("Bob")),
+        ]
+        .into();
+        let keys = |props: &HashMap<String, Value>| {
+            let mut keys: Vec<String> = props.keys().cloned().collect();
+            keys.sort();
+            keys
+        };
+        let meta = compile_prop_pattern("^meta_").unwrap();
+        let source = compile_prop_pattern("source$").unwrap();
+
+        // Include only...
+        let mut p = props.clone();
+        filter_props(&mut p, Some(&meta), None);
+        assert_eq!(keys(&p), vec!["meta_created_by", "meta_source"]);
+
+        // Exclude only...
+        let mut p = props.clone();
+        filter_props(&mut p, None, Some(&meta));
+        assert_eq!(keys(&p), vec!["name"]);
+
+        // Both...
+        let mut p = props.clone();
+        filter_props(&mut p, Some(&meta), Some(&source));
+        assert_eq!(keys(&p), vec!["meta_created_by"]);
+
+        // Invalid patterns...
+        assert!(compile_prop_pattern("meta_(").is_err());
+    }
+
+    #[test]
+    fn test_lock_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".lock");
+
+        // Take the lock...
+        let lock = LockFile::acquire(&path).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            std::process::id().to_string()
+        );
+
+        // A second operation is rejected while it's held...
+        let err = LockFile::acquire(&path).unwrap_err();
+        assert_eq!(error::kind(&err), Some(error::ErrorKind::Connection));
+        let err = err.to_string();
+        assert!(err.contains("Another graphctl operation is in progress"));
+        assert!(err.contains(&std::process::id().to_string()));
+
+        // Dropping it releases it...
+        drop(lock);
+        assert!(!path.exists());
+        let lock = LockFile::acquire(&path).unwrap();
+
+        // A stale lock can be removed...
+        std::mem::forget(lock);
+        assert!(LockFile::acquire(&path).is_err());
+        LockFile::force_unlock(&path).unwrap();
+        LockFile::force_unlock(&path).unwrap();
+        assert!(LockFile::acquire(&path).is_ok());
+    }
 }
@@ -0,0 +1,61 @@
+//! Reports version and build info (e.g. for bug reports).
+use graphctl::secrets;
+use serde::Serialize;
+
+/// The crate version.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The git commit graphctl was built from (or "unknown").
+pub const GIT_SHA: &str = env!("GRAPHCTL_GIT_SHA");
+
+/// The version of libsql graphctl was built against.
+pub const LIBSQL_VERSION: &str = env!("GRAPHCTL_LIBSQL_VERSION");
+
+/// Version and build info, plus a few details about the environment
+/// that commonly matter when triaging issues.
+#[derive(Debug, Serialize)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    pub git_commit: &'static str,
+    pub libsql_version: &'static str,
+    pub os: &'static str,
+    pub arch: &'static str,
+    pub keyring_backend: &'static str,
+
+    /// Whether the keyring could be reached.
+    pub keyring_available: bool,
+
+    /// Why the keyring couldn't be reached, if it couldn't.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keyring_error: Option<String>,
+}
+
+/// Collect the build info, probing the keyring.
+pub fn build_info() -> BuildInfo {
+    let keyring = secrets::check_keyring();
+    BuildInfo {
+        version: VERSION,
+        git_commit: GIT_SHA,
+        libsql_version: LIBSQL_VERSION,
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        keyring_backend: secrets::KEYRING_BACKEND,
+        keyring_available: keyring.is_ok(),
+        keyring_error: keyring.err().map(|err| format!("{:#}", err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_info() {
+        let info = build_info();
+        let out = serde_json::to_string(&info).unwrap();
+        assert!(out.contains(&format!("\"version\":\"{}\"", env!("CARGO_PKG_VERSION"))));
+        assert_eq!(info.os, std::env::consts::OS);
+        assert!(!info.git_commit.is_empty());
+        assert!(!info.libsql_version.is_empty());
+    }
+}
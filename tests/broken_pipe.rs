@@ -0,0 +1,71 @@
+//! Checks that output piped into a reader that stops early (e.g.
+//! `graphctl list nodes | head`) ends quietly, rather than panicking.
+use std::io::Read;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// A `graphctl` command using the given config dir.
+fn graphctl(conf_dir: &Path) -> Command {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_graphctl"));
+    cmd.env("GRAPHCTL_CONFIG_DIR", conf_dir)
+        .env("RUST_BACKTRACE", "0");
+    cmd
+}
+
+#[test]
+fn test_list_into_closed_pipe() {
+    // Set up a local graph...
+    let dir = tempfile::tempdir().unwrap();
+    let conf_dir = dir.path().join("graphctl");
+    let status = graphctl(&conf_dir)
+        .args(["cfg", "init", "--db-type", "local", "--no-encrypt"])
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    // ...with enough nodes to fill the pipe's buffer...
+    let data: String = (0..5000)
+        .map(|i| {
+            format!(
+                r#"{{"kind":"node","id":"n-{}","labels":["Person"],"props":{{"name":"Person {}"}},"created_at":"2024-01-01T00:00:00Z","updated_at":"2024-01-01T00:00:00Z"}}"#,
+                i, i
+            ) + "\n"
+        })
+        .collect();
+    let file = dir.path().join("nodes.ndjson");
+    std::fs::write(&file, data).unwrap();
+    let status = graphctl(&conf_dir)
+        .args(["import", "--from-file"])
+        .arg(&file)
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    for format in ["json", "ndjson", "table"] {
+        // List them, but stop reading (and close the pipe) early...
+        let mut child = graphctl(&conf_dir)
+            .args(["list", "nodes", "--all", "--format", format])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut stdout = child.stdout.take().unwrap();
+        let mut buf = [0; 16];
+        stdout.read_exact(&mut buf).unwrap();
+        drop(stdout);
+
+        // It exits cleanly, without a panic...
+        let out = child.wait_with_output().unwrap();
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert!(
+            out.status.success(),
+            "{}: {:?} {}",
+            format,
+            out.status,
+            stderr
+        );
+        assert!(!stderr.contains("panicked"), "{}: {}", format, stderr);
+    }
+}